@@ -0,0 +1,165 @@
+//! # AI
+//!
+//! Minimax search over tic tac toe positions, larger playmats are not searched
+
+use crate::coords::Coordinates;
+use crate::gomoku::BoardSize;
+use crate::tictactoe::{GameError, Grid, Marks, Rules, TicTacToe, Tile};
+pub use crate::tictactoe::Evaluation;
+
+/// Returns empty fields of the playmat
+pub fn free_fields(game: &TicTacToe) -> Vec<Coordinates> {
+    game.iter_cells().filter(|(_, tile)| *tile == Tile::Empty).map(|(field, _)| field).collect()
+}
+
+/// Returns moves of given player, every free field with every tile rules allow
+fn legal_moves(game: &TicTacToe, my_turn: bool) -> Vec<(Coordinates, Tile)> {
+    let marks = if my_turn { game.my_marks() } else { game.opponent_marks() };
+    free_fields(game)
+        .into_iter()
+        .flat_map(|field| marks.iter().map(move |mark| (field, *mark)))
+        .collect()
+}
+
+fn play(game: &TicTacToe, (x, y): Coordinates, mark: Tile, my_turn: bool) -> TicTacToe {
+    let mut next = game.clone();
+    let _ = if my_turn {
+        next.make_my_mark(x, y, mark)
+    } else {
+        next.make_opponent_mark(x, y, mark)
+    };
+    next
+}
+
+/// Returns true when positions of the game can be searched, i.e. on classic playmat
+pub fn is_searchable(game: &TicTacToe) -> bool {
+    game.board_size() == BoardSize::CLASSIC
+}
+
+/// Evaluates position from my point of view, `my_turn` tells who moves next
+pub fn evaluate(game: &TicTacToe, my_turn: bool) -> Evaluation {
+    search(game, my_turn, &mut std::collections::HashMap::new())
+}
+
+/// Minimax with positions already seen, wild variant has too many move orders without it
+fn search(game: &TicTacToe, my_turn: bool, seen: &mut std::collections::HashMap<(Grid, bool), Evaluation>) -> Evaluation {
+    if game.am_i_winner() {
+        return Evaluation::Win;
+    }
+    if game.is_opponent_winner() {
+        return Evaluation::Loss;
+    }
+    if let Some(evaluation) = seen.get(&(game.grid(), my_turn)) {
+        return *evaluation;
+    }
+
+    let results = legal_moves(game, my_turn)
+        .into_iter()
+        .map(|(field, mark)| search(&play(game, field, mark, my_turn), !my_turn, seen));
+    let best = if my_turn { results.max() } else { results.min() };
+    let evaluation = best.unwrap_or(Evaluation::Draw);
+    seen.insert((game.grid(), my_turn), evaluation);
+    evaluation
+}
+
+/// Returns my best move with its evaluation, none when playmat is full or game is over
+pub fn best_move(game: &TicTacToe) -> Option<(Coordinates, Evaluation)> {
+    best_marked_move(game).map(|(field, _, evaluation)| (field, evaluation))
+}
+
+/// Returns my best move together with tile to place and its evaluation
+pub fn best_marked_move(game: &TicTacToe) -> Option<(Coordinates, Tile, Evaluation)> {
+    scored_moves(game).into_iter().max_by_key(|(_, _, evaluation)| *evaluation)
+}
+
+/// Returns all my moves with their evaluation, none when game is over or cannot be searched
+pub fn scored_moves(game: &TicTacToe) -> Vec<(Coordinates, Tile, Evaluation)> {
+    if !is_searchable(game) || game.am_i_winner() || game.is_opponent_winner() {
+        return Vec::new();
+    }
+
+    let mut seen = std::collections::HashMap::new();
+    legal_moves(game, true)
+        .into_iter()
+        .map(|(field, mark)| (field, mark, search(&play(game, field, mark, true), false, &mut seen)))
+        .collect()
+}
+
+/// Opponent played by this client, keeps playmat from its own side as a peer would
+#[derive(Debug, Clone)]
+pub struct BotPlayer {
+    game: TicTacToe,
+}
+
+impl BotPlayer {
+    /// Bot placing given marks, the other player's marks swapped
+    pub fn new(marks: Marks, rules: Rules) -> BotPlayer {
+        BotPlayer { game: TicTacToe::with_rules(marks, rules) }
+    }
+
+    /// Applies turn of the other player
+    pub fn opponent_turn(&mut self, (x, y): Coordinates, mark: Tile) -> Result<(), GameError> {
+        self.game.make_opponent_mark(x, y, mark)
+    }
+
+    /// Plays best move and returns it with number of moves played, none when game is over
+    pub fn reply(&mut self) -> Option<(Coordinates, Tile, usize)> {
+        let ((x, y), mark, _) = best_marked_move(&self.game)?;
+        self.game.make_my_mark(x, y, mark).ok()?;
+        Some(((x, y), mark, self.game.moves().len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tictactoe::Variant;
+
+    #[test]
+    fn empty_playmat_is_draw() {
+        assert_eq!(evaluate(&TicTacToe::new(), true), Evaluation::Draw);
+    }
+
+    #[test]
+    fn finds_winning_move() {
+        let mut game = TicTacToe::new();
+        let _ = game.make_my_turn(0, 0);
+        let _ = game.make_opponent_turn(1, 0);
+        let _ = game.make_my_turn(0, 1);
+        let _ = game.make_opponent_turn(1, 1);
+
+        assert_eq!(evaluate(&game, true), Evaluation::Win);
+        assert_eq!(best_move(&game), Some(((0, 2), Evaluation::Win)));
+    }
+
+    #[test]
+    fn wild_first_player_wins() {
+        let game = TicTacToe::with_rules(Marks::default(), Rules { variant: Variant::Wild, ..Rules::default() });
+        assert_eq!(evaluate(&game, true), Evaluation::Win);
+    }
+
+    #[test]
+    fn detects_lost_position() {
+        let mut game = TicTacToe::new();
+        let _ = game.make_opponent_turn(0, 0);
+        let _ = game.make_my_turn(0, 1);
+        let _ = game.make_opponent_turn(1, 1);
+        let _ = game.make_my_turn(2, 2);
+        let _ = game.make_opponent_turn(1, 0);
+
+        // opponent threatens both column and row, one block is not enough
+        assert_eq!(evaluate(&game, true), Evaluation::Loss);
+        assert_eq!(Evaluation::Loss.flipped(), Evaluation::Win);
+    }
+
+    #[test]
+    fn bot_blocks_line() {
+        let mut bot = BotPlayer::new(Marks::default(), Rules::default());
+        assert!(bot.opponent_turn((0, 0), Tile::Cross).is_ok());
+        // only center holds draw after corner opening
+        assert_eq!(bot.reply(), Some(((1, 1), Tile::Circle, 2)));
+        assert!(bot.opponent_turn((1, 1), Tile::Cross).is_err(), "field is taken by bot");
+        assert!(bot.opponent_turn((0, 1), Tile::Cross).is_ok());
+        assert_eq!(bot.reply(), Some(((0, 2), Tile::Circle, 4)));
+    }
+}
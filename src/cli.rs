@@ -0,0 +1,175 @@
+//! # Cli
+//!
+//! Command line options of the client
+
+/// What client does after start
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Command {
+    #[default]
+    Play,
+    /// Check environment and exit
+    Doctor,
+    /// Run virtual players in one process and report network statistics
+    LoadTest,
+    /// Play against AI personality in terminal
+    PlayAi,
+    /// Play many games between two AI players and report results
+    Simulate,
+    /// Write identity, config, stats and correspondence games into encrypted archive
+    ExportIdentity,
+    /// Restore identity and everything else from encrypted archive
+    ImportIdentity,
+}
+
+/// Frontend of the peer to peer client
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Ui {
+    /// Typed commands and printed lines, works in any terminal or pipe
+    #[default]
+    Stdio,
+    /// Panels with board, peers and log, needs the tui feature
+    Tui,
+}
+
+/// Options given on command line, they override config
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub ui: Ui,
+    /// Terminal UI leaves mouse to terminal, e.g. where it selects text or scrolls
+    pub no_mouse: bool,
+    pub config: Option<std::path::PathBuf>,
+    pub simul: Option<usize>,
+    pub engine: Option<std::path::PathBuf>,
+    pub eval: bool,
+    pub teach: bool,
+    pub room: Option<String>,
+    pub password: Option<String>,
+    /// Number of virtual players in load test
+    pub players: Option<usize>,
+    /// Duration of load test
+    pub seconds: Option<u64>,
+    /// AI personality to play against
+    pub personality: Option<String>,
+    /// Number of simulated games
+    pub games: Option<u64>,
+    /// Players of simulated games, the first one moves first
+    pub p1: Option<String>,
+    pub p2: Option<String>,
+    /// Archive written by export and read by import of identity
+    pub archive: Option<std::path::PathBuf>,
+}
+
+impl Options {
+    /// Parses arguments without program name
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "doctor" => options.command = Command::Doctor,
+                "loadtest" => options.command = Command::LoadTest,
+                "play" => options.command = Command::Play,
+                "ai" if options.command == Command::Play => options.command = Command::PlayAi,
+                "simulate" => options.command = Command::Simulate,
+                "export-identity" | "import-identity" => {
+                    options.archive = Some(value(&arg, args.next())?.into());
+                    options.command = if arg == "export-identity" { Command::ExportIdentity } else { Command::ImportIdentity };
+                }
+                "--games" => {
+                    let games = value(&arg, args.next())?;
+                    options.games = Some(games.parse().map_err(|_| format!("invalid number of games '{}'", games))?);
+                }
+                "--p1" => options.p1 = Some(value(&arg, args.next())?),
+                "--p2" => options.p2 = Some(value(&arg, args.next())?),
+                "--personality" => options.personality = Some(value(&arg, args.next())?),
+                "--players" => {
+                    let players = value(&arg, args.next())?;
+                    options.players = Some(players.parse().map_err(|_| format!("invalid number of players '{}'", players))?);
+                }
+                "--seconds" => {
+                    let seconds = value(&arg, args.next())?;
+                    options.seconds = Some(seconds.parse().map_err(|_| format!("invalid number of seconds '{}'", seconds))?);
+                }
+                "--ui" => {
+                    options.ui = match value(&arg, args.next())?.as_str() {
+                        "stdio" => Ui::Stdio,
+                        "tui" => Ui::Tui,
+                        other => return Err(format!("unknown ui '{}', use stdio or tui", other)),
+                    };
+                }
+                "--no-mouse" => options.no_mouse = true,
+                "--config" => options.config = Some(value(&arg, args.next())?.into()),
+                "--engine" => options.engine = Some(value(&arg, args.next())?.into()),
+                "--password" => options.password = Some(value(&arg, args.next())?),
+                "--eval" => options.eval = true,
+                "--teach" => options.teach = true,
+                "--room" => {
+                    let room = value(&arg, args.next())?;
+                    if room.is_empty() || room.contains('/') {
+                        return Err(format!("invalid room name '{}'", room));
+                    }
+                    options.room = Some(room);
+                }
+                "--simul" => {
+                    let limit = value(&arg, args.next())?;
+                    options.simul = Some(limit.parse().map_err(|_| format!("invalid number of games '{}'", limit))?);
+                }
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn value(option: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("missing value of {}", option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse(&["--simul", "5", "--config", "my.json", "--engine", "./bot"]).unwrap();
+        assert_eq!(options.simul, Some(5));
+        assert_eq!(options.engine, Some("./bot".into()));
+        assert!(!options.eval);
+        assert!(parse(&["--eval"]).unwrap().eval);
+        assert!(parse(&["--teach"]).unwrap().teach);
+        assert_eq!(options.config, Some("my.json".into()));
+        assert_eq!(parse(&["--room", "class-4b"]).unwrap().room, Some("class-4b".to_string()));
+        assert_eq!(parse(&["--password", "pw"]).unwrap().password, Some("pw".to_string()));
+        assert_eq!(parse(&["--ui", "tui"]).unwrap().ui, Ui::Tui);
+        assert!(parse(&["--ui", "tui", "--no-mouse"]).unwrap().no_mouse);
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+        assert_eq!(parse(&["doctor", "--config", "my.json"]).unwrap().command, Command::Doctor);
+        let load_test = parse(&["loadtest", "--players", "16", "--seconds", "60"]).unwrap();
+        assert_eq!((load_test.command, load_test.players, load_test.seconds), (Command::LoadTest, Some(16), Some(60)));
+        let play_ai = parse(&["play", "ai", "--personality", "aggressive"]).unwrap();
+        assert_eq!((play_ai.command, play_ai.personality), (Command::PlayAi, Some("aggressive".to_string())));
+        let simulate = parse(&["simulate", "--games", "10000", "--p1", "random", "--p2", "minimax"]).unwrap();
+        assert_eq!(simulate.command, Command::Simulate);
+        assert_eq!((simulate.games, simulate.p1.as_deref(), simulate.p2.as_deref()), (Some(10000), Some("random"), Some("minimax")));
+        let export = parse(&["export-identity", "me.archive", "--config", "my.json"]).unwrap();
+        assert_eq!((export.command, export.archive), (Command::ExportIdentity, Some("me.archive".into())));
+        assert_eq!(parse(&["import-identity", "me.archive"]).unwrap().command, Command::ImportIdentity);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(parse(&["--simul"]).is_err());
+        assert!(parse(&["--simul", "many"]).is_err());
+        assert!(parse(&["simulate", "--games", "-1"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+        assert!(parse(&["--room", "a/b"]).is_err());
+        assert!(parse(&["import-identity"]).is_err());
+        assert!(parse(&["--ui", "gtk"]).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+//! # Config
+//!
+//! User configuration loaded from JSON file
+
+use crate::coords::Labels;
+use crate::dates::DateFormat;
+use crate::network_communication::Settings;
+#[cfg(feature = "ai")]
+use crate::personality::Personality;
+use crate::theme::ThemeConfig;
+
+/// Environment variable overriding config file location
+pub const CONFIG_ENV: &str = "TICTACTOE_CONFIG";
+
+/// Config file used when no location is given
+pub const DEFAULT_CONFIG_PATH: &str = "tictactoe.json";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    /// Config parses but some value is not allowed
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "cannot read config: {}", err),
+            ConfigError::Parse(err) => write!(f, "invalid config: {}", err),
+            ConfigError::Invalid(err) => write!(f, "invalid config: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub theme: ThemeConfig,
+    pub coordinates: Labels,
+    /// Time zone and style of shown dates
+    pub dates: DateFormat,
+    pub session: Settings,
+    /// Own AI personalities, they replace built-in ones of the same name
+    #[cfg(feature = "ai")]
+    pub personalities: Vec<Personality>,
+}
+
+impl Config {
+    /// Returns config file location, explicit path wins over environment
+    pub fn path(explicit: Option<&std::path::Path>) -> std::path::PathBuf {
+        explicit
+            .map(std::path::Path::to_path_buf)
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(std::path::PathBuf::from))
+            .unwrap_or_else(|| std::path::PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Parses config from given file
+    pub fn from_file(path: &std::path::Path) -> Result<Config, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        serde_json::from_str(&content).map_err(ConfigError::Parse)
+    }
+
+    /// Loads config, missing file means default config
+    pub fn load(explicit: Option<&std::path::Path>) -> Config {
+        let path = Config::path(explicit);
+        if !path.exists() {
+            return Config::default();
+        }
+
+        Config::from_file(&path).unwrap_or_else(|err| {
+            eprintln!("{}, using defaults", err);
+            Config::default()
+        })
+    }
+}
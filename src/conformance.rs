@@ -0,0 +1,193 @@
+//! # Conformance
+//!
+//! Invariants every [`Game`] keeps, written once for all variants. Variant
+//! describes a complete game of its own as [`Script`] and [`check`] plays it
+//! from the side of both players, asserting that illegal moves and moves out of
+//! turn are rejected without changing the game, that winner is reported to the
+//! right player only once the game ends and that finished game takes no move.
+//!
+//! The suite panics on broken invariant, so new variant just calls it from test.
+
+use crate::game::Game;
+
+/// Player by order of moves, not by who uses the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    First,
+    Second,
+}
+
+impl Side {
+    fn other(&self) -> Side {
+        match self {
+            Side::First => Side::Second,
+            Side::Second => Side::First,
+        }
+    }
+}
+
+/// Complete game played by the suite
+#[derive(Debug, Clone)]
+pub struct Script<M> {
+    /// Legal moves with player who plays each, the last one ends the game
+    pub moves: Vec<(Side, M)>,
+    /// Player who wins after the last move, none for draw
+    pub winner: Option<Side>,
+    /// Moves rejected by game before move with given index, e.g. overwriting
+    /// occupied field, played by player on turn
+    pub illegal: Vec<(usize, M)>,
+    /// Moves rejected after the game ends, they would be legal otherwise
+    pub late: Vec<M>,
+    /// Whether game itself rejects moves out of turn, some games leave turn
+    /// order to their caller
+    pub turn_order: bool,
+}
+
+/// Plays script from the side of both players, panics on broken invariant.
+/// Game is created with true when its user moves first.
+pub fn check<G, F>(new_game: F, script: &Script<G::Move>)
+where
+    G: Game,
+    G::Move: Clone + std::fmt::Debug,
+    G::Error: std::fmt::Display,
+    F: Fn(bool) -> G,
+{
+    for me in [Side::First, Side::Second] {
+        check_side(new_game(me == Side::First), script, me);
+    }
+}
+
+fn check_side<G>(mut game: G, script: &Script<G::Move>, me: Side)
+where
+    G: Game,
+    G::Move: Clone + std::fmt::Debug,
+    G::Error: std::fmt::Display,
+{
+    for (index, (side, turn)) in script.moves.iter().enumerate() {
+        assert!(!game.is_finished(), "{:?} player: game ended before move {}", me, index);
+        assert!(!game.am_i_winner() && !game.is_opponent_winner(), "{:?} player: winner before move {}", me, index);
+        for (_, illegal) in script.illegal.iter().filter(|(before, _)| *before == index) {
+            let accepted = play(&mut game, me, *side, illegal.clone()).is_ok();
+            assert!(!accepted, "{:?} player: illegal move {:?} accepted before move {}", me, illegal, index);
+        }
+        if script.turn_order {
+            let accepted = play(&mut game, me, side.other(), turn.clone()).is_ok();
+            assert!(!accepted, "{:?} player: move {} accepted out of turn", me, index);
+        }
+        if let Err(error) = play(&mut game, me, *side, turn.clone()) {
+            panic!("{:?} player: move {} {:?} rejected: {}", me, index, turn, error);
+        }
+    }
+
+    assert!(game.is_finished(), "{:?} player: game did not end after the last move", me);
+    let expected = (script.winner == Some(me), script.winner == Some(me.other()));
+    assert_eq!((game.am_i_winner(), game.is_opponent_winner()), expected, "{:?} player: wrong winner", me);
+    for turn in &script.late {
+        for side in [Side::First, Side::Second] {
+            let accepted = play(&mut game, me, side, turn.clone()).is_ok();
+            assert!(!accepted, "{:?} player: move {:?} accepted after the game ended", me, turn);
+        }
+    }
+    assert!(game.is_finished(), "{:?} player: finished game was changed", me);
+    assert_eq!((game.am_i_winner(), game.is_opponent_winner()), expected, "{:?} player: finished game was changed", me);
+}
+
+fn play<G: Game>(game: &mut G, me: Side, side: Side, turn: G::Move) -> Result<(), G::Error> {
+    if side == me {
+        game.make_my_move(turn)
+    } else {
+        game.make_opponent_move(turn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gomoku::BoardSize;
+    use crate::order_chaos::{OrderChaos, OrderChaosMove, Role};
+    use crate::quantum::{QuantumMove, QuantumTicTacToe};
+    use crate::tictactoe::{Marks, Rules, TicTacToe, Tile, Variant};
+    use Side::{First, Second};
+
+    fn alternating<M>(moves: Vec<M>) -> Vec<(Side, M)> {
+        moves.into_iter().enumerate().map(|(index, turn)| (if index % 2 == 0 { First } else { Second }, turn)).collect()
+    }
+
+    fn marks(first: bool) -> Marks {
+        if first {
+            Marks::default().swapped()
+        } else {
+            Marks::default()
+        }
+    }
+
+    #[test]
+    fn classic_playmat_conforms() {
+        let win = Script {
+            moves: alternating(vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]),
+            winner: Some(First),
+            illegal: vec![(1, (0, 0)), (2, (3, 0))],
+            late: vec![(2, 2)],
+            turn_order: false,
+        };
+        let draw = Script {
+            moves: alternating(vec![(0, 0), (0, 1), (0, 2), (1, 1), (1, 0), (1, 2), (2, 1), (2, 0), (2, 2)]),
+            winner: None,
+            illegal: vec![(4, (1, 1))],
+            late: Vec::new(),
+            turn_order: false,
+        };
+        for script in [&win, &draw] {
+            check(|first| TicTacToe::with_marks(marks(first)), script);
+            let wild = Rules { variant: Variant::Wild, ..Rules::default() };
+            check(|first| TicTacToe::with_rules(marks(first), wild), script);
+        }
+    }
+
+    #[test]
+    fn gomoku_conforms() {
+        let script = Script {
+            moves: alternating(vec![(0, 0), (9, 0), (1, 1), (9, 1), (2, 2), (9, 2), (3, 3), (9, 3), (4, 4)]),
+            winner: Some(First),
+            illegal: vec![(1, (0, 0)), (2, (10, 0))],
+            late: vec![(5, 5)],
+            turn_order: false,
+        };
+        let rules = Rules { board: Some(BoardSize::GOMOKU), ..Rules::default() };
+        check(|first| TicTacToe::with_rules(marks(first), rules), &script);
+    }
+
+    #[test]
+    fn order_chaos_conforms() {
+        let order = |y| OrderChaosMove { x: 1, y, mark: Tile::Circle };
+        let chaos = |y| OrderChaosMove { x: 4, y, mark: Tile::Cross };
+        let script = Script {
+            moves: alternating(vec![order(0), chaos(0), order(1), chaos(1), order(2), chaos(2), order(3), chaos(3), order(4)]),
+            winner: Some(First),
+            illegal: vec![(0, OrderChaosMove { mark: Tile::Empty, ..order(0) }), (1, order(0))],
+            late: vec![OrderChaosMove { x: 5, y: 5, mark: Tile::Cross }],
+            turn_order: true,
+        };
+        check(|first| OrderChaos::new(if first { Role::Order } else { Role::Chaos }), &script);
+    }
+
+    #[test]
+    fn quantum_conforms() {
+        let spooky = QuantumMove::Spooky;
+        let script = Script {
+            moves: vec![
+                (First, spooky((0, 0), (0, 1))),
+                (Second, spooky((2, 0), (2, 1))),
+                (First, spooky((0, 1), (0, 2))),
+                (Second, spooky((2, 1), (2, 2))),
+                (First, spooky((0, 2), (0, 0))),
+                (Second, QuantumMove::Collapse((0, 0))),
+            ],
+            winner: Some(First),
+            illegal: vec![(1, spooky((1, 1), (1, 1))), (5, spooky((1, 0), (1, 1)))],
+            late: vec![spooky((1, 0), (1, 1))],
+            turn_order: false,
+        };
+        check(|first| QuantumTicTacToe::with_marks(marks(first)), &script);
+    }
+}
@@ -0,0 +1,267 @@
+//! # Coords
+//!
+//! Labels of playmat rows and columns shared by input parser and renderer.
+//! Labels are only for the user, coordinates sent to peers stay numeric.
+//! Fields can also be called out in words, e.g. "top left" or "center", which
+//! suits voice control. Words come from built-in table of chosen language or
+//! from own table in config.
+
+/// Number of rows and columns on playmat
+pub const SIZE: usize = 3;
+
+/// Zero based row and column of playmat field
+pub type Coordinates = (usize, usize);
+
+pub enum CoordinatesError {
+    InvalidFormat,
+    InvalidValue,
+}
+
+/// Alphabet used for row and column labels
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Labels {
+    pub rows: String,
+    pub cols: String,
+    /// Spoken names of fields
+    pub callouts: Callouts,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels {
+            rows: "ABC".to_string(),
+            cols: "123".to_string(),
+            callouts: Callouts::default(),
+        }
+    }
+}
+
+/// Built-in callout words by language: rows from top, columns from left and
+/// the middle field alone. Synonyms are separated by '|'.
+const CALLOUTS: [(&str, [&str; SIZE], [&str; SIZE], &str); 3] = [
+    ("en", ["top|upper", "middle|center|centre", "bottom|lower"], ["left", "middle|center|centre", "right"], "center|centre|middle"),
+    ("cs", ["nahoře|horní", "uprostřed|prostřední", "dole|dolní"], ["vlevo|levý", "uprostřed|prostřední", "vpravo|pravý"], "střed|uprostřed"),
+    ("de", ["oben", "mitte", "unten"], ["links", "mitte", "rechts"], "mitte|zentrum"),
+];
+
+/// Words naming fields, either built-in language or own table
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Callouts {
+    /// Code of built-in language, e.g. "en"
+    Language(String),
+    /// Own words, synonyms separated by '|'
+    Table { rows: [String; SIZE], cols: [String; SIZE], center: String },
+}
+
+impl Default for Callouts {
+    fn default() -> Self {
+        Callouts::Language("en".to_string())
+    }
+}
+
+impl Callouts {
+    /// Returns words of rows, columns and middle field
+    fn table(&self) -> Option<([&str; SIZE], [&str; SIZE], &str)> {
+        match self {
+            Callouts::Language(code) => CALLOUTS
+                .iter()
+                .find(|(language, ..)| language.eq_ignore_ascii_case(code))
+                .map(|&(_, rows, cols, center)| (rows, cols, center)),
+            Callouts::Table { rows, cols, center } => {
+                Some((rows.each_ref().map(String::as_str), cols.each_ref().map(String::as_str), center.as_str()))
+            }
+        }
+    }
+
+    /// Parses spoken field, e.g. "top left", "left top" or "center"
+    pub fn parse(&self, spoken: &str) -> Result<Coordinates, CoordinatesError> {
+        let (rows, cols, center) = self.table().ok_or(CoordinatesError::InvalidValue)?;
+        // speech recognizers add punctuation and capitals
+        let spoken = spoken.to_lowercase();
+        let words: Vec<&str> = spoken
+            .split(|c: char| c.is_whitespace() || c == '-' || c == ',')
+            .map(|word| word.trim_matches(|c: char| c.is_ascii_punctuation()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        let named = |options: &str, word: &str| options.split('|').any(|option| option.to_lowercase() == word);
+        let find = |table: [&str; SIZE], word: &str| table.iter().position(|options| named(options, word));
+
+        match words.as_slice() {
+            [word] if named(center, word) => Ok((SIZE / 2, SIZE / 2)),
+            [first, second] => match (find(rows, first), find(cols, second)) {
+                (Some(x), Some(y)) => Ok((x, y)),
+                _ => match (find(rows, second), find(cols, first)) {
+                    (Some(x), Some(y)) => Ok((x, y)),
+                    _ => Err(CoordinatesError::InvalidValue),
+                },
+            },
+            [_] => Err(CoordinatesError::InvalidValue),
+            _ => Err(CoordinatesError::InvalidFormat),
+        }
+    }
+}
+
+impl Labels {
+    /// Returns labels when both alphabets have one unique symbol per field
+    pub fn validated(self) -> Result<Labels, String> {
+        for (name, alphabet) in [("rows", &self.rows), ("cols", &self.cols)] {
+            let symbols: Vec<char> = alphabet.chars().collect();
+            if symbols.len() != SIZE {
+                return Err(format!("{} need {} labels, got '{}'", name, SIZE, alphabet));
+            }
+            if symbols.iter().any(|c| c.is_whitespace()) {
+                return Err(format!("{} labels cannot contain whitespace", name));
+            }
+            let unique = symbols.iter().collect::<std::collections::HashSet<_>>();
+            if unique.len() != SIZE {
+                return Err(format!("{} labels must be unique, got '{}'", name, alphabet));
+            }
+        }
+        if self.callouts.table().is_none() {
+            let languages = CALLOUTS.iter().map(|(language, ..)| *language).collect::<Vec<_>>().join(", ");
+            return Err(format!("unknown callout language, use one of {} or own table", languages));
+        }
+        Ok(self)
+    }
+
+    /// Returns label of row with given index, rows of larger playmat beyond
+    /// alphabet go on with letters, e.g. D after ABC
+    pub fn row(&self, index: usize) -> String {
+        match self.rows.chars().nth(index) {
+            Some(label) => label.to_string(),
+            None => char::from(b'A' + index as u8).to_string(),
+        }
+    }
+
+    /// Returns label of column with given index, columns of larger playmat
+    /// beyond alphabet go on with numbers, e.g. 10 after 9
+    pub fn col(&self, index: usize) -> String {
+        match self.cols.chars().nth(index) {
+            Some(label) => label.to_string(),
+            None => (index + 1).to_string(),
+        }
+    }
+
+    /// Returns labels of given number of rows
+    pub fn row_names(&self, count: usize) -> Vec<String> {
+        (0..count).map(|index| self.row(index)).collect()
+    }
+
+    /// Returns labels of given number of columns
+    pub fn col_names(&self, count: usize) -> Vec<String> {
+        (0..count).map(|index| self.col(index)).collect()
+    }
+
+    /// Parses row and column labels, e.g. "B" and "2"
+    pub fn parse(&self, row: &str, col: &str) -> Result<Coordinates, CoordinatesError> {
+        self.parse_sized(row, col, SIZE)
+    }
+
+    /// Parses row and column labels of playmat with given number of rows,
+    /// e.g. "J" and "10" on 10x10 playmat
+    pub fn parse_sized(&self, row: &str, col: &str, size: usize) -> Result<Coordinates, CoordinatesError> {
+        Self::single_char(row)?;
+        if Self::single_char(col).is_err() && !col.chars().all(|c| c.is_ascii_digit()) {
+            return Err(CoordinatesError::InvalidFormat);
+        }
+
+        let named = |label: String, token: &str| label.to_lowercase() == token.to_lowercase();
+        let x = (0..size).find(|&index| named(self.row(index), row));
+        let y = (0..size).find(|&index| named(self.col(index), col));
+        match (x, y) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => Err(CoordinatesError::InvalidValue),
+        }
+    }
+
+    /// Parses field written as one token, e.g. "B2"
+    pub fn parse_field(&self, field: &str) -> Result<Coordinates, CoordinatesError> {
+        let mut chars = field.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(row), Some(col), None) => self.parse(&row.to_string(), &col.to_string()),
+            _ => Err(CoordinatesError::InvalidFormat),
+        }
+    }
+
+    /// Parses field called out in words, see [`Callouts`]
+    pub fn parse_callout(&self, spoken: &str) -> Result<Coordinates, CoordinatesError> {
+        self.callouts.parse(spoken)
+    }
+
+    /// Returns syntax of turn command with current labels
+    pub fn turn_syntax(&self) -> String {
+        format!("turn <{}> <{}>", Self::options(&self.rows), Self::options(&self.cols))
+    }
+
+    /// Returns syntax of turn command on playmat with given number of rows
+    pub fn turn_syntax_sized(&self, size: usize) -> String {
+        if size == SIZE {
+            return self.turn_syntax();
+        }
+        format!("turn <{}-{}> <{}-{}>", self.row(0), self.row(size - 1), self.col(0), self.col(size - 1))
+    }
+
+    fn single_char(token: &str) -> Result<char, CoordinatesError> {
+        let mut chars = token.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(CoordinatesError::InvalidFormat),
+        }
+    }
+
+    fn options(alphabet: &str) -> String {
+        alphabet.chars().map(String::from).collect::<Vec<_>>().join("|")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_default_labels() {
+        let labels = Labels::default();
+        assert!(matches!(labels.parse("A", "1"), Ok((0, 0))));
+        assert!(matches!(labels.parse("c", "2"), Ok((2, 1))));
+        assert!(matches!(labels.parse("D", "1"), Err(CoordinatesError::InvalidValue)));
+        assert!(matches!(labels.parse("AB", "1"), Err(CoordinatesError::InvalidFormat)));
+        assert!(matches!(labels.parse_sized("j", "10", 10), Ok((9, 9))));
+        assert!(matches!(labels.parse_sized("K", "1", 10), Err(CoordinatesError::InvalidValue)));
+        assert_eq!(labels.col_names(10).last().map(String::as_str), Some("10"));
+    }
+
+    #[test]
+    fn parses_custom_labels() {
+        let labels = Labels { rows: "ČŘŠ".to_string(), cols: "+ěš".to_string(), ..Labels::default() }.validated().unwrap();
+        assert!(matches!(labels.parse("ř", "š"), Ok((1, 2))));
+        assert_eq!(labels.row(1), "Ř");
+        assert_eq!(labels.turn_syntax(), "turn <Č|Ř|Š> <+|ě|š>");
+    }
+
+    #[test]
+    fn rejects_invalid_alphabets() {
+        assert!(Labels { rows: "AB".to_string(), cols: "123".to_string(), ..Labels::default() }.validated().is_err());
+        assert!(Labels { rows: "AAB".to_string(), cols: "123".to_string(), ..Labels::default() }.validated().is_err());
+        assert!(Labels { rows: "ABC".to_string(), cols: "1 3".to_string(), ..Labels::default() }.validated().is_err());
+    }
+
+    #[test]
+    fn parses_callouts() {
+        let labels = Labels::default();
+        assert!(matches!(labels.parse_callout("top left"), Ok((0, 0))));
+        assert!(matches!(labels.parse_callout("Right, bottom."), Ok((2, 2))));
+        assert!(matches!(labels.parse_callout("center"), Ok((1, 1))));
+        assert!(matches!(labels.parse_callout("middle left"), Ok((1, 0))));
+        assert!(matches!(labels.parse_callout("left left"), Err(CoordinatesError::InvalidValue)));
+        assert!(matches!(labels.parse_callout("top left corner"), Err(CoordinatesError::InvalidFormat)));
+
+        let czech = Callouts::Language("cs".to_string());
+        assert!(matches!(czech.parse("vpravo nahoře"), Ok((0, 2))));
+        let own: Callouts = serde_json::from_str(r#"{"rows": ["up", "mid", "down"], "cols": ["west", "mid", "east"], "center": "bullseye"}"#).unwrap();
+        assert!(matches!(own.parse("down west"), Ok((2, 0))));
+        assert!(matches!(own.parse("bullseye"), Ok((1, 1))));
+        assert!(Labels { callouts: Callouts::Language("xx".to_string()), ..Labels::default() }.validated().is_err());
+    }
+}
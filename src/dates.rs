@@ -0,0 +1,210 @@
+//! # Dates
+//!
+//! Timestamps are stored as UTC milliseconds since unix epoch and turned into
+//! dates only when shown, in configured time zone and date style. Recent times
+//! read relative to now, e.g. "2 hours ago".
+
+/// Times older than this are shown as dates instead of relative times
+const RELATIVE_LIMIT_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Order and notation of date parts
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateStyle {
+    /// 2024-03-09 14:05
+    #[default]
+    Iso,
+    /// 03/09/2024 2:05 PM
+    Us,
+    /// 09.03.2024 14:05
+    European,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DateFormat {
+    /// "UTC" or offset from it, e.g. "+02:00"
+    pub time_zone: String,
+    pub style: DateStyle,
+}
+
+impl Default for DateFormat {
+    fn default() -> Self {
+        DateFormat {
+            time_zone: "UTC".to_string(),
+            style: DateStyle::Iso,
+        }
+    }
+}
+
+impl DateFormat {
+    /// Returns format when its time zone can be parsed
+    pub fn validated(self) -> Result<DateFormat, String> {
+        parse_offset(&self.time_zone)?;
+        Ok(self)
+    }
+
+    /// Returns date and time in configured time zone
+    pub fn absolute(&self, utc_millis: u64) -> String {
+        let offset_secs = parse_offset(&self.time_zone).unwrap_or_default() * 60;
+        let (year, month, day, hour, minute, _) = civil(utc_millis as i64 / 1000 + offset_secs);
+        match self.style {
+            DateStyle::Iso => format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute),
+            DateStyle::Us => format!(
+                "{:02}/{:02}/{:04} {}:{:02} {}",
+                month,
+                day,
+                year,
+                (hour + 11) % 12 + 1,
+                minute,
+                if hour < 12 { "AM" } else { "PM" }
+            ),
+            DateStyle::European => format!("{:02}.{:02}.{:04} {:02}:{:02}", day, month, year, hour, minute),
+        }
+    }
+
+    /// Returns how long ago the time was, older times and times in future as date
+    pub fn relative(&self, utc_millis: u64, now_millis: u64) -> String {
+        let secs = match now_millis.checked_sub(utc_millis) {
+            Some(millis) if millis / 1000 < RELATIVE_LIMIT_SECS => millis / 1000,
+            _ => return self.absolute(utc_millis),
+        };
+        if secs < 60 {
+            "just now".to_string()
+        } else {
+            format!("{} ago", duration(secs))
+        }
+    }
+
+    /// Parses time typed by user as "2024-03-09 14:05" in configured time zone,
+    /// whatever the style, returns UTC milliseconds
+    pub fn parse(&self, text: &str) -> Result<u64, String> {
+        let invalid = || format!("time must look like 2024-03-09 14:05, got '{}'", text);
+        let (date, time) = text.trim().split_once(' ').ok_or_else(invalid)?;
+        let numbers = |part: &str, separator: char| -> Option<Vec<i64>> {
+            part.trim().split(separator).map(|number| number.parse().ok()).collect()
+        };
+        let (date, time) = (numbers(date, '-').ok_or_else(invalid)?, numbers(time, ':').ok_or_else(invalid)?);
+        let (year, month, day, hour, minute) = match (&date[..], &time[..]) {
+            (&[year, month, day], &[hour, minute]) => (year, month, day, hour, minute),
+            _ => return Err(invalid()),
+        };
+        if !(1970..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || !(1..=31).contains(&day)
+            || !(0..24).contains(&hour)
+            || !(0..60).contains(&minute)
+        {
+            return Err(invalid());
+        }
+        let offset_secs = parse_offset(&self.time_zone)? * 60;
+        let secs = days_from_civil(year, month, day) * 24 * 60 * 60 + hour * 60 * 60 + minute * 60 - offset_secs;
+        // day past end of month would roll over into next one
+        if civil(secs + offset_secs).2 != day || secs < 0 {
+            return Err(invalid());
+        }
+        Ok(secs as u64 * 1000)
+    }
+}
+
+/// Returns duration in its largest whole unit, e.g. "3 hours"
+pub fn duration(secs: u64) -> String {
+    let (count, unit) = match secs {
+        secs if secs < 60 * 60 => (secs / 60, "minute"),
+        secs if secs < 24 * 60 * 60 => (secs / (60 * 60), "hour"),
+        secs => (secs / (24 * 60 * 60), "day"),
+    };
+    if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+/// Returns UTC time in ISO 8601, meant for files read by other programs
+pub fn iso8601(utc_millis: u64) -> String {
+    let (year, month, day, hour, minute, second) = civil(utc_millis as i64 / 1000);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, second)
+}
+
+/// Parses time zone into minutes east of UTC
+fn parse_offset(time_zone: &str) -> Result<i64, String> {
+    let invalid = || format!("time zone must be UTC or offset like +02:00, got '{}'", time_zone);
+    if time_zone.eq_ignore_ascii_case("utc") || time_zone == "Z" {
+        return Ok(0);
+    }
+    let (sign, offset) = if let Some(offset) = time_zone.strip_prefix('+') {
+        (1, offset)
+    } else if let Some(offset) = time_zone.strip_prefix('-') {
+        (-1, offset)
+    } else {
+        return Err(invalid());
+    };
+    let (hours, minutes) = offset.split_once(':').unwrap_or((offset, "0"));
+    let hours: i64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i64 = minutes.parse().map_err(|_| invalid())?;
+    if !(0..=14).contains(&hours) || !(0..60).contains(&minutes) {
+        return Err(invalid());
+    }
+    Ok(sign * (hours * 60 + minutes))
+}
+
+/// Splits seconds since unix epoch into year, month, day, hour, minute and second
+fn civil(secs: i64) -> (i64, i64, i64, i64, i64, i64) {
+    let (days, time) = (secs.div_euclid(24 * 60 * 60), secs.rem_euclid(24 * 60 * 60));
+    // days to proleptic Gregorian date, eras of 400 years start on March 1st
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let march_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * march_month + 2) / 5 + 1;
+    let month = if march_month < 10 { march_month + 3 } else { march_month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// Returns days since unix epoch of proleptic Gregorian date, inverse of `civil`
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let march_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * march_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_in_time_zone_and_style() {
+        // 2024-03-09 14:05 UTC
+        let at = 1_709_993_100_000;
+        assert_eq!(DateFormat::default().absolute(at), "2024-03-09 14:05");
+        assert_eq!(iso8601(at), "2024-03-09T14:05:00Z");
+        let us = DateFormat { time_zone: "-05:30".to_string(), style: DateStyle::Us };
+        assert_eq!(us.absolute(at), "03/09/2024 8:35 AM");
+
+        // offset moves new year's eve into next year
+        let european = DateFormat { time_zone: "+02:00".to_string(), style: DateStyle::European };
+        assert_eq!(european.absolute(946_683_000_000), "01.01.2000 01:30");
+
+        assert_eq!(european.relative(at, at + 30_000), "just now");
+        assert_eq!(european.relative(at, at + 60_000), "1 minute ago");
+        assert_eq!(european.relative(at, at + 2 * 60 * 60 * 1000 + 5), "2 hours ago");
+        assert_eq!(european.relative(at, at + 8 * 24 * 60 * 60 * 1000), "09.03.2024 16:05");
+
+        assert_eq!(DateFormat::default().parse("2024-03-09 14:05"), Ok(at));
+        assert_eq!(us.parse("2024-03-09 08:35"), Ok(at));
+        assert_eq!(european.parse("2000-01-01 01:30"), Ok(946_683_000_000));
+        assert!(DateFormat::default().parse("2024-02-30 10:00").is_err());
+        assert!(DateFormat::default().parse("tomorrow").is_err());
+
+        assert!(DateFormat { time_zone: "Mars".to_string(), ..DateFormat::default() }.validated().is_err());
+        assert!(DateFormat { time_zone: "+15:00".to_string(), ..DateFormat::default() }.validated().is_err());
+    }
+}
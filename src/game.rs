@@ -0,0 +1,212 @@
+//! # Game
+//!
+//! Common interface of game engines, variants with own rules implement it next to
+//! the classic playmat. Every game is also an object safe [`Engine`] taking moves
+//! as JSON, so [`Registry`] can create variants by the name agreed on in rules
+//! handshake, including variants registered by other crates.
+
+use crate::gomoku::BoardSize;
+use crate::order_chaos::{OrderChaos, Role};
+use crate::quantum::QuantumTicTacToe;
+use crate::tictactoe::{GameError, Marks, Rules, TicTacToe, Tile, Variant};
+
+/// Two player game played in turns, [`crate::conformance`] checks that variant
+/// keeps invariants shared by all games
+pub trait Game {
+    /// Turn of one player
+    type Move;
+    /// Reason why turn was rejected
+    type Error;
+
+    fn make_my_move(&mut self, turn: Self::Move) -> Result<(), Self::Error>;
+    fn make_opponent_move(&mut self, turn: Self::Move) -> Result<(), Self::Error>;
+    fn am_i_winner(&self) -> bool;
+    fn is_opponent_winner(&self) -> bool;
+    /// Returns true when no more turns can be played
+    fn is_finished(&self) -> bool;
+}
+
+impl Game for TicTacToe {
+    type Move = crate::tictactoe::Move;
+    type Error = GameError;
+
+    fn make_my_move(&mut self, (x, y): Self::Move) -> Result<(), GameError> {
+        self.make_my_turn(x, y).map(|_| ())
+    }
+
+    fn make_opponent_move(&mut self, (x, y): Self::Move) -> Result<(), GameError> {
+        self.make_opponent_turn(x, y).map(|_| ())
+    }
+
+    fn am_i_winner(&self) -> bool {
+        TicTacToe::am_i_winner(self)
+    }
+
+    fn is_opponent_winner(&self) -> bool {
+        TicTacToe::is_opponent_winner(self)
+    }
+
+    fn is_finished(&self) -> bool {
+        let full = self.iter_cells().all(|(_, tile)| tile != Tile::Empty);
+        full || self.am_i_winner() || self.is_opponent_winner()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    /// Move does not have the shape game expects
+    InvalidMove(String),
+    /// Game refused the move
+    Rejected(String),
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::InvalidMove(err) => write!(f, "invalid move: {}", err),
+            EngineError::Rejected(err) => write!(f, "move rejected: {}", err),
+        }
+    }
+}
+
+/// Object safe view of any game, moves are JSON in shape of the game's own moves
+pub trait Engine: Send {
+    fn play_my_move(&mut self, turn: &serde_json::Value) -> Result<(), EngineError>;
+    fn play_opponent_move(&mut self, turn: &serde_json::Value) -> Result<(), EngineError>;
+    fn i_won(&self) -> bool;
+    fn opponent_won(&self) -> bool;
+    fn is_over(&self) -> bool;
+}
+
+impl<G> Engine for G
+where
+    G: Game + Send,
+    G::Move: serde::de::DeserializeOwned,
+    G::Error: std::fmt::Display,
+{
+    fn play_my_move(&mut self, turn: &serde_json::Value) -> Result<(), EngineError> {
+        let turn = serde_json::from_value(turn.clone()).map_err(|err| EngineError::InvalidMove(err.to_string()))?;
+        self.make_my_move(turn).map_err(|err| EngineError::Rejected(err.to_string()))
+    }
+
+    fn play_opponent_move(&mut self, turn: &serde_json::Value) -> Result<(), EngineError> {
+        let turn = serde_json::from_value(turn.clone()).map_err(|err| EngineError::InvalidMove(err.to_string()))?;
+        self.make_opponent_move(turn).map_err(|err| EngineError::Rejected(err.to_string()))
+    }
+
+    fn i_won(&self) -> bool {
+        self.am_i_winner()
+    }
+
+    fn opponent_won(&self) -> bool {
+        self.is_opponent_winner()
+    }
+
+    fn is_over(&self) -> bool {
+        self.is_finished()
+    }
+}
+
+/// Creates new game, true when I proposed it and move first
+pub type Factory = fn(initiator: bool) -> Box<dyn Engine>;
+
+/// Variants known to the client by name
+#[derive(Clone)]
+pub struct Registry {
+    factories: std::collections::BTreeMap<String, Factory>,
+}
+
+impl Default for Registry {
+    /// Registry with all variants of this crate
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        registry.register(Variant::Standard.name(), |initiator| {
+            Box::new(TicTacToe::with_rules(initiator_marks(initiator), Rules::default()))
+        });
+        registry.register(Variant::Wild.name(), |initiator| {
+            Box::new(TicTacToe::with_rules(initiator_marks(initiator), Rules { variant: Variant::Wild, ..Rules::default() }))
+        });
+        registry.register(crate::gomoku::NAME, |initiator| {
+            Box::new(TicTacToe::with_rules(initiator_marks(initiator), Rules { board: Some(BoardSize::GOMOKU), ..Rules::default() }))
+        });
+        registry.register("order_chaos", |initiator| {
+            Box::new(OrderChaos::new(if initiator { Role::Order } else { Role::Chaos }))
+        });
+        registry.register("quantum", |initiator| Box::new(QuantumTicTacToe::with_marks(initiator_marks(initiator))));
+        registry
+    }
+}
+
+impl std::fmt::Debug for Registry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.factories.keys()).finish()
+    }
+}
+
+impl Registry {
+    /// Registry without any variant
+    pub fn empty() -> Registry {
+        Registry { factories: std::collections::BTreeMap::new() }
+    }
+
+    /// Adds variant, it replaces variant of the same name
+    pub fn register(&mut self, name: &str, factory: Factory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Returns names of registered variants in alphabetical order
+    pub fn names(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
+
+    /// Creates new game of given variant, none when it is not registered
+    pub fn create(&self, name: &str, initiator: bool) -> Option<Box<dyn Engine>> {
+        self.factories.get(name).map(|factory| factory(initiator))
+    }
+}
+
+/// Initiator plays crosses, which move first
+fn initiator_marks(initiator: bool) -> Marks {
+    if initiator {
+        Marks::default().swapped()
+    } else {
+        Marks::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crosses(_: bool) -> Box<dyn Engine> {
+        Box::new(TicTacToe::with_marks(Marks::default().swapped()))
+    }
+
+    #[test]
+    fn creates_registered_variants() {
+        let mut registry = Registry::default();
+        assert_eq!(registry.names(), vec!["gomoku", "order_chaos", "quantum", "standard", "wild"]);
+        assert!(registry.create("crosses", true).is_none());
+
+        let mut game = registry.create("standard", true).unwrap();
+        assert!(matches!(game.play_my_move(&serde_json::json!("B2")), Err(EngineError::InvalidMove(_))));
+        for turn in [[0, 0], [0, 1], [0, 2]] {
+            game.play_my_move(&serde_json::json!(turn)).unwrap();
+        }
+        assert!(game.i_won() && game.is_over());
+
+        let mut quantum = registry.create("quantum", false).unwrap();
+        let spooky = serde_json::json!({ "spooky": [[0, 0], [0, 0]] });
+        assert!(matches!(quantum.play_opponent_move(&spooky), Err(EngineError::Rejected(_))));
+
+        let mut gomoku = registry.create("gomoku", true).unwrap();
+        assert!(gomoku.play_my_move(&serde_json::json!([9, 9])).is_ok());
+
+        registry.register("crosses", crosses);
+        assert!(registry.contains("crosses"));
+    }
+}
@@ -0,0 +1,88 @@
+//! # Gomoku
+//!
+//! Square playmat of any size where line of given length wins, e.g. five in a
+//! row on 10x10 playmat. Classic game is 3x3 playmat with line of three, larger
+//! playmats are proposed in rules and played by [`crate::tictactoe::TicTacToe`]
+//! like the classic one. Crosses move first and players alternate, full playmat
+//! without line is a draw.
+
+use crate::coords::Coordinates;
+use crate::tictactoe::Tile;
+
+/// Name of the variant in [`crate::game::Registry`], clients without it decline larger playmats
+pub const NAME: &str = "gomoku";
+/// Largest playmat, labels and messages stay short up to it
+pub const MAX_SIZE: usize = 19;
+/// Shortest winning line
+pub const MIN_LINE: usize = 3;
+
+/// Size of square playmat and length of line which wins
+#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BoardSize {
+    pub size: usize,
+    pub line: usize,
+}
+
+impl BoardSize {
+    pub const CLASSIC: BoardSize = BoardSize { size: 3, line: 3 };
+    pub const GOMOKU: BoardSize = BoardSize { size: 10, line: 5 };
+
+    /// Returns board size when both players can play it
+    pub fn validated(self) -> Result<BoardSize, String> {
+        if !(MIN_LINE..=MAX_SIZE).contains(&self.size) {
+            return Err(format!("playmat has to have {} to {} rows, got {}", MIN_LINE, MAX_SIZE, self.size));
+        }
+        if !(MIN_LINE..=self.size).contains(&self.line) {
+            return Err(format!("line has to be {} to {} long, got {}", MIN_LINE, self.size, self.line));
+        }
+        Ok(self)
+    }
+}
+
+impl std::fmt::Display for BoardSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{} playmat with line of {}", self.size, self.size, self.line)
+    }
+}
+
+/// Returns true when tile in given field is part of line of given length
+pub fn completes_line<Row: AsRef<[Tile]>>(cells: &[Row], line: usize, (x, y): Coordinates) -> bool {
+    let tile = cells[x].as_ref()[y];
+    if tile == Tile::Empty {
+        return false;
+    }
+    let size = cells.len() as isize;
+    let count = |dx: isize, dy: isize| {
+        (1..line as isize)
+            .map(|step| (x as isize + step * dx, y as isize + step * dy))
+            .take_while(|&(x, y)| (0..size).contains(&x) && (0..size).contains(&y) && cells[x as usize].as_ref()[y as usize] == tile)
+            .count()
+    };
+    [(0, 1), (1, 0), (1, 1), (1, -1)]
+        .iter()
+        .any(|&(dx, dy)| 1 + count(dx, dy) + count(-dx, -dy) >= line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tictactoe::{Marks, Rules, TicTacToe};
+
+    #[test]
+    fn five_in_diagonal_wins_large_playmat() {
+        assert!(BoardSize { size: 10, line: 11 }.validated().is_err());
+        assert!(BoardSize { size: 20, line: 5 }.validated().is_err());
+
+        let rules = Rules { board: Some(BoardSize::GOMOKU.validated().unwrap()), ..Rules::default() };
+        let mut game = TicTacToe::with_rules(Marks::default().swapped(), rules);
+        for step in 0..4 {
+            assert!(game.make_my_turn(step + 3, step + 3).is_ok());
+            assert!(game.make_opponent_turn(0, step).is_ok());
+        }
+        assert!(game.make_opponent_turn(10, 0).is_err());
+        assert!(!game.am_i_winner());
+        assert!(game.make_my_turn(7, 7).is_ok());
+        assert!(game.am_i_winner());
+        assert_eq!(game.tile(7, 7), crate::tictactoe::Tile::Cross);
+    }
+}
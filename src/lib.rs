@@ -0,0 +1,48 @@
+//! # TicTacToe
+//!
+//! Tic tac toe engine with peer to peer multiplayer.
+//!
+//! Game engine, coordinates and themes have no networking dependencies,
+//! build with `default-features = false` to embed just the engine. Feature
+//! `network` alone builds the minimal peer to peer client, other features add
+//! optional subsystems on top of it.
+//!
+//! Engine API is re-exported here: [`Game`] trait implemented by every variant
+//! and classic [`TicTacToe`] with its [`Board`], [`Move`] and [`GameResult`].
+//! [`Turns`] keeps turn order of a game against remote opponent. Other
+//! frontends embed them the same way the terminal client does, which is only a
+//! consumer of this library.
+
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
+#[cfg(feature = "ai")]
+pub mod ai;
+pub mod cli;
+pub mod conformance;
+pub mod coords;
+pub mod dates;
+pub mod game;
+pub mod gomoku;
+pub mod order_chaos;
+#[cfg(feature = "ai")]
+pub mod personality;
+pub mod quantum;
+pub mod setup;
+#[cfg(feature = "ai")]
+pub mod simulate;
+#[cfg(feature = "ai")]
+pub mod solo;
+pub mod theme;
+pub mod tictactoe;
+pub mod turns;
+
+#[cfg(feature = "network")]
+pub mod config;
+#[cfg(feature = "network")]
+pub mod network_communication;
+
+pub use game::{Engine, Game};
+pub use tictactoe::{Board, GameError, GameResult, Move, TicTacToe, Tile};
+pub use turns::{TurnError, Turns};
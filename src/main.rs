@@ -1,10 +1,187 @@
-#[macro_use]
-extern crate quickcheck;
-
-pub mod network_communication;
+use tictactoe::{cli, config, coords, dates, network_communication, theme};
+#[cfg(feature = "ai")]
+use tictactoe::{personality, simulate, solo};
 
 #[tokio::main]
 async fn main() {
-    let mut input = network_communication::input::Stdio::new();
-    network_communication::start::<network_communication::input::Stdio>(&mut input).await;
+    let options = cli::Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+
+    if options.command == cli::Command::Doctor {
+        let checks = network_communication::doctor::run(&config::Config::path(options.config.as_deref())).await;
+        checks.iter().for_each(|check| println!("{}", check));
+        let failed = checks.iter().any(|check| check.status == network_communication::doctor::Status::Failed);
+        std::process::exit(if failed { 1 } else { 0 });
+    }
+
+    if options.command == cli::Command::LoadTest {
+        let duration = std::time::Duration::from_secs(options.seconds.unwrap_or(30));
+        let report = network_communication::loadtest::run(options.players.unwrap_or(8), duration).await;
+        println!("{}", report);
+        std::process::exit(0);
+    }
+
+    if let Some(archive) = &options.archive {
+        #[cfg(feature = "migrate")]
+        std::process::exit(migrate_identity(options.command, &config::Config::path(options.config.as_deref()), archive));
+        #[cfg(not(feature = "migrate"))]
+        {
+            eprintln!("Moving identity to {} needs the migrate feature.", archive.display());
+            std::process::exit(2);
+        }
+    }
+
+    let mut config = config::Config::load(options.config.as_deref());
+    if options.simul.is_some() {
+        config.session.simul_limit = options.simul;
+    }
+    if options.engine.is_some() {
+        config.session.engine = options.engine;
+    }
+    if options.eval {
+        config.session.eval_bar = true;
+    }
+    if options.teach {
+        config.session.teach = true;
+    }
+    if options.room.is_some() {
+        config.session.room = options.room;
+    }
+    if options.password.is_some() {
+        config.session.password = options.password;
+    }
+
+    let theme = theme::Theme::from_config(&config.theme);
+    let labels = config.coordinates.clone().validated().unwrap_or_else(|err| {
+        eprintln!("{}, using default coordinates", err);
+        coords::Labels::default()
+    });
+    let dates = config.dates.clone().validated().unwrap_or_else(|err| {
+        eprintln!("{}, using UTC", err);
+        dates::DateFormat::default()
+    });
+
+    #[cfg(feature = "ai")]
+    if options.command == cli::Command::PlayAi {
+        let name = options.personality.as_deref().unwrap_or(personality::DEFAULT_PERSONALITY);
+        let opponent = personality::find(name, &config.personalities).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        });
+        let stdin = std::io::stdin();
+        if let Err(err) = solo::play(&opponent, &labels, theme.move_delay, &mut personality::Rng::from_time(), stdin.lock(), &mut std::io::stdout()) {
+            eprintln!("{}", err);
+        }
+        std::process::exit(0);
+    }
+    #[cfg(feature = "ai")]
+    if options.command == cli::Command::Simulate {
+        let names = (options.p1.as_deref().unwrap_or("random"), options.p2.as_deref().unwrap_or("minimax"));
+        for name in [names.0, names.1] {
+            if let Err(err) = simulate::player(name, &config.personalities) {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }
+        }
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let seed = personality::Rng::from_time().below(usize::MAX) as u64;
+        let report = simulate::run(options.games.unwrap_or(1000), threads, seed, || {
+            let player = |name| simulate::player(name, &config.personalities).expect("player was found before");
+            (player(names.0), player(names.1))
+        });
+        println!("{}", report);
+        std::process::exit(0);
+    }
+    #[cfg(not(feature = "ai"))]
+    if matches!(options.command, cli::Command::PlayAi | cli::Command::Simulate) {
+        eprintln!("Playing against AI needs the ai feature.");
+        std::process::exit(2);
+    }
+    let extensions = network_communication::Extensions {
+        config_path: Some(config::Config::path(options.config.as_deref())),
+        ..Default::default()
+    };
+    match options.ui {
+        cli::Ui::Stdio => {
+            let mut input = network_communication::input::Stdio::new(theme, labels, dates);
+            network_communication::start_with(&mut input, config.session, extensions).await;
+        }
+        #[cfg(feature = "tui")]
+        cli::Ui::Tui => {
+            let mut tui = network_communication::tui::Tui::new(theme, labels, dates, !options.no_mouse).unwrap_or_else(|err| {
+                eprintln!("Cannot start terminal UI: {}", err);
+                std::process::exit(1);
+            });
+            network_communication::start_with(&mut tui, config.session, extensions).await;
+        }
+        #[cfg(not(feature = "tui"))]
+        cli::Ui::Tui => {
+            eprintln!("Terminal UI needs the tui feature.");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Exports or imports identity archive, returns exit code
+#[cfg(feature = "migrate")]
+fn migrate_identity(command: cli::Command, config_path: &std::path::Path, archive_path: &std::path::Path) -> i32 {
+    use network_communication::migrate;
+
+    let passphrase = read_passphrase("Passphrase: ");
+    let result = if command == cli::Command::ExportIdentity {
+        if read_passphrase("Repeat passphrase: ") != passphrase {
+            eprintln!("Passphrases do not match.");
+            return 2;
+        }
+        migrate::export(config_path, archive_path, &passphrase)
+            .map(|_| println!("Identity exported to {}, keep it and its passphrase safe.", archive_path.display()))
+    } else {
+        migrate::import(archive_path, config_path, &passphrase)
+            .map(|files| files.iter().for_each(|file| println!("Restored {}", file.display())))
+    };
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+/// Reads passphrase from terminal without showing it, piped one is read as line
+#[cfg(feature = "migrate")]
+fn read_passphrase(prompt: &str) -> String {
+    use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    eprint!("{}", prompt);
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) || crossterm::terminal::enable_raw_mode().is_err() {
+        let mut line = String::new();
+        let _ = std::io::stdin().read_line(&mut line);
+        return line.trim_end_matches(['\r', '\n']).to_string();
+    }
+    // raw mode does not echo typed keys
+    let mut passphrase = String::new();
+    while let Ok(event) = crossterm::event::read() {
+        let key = match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Enter => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                passphrase.clear();
+                break;
+            }
+            KeyCode::Char(c) => passphrase.push(c),
+            KeyCode::Backspace => {
+                passphrase.pop();
+            }
+            _ => {}
+        }
+    }
+    let _ = crossterm::terminal::disable_raw_mode();
+    eprintln!();
+    passphrase
 }
@@ -0,0 +1,286 @@
+//! # Audit
+//!
+//! Append-only log of every message exchanged on game topics, one JSON lines file
+//! per game. Each entry carries hash of the previous one and is signed with my
+//! identity, so edited entry no longer verifies. Signed head with number of
+//! entries and hash of the last one is kept next to the log and rewritten on
+//! every append, so log cut short or missing its head no longer verifies either.
+
+use sha2::{Digest, Sha256};
+
+use super::auth::{from_hex, to_hex};
+
+#[derive(Debug)]
+pub enum AuditError {
+    /// No audit directory is configured
+    Disabled,
+    NoSuchGame(String),
+    /// Entry with given sequence number breaks hash chain or signature
+    Tampered(u64),
+    Signing(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::Disabled => write!(f, "games are not audited, set audit_dir in config"),
+            AuditError::NoSuchGame(game_id) => write!(f, "there is no audit log of game {}", game_id),
+            AuditError::Tampered(seq) => write!(f, "audit log was modified at entry {}", seq),
+            AuditError::Signing(err) => write!(f, "cannot sign audit entry: {}", err),
+            AuditError::Io(err) => write!(f, "cannot access audit log: {}", err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One message as it went over the wire
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub at_millis: u64,
+    pub direction: Direction,
+    /// Peer which published the message, me for sent ones
+    pub peer_id: String,
+    pub payload: String,
+    /// Hash of previous entry, empty for the first one
+    pub prev: String,
+    /// Signature of entry with empty signature field
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl AuditEntry {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = AuditEntry { signature: String::new(), ..self.clone() };
+        serde_json::to_vec(&unsigned).expect("cannot jsonify audit entry")
+    }
+
+    fn hash(&self) -> String {
+        to_hex(&Sha256::digest(&serde_json::to_vec(self).expect("cannot jsonify audit entry")))
+    }
+}
+
+/// Number of entries in log and hash of the last one, stored apart from entries
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditHead {
+    pub entries: u64,
+    /// Hash of the last entry, empty for empty log
+    pub last: String,
+    /// Signature of head with empty signature field
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl AuditHead {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let unsigned = AuditHead { signature: String::new(), ..self.clone() };
+        serde_json::to_vec(&unsigned).expect("cannot jsonify audit head")
+    }
+}
+
+/// First line of exported log, tells who signed the entries
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExportHeader {
+    pub game_id: String,
+    pub signer: String,
+    /// Protobuf encoded public key of signer
+    pub public_key: String,
+    /// UTC time in ISO 8601
+    pub exported_at: String,
+    /// Signed head the exported entries must end with
+    pub head: AuditHead,
+}
+
+/// Audit logs of all games, signed by my identity
+pub struct AuditLog {
+    dir: std::path::PathBuf,
+    key: libp2p::identity::Keypair,
+    /// Sequence number and hash of the last entry per game
+    heads: std::collections::HashMap<String, (u64, String)>,
+}
+
+impl AuditLog {
+    pub fn new(dir: &std::path::Path, key: libp2p::identity::Keypair) -> AuditLog {
+        AuditLog { dir: dir.to_path_buf(), key, heads: std::collections::HashMap::new() }
+    }
+
+    /// Appends message to log of given game
+    pub fn record(&mut self, game_id: &str, direction: Direction, peer_id: &str, payload: &[u8]) -> Result<(), AuditError> {
+        use std::io::Write;
+        let (seq, prev) = match self.heads.get(game_id) {
+            Some((seq, hash)) => (seq + 1, hash.clone()),
+            None => match self.read(game_id).map(|entries| entries.last().cloned()) {
+                Ok(Some(last)) => (last.seq + 1, last.hash()),
+                Ok(None) | Err(AuditError::NoSuchGame(_)) => (0, String::new()),
+                Err(error) => return Err(error),
+            },
+        };
+
+        let mut entry = AuditEntry {
+            seq,
+            at_millis: super::clock::now_millis(),
+            direction,
+            peer_id: peer_id.to_string(),
+            payload: String::from_utf8_lossy(payload).into_owned(),
+            prev,
+            signature: String::new(),
+        };
+        let signature = self.key.sign(&entry.signed_bytes()).map_err(|error| AuditError::Signing(error.to_string()))?;
+        entry.signature = to_hex(&signature);
+
+        std::fs::create_dir_all(&self.dir).map_err(AuditError::Io)?;
+        let line = serde_json::to_string(&entry).expect("cannot jsonify audit entry");
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(game_id))
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(AuditError::Io)?;
+        let hash = entry.hash();
+        self.write_head(game_id, seq + 1, &hash)?;
+        self.heads.insert(game_id.to_string(), (seq, hash));
+        Ok(())
+    }
+
+    /// Signs head of log and replaces the previous one
+    fn write_head(&self, game_id: &str, entries: u64, last: &str) -> Result<(), AuditError> {
+        let mut head = AuditHead { entries, last: last.to_string(), signature: String::new() };
+        let signature = self.key.sign(&head.signed_bytes()).map_err(|error| AuditError::Signing(error.to_string()))?;
+        head.signature = to_hex(&signature);
+        // written aside and renamed so crash never leaves half of head
+        let path = self.head_path(game_id);
+        let temporary = path.with_extension("head.tmp");
+        std::fs::write(&temporary, serde_json::to_string(&head).expect("cannot jsonify audit head"))
+            .and_then(|_| std::fs::rename(&temporary, &path))
+            .map_err(AuditError::Io)
+    }
+
+    /// Verifies log of given game and writes it with signer header into target file,
+    /// returns number of entries
+    pub fn export(&self, game_id: &str, target: &std::path::Path) -> Result<usize, AuditError> {
+        let entries = self.read(game_id)?;
+        let head = self.read_head(game_id, entries.len())?;
+        let public = self.key.public();
+        verify(&public, &entries)?;
+        verify_head(&public, &head, &entries)?;
+
+        let header = ExportHeader {
+            game_id: game_id.to_string(),
+            signer: libp2p::PeerId::from(public.clone()).to_string(),
+            public_key: to_hex(&public.encode_protobuf()),
+            exported_at: crate::dates::iso8601(super::clock::now_millis()),
+            head,
+        };
+        let lines: Vec<String> = std::iter::once(serde_json::to_string(&header))
+            .chain(entries.iter().map(serde_json::to_string))
+            .collect::<Result<_, _>>()
+            .expect("cannot jsonify audit log");
+        std::fs::write(target, lines.join("\n") + "\n").map_err(AuditError::Io)?;
+        Ok(entries.len())
+    }
+
+    fn read(&self, game_id: &str) -> Result<Vec<AuditEntry>, AuditError> {
+        let content = match std::fs::read_to_string(self.path(game_id)) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Err(AuditError::NoSuchGame(game_id.to_string())),
+            Err(error) => return Err(AuditError::Io(error)),
+        };
+        content
+            .lines()
+            .enumerate()
+            .map(|(seq, line)| serde_json::from_str(line).map_err(|_| AuditError::Tampered(seq as u64)))
+            .collect()
+    }
+
+    /// Reads head of log with given number of entries, missing or unreadable
+    /// head counts as tampering after the last entry
+    fn read_head(&self, game_id: &str, entries: usize) -> Result<AuditHead, AuditError> {
+        match std::fs::read_to_string(self.head_path(game_id)) {
+            Ok(content) => serde_json::from_str(&content).map_err(|_| AuditError::Tampered(entries as u64)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Err(AuditError::Tampered(entries as u64)),
+            Err(error) => Err(AuditError::Io(error)),
+        }
+    }
+
+    fn path(&self, game_id: &str) -> std::path::PathBuf {
+        self.dir.join(format!("{}.jsonl", file_stem(game_id)))
+    }
+
+    fn head_path(&self, game_id: &str) -> std::path::PathBuf {
+        self.dir.join(format!("{}.head", file_stem(game_id)))
+    }
+}
+
+/// Returns game id usable as file name, topic separators are replaced
+pub fn file_stem(game_id: &str) -> String {
+    game_id.replace('/', "_")
+}
+
+/// Checks that entries form unbroken chain signed by given key
+pub fn verify(public: &libp2p::identity::PublicKey, entries: &[AuditEntry]) -> Result<(), AuditError> {
+    let mut prev = String::new();
+    for (seq, entry) in entries.iter().enumerate() {
+        let signed = from_hex(&entry.signature).is_some_and(|signature| public.verify(&entry.signed_bytes(), &signature));
+        if entry.seq != seq as u64 || entry.prev != prev || !signed {
+            return Err(AuditError::Tampered(seq as u64));
+        }
+        prev = entry.hash();
+    }
+    Ok(())
+}
+
+/// Checks that entries end where signed head says, reports the first missing
+/// entry of truncated log
+pub fn verify_head(public: &libp2p::identity::PublicKey, head: &AuditHead, entries: &[AuditEntry]) -> Result<(), AuditError> {
+    let signed = from_hex(&head.signature).is_some_and(|signature| public.verify(&head.signed_bytes(), &signature));
+    let last = entries.last().map(AuditEntry::hash).unwrap_or_default();
+    if !signed || head.entries != entries.len() as u64 || head.last != last {
+        return Err(AuditError::Tampered(entries.len().min(head.entries as usize) as u64));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_modified_entry() {
+        let dir = std::env::temp_dir().join(format!("tictactoe-audit-{}", std::process::id()));
+        let key = libp2p::identity::Keypair::generate_ed25519();
+        let mut log = AuditLog::new(&dir, key.clone());
+        log.record("TicTacToe/a/b", Direction::Sent, "a", br#"{"turn":1}"#).unwrap();
+        log.record("TicTacToe/a/b", Direction::Received, "b", br#"{"turn":2}"#).unwrap();
+        // reopened log continues the chain
+        AuditLog::new(&dir, key.clone()).record("TicTacToe/a/b", Direction::Sent, "a", br#"{"turn":3}"#).unwrap();
+
+        let mut entries = log.read("TicTacToe/a/b").unwrap();
+        let head = log.read_head("TicTacToe/a/b", entries.len()).unwrap();
+        let exported = log.export("TicTacToe/a/b", &dir.join("export.jsonl"));
+        // log cut after its second entry still forms a chain, but not the signed head
+        let content = std::fs::read_to_string(log.path("TicTacToe/a/b")).unwrap();
+        let truncated: Vec<&str> = content.lines().take(2).collect();
+        std::fs::write(log.path("TicTacToe/a/b"), truncated.join("\n") + "\n").unwrap();
+        let exported_truncated = log.export("TicTacToe/a/b", &dir.join("export.jsonl"));
+        std::fs::remove_file(log.head_path("TicTacToe/a/b")).unwrap();
+        let exported_headless = log.export("TicTacToe/a/b", &dir.join("export.jsonl"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(exported.unwrap(), 3);
+        assert!(matches!(exported_truncated, Err(AuditError::Tampered(2))));
+        assert!(matches!(exported_headless, Err(AuditError::Tampered(2))));
+        assert!(verify(&key.public(), &entries).is_ok());
+        assert!(verify_head(&key.public(), &head, &entries).is_ok());
+        assert!(matches!(verify_head(&key.public(), &head, &entries[..2]), Err(AuditError::Tampered(2))));
+        entries[1].payload = r#"{"turn":5}"#.to_string();
+        assert!(matches!(verify(&key.public(), &entries), Err(AuditError::Tampered(1))));
+        assert!(matches!(verify(&key.public(), &entries[1..]), Err(AuditError::Tampered(0))));
+    }
+}
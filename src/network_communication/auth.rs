@@ -0,0 +1,57 @@
+//! # Auth
+//!
+//! Password protected games. Invitation carries salted hash of the password
+//! bound to inviting peer, so the password itself never goes over the network.
+
+use sha2::{Digest, Sha256};
+
+use super::protocol::Credentials;
+
+/// Hashes password for invitation sent by given peer
+pub fn sign(password: &str, inviter_id: &str) -> Credentials {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    let salt = to_hex(&Sha256::digest(format!("{}:{}", inviter_id, nanos).as_bytes())[..16]);
+    let hash = hash(&salt, inviter_id, password);
+    Credentials { salt, hash }
+}
+
+/// Returns true when credentials of invitation from given peer match password
+pub fn verify(password: &str, inviter_id: &str, credentials: Option<&Credentials>) -> bool {
+    credentials.is_some_and(|credentials| hash(&credentials.salt, inviter_id, password) == credentials.hash)
+}
+
+fn hash(salt: &str, inviter_id: &str, password: &str) -> String {
+    to_hex(&Sha256::digest(format!("{}:{}:{}", salt, inviter_id, password).as_bytes()))
+}
+
+pub(super) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(super) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| hex.get(index..index + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_password() {
+        let credentials = sign("secret", "peer");
+        assert_ne!(credentials.hash, sign("secret", "peer").hash);
+        assert!(verify("secret", "peer", Some(&credentials)));
+        assert!(!verify("wrong", "peer", Some(&credentials)));
+        assert!(!verify("secret", "other", Some(&credentials)));
+        assert!(!verify("secret", "peer", None));
+    }
+}
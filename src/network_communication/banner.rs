@@ -0,0 +1,44 @@
+//! # Banner
+//!
+//! Who I am on the network, shown when client starts and by 'whoami' command.
+//! Fingerprint is short enough to be read out to a friend, who compares it with
+//! the one of peer they are about to invite.
+
+use sha2::{Digest, Sha256};
+
+/// Bytes of key hash shown in fingerprint
+const FINGERPRINT_BYTES: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Banner {
+    pub peer_id: String,
+    pub fingerprint: String,
+    pub nickname: Option<String>,
+    pub listen_addrs: Vec<libp2p::Multiaddr>,
+}
+
+/// Returns hash of public key in groups of four hex digits, e.g. "3f2a 91c0 77de 0b15"
+pub fn fingerprint(public: &libp2p::identity::PublicKey) -> String {
+    let hash = Sha256::digest(&public.clone().encode_protobuf());
+    let hex = super::auth::to_hex(&hash[..FINGERPRINT_BYTES]);
+    hex.as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_is_short_and_stable() {
+        let key = libp2p::identity::Keypair::generate_ed25519();
+        let fingerprint = fingerprint(&key.public());
+        assert_eq!(fingerprint.len(), 19);
+        assert_eq!(fingerprint.split(' ').count(), 4);
+        assert_eq!(super::fingerprint(&key.public()), fingerprint);
+        assert_ne!(super::fingerprint(&libp2p::identity::Keypair::generate_ed25519().public()), fingerprint);
+    }
+}
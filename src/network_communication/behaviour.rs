@@ -0,0 +1,618 @@
+//! # Behaviour
+//!
+//! Network behaviour of the client. It receives messages of every topic and
+//! protocol, checks them and passes them to the main loop as [`PeerMessage`].
+
+use super::session::GameSession;
+use super::swarm::IDENTIFY_PROTOCOL;
+use super::{
+    audit, channel, clock, compression, direct, discovery, ladder, lobby, nat, netstats, protocol, referee, replay, review, seal, spectate, transfer,
+    validation,
+};
+use crate::coords::Coordinates;
+use crate::tictactoe;
+use itertools::Itertools;
+use libp2p::core::transport::PortUse;
+use libp2p::core::Endpoint;
+use libp2p::swarm::behaviour::toggle::Toggle;
+use libp2p::swarm::{ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm};
+use tokio::sync::mpsc;
+
+pub(super) type InitiatorId = String;
+
+#[derive(Debug)]
+pub(super) enum GameStatus {
+    /// Invitation naming invited peer, with password proof when given, rules, nonce,
+    /// proposed start time of the game and proposer's introduction
+    Init(InitiatorId, Option<protocol::Credentials>, tictactoe::Rules, Option<String>, Option<u64>, protocol::Introduction),
+    Start(bool),
+    /// Turn with sender time when it was sent, placed tile in variants where players choose it,
+    /// move number and whether client of away opponent played it
+    Turn(usize, usize, Option<u64>, Option<tictactoe::Tile>, Option<usize>, bool),
+    Invalid(validation::InvalidMessage, validation::Diagnostics),
+    Nudge,
+    /// Opponent withdrew invitation before it was answered
+    Withdrawn,
+    /// Internal tick of session reminder timer
+    ReminderTick,
+    /// Peer is no longer discovered on network
+    PeerLost,
+    PeerFound,
+    /// Peer subscribed to game topic, messages queued for it can be sent
+    TopicJoined,
+    /// Opponent's number of played moves after its network was rebuilt
+    Resume(usize),
+    /// Other start time proposed by opponent, or my one sent back when they agree
+    Reschedule(u64),
+    /// Opponent did not move in time and gave up the game
+    Forfeit,
+    /// Opponent resigned the game
+    Resign,
+    /// Peer found by discovery strategy with addresses to dial
+    Discovered(discovery::DiscoveryMethod, Vec<libp2p::Multiaddr>),
+    NatStatus(nat::NatStatus),
+    /// Clock synchronization request with sender time
+    Ping(u64),
+    /// Answer to my ping: my ping time, time peer received it and sent answer
+    Pong(u64, u64, u64),
+    Chat(String),
+    Review(review::ReviewMessage),
+    /// Move chosen by external engine, sender is opponent of the session
+    EngineMove(usize, usize, tictactoe::Tile),
+    EngineFailed(String),
+    /// Ladder result gossiped in room, sender may only relay it
+    Attested(ladder::Attestation),
+    /// Peer joined ladder topic, it gets results I know
+    LadderJoined,
+    /// Peer looks for a game or no longer does
+    Lobby(lobby::LobbyMessage),
+    /// Peer joined seeking topic, it gets my open game
+    LobbyJoined,
+
+    /// Direct message to peer failed with reason, it was broadcast instead
+    Undelivered(String),
+    /// Relayed connection to peer became direct, or why hole punching failed
+    HolePunch(Result<(), String>),
+    /// Handshake shows peer runs older version which lacks given features
+    OlderPeer(&'static [&'static str]),
+    /// Chunks of replay acknowledged by peer and all its chunks
+    ReplayProgress(usize, usize),
+    ReplayReceived(replay::Replay),
+    /// Replay could not be sent or received, with reason
+    ReplayTransferFailed(String),
+    /// All moves of watched game with given id and its rules, sender plays it
+    Spectated(String, tictactoe::Rules, Vec<(Coordinates, tictactoe::Tile)>),
+    /// Result of game signed by referee, signature was checked
+    Verdict(referee::Verdict),
+    /// Opponent did not play my turn on given field
+    InvalidMove(Coordinates, protocol::MoveRejection),
+}
+
+impl GameStatus {
+    /// Returns what message of peer was, as told to user
+    pub(super) fn kind(&self) -> &'static str {
+        match self {
+            GameStatus::Init(..) => "invitation",
+            GameStatus::Start(_) | GameStatus::Withdrawn => "answer to invitation",
+            GameStatus::Turn(..) => "turn",
+            GameStatus::Chat(_) => "chat message",
+            GameStatus::Resign | GameStatus::Forfeit => "resignation",
+            GameStatus::Review(_) => "review message",
+            _ => "game message",
+        }
+    }
+}
+
+/// Game message together with peer which published it
+#[derive(Debug)]
+pub(crate) struct PeerMessage {
+    pub(super) sender: String,
+    pub(super) status: GameStatus,
+    /// Format of received message, none when produced by this client
+    pub(super) format: Option<protocol::WireFormat>,
+}
+
+impl channel::Droppable for PeerMessage {
+    /// Chatter about peers and clocks can be dropped, it is repeated or refreshed later
+    fn is_droppable(&self) -> bool {
+        matches!(
+            self.status,
+            GameStatus::PeerFound
+                | GameStatus::Discovered(..)
+                | GameStatus::NatStatus(_)
+                | GameStatus::ReminderTick
+                | GameStatus::Ping(_)
+                | GameStatus::Pong(..)
+        )
+    }
+}
+
+impl PeerMessage {
+    /// Message produced by this client, not by any peer
+    pub(super) fn internal(status: GameStatus) -> PeerMessage {
+        PeerMessage::about(String::new(), status)
+    }
+
+    /// Message produced by this client concerning given peer
+    pub(super) fn about(peer_id: String, status: GameStatus) -> PeerMessage {
+        PeerMessage { sender: peer_id, status, format: None }
+    }
+}
+
+/// Protocols the client speaks, [`TicTacToeBehaviour`] processes their events
+#[derive(libp2p::swarm::NetworkBehaviour)]
+pub(crate) struct Protocols {
+    pub(super) floodsub: libp2p::floodsub::Floodsub,
+    /// Disabled on in-process network of load test
+    pub(super) mdns: Toggle<libp2p::mdns::tokio::Behaviour>,
+    pub(super) kademlia: Toggle<libp2p::kad::Behaviour<libp2p::kad::store::MemoryStore>>,
+    pub(super) identify: libp2p::identify::Behaviour,
+    /// Asks peers to dial me back, answers the same question of others
+    pub(super) autonat: libp2p::autonat::Behaviour,
+    /// Reservations at relays and connections to peers through them
+    pub(super) relay: Toggle<libp2p::relay::client::Behaviour>,
+    /// Upgrades relayed connections to direct ones by hole punching
+    pub(super) dcutr: Toggle<libp2p::dcutr::Behaviour>,
+    /// Relays connections between other peers
+    pub(super) relay_server: Toggle<libp2p::relay::Behaviour>,
+    /// Invitations, answers and turns sent only to the peer they are for
+    pub(super) direct: libp2p::request_response::Behaviour<direct::DirectCodec>,
+    /// Stored replays shared with peers chunk by chunk
+    pub(super) replays: libp2p::request_response::Behaviour<transfer::ReplayCodec>,
+    /// Registrations at rendezvous point and discovery of players registered there
+    pub(super) rendezvous: Toggle<libp2p::rendezvous::client::Behaviour>,
+    /// Keeps registrations of other players, I am their rendezvous point
+    pub(super) rendezvous_point: Toggle<libp2p::rendezvous::server::Behaviour>,
+}
+
+pub(crate) struct TicTacToeBehaviour {
+    pub(super) protocols: Protocols,
+    /// Direct messages waiting for acknowledgement, broadcast when delivery fails
+    pub(super) unacknowledged: std::collections::HashMap<libp2p::request_response::OutboundRequestId, direct::DirectMessage>,
+    /// Peers which do not support direct messages, e.g. older clients
+    pub(super) floodsub_only: std::collections::HashSet<libp2p::PeerId>,
+    /// Replays being sent by request of their last chunk
+    pub(super) outgoing_replays: std::collections::HashMap<libp2p::request_response::OutboundRequestId, transfer::Outgoing>,
+    pub(super) incoming_replays: transfer::Incoming,
+    /// Addresses peers see me at and connections opened to me from internet
+    pub(super) nat: nat::NatObserver,
+    pub(super) response_sender: channel::Sender<PeerMessage>,
+    pub(super) diagnostics: validation::Diagnostics,
+    /// Peers in floodsub view with time they were last discovered or heard from
+    pub(super) last_seen: std::collections::HashMap<libp2p::PeerId, std::time::Instant>,
+    pub(super) lobby: libp2p::floodsub::Topic,
+    /// Peers subscribed to my lobby, i.e. in the same room
+    pub(super) lobby_members: std::collections::HashSet<libp2p::PeerId>,
+    pub(super) user_peer_id: String,
+    /// Log of game messages, none when auditing is off
+    pub(super) audit: Option<audit::AuditLog>,
+    pub(super) netstats: netstats::NetStats,
+    /// Strategies which found each peer
+    pub(super) found_by: std::collections::HashMap<libp2p::PeerId, std::collections::BTreeSet<discovery::DiscoveryMethod>>,
+    /// Addresses peers were found or dialed at, the most recent first
+    pub(super) addresses: std::collections::HashMap<libp2p::PeerId, Vec<libp2p::Multiaddr>>,
+    /// Topic of room's ladder when it is played
+    pub(super) ladder_topic: Option<libp2p::floodsub::Topic>,
+    /// Topic where players of my room announce open games
+    pub(super) seeking_topic: libp2p::floodsub::Topic,
+    /// Nonces and sequence numbers of running games
+    pub(super) seals: seal::Seals,
+    /// Codecs opponents read
+    pub(super) compression: compression::Negotiated,
+    pub(super) errors: mpsc::UnboundedSender<String>,
+}
+
+impl TicTacToeBehaviour {
+    /// Notes address where peer was found or dialed
+    pub(super) fn note_address(&mut self, peer: libp2p::PeerId, address: libp2p::Multiaddr) {
+        let addresses = self.addresses.entry(peer).or_default();
+        addresses.retain(|known| *known != address);
+        addresses.insert(0, address);
+    }
+
+    /// Returns true when mDNS currently sees the peer
+    pub(super) fn is_discovered(&self, peer: &libp2p::PeerId) -> bool {
+        self.protocols.mdns.as_ref().is_some_and(|mdns| mdns.discovered_nodes().any(|node| node == peer))
+    }
+
+    /// Adds message on game topic to audit log, lobby is not audited
+    pub(super) fn audit(&mut self, topic: &libp2p::floodsub::Topic, direction: audit::Direction, peer_id: &str, payload: &[u8]) {
+        if Some(topic) == self.ladder_topic.as_ref() || spectate::is_watch_topic(topic) {
+            return;
+        }
+        if let Some(log) = self.audit.as_mut().filter(|_| *topic != self.lobby) {
+            if let Err(error) = log.record(topic.id(), direction, peer_id, payload) {
+                let _ = self.errors.send(format!("Cannot write audit log: {}", error));
+            }
+        }
+    }
+
+    /// Subscribes to game topic, messages of games with nonce are sealed from now on
+    pub(super) fn join_game(&mut self, game_session: &GameSession) {
+        if let Some(nonce) = &game_session.nonce {
+            self.seals.start(game_session.topic.id(), nonce, clock::now_millis());
+        }
+        self.compression.join(game_session.topic.id(), &game_session.opponent_id);
+        self.protocols.floodsub.subscribe(game_session.topic.clone());
+    }
+
+    pub(super) fn leave_game(&mut self, game_session: &GameSession) {
+        self.seals.end(game_session.topic.id());
+        self.compression.leave(game_session.topic.id());
+        self.protocols.floodsub.unsubscribe(game_session.topic.clone());
+    }
+
+    /// Serializes message for topic, sealed when it belongs to game with nonce and
+    /// compressed when it is large and opponent reads compressed messages
+    pub(super) fn encode(&mut self, topic: &libp2p::floodsub::Topic, message: &protocol::WireMessage, format: protocol::WireFormat) -> String {
+        let seal = self.seals.seal(topic.id(), message);
+        let payload = protocol::encode_sealed(message, format, seal);
+        match self.compression.codec(topic.id()) {
+            Some(codec) if format == protocol::WireFormat::Tagged => compression::compress(payload, codec),
+            _ => payload,
+        }
+    }
+
+    /// Publishes encoded message, game messages are audited
+    pub(super) fn publish(&mut self, topic: libp2p::floodsub::Topic, payload: String) {
+        let user_peer_id = self.user_peer_id.clone();
+        self.audit(&topic, audit::Direction::Sent, &user_peer_id, payload.as_bytes());
+        self.netstats.on_sent(topic.id(), payload.as_bytes());
+        self.protocols.floodsub.publish(topic, payload.into_bytes());
+    }
+
+    /// Sends encoded message only to peer, over floodsub when peer cannot take it directly
+    pub(super) fn deliver(&mut self, peer: libp2p::PeerId, topic: libp2p::floodsub::Topic, payload: String) {
+        if self.floodsub_only.contains(&peer) {
+            self.publish(topic, payload);
+            return;
+        }
+        let user_peer_id = self.user_peer_id.clone();
+        self.audit(&topic, audit::Direction::Sent, &user_peer_id, payload.as_bytes());
+        self.netstats.on_sent(topic.id(), payload.as_bytes());
+        let message = direct::DirectMessage { topic: topic.id().to_string(), payload };
+        let request = self.protocols.direct.send_request(&peer, message.clone());
+        self.unacknowledged.insert(request, message);
+    }
+
+    /// Starts sending replay to peer, each next chunk goes once the previous one is acknowledged
+    pub(super) fn send_replay(&mut self, peer: libp2p::PeerId, content: &str) {
+        let outgoing = transfer::Outgoing::new(peer.to_string(), clock::now_millis(), content);
+        self.send_next_chunk(peer, outgoing);
+    }
+
+    /// Sends next chunk of replay and reports how many were acknowledged
+    pub(super) fn send_next_chunk(&mut self, peer: libp2p::PeerId, mut outgoing: transfer::Outgoing) {
+        let (sent, total) = outgoing.progress();
+        if sent > 0 {
+            let status = GameStatus::ReplayProgress(sent, total);
+            let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+        }
+        if let Some(chunk) = outgoing.next_chunk() {
+            let request = self.protocols.replays.send_request(&peer, chunk);
+            self.outgoing_replays.insert(request, outgoing);
+        }
+    }
+
+    /// Records and validates message from peer, none when it is not passed to main loop
+    pub(super) fn receive(
+        &mut self,
+        peer: libp2p::PeerId,
+        topics: &[libp2p::floodsub::Topic],
+        data: &[u8],
+    ) -> Option<(GameStatus, Option<protocol::WireFormat>)> {
+        self.last_seen.insert(peer, std::time::Instant::now());
+        let source = peer.to_string();
+        for topic in topics {
+            self.audit(topic, audit::Direction::Received, &source, data);
+        }
+        let now = clock::now_millis();
+        let fresh = topics.iter().all(|topic| self.netstats.on_received(topic.id(), &source, data, now));
+        if !fresh {
+            return None;
+        }
+        if self.ladder_topic.as_ref().is_some_and(|topic| topics.contains(topic)) {
+            let attestation = serde_json::from_slice(data).ok()?;
+            return Some((GameStatus::Attested(attestation), None));
+        }
+        if topics.contains(&self.seeking_topic) {
+            let message = serde_json::from_slice(data).ok()?;
+            return Some((GameStatus::Lobby(message), None));
+        }
+        if let Some((message, _, seal)) = protocol::decode_sealed(data) {
+            if let Some(codecs) = message.compression() {
+                self.compression.on_offer(&source, codecs);
+            }
+            for topic in topics {
+                if let Err(error) = self.seals.check(topic.id(), &source, &message, seal.as_ref()) {
+                    let error = validation::InvalidMessage::Seal(error);
+                    self.diagnostics.record(&error);
+                    return Some((GameStatus::Invalid(error, self.diagnostics), None));
+                }
+            }
+        }
+        match validation::validate(data) {
+            Ok((status, format)) => Some((status, Some(format))),
+            Err(error) => {
+                self.diagnostics.record(&error);
+                Some((GameStatus::Invalid(error, self.diagnostics), None))
+            }
+        }
+    }
+
+    /// Publishes message which was queued before, it is counted as retransmission
+    pub(super) fn republish(&mut self, topic: libp2p::floodsub::Topic, payload: String) {
+        self.netstats.on_retransmitted(topic.id());
+        self.publish(topic, payload);
+    }
+
+    /// Passes message from peer to main loop, panic caused by one message must not take down the client
+    pub(super) fn pass_received(&mut self, peer: libp2p::PeerId, topics: &[libp2p::floodsub::Topic], data: &[u8]) {
+        let (status, format) = match validation::contain(|| self.receive(peer, topics, data)) {
+            Ok(Some(received)) => received,
+            Ok(None) => return,
+            Err(error) => {
+                self.diagnostics.record(&error);
+                (GameStatus::Invalid(error, self.diagnostics), None)
+            }
+        };
+        self.response_sender
+            .send(PeerMessage { sender: peer.to_string(), status, format })
+            .expect("Error while sending message");
+    }
+}
+
+impl TicTacToeBehaviour {
+    /// Passes event of one of the protocols to its handler
+    fn process(&mut self, event: ProtocolsEvent) {
+        match event {
+            ProtocolsEvent::Floodsub(event) => self.on_floodsub(event),
+            ProtocolsEvent::Mdns(event) => self.on_mdns(event),
+            ProtocolsEvent::Kademlia(event) => self.on_kademlia(event),
+            ProtocolsEvent::Identify(event) => self.on_identify(event),
+            ProtocolsEvent::Autonat(event) => self.on_autonat(event),
+            ProtocolsEvent::Direct(event) => self.on_direct(event),
+            ProtocolsEvent::Replays(event) => self.on_replays(event),
+            ProtocolsEvent::Rendezvous(event) => self.on_rendezvous(event),
+            ProtocolsEvent::RendezvousPoint(event) => self.on_rendezvous_point(event),
+            ProtocolsEvent::Dcutr(event) => self.on_dcutr(event),
+            // relayed connections show up as any other
+            ProtocolsEvent::Relay(_) | ProtocolsEvent::RelayServer(_) => {}
+        }
+    }
+
+    fn on_floodsub(&mut self, event: libp2p::floodsub::FloodsubEvent) {
+        match event {
+            libp2p::floodsub::FloodsubEvent::Message(msg) => {
+                self.pass_received(msg.source, &msg.topics, &msg.data);
+            }
+            libp2p::floodsub::FloodsubEvent::Subscribed { peer_id, topic } if topic == self.lobby => {
+                self.lobby_members.insert(peer_id);
+            }
+            libp2p::floodsub::FloodsubEvent::Subscribed { peer_id, topic } if Some(&topic) == self.ladder_topic.as_ref() => {
+                let _ = self.response_sender.send(PeerMessage::about(peer_id.to_string(), GameStatus::LadderJoined));
+            }
+            libp2p::floodsub::FloodsubEvent::Subscribed { peer_id, topic } if topic == self.seeking_topic => {
+                let _ = self.response_sender.send(PeerMessage::about(peer_id.to_string(), GameStatus::LobbyJoined));
+            }
+            libp2p::floodsub::FloodsubEvent::Subscribed { peer_id, .. } => {
+                let _ = self.response_sender.send(PeerMessage::about(peer_id.to_string(), GameStatus::TopicJoined));
+            }
+            libp2p::floodsub::FloodsubEvent::Unsubscribed { peer_id, topic } if topic == self.lobby => {
+                self.lobby_members.remove(&peer_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_direct(&mut self, event: libp2p::request_response::Event<direct::DirectMessage, direct::Ack>) {
+        use libp2p::request_response::{Event, Message, OutboundFailure};
+        match event {
+            Event::Message { peer, message: Message::Request { request, channel, .. } } => {
+                let _ = self.protocols.direct.send_response(channel, direct::Ack);
+                let topic = libp2p::floodsub::Topic::new(request.topic);
+                self.pass_received(peer, &[topic], request.payload.as_bytes());
+            }
+            Event::Message { message: Message::Response { request_id, .. }, .. } => {
+                self.unacknowledged.remove(&request_id);
+            }
+            Event::OutboundFailure { peer, request_id, error } => {
+                let message = match self.unacknowledged.remove(&request_id) {
+                    Some(message) => message,
+                    None => return,
+                };
+                // floodsub may still reach peer through others, as it did before direct messages
+                if let OutboundFailure::UnsupportedProtocols = error {
+                    self.floodsub_only.insert(peer);
+                } else {
+                    let status = GameStatus::Undelivered(error.to_string());
+                    let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+                }
+                let topic = libp2p::floodsub::Topic::new(message.topic);
+                self.protocols.floodsub.publish(topic, message.payload.into_bytes());
+            }
+            Event::InboundFailure { .. } | Event::ResponseSent { .. } => {}
+        }
+    }
+
+    fn on_replays(&mut self, event: libp2p::request_response::Event<transfer::Chunk, direct::Ack>) {
+        use libp2p::request_response::{Event, Message};
+        match event {
+            Event::Message { peer, message: Message::Request { request, channel, .. } } => {
+                let _ = self.protocols.replays.send_response(channel, direct::Ack);
+                let status = match self.incoming_replays.accept(&peer.to_string(), request) {
+                    Ok(None) => return,
+                    Ok(Some(content)) => match serde_json::from_str(&content) {
+                        Ok(replay) => GameStatus::ReplayReceived(replay),
+                        Err(_) => GameStatus::ReplayTransferFailed("received malformed replay".to_string()),
+                    },
+                    Err(error) => GameStatus::ReplayTransferFailed(error.to_string()),
+                };
+                let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+            }
+            Event::Message { peer, message: Message::Response { request_id, .. } } => {
+                if let Some(outgoing) = self.outgoing_replays.remove(&request_id) {
+                    self.send_next_chunk(peer, outgoing);
+                }
+            }
+            Event::OutboundFailure { peer, request_id, error } => {
+                if self.outgoing_replays.remove(&request_id).is_some() {
+                    let status = GameStatus::ReplayTransferFailed(error.to_string());
+                    let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+                }
+            }
+            Event::InboundFailure { .. } | Event::ResponseSent { .. } => {}
+        }
+    }
+
+    fn on_mdns(&mut self, event: libp2p::mdns::Event) {
+        match event {
+            libp2p::mdns::Event::Discovered(discovered) => {
+                // peer may be discovered on several addresses at once
+                for (peer, address) in &discovered {
+                    self.note_address(*peer, address.clone());
+                }
+                for peer in discovered.into_iter().map(|(peer, _addr)| peer).unique() {
+                    self.protocols.floodsub.add_node_to_partial_view(peer);
+                    self.last_seen.insert(peer, std::time::Instant::now());
+                    self.found_by.entry(peer).or_default().insert(discovery::DiscoveryMethod::Mdns);
+                    let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), GameStatus::PeerFound));
+                }
+            }
+            libp2p::mdns::Event::Expired(expired) => {
+                for peer in expired.into_iter().map(|(peer, _addr)| peer).unique() {
+                    if !self.is_discovered(&peer) {
+                        self.protocols.floodsub.remove_node_from_partial_view(&peer);
+                        self.last_seen.remove(&peer);
+                        self.lobby_members.remove(&peer);
+                        self.found_by.remove(&peer);
+                        let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), GameStatus::PeerLost));
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_kademlia(&mut self, event: libp2p::kad::Event) {
+        if let libp2p::kad::Event::RoutingUpdated { peer, addresses, .. } = event {
+            let status = GameStatus::Discovered(discovery::DiscoveryMethod::Kademlia, addresses.into_vec());
+            let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+        }
+    }
+
+    fn on_identify(&mut self, event: libp2p::identify::Event) {
+        if let libp2p::identify::Event::Received { peer_id, info, .. } = event {
+            let speaks_direct = info.protocols.contains(&direct::PROTOCOL);
+            if info.protocol_version == IDENTIFY_PROTOCOL && !speaks_direct {
+                self.floodsub_only.insert(peer_id);
+                let status = GameStatus::OlderPeer(direct::MISSING_WITHOUT);
+                let _ = self.response_sender.send(PeerMessage::about(peer_id.to_string(), status));
+            }
+            if let Some(nat) = self.nat.on_observed(peer_id, info.observed_addr) {
+                let _ = self.response_sender.send(PeerMessage::internal(GameStatus::NatStatus(nat)));
+            }
+        }
+    }
+
+    fn on_autonat(&mut self, event: libp2p::autonat::Event) {
+        if let libp2p::autonat::Event::StatusChanged { new, .. } = event {
+            if let Some(nat) = self.nat.on_autonat(new) {
+                let _ = self.response_sender.send(PeerMessage::internal(GameStatus::NatStatus(nat)));
+            }
+        }
+    }
+
+    fn on_dcutr(&mut self, event: libp2p::dcutr::Event) {
+        let result = event.result.map(|_| ()).map_err(|error| error.to_string());
+        let _ = self.response_sender.send(PeerMessage::about(event.remote_peer_id.to_string(), GameStatus::HolePunch(result)));
+    }
+
+    /// Reports players registered at rendezvous point as discovered
+    fn on_rendezvous(&mut self, event: libp2p::rendezvous::client::Event) {
+        // failed registration is tried again on the next refresh
+        if let libp2p::rendezvous::client::Event::Discovered { registrations, .. } = event {
+            for registration in registrations {
+                self.report_registered(registration);
+            }
+        }
+    }
+
+    /// Reports players registered at me as discovered, rendezvous point plays too
+    fn on_rendezvous_point(&mut self, event: libp2p::rendezvous::server::Event) {
+        if let libp2p::rendezvous::server::Event::PeerRegistered { registration, .. } = event {
+            self.report_registered(registration);
+        }
+    }
+
+    fn report_registered(&mut self, registration: libp2p::rendezvous::Registration) {
+        let peer = registration.record.peer_id();
+        let status = GameStatus::Discovered(discovery::DiscoveryMethod::Rendezvous, registration.record.addresses().to_vec());
+        let _ = self.response_sender.send(PeerMessage::about(peer.to_string(), status));
+    }
+}
+
+/// Protocols do the networking, their events are processed here and not passed to swarm
+impl NetworkBehaviour for TicTacToeBehaviour {
+    type ConnectionHandler = THandler<Protocols>;
+    type ToSwarm = std::convert::Infallible;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &libp2p::Multiaddr,
+        remote_addr: &libp2p::Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.protocols.handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: libp2p::PeerId,
+        local_addr: &libp2p::Multiaddr,
+        remote_addr: &libp2p::Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.protocols.handle_established_inbound_connection(connection_id, peer, local_addr, remote_addr)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<libp2p::PeerId>,
+        addresses: &[libp2p::Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<libp2p::Multiaddr>, ConnectionDenied> {
+        self.protocols.handle_pending_outbound_connection(connection_id, maybe_peer, addresses, effective_role)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: libp2p::PeerId,
+        addr: &libp2p::Multiaddr,
+        role_override: Endpoint,
+        port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.protocols.handle_established_outbound_connection(connection_id, peer, addr, role_override, port_use)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        self.protocols.on_swarm_event(event)
+    }
+
+    fn on_connection_handler_event(&mut self, peer_id: libp2p::PeerId, connection_id: ConnectionId, event: THandlerOutEvent<Self>) {
+        self.protocols.on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn poll(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        loop {
+            match self.protocols.poll(cx) {
+                std::task::Poll::Ready(ToSwarm::GenerateEvent(event)) => self.process(event),
+                std::task::Poll::Ready(action) => {
+                    return std::task::Poll::Ready(action.map_out(|_| unreachable!("events are generated only by protocols")))
+                }
+                std::task::Poll::Pending => return std::task::Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! # Builder
+//!
+//! Typed configuration of client before it starts: where identity comes from,
+//! transport, listen addresses, discovery and optional behaviours. Settings from
+//! config file give defaults, integrators and developer modes override them.
+
+use super::behaviour::PeerMessage;
+use super::{channel, chat, discovery, hints, loadtest, netstats, prompt, undo, Settings, UserSession};
+
+/// Where client identity comes from
+#[derive(Clone)]
+pub enum KeySource {
+    /// New identity on every start
+    Generate,
+    Keypair(Box<libp2p::identity::Keypair>),
+    /// Ed25519 key kept in file, it is created on first start
+    File(std::path::PathBuf),
+}
+
+impl KeySource {
+    /// Returns identity, or why key file cannot be used
+    fn resolve(&self) -> Result<libp2p::identity::Keypair, String> {
+        match self {
+            KeySource::Generate => Ok(libp2p::identity::Keypair::generate_ed25519()),
+            KeySource::Keypair(key) => Ok(key.as_ref().clone()),
+            KeySource::File(path) => load_key(path).map_err(|error| format!("Cannot use key file {}: {}, identity is generated", path.display(), error)),
+        }
+    }
+}
+
+/// Returns identity kept in file, new one is generated and saved when file is missing
+pub fn load_key(path: &std::path::Path) -> std::io::Result<libp2p::identity::Keypair> {
+    match std::fs::read(path) {
+        Ok(mut bytes) => libp2p::identity::ed25519::Keypair::try_from_bytes(&mut bytes)
+            .map(libp2p::identity::Keypair::from)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            let keypair = libp2p::identity::ed25519::Keypair::generate();
+            super::correspondence::write_private(path, &keypair.to_bytes())?;
+            Ok(libp2p::identity::Keypair::from(keypair))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenError {
+    InvalidAddress(String),
+    /// Address needs transport the client does not have
+    Unsupported(String, &'static str),
+}
+
+impl std::fmt::Display for ListenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenError::InvalidAddress(address) => write!(f, "invalid listen address '{}', expected e.g. /ip4/0.0.0.0/tcp/0", address),
+            ListenError::Unsupported(address, reason) => write!(f, "cannot listen on '{}': {}", address, reason),
+        }
+    }
+}
+
+/// Parses listen address, TCP one optionally carrying WebSocket, e.g. /ip4/0.0.0.0/tcp/8080/ws
+pub fn parse_listen_address(address: &str) -> Result<libp2p::Multiaddr, ListenError> {
+    use libp2p::multiaddr::Protocol;
+
+    let multiaddr: libp2p::Multiaddr = address.parse().map_err(|_| ListenError::InvalidAddress(address.to_string()))?;
+    let unsupported = |reason| Err(ListenError::Unsupported(address.to_string(), reason));
+    let mut protocols = multiaddr.iter();
+    match protocols.next() {
+        Some(Protocol::Ip4(_) | Protocol::Ip6(_)) => {}
+        _ => return unsupported("address has to start with /ip4 or /ip6"),
+    }
+    match protocols.next() {
+        Some(Protocol::Tcp(_)) => {}
+        Some(Protocol::Udp(_)) => return unsupported("QUIC and other UDP transports need newer libp2p"),
+        _ => return unsupported("only TCP transport is available"),
+    }
+    match (protocols.next(), protocols.next()) {
+        (None, _) | (Some(Protocol::Ws(_)), None) => Ok(multiaddr),
+        _ => unsupported("only WebSocket may follow TCP"),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    /// Encrypted and multiplexed TCP, also with WebSocket on addresses ending with /ws
+    Tcp,
+    /// In-process memory transport of load test
+    Memory,
+}
+
+/// Swarm part of client configuration, kept so network restarts build the same swarm
+#[derive(Clone)]
+pub struct SwarmConfig {
+    pub transport: TransportKind,
+    pub listen_addrs: Vec<libp2p::Multiaddr>,
+    /// Discover peers on local network with mDNS
+    pub mdns: bool,
+    /// Join Kademlia DHT to discover peers
+    pub kademlia: bool,
+    /// Topics subscribed next to lobby and running games
+    pub topics: Vec<libp2p::floodsub::Topic>,
+    /// Record game messages into signed audit log, needs audit_dir
+    pub audit: bool,
+    /// Register and discover players at rendezvous point
+    pub rendezvous: bool,
+    /// Keep registrations of other players, I am their rendezvous point
+    pub rendezvous_point: bool,
+    /// Reach peers through relays
+    pub relay: bool,
+    /// Relay connections between other peers
+    pub relay_server: bool,
+    /// Relays I listen through, peers on other networks reach me there
+    pub relays: Vec<(libp2p::PeerId, libp2p::Multiaddr)>,
+}
+
+impl Default for SwarmConfig {
+    fn default() -> Self {
+        SwarmConfig {
+            transport: TransportKind::Tcp,
+            listen_addrs: vec![super::swarm::listen_address()],
+            mdns: false,
+            kademlia: false,
+            topics: Vec::new(),
+            audit: true,
+            rendezvous: false,
+            rendezvous_point: false,
+            relay: false,
+            relay_server: false,
+            relays: Vec::new(),
+        }
+    }
+}
+
+/// Builds user session, every part not set explicitly follows settings
+pub struct SessionBuilder {
+    settings: Settings,
+    key: KeySource,
+    swarm: SwarmConfig,
+    chat_hooks: Vec<Box<dyn chat::TextHook>>,
+    netstats: netstats::NetStats,
+    virtual_network: Option<loadtest::VirtualNetwork>,
+    discovery: Vec<Box<dyn discovery::Discovery>>,
+    variants: crate::game::Registry,
+    plugins: super::plugin::Plugins,
+    /// Listen addresses were given to builder, settings no longer apply
+    explicit_listen: bool,
+    errors: tokio::sync::mpsc::UnboundedSender<String>,
+    /// Settings which cannot be used, reported once session is built
+    rejected: Vec<String>,
+}
+
+impl SessionBuilder {
+    pub fn new(settings: Settings) -> SessionBuilder {
+        // identity of correspondence games has to survive restarts
+        let key = match &settings.correspondence_dir {
+            Some(dir) => KeySource::File(dir.join(super::correspondence::KEY_FILE)),
+            None => KeySource::Generate,
+        };
+        let mut swarm = SwarmConfig::default();
+        let mut rejected = Vec::new();
+        let listen_addrs: Vec<libp2p::Multiaddr> = settings
+            .listen_addrs
+            .iter()
+            .filter_map(|address| parse_listen_address(address).map_err(|error| rejected.push(error.to_string())).ok())
+            .collect();
+        if !listen_addrs.is_empty() {
+            swarm.listen_addrs = listen_addrs;
+        }
+        swarm.relays = settings
+            .relays
+            .iter()
+            .filter_map(|address| discovery::parse_peer_address(address).map_err(|error| rejected.push(error.to_string())).ok())
+            .collect();
+        swarm.relay = !swarm.relays.is_empty();
+        swarm.relay_server = settings.relay_server;
+        // errors are dropped until channel of main loop is given
+        let (errors, _) = tokio::sync::mpsc::unbounded_channel();
+        SessionBuilder {
+            discovery: discovery::from_settings(&settings, &mut rejected),
+            settings,
+            key,
+            swarm,
+            chat_hooks: Vec::new(),
+            netstats: netstats::NetStats::default(),
+            virtual_network: None,
+            variants: crate::game::Registry::default(),
+            plugins: super::plugin::Plugins::default(),
+            explicit_listen: false,
+            errors,
+            rejected,
+        }
+    }
+
+    pub fn key(mut self, key: KeySource) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn transport(mut self, transport: TransportKind) -> Self {
+        self.swarm.transport = transport;
+        self
+    }
+
+    /// Replaces listen addresses from settings, can be called several times to
+    /// listen on several transports at once
+    pub fn listen_on(mut self, address: libp2p::Multiaddr) -> Self {
+        if !self.explicit_listen {
+            self.swarm.listen_addrs.clear();
+            self.explicit_listen = true;
+        }
+        self.swarm.listen_addrs.push(address);
+        self
+    }
+
+    pub fn subscribe(mut self, topic: libp2p::floodsub::Topic) -> Self {
+        self.swarm.topics.push(topic);
+        self
+    }
+
+    /// Enables relay transport, also without any relay to listen through
+    pub fn relay(mut self, enabled: bool) -> Self {
+        self.swarm.relay = enabled;
+        self
+    }
+
+    pub fn audit(mut self, enabled: bool) -> Self {
+        self.swarm.audit = enabled;
+        self
+    }
+
+    pub fn chat_hooks(mut self, hooks: Vec<Box<dyn chat::TextHook>>) -> Self {
+        self.chat_hooks = hooks;
+        self
+    }
+
+    pub fn netstats(mut self, netstats: netstats::NetStats) -> Self {
+        self.netstats = netstats;
+        self
+    }
+
+    /// Replaces variants I accept invitations to
+    pub fn variants(mut self, variants: crate::game::Registry) -> Self {
+        self.variants = variants;
+        self
+    }
+
+    /// Adds commands to built-in ones
+    pub fn plugins(mut self, plugins: super::plugin::Plugins) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Sends failures of session, e.g. when file cannot be saved, to given channel
+    pub fn errors(mut self, errors: tokio::sync::mpsc::UnboundedSender<String>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Plays on in-process network, it gives identity, transport, address and peers
+    pub fn virtual_network(mut self, network: loadtest::VirtualNetwork) -> Self {
+        self.key = KeySource::Keypair(Box::new(network.key.clone()));
+        self.swarm.transport = TransportKind::Memory;
+        self.swarm.listen_addrs = vec![network.address.clone()];
+        self.swarm.relay = false;
+        self.swarm.relay_server = false;
+        self.discovery = Vec::new();
+        self.virtual_network = Some(network);
+        self
+    }
+
+    pub(crate) fn build(mut self, internal_sender: channel::WeakSender<PeerMessage>) -> UserSession {
+        let key = self.key.resolve().unwrap_or_else(|error| {
+            self.rejected.push(error);
+            libp2p::identity::Keypair::generate_ed25519()
+        });
+        for error in self.rejected {
+            let _ = self.errors.send(error);
+        }
+        for strategy in &self.discovery {
+            strategy.configure(&mut self.swarm);
+        }
+        let stats = self.settings.stats_file.as_deref().map(super::stats::Stats::load).unwrap_or_default();
+        let ladder = match (&self.settings.room, &self.settings.ladder_file) {
+            (Some(_), Some(path)) => Some(super::ladder::Ladder::load(path)),
+            _ => None,
+        };
+        UserSession {
+            user_peer_id: libp2p::PeerId::from(key.public()),
+            user_key: key,
+            sessions: super::session::SessionMap::new(super::session::GameSession::new(internal_sender.clone())),
+            active: 0,
+            lobby: super::lobby_topic(self.settings.room.as_deref()),
+            moderator: chat::Moderator::new(&self.settings.text_limits, &self.settings.chat_filter),
+            nicknames: std::collections::HashMap::new(),
+            seeking: None,
+            open_games: super::lobby::OpenGames::default(),
+            settings: self.settings,
+            internal_sender,
+            engine: None,
+            stats,
+            legacy_peers: std::collections::HashSet::new(),
+            missing_features: std::collections::HashMap::new(),
+            watched: std::collections::HashMap::new(),
+            refereed: std::collections::HashMap::new(),
+            chat_hooks: self.chat_hooks,
+            virtual_network: self.virtual_network,
+            replayed_game: 1,
+            last_game: None,
+            #[cfg(feature = "ai")]
+            drill: None,
+            setup: None,
+            review: None,
+            prompts: prompt::Prompts::default(),
+            outgoing: undo::OutgoingQueue::default(),
+            correspondence: None,
+            known_peers: Vec::new(),
+            ladder,
+            last_input: std::time::Instant::now(),
+            hints: hints::Hints::default(),
+            netstats: self.netstats,
+            swarm_config: self.swarm,
+            discovery: self.discovery,
+            variants: self.variants,
+            plugins: self.plugins,
+            errors: self.errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_parts_override_settings() {
+        let (sender, _receiver) = channel::bounded(1);
+        let key = libp2p::identity::Keypair::generate_ed25519();
+        let address: libp2p::Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        let settings = Settings { room: Some("club".to_string()), ..Settings::default() };
+        let mut builder = SessionBuilder::new(settings).key(KeySource::Keypair(Box::new(key.clone()))).listen_on(address.clone());
+        builder.discovery = vec![Box::new(discovery::Kademlia { bootstrap: Vec::new() })];
+        let session = builder.build(sender.downgrade());
+
+        assert_eq!(session.user_peer_id, libp2p::PeerId::from(key.public()));
+        assert_eq!(session.swarm_config.listen_addrs, vec![address]);
+        assert!(!session.swarm_config.mdns);
+        assert!(session.swarm_config.kademlia);
+        assert_eq!(session.lobby, super::super::lobby_topic(Some("club")));
+    }
+
+    #[test]
+    fn listens_on_tcp_and_websocket() {
+        let (sender, _receiver) = channel::bounded(1);
+        let (errors, mut rejected) = tokio::sync::mpsc::unbounded_channel();
+        let listen_addrs = vec!["/ip4/0.0.0.0/tcp/0".to_string(), "/ip6/::/tcp/8080/ws".to_string(), "/ip4/0.0.0.0/udp/0/quic".to_string()];
+        let session = SessionBuilder::new(Settings { listen_addrs, ..Settings::default() }).errors(errors).build(sender.downgrade());
+        let expected: Vec<libp2p::Multiaddr> = vec!["/ip4/0.0.0.0/tcp/0".parse().unwrap(), "/ip6/::/tcp/8080/ws".parse().unwrap()];
+        assert_eq!(session.swarm_config.listen_addrs, expected);
+        assert!(rejected.try_recv().is_ok_and(|error| error.contains("QUIC")));
+        assert!(rejected.try_recv().is_err());
+
+        assert!(matches!(parse_listen_address("/ip4/0.0.0.0/udp/0/quic"), Err(ListenError::Unsupported(..))));
+        assert!(matches!(parse_listen_address("/ip4/0.0.0.0/tcp/0/ws/ws"), Err(ListenError::Unsupported(..))));
+        assert!(matches!(parse_listen_address("tcp 4001"), Err(ListenError::InvalidAddress(_))));
+    }
+
+    #[test]
+    fn relays_only_when_configured() {
+        let (sender, _receiver) = channel::bounded(1);
+        let session = SessionBuilder::new(Settings::default()).build(sender.downgrade());
+        assert!(!session.swarm_config.relay);
+
+        let relay = libp2p::PeerId::from(libp2p::identity::Keypair::generate_ed25519().public());
+        let relays = vec![format!("/ip4/1.2.3.4/tcp/4001/p2p/{}", relay), "/ip4/1.2.3.4/tcp/4001".to_string()];
+        let session = SessionBuilder::new(Settings { relays, ..Settings::default() }).build(sender.downgrade());
+        assert!(session.swarm_config.relay);
+        assert_eq!(session.swarm_config.relays, vec![(relay, "/ip4/1.2.3.4/tcp/4001".parse().unwrap())]);
+
+        // relay server relays for others, it does not listen through relays itself
+        let session = SessionBuilder::new(Settings { relay_server: true, ..Settings::default() }).build(sender.downgrade());
+        assert!(session.swarm_config.relay_server && !session.swarm_config.relay);
+    }
+}
@@ -0,0 +1,194 @@
+//! # Channel
+//!
+//! Bounded channel between network behaviour and main loop. When it is full,
+//! droppable messages are dropped, main loop gets warning about them, and the
+//! rest waits in overflow queue, so a peer flooding me cannot grow memory with
+//! chatter while moves are never lost.
+
+use tokio::sync::mpsc;
+
+/// Message which may be dropped when main loop does not keep up
+pub trait Droppable {
+    fn is_droppable(&self) -> bool;
+}
+
+/// Warning about dropped messages is repeated after this many more were dropped
+const WARN_EVERY: u64 = 1000;
+
+struct Shared<T> {
+    /// Messages which must not be dropped and did not fit into channel, in order
+    overflow: std::sync::Mutex<std::collections::VecDeque<T>>,
+    dropped: std::sync::atomic::AtomicU64,
+}
+
+pub struct Sender<T> {
+    sender: mpsc::Sender<T>,
+    shared: std::sync::Arc<Shared<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender { sender: self.sender.clone(), shared: self.shared.clone() }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Returns handle which sends while other senders exist, but does not keep channel open
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender { sender: self.sender.downgrade(), shared: self.shared.clone() }
+    }
+}
+
+impl<T: Droppable> Sender<T> {
+    /// Sends message without waiting, fails only when receiver is gone
+    pub fn send(&self, message: T) -> Result<(), mpsc::error::SendError<T>> {
+        let mut overflow = self.shared.overflow.lock().expect("channel lock poisoned");
+        // while overflow is not empty channel counts as full, it keeps messages in order
+        let message = if overflow.is_empty() {
+            match self.sender.try_send(message) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Closed(message)) => return Err(mpsc::error::SendError(message)),
+                Err(mpsc::error::TrySendError::Full(message)) => message,
+            }
+        } else if self.sender.is_closed() {
+            return Err(mpsc::error::SendError(message));
+        } else {
+            message
+        };
+
+        if message.is_droppable() {
+            self.shared.dropped.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        } else {
+            overflow.push_back(message);
+        }
+        Ok(())
+    }
+}
+
+/// Sender of sessions and their tasks, channel closes once network drops its sender
+pub struct WeakSender<T> {
+    sender: mpsc::WeakSender<T>,
+    shared: std::sync::Arc<Shared<T>>,
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> Self {
+        WeakSender { sender: self.sender.clone(), shared: self.shared.clone() }
+    }
+}
+
+impl<T> WeakSender<T> {
+    /// Returns sender, none when channel is closed
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        self.sender.upgrade().map(|sender| Sender { sender, shared: self.shared.clone() })
+    }
+}
+
+impl<T: Droppable> WeakSender<T> {
+    /// Sends message like sender, fails when channel is closed
+    pub fn send(&self, message: T) -> Result<(), mpsc::error::SendError<T>> {
+        match self.upgrade() {
+            Some(sender) => sender.send(message),
+            None => Err(mpsc::error::SendError(message)),
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    receiver: mpsc::Receiver<T>,
+    shared: std::sync::Arc<Shared<T>>,
+    /// Warnings about dropped messages given so far
+    warned: u64,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for next message, none once all senders are gone and nothing is left
+    pub async fn recv(&mut self) -> Option<T> {
+        if let Ok(message) = self.receiver.try_recv() {
+            return Some(message);
+        }
+        if let Some(message) = self.shared.overflow.lock().expect("channel lock poisoned").pop_front() {
+            return Some(message);
+        }
+        self.receiver.recv().await
+    }
+
+    /// Returns number of messages dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns warning about the first dropped message, and again after every
+    /// `WARN_EVERY` more, none when no warning is due
+    pub fn drop_warning(&mut self) -> Option<String> {
+        let dropped = self.dropped();
+        let due = dropped.div_ceil(WARN_EVERY);
+        if due <= self.warned {
+            return None;
+        }
+        self.warned = due;
+        Some(format!("Main loop is overloaded, dropped {} messages about peers", dropped))
+    }
+}
+
+/// Creates channel holding given number of messages before overflow policy applies
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = mpsc::channel(capacity);
+    let shared = std::sync::Arc::new(Shared {
+        overflow: std::sync::Mutex::new(std::collections::VecDeque::new()),
+        dropped: std::sync::atomic::AtomicU64::new(0),
+    });
+    (Sender { sender, shared: shared.clone() }, Receiver { receiver, shared, warned: 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Chatter,
+        Move(u8),
+    }
+
+    impl Droppable for Message {
+        fn is_droppable(&self) -> bool {
+            *self == Message::Chatter
+        }
+    }
+
+    #[tokio::test]
+    async fn drops_chatter_and_keeps_moves_in_order() {
+        let (sender, mut receiver) = bounded(2);
+        sender.send(Message::Move(1)).unwrap();
+        sender.send(Message::Chatter).unwrap();
+        sender.send(Message::Move(2)).unwrap();
+        sender.send(Message::Chatter).unwrap();
+        assert_eq!(receiver.recv().await, Some(Message::Move(1)));
+        // overflow is not empty yet, so chatter is dropped even though channel has room
+        sender.send(Message::Chatter).unwrap();
+        sender.send(Message::Move(3)).unwrap();
+        drop(sender);
+
+        let mut received = Vec::new();
+        while let Some(message) = receiver.recv().await {
+            received.push(message);
+        }
+        assert_eq!(received, vec![Message::Chatter, Message::Move(2), Message::Move(3)]);
+        assert_eq!(receiver.dropped(), 2);
+        assert_eq!(receiver.drop_warning(), Some("Main loop is overloaded, dropped 2 messages about peers".to_string()));
+        assert_eq!(receiver.drop_warning(), None);
+    }
+
+    #[tokio::test]
+    async fn weak_sender_does_not_keep_channel_open() {
+        let (sender, mut receiver) = bounded(2);
+        let weak = sender.downgrade();
+        weak.send(Message::Move(1)).unwrap();
+        drop(sender);
+        assert_eq!(weak.send(Message::Move(2)), Err(mpsc::error::SendError(Message::Move(2))));
+        assert_eq!(receiver.recv().await, Some(Message::Move(1)));
+        assert_eq!(receiver.recv().await, None);
+        assert!(weak.upgrade().is_none());
+    }
+}
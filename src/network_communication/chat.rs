@@ -0,0 +1,191 @@
+//! # Chat
+//!
+//! Processing of incoming chat messages. Integrators plug in hooks, e.g. for
+//! translation into the language chosen for the session or for filtering.
+//!
+//! Text exchanged with peers, chat and nicknames, is limited in one place, on
+//! send and on receive. Control characters are stripped, so peer cannot drive
+//! terminal with escape sequences, long text is cut and nicknames are compared
+//! by their look, so peer cannot pass for other one with lookalike letters.
+
+use super::display;
+
+/// Text processing applied to incoming chat, does nothing by default
+pub trait TextHook: Send {
+    /// Returns text shown to user, language is the one chosen for the session
+    fn incoming(&self, text: String, _language: Option<&str>) -> String {
+        text
+    }
+}
+
+/// Masks configured words with asterisks, case is ignored
+#[derive(Debug, Clone, Default)]
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(words: &[String]) -> WordFilter {
+        WordFilter {
+            words: words.iter().map(|word| word.to_lowercase()).filter(|word| !word.is_empty()).collect(),
+        }
+    }
+
+    /// Returns text with filtered words masked
+    pub fn apply(&self, text: &str) -> String {
+        text.split(' ').map(|word| self.mask(word)).collect::<Vec<_>>().join(" ")
+    }
+
+    fn mask(&self, word: &str) -> String {
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if self.words.contains(&bare.to_lowercase()) {
+            word.replacen(bare, &"*".repeat(bare.chars().count()), 1)
+        } else {
+            word.to_string()
+        }
+    }
+}
+
+impl TextHook for WordFilter {
+    fn incoming(&self, text: String, _language: Option<&str>) -> String {
+        self.apply(&text)
+    }
+}
+
+/// Words masked by profanity filter
+const PROFANITY: [&str; 12] = [
+    "arse", "asshole", "bastard", "bitch", "bollocks", "cunt", "dick", "fuck", "fucking", "motherfucker", "shit", "wanker",
+];
+
+/// Limits of chat and nicknames
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TextLimits {
+    /// Longest chat message, in characters
+    pub chat_chars: usize,
+    /// Longest nickname, in characters, peers reject invitations with nickname
+    /// longer than `validation::MAX_NICKNAME_CHARS`
+    pub nickname_chars: usize,
+    /// Masks built-in list of profanities besides own filtered words
+    pub profanity_filter: bool,
+}
+
+impl Default for TextLimits {
+    fn default() -> Self {
+        TextLimits { chat_chars: 500, nickname_chars: 32, profanity_filter: false }
+    }
+}
+
+/// Applies limits and word filter to text sent to or received from peers
+#[derive(Debug, Clone, Default)]
+pub struct Moderator {
+    limits: TextLimits,
+    filter: WordFilter,
+}
+
+impl Moderator {
+    pub fn new(limits: &TextLimits, words: &[String]) -> Moderator {
+        let mut words = words.to_vec();
+        if limits.profanity_filter {
+            words.extend(PROFANITY.iter().map(|word| word.to_string()));
+        }
+        Moderator { limits: limits.clone(), filter: WordFilter::new(&words) }
+    }
+
+    /// Returns chat message safe to send or show
+    pub fn chat(&self, text: &str) -> String {
+        self.filter.apply(&sanitize(text, self.limits.chat_chars))
+    }
+
+    /// Returns nickname safe to send or show, it is on one line
+    pub fn nickname(&self, nickname: &str) -> String {
+        let line = nickname.lines().collect::<Vec<_>>().join(" ");
+        self.filter.apply(&sanitize(&line, self.limits.nickname_chars))
+    }
+}
+
+/// Returns text without control and invisible characters but line breaks, cut
+/// to given number of characters
+pub fn sanitize(text: &str, max_chars: usize) -> String {
+    let line: String = text
+        .chars()
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .filter(|&c| !display::is_unsafe(c))
+        .take(max_chars)
+        .collect();
+    line.trim().to_string()
+}
+
+/// Returns look of nickname, nicknames of the same look are mistaken for each
+/// other, e.g. 'Alice', 'ALlCE' and 'Аlice' with cyrillic 'А'
+pub fn skeleton(nickname: &str) -> String {
+    let folded: String = sanitize(nickname, usize::MAX)
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| match c {
+            'а' | 'α' => 'a',
+            'в' | 'β' => 'b',
+            'с' | 'ϲ' => 'c',
+            'е' | 'ё' | 'ε' | '3' => 'e',
+            'н' | 'η' => 'h',
+            'і' | 'ї' | 'ι' | 'i' | '1' | 'ӏ' => 'l',
+            'ј' => 'j',
+            'к' | 'κ' => 'k',
+            'м' | 'μ' => 'm',
+            'п' => 'n',
+            'о' | 'ο' | '0' => 'o',
+            'р' | 'ρ' => 'p',
+            'ѕ' | '5' => 's',
+            'т' | 'τ' => 't',
+            'у' | 'γ' => 'y',
+            'х' | 'χ' => 'x',
+            'ν' => 'v',
+            c => c,
+        })
+        .collect();
+    folded.replace("rn", "m").replace("vv", "w")
+}
+
+/// Runs text through hooks in order
+pub fn process(hooks: &[Box<dyn TextHook>], text: String, language: Option<&str>) -> String {
+    hooks.iter().fold(text, |text, hook| hook.incoming(text, language))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upper;
+
+    impl TextHook for Upper {
+        fn incoming(&self, text: String, language: Option<&str>) -> String {
+            format!("[{}] {}", language.unwrap_or("-"), text.to_uppercase())
+        }
+    }
+
+    #[test]
+    fn masks_filtered_words() {
+        let filter = WordFilter::new(&["darn".to_string()]);
+        assert_eq!(filter.incoming("Darn, that was close".to_string(), None), "****, that was close");
+        assert_eq!(filter.incoming("darnation".to_string(), None), "darnation");
+    }
+
+    #[test]
+    fn limits_text_of_peers() {
+        let moderator = Moderator::new(&TextLimits { chat_chars: 12, profanity_filter: true, ..TextLimits::default() }, &[]);
+        assert_eq!(moderator.chat("\u{1b}[2Jhi\r\nthere\u{7}"), "[2Jhi\nthere");
+        assert_eq!(moderator.chat("oh shit, that was a close one"), "oh ****, tha");
+        assert_eq!(moderator.nickname("\u{202e}ecila\nsays: hi"), "ecila says: hi");
+        assert_eq!(skeleton("Alice"), skeleton("ALlCE"));
+        assert_eq!(skeleton("Alice"), skeleton("\u{410}l\u{456}ce"));
+        assert_eq!(skeleton("bob"), skeleton("B0b"));
+        assert_ne!(skeleton("alice"), skeleton("bob"));
+    }
+
+    #[test]
+    fn runs_hooks_in_order() {
+        let hooks: Vec<Box<dyn TextHook>> = vec![Box::new(WordFilter::new(&["gg".to_string()])), Box::new(Upper)];
+        assert_eq!(process(&hooks, "gg wp".to_string(), Some("cs")), "[cs] ** WP");
+    }
+}
@@ -0,0 +1,163 @@
+//! # Clock
+//!
+//! Latency compensated clock synchronization between peers. Offset of opponent's
+//! clock is estimated from ping exchanges, the sample with the shortest round trip
+//! is trusted most.
+
+/// Events closer than this are treated as simultaneous when round trip is unknown
+pub const SIMULTANEITY_MILLIS: u64 = 50;
+
+/// Number of round trips in rolling average
+pub const LATENCY_WINDOW: usize = 10;
+
+/// Round trip this many times over average is a spike
+const SPIKE_FACTOR: u64 = 3;
+
+/// Round trips shorter than this are never spikes
+const SPIKE_MINIMUM_MILLIS: u64 = 200;
+
+/// Returns milliseconds since unix epoch on local clock
+pub fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Result of one ping exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Peer clock minus local clock
+    pub offset: i64,
+    pub round_trip: u64,
+}
+
+impl Sample {
+    /// Computes sample from ping sent, ping received by peer, pong sent by peer and pong received
+    pub fn new(ping_sent: u64, ping_received: u64, pong_sent: u64, pong_received: u64) -> Sample {
+        let (t0, t1, t2, t3) = (ping_sent as i64, ping_received as i64, pong_sent as i64, pong_received as i64);
+        Sample {
+            offset: ((t1 - t0) + (t2 - t3)) / 2,
+            round_trip: ((t3 - t0) - (t2 - t1)).max(0) as u64,
+        }
+    }
+}
+
+/// Offset estimate of one peer's clock
+#[derive(Debug, Clone, Default)]
+pub struct ClockSync {
+    best: Option<Sample>,
+}
+
+impl ClockSync {
+    /// Adds sample, it replaces current one when it has shorter round trip
+    pub fn add(&mut self, sample: Sample) {
+        if self.best.map_or(true, |best| sample.round_trip <= best.round_trip) {
+            self.best = Some(sample);
+        }
+    }
+
+    /// Returns estimated peer clock minus local clock
+    pub fn offset(&self) -> i64 {
+        self.best.map(|sample| sample.offset).unwrap_or_default()
+    }
+
+    pub fn round_trip(&self) -> Option<u64> {
+        self.best.map(|sample| sample.round_trip)
+    }
+
+    /// Converts peer timestamp to local clock
+    pub fn to_local(&self, remote_millis: u64) -> u64 {
+        (remote_millis as i64 - self.offset()).max(0) as u64
+    }
+
+    /// Returns how far apart events may be to count as simultaneous
+    pub fn tolerance(&self) -> u64 {
+        self.round_trip().map_or(SIMULTANEITY_MILLIS, |round_trip| (round_trip / 2).max(SIMULTANEITY_MILLIS))
+    }
+}
+
+/// Rolling average of round trips to one peer
+#[derive(Debug, Clone, Default)]
+pub struct Latency {
+    round_trips: std::collections::VecDeque<u64>,
+}
+
+impl Latency {
+    /// Adds round trip, returns true when it is a spike against previous average
+    pub fn record(&mut self, round_trip: u64) -> bool {
+        let spike = self
+            .average()
+            .is_some_and(|average| round_trip >= SPIKE_MINIMUM_MILLIS && round_trip > average * SPIKE_FACTOR);
+
+        self.round_trips.push_back(round_trip);
+        if self.round_trips.len() > LATENCY_WINDOW {
+            self.round_trips.pop_front();
+        }
+        spike
+    }
+
+    pub fn average(&self) -> Option<u64> {
+        if self.round_trips.is_empty() {
+            return None;
+        }
+        Some(self.round_trips.iter().sum::<u64>() / self.round_trips.len() as u64)
+    }
+}
+
+/// Outcome of disagreement whether move came before flag fell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Claim {
+    MoveCounts,
+    FlagFell,
+}
+
+/// Decides between move and timeout claim, both times on local clock.
+/// Move sent within tolerance after the deadline still counts, so both peers
+/// agree on the result even when their estimates differ slightly.
+pub fn resolve_claim(move_sent: u64, deadline: u64, tolerance: u64) -> Claim {
+    if move_sent <= deadline.saturating_add(tolerance) {
+        Claim::MoveCounts
+    } else {
+        Claim::FlagFell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_offset() {
+        // peer clock is 1000 ms ahead, each direction takes 20 ms
+        let sample = Sample::new(0, 1020, 1030, 50);
+        assert_eq!(sample, Sample { offset: 1000, round_trip: 40 });
+
+        let mut sync = ClockSync::default();
+        sync.add(Sample::new(0, 1100, 1110, 210));
+        sync.add(sample);
+        assert_eq!(sync.offset(), 1000);
+        assert_eq!(sync.to_local(1500), 500);
+    }
+
+    #[test]
+    fn detects_latency_spike() {
+        let mut latency = Latency::default();
+        assert!(!latency.record(40));
+        assert!(!latency.record(60));
+        assert_eq!(latency.average(), Some(50));
+        assert!(latency.record(400));
+
+        for _ in 0..LATENCY_WINDOW {
+            latency.record(20);
+        }
+        assert_eq!(latency.average(), Some(20));
+    }
+
+    #[test]
+    fn resolves_near_simultaneous_claims() {
+        assert_eq!(resolve_claim(1000, 1000, 50), Claim::MoveCounts);
+        assert_eq!(resolve_claim(1040, 1000, 50), Claim::MoveCounts);
+        assert_eq!(resolve_claim(1060, 1000, 50), Claim::FlagFell);
+    }
+}
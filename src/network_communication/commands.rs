@@ -0,0 +1,117 @@
+//! # Commands
+//!
+//! What frontend asks the client to do, parsed from typed lines or clicks.
+
+use crate::network_communication::{pending, prompt, review, Coordinates};
+use crate::tictactoe;
+
+/// Step of setting up position by hand
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetupCommand {
+    /// Start with empty playmat, or show the position when already setting up
+    Begin,
+    /// Place tile on field, empty tile clears it
+    Place(Coordinates, tictactoe::Tile),
+    Analyze,
+    /// Save position as drill
+    Puzzle,
+    /// Invite peer with given index to game starting from the position
+    Propose(String),
+    End,
+}
+
+/// Looking for a game beyond local network
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LobbyCommand {
+    /// Show open games of other players
+    List,
+    /// Announce I look for a game with my rules
+    Seek,
+    Leave,
+}
+
+pub enum Input {
+    ListPeers,
+    /// Turn with tile to place, own one when none
+    Turn(usize, usize, Option<tictactoe::Tile>),
+    /// Turn in game with given index, it becomes the active game
+    TurnIn(usize, usize, usize, Option<tictactoe::Tile>),
+    /// Invite peer with given index, optionally with password of their game, start time
+    /// and attached message
+    InitiateGame(String, Option<String>, Option<u64>, Option<String>),
+    /// Answer to prompt with given id
+    Answer(u64, prompt::Answer),
+    /// Answer invitation with given prompt id by other start time in UTC milliseconds
+    CounterPropose(u64, u64),
+    /// List games with start time
+    Schedule,
+    Nudge,
+    /// Give up current game
+    Resign,
+    /// Take back resignation, declined or withdrawn invitation which was not sent yet
+    Undo,
+    /// Resign games which do not continue after restart and exit
+    Quit,
+    ListGames,
+    /// List only games waiting for my turn
+    PendingGames,
+    SwitchGame(usize),
+    /// Print recent output events again
+    Log,
+    /// Show my invite code, optionally as QR code
+    InviteCode(bool),
+    /// Invite peer given by invite code, optionally with password of their game, start time
+    /// and attached message
+    Join(String, Option<String>, Option<u64>, Option<String>),
+    /// Send chat message to opponent of current game
+    Chat(String),
+    /// Send current position to opponent as emoji board, after text when any
+    ChatBoard(String),
+    /// Set language for chat in current game, none for default
+    ChatLanguage(Option<String>),
+    /// Show finished game counted from the most recent one, none for the last replayed
+    Replay(Option<usize>),
+    /// Send finished game counted from the most recent one to peer with index, current
+    /// opponent when none
+    SendReplay(usize, Option<String>),
+    /// Comment move of the last replayed game
+    Annotate(usize, String),
+    /// Show given number of most recent finished games, none for default
+    History(Option<usize>),
+    /// Show score against current opponent and overall
+    Score,
+    /// Show due drill, or answer the shown one with given field
+    Drill(Option<Coordinates>),
+    Setup(SetupCommand),
+    Lobby(LobbyCommand),
+    /// Propose review of last game or join the one proposed by opponent
+    Review,
+    /// Show other position of reviewed game, also to opponent
+    ReviewNavigate(review::ReviewStep),
+    EndReview,
+    /// Write verified audit log of game with given id into current directory
+    AuditExport(String),
+    NetStats,
+    NetInfo,
+    WhoAmI,
+    /// Rebuild network, listening on given addresses when there are some
+    Reconnect(Vec<libp2p::Multiaddr>),
+    /// Dial recent opponents at addresses remembered from last game
+    ReconnectKnown,
+    /// Dial peer at given address, e.g. on other network through relay
+    Connect(libp2p::PeerId, libp2p::Multiaddr),
+    /// List what waits for answer or for offline peer
+    Pending,
+    /// Drop pending item
+    Clear(pending::PendingId),
+    /// Show ladder of my room
+    Ladder,
+    /// Start watching game with given id, or stop when it is watched
+    Watch(String),
+    /// Start refereeing game with given id, or stop when it is refereed
+    Referee(String),
+    /// Built-in help was shown, registered commands follow
+    Help,
+    /// Command which is not built-in, name followed by arguments
+    Plugin(Vec<String>),
+}
@@ -0,0 +1,145 @@
+//! # Compression
+//!
+//! Large game messages, e.g. state synced by variants with bigger boards, are
+//! deflated for opponents which can inflate them. Both players list codecs they
+//! read in invitation and in its answer. Message on game topic longer than
+//! [`THRESHOLD`] goes compressed when opponent reads codec this client writes and
+//! compression makes it shorter, turns and other small messages never are.
+//! Compressed message is envelope of its own with base64 encoded data, older
+//! clients never list codecs, so they never get one.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use base64::Engine;
+
+/// Messages up to this many bytes are sent as they are
+pub const THRESHOLD: usize = 512;
+
+/// Inflated message must fit into this many bytes, longer one is dropped
+const MAX_INFLATED: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Deflate,
+}
+
+/// Codecs this client reads and writes, listed in invitations and answers
+pub fn supported() -> Vec<Codec> {
+    vec![Codec::Deflate]
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Compressed {
+    version: u32,
+    codec: Codec,
+    data: String,
+}
+
+/// Returns payload compressed with codec when it is longer than threshold and
+/// compression shortens it, otherwise the payload itself
+pub fn compress(payload: String, codec: Codec) -> String {
+    if payload.len() <= THRESHOLD {
+        return payload;
+    }
+    let deflated = match codec {
+        Codec::Deflate => {
+            let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(payload.as_bytes()).and_then(|_| encoder.finish())
+        }
+    };
+    let compressed = Compressed {
+        version: super::protocol::PROTOCOL_VERSION,
+        codec,
+        data: base64::engine::general_purpose::STANDARD.encode(deflated.expect("cannot deflate message in memory")),
+    };
+    let compressed = serde_json::to_string(&compressed).expect("cannot jsonify compressed message");
+    if compressed.len() < payload.len() {
+        compressed
+    } else {
+        payload
+    }
+}
+
+/// Returns inflated data of compressed message, none when data is not compressed
+/// message or it cannot be inflated
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let compressed = serde_json::from_slice::<Compressed>(data).ok()?;
+    let deflated = base64::engine::general_purpose::STANDARD.decode(compressed.data).ok()?;
+    let mut inflated = Vec::new();
+    match compressed.codec {
+        Codec::Deflate => flate2::read::DeflateDecoder::new(deflated.as_slice())
+            .take(MAX_INFLATED + 1)
+            .read_to_end(&mut inflated)
+            .ok()?,
+    };
+    (inflated.len() as u64 <= MAX_INFLATED).then_some(inflated)
+}
+
+/// Codecs opponents read, learned from their invitations and answers
+#[derive(Debug, Default)]
+pub struct Negotiated {
+    /// Codec each peer reads
+    peers: HashMap<String, Codec>,
+    /// Opponent of each game topic
+    games: HashMap<String, String>,
+}
+
+impl Negotiated {
+    /// Notes codecs peer listed, the first one this client writes is used
+    pub fn on_offer(&mut self, peer_id: &str, codecs: &[Codec]) {
+        let supported = supported();
+        match codecs.iter().find(|codec| supported.contains(codec)) {
+            Some(codec) => self.peers.insert(peer_id.to_string(), *codec),
+            None => self.peers.remove(peer_id),
+        };
+    }
+
+    pub fn join(&mut self, game_id: &str, opponent_id: &str) {
+        self.games.insert(game_id.to_string(), opponent_id.to_string());
+    }
+
+    pub fn leave(&mut self, game_id: &str) {
+        self.games.remove(game_id);
+    }
+
+    /// Returns codec messages of game are compressed with, none when opponent reads none
+    pub fn codec(&self, game_id: &str) -> Option<Codec> {
+        let opponent_id = self.games.get(game_id)?;
+        self.peers.get(opponent_id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compresses_only_large_messages() {
+        let turn = r#"{"version":2,"message":{"type":"turn","x":1,"y":2}}"#.to_string();
+        assert_eq!(compress(turn.clone(), Codec::Deflate), turn);
+        assert_eq!(decompress(turn.as_bytes()), None);
+
+        let moves: Vec<String> = (0..150).map(|index| format!(r#"{{"x":{},"y":{},"mark":"cross"}}"#, index / 15, index % 15)).collect();
+        let spectated = format!(r#"{{"version":2,"message":{{"type":"spectated","game":"a/b","moves":[{}]}}}}"#, moves.join(","));
+        let compressed = compress(spectated.clone(), Codec::Deflate);
+        assert!(compressed.len() * 4 < spectated.len(), "{} bytes compressed to {}", spectated.len(), compressed.len());
+        assert_eq!(decompress(compressed.as_bytes()), Some(spectated.into_bytes()));
+    }
+
+    #[test]
+    fn codec_is_used_once_opponent_lists_it() {
+        let mut negotiated = Negotiated::default();
+        negotiated.join("TicTacToe/alice/bob", "bob");
+        assert_eq!(negotiated.codec("TicTacToe/alice/bob"), None);
+
+        negotiated.on_offer("bob", &[Codec::Deflate]);
+        assert_eq!(negotiated.codec("TicTacToe/alice/bob"), Some(Codec::Deflate));
+        assert_eq!(negotiated.codec("TicTacToe/alice/carol"), None);
+
+        negotiated.on_offer("bob", &[]);
+        assert_eq!(negotiated.codec("TicTacToe/alice/bob"), None);
+    }
+}
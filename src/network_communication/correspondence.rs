@@ -0,0 +1,272 @@
+//! # Correspondence
+//!
+//! Long running games kept on disk. Directory holds identity of this client, so
+//! opponents recognize it after restart, running games, outbox with turns
+//! waiting until their offline opponent comes back and addresses of recent
+//! opponents, which are dialed at start without waiting for discovery.
+
+use crate::tictactoe::{self, Rules, Tile};
+
+/// File in correspondence directory keeping identity
+pub const KEY_FILE: &str = "identity.key";
+const GAMES_FILE: &str = "games.json";
+const OUTBOX_FILE: &str = "outbox.jsonl";
+const KNOWN_PEERS_FILE: &str = "known_peers.json";
+/// All files client keeps in correspondence directory
+pub const FILES: [&str; 4] = [KEY_FILE, GAMES_FILE, OUTBOX_FILE, KNOWN_PEERS_FILE];
+
+/// Number of recent opponents remembered
+pub const MAX_KNOWN_PEERS: usize = 20;
+
+/// Addresses remembered for one opponent
+pub const MAX_KNOWN_ADDRESSES: usize = 4;
+
+/// Creates file only its owner can read, e.g. identity key, existing file is never replaced
+pub fn write_private(path: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(content)
+}
+
+/// Running game as written to disk
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SavedGame {
+    pub opponent_id: String,
+    /// True when I proposed the game, proposer plays crosses and moves first
+    pub initiator: bool,
+    #[serde(default, skip_serializing_if = "Rules::is_standard")]
+    pub rules: Rules,
+    /// Played fields with placed tiles in order of turns
+    pub moves: Vec<(usize, usize, Tile)>,
+    /// Nonce of the game, messages after restart are sealed with it again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    /// Agreed start time in UTC milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_at: Option<u64>,
+}
+
+impl SavedGame {
+    pub fn new(opponent_id: &str, game: &tictactoe::TicTacToe) -> SavedGame {
+        SavedGame {
+            opponent_id: opponent_id.to_string(),
+            initiator: game.marks().you == Tile::Cross,
+            rules: game.rules(),
+            moves: game.moves().iter().map(|&(x, y)| (x, y, game.tile(x, y))).collect(),
+            nonce: None,
+            start_at: None,
+        }
+    }
+
+    /// Plays saved turns again, returns the game and true when I am on turn
+    pub fn restore(&self) -> (tictactoe::TicTacToe, bool) {
+        let marks = if self.initiator {
+            tictactoe::Marks::default().swapped()
+        } else {
+            tictactoe::Marks::default()
+        };
+        let mut game = tictactoe::TicTacToe::with_rules(marks, self.rules);
+        let mut my_turn = self.initiator;
+        for &(x, y, tile) in &self.moves {
+            let _ = if my_turn {
+                game.make_my_mark(x, y, tile)
+            } else {
+                game.make_opponent_mark(x, y, tile)
+            };
+            my_turn = !my_turn;
+        }
+        (game, my_turn)
+    }
+}
+
+/// Message waiting for offline peer
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OutboxEntry {
+    pub opponent_id: String,
+    pub topic: String,
+    /// Encoded message as it is published
+    pub payload: String,
+}
+
+/// Recent opponent with addresses it was reachable at
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct KnownPeer {
+    pub peer_id: String,
+    /// Multiaddrs, the most recently seen first
+    pub addresses: Vec<String>,
+    /// UTC milliseconds when our last game started
+    pub last_played: u64,
+}
+
+/// Adds opponent as the most recent one, older entry of the same peer is replaced
+/// and the least recent opponents are forgotten over the limit
+pub fn remember(known: &mut Vec<KnownPeer>, mut peer: KnownPeer) {
+    peer.addresses.truncate(MAX_KNOWN_ADDRESSES);
+    known.retain(|known| known.peer_id != peer.peer_id);
+    known.insert(0, peer);
+    known.truncate(MAX_KNOWN_PEERS);
+}
+
+/// Directory with correspondence games
+pub struct CorrespondenceStore {
+    dir: std::path::PathBuf,
+}
+
+impl CorrespondenceStore {
+    /// Opens directory, it is created when missing
+    pub fn open(dir: &std::path::Path) -> std::io::Result<CorrespondenceStore> {
+        std::fs::create_dir_all(dir)?;
+        Ok(CorrespondenceStore { dir: dir.to_path_buf() })
+    }
+
+    /// Returns identity kept in directory, new one is generated and saved on first use
+    pub fn identity(&self) -> std::io::Result<libp2p::identity::Keypair> {
+        super::builder::load_key(&self.dir.join(KEY_FILE))
+    }
+
+    /// Loads running games, missing or broken file means no games
+    pub fn load_games(&self) -> Vec<SavedGame> {
+        std::fs::read_to_string(self.dir.join(GAMES_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replaces running games
+    pub fn save_games(&self, games: &[SavedGame]) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(games).expect("cannot jsonify games");
+        std::fs::write(self.dir.join(GAMES_FILE), json)
+    }
+
+    /// Loads recent opponents, the most recent first, missing or broken file means none
+    pub fn load_known_peers(&self) -> Vec<KnownPeer> {
+        std::fs::read_to_string(self.dir.join(KNOWN_PEERS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Replaces recent opponents
+    pub fn save_known_peers(&self, peers: &[KnownPeer]) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(peers).expect("cannot jsonify known peers");
+        std::fs::write(self.dir.join(KNOWN_PEERS_FILE), json)
+    }
+
+    /// Appends message for offline peer
+    pub fn queue(&self, entry: &OutboxEntry) -> std::io::Result<()> {
+        use std::io::Write;
+        let line = serde_json::to_string(entry).expect("cannot jsonify outbox entry");
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(OUTBOX_FILE))
+            .and_then(|mut file| writeln!(file, "{}", line))
+    }
+
+    /// Returns messages waiting for any peer, in order they were queued
+    pub fn outbox(&self) -> std::io::Result<Vec<OutboxEntry>> {
+        match std::fs::read_to_string(self.dir.join(OUTBOX_FILE)) {
+            Ok(content) => Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Removes and returns messages waiting for given peer, in order they were queued
+    pub fn take_for(&self, peer_id: &str) -> std::io::Result<Vec<OutboxEntry>> {
+        let (taken, kept): (Vec<OutboxEntry>, Vec<OutboxEntry>) =
+            self.outbox()?.into_iter().partition(|entry: &OutboxEntry| entry.opponent_id == peer_id);
+        if !taken.is_empty() {
+            self.write_outbox(&kept)?;
+        }
+        Ok(taken)
+    }
+
+    /// Removes and returns message at given position counted from 1, none when there is no such one
+    pub fn unqueue(&self, position: usize) -> std::io::Result<Option<OutboxEntry>> {
+        let mut entries = self.outbox()?;
+        if position == 0 || position > entries.len() {
+            return Ok(None);
+        }
+        let entry = entries.remove(position - 1);
+        self.write_outbox(&entries)?;
+        Ok(Some(entry))
+    }
+
+    fn write_outbox(&self, entries: &[OutboxEntry]) -> std::io::Result<()> {
+        let content: String = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).expect("cannot jsonify outbox entry") + "\n")
+            .collect();
+        std::fs::write(self.dir.join(OUTBOX_FILE), content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restores_game_and_outbox() {
+        let dir = std::env::temp_dir().join(format!("tictactoe-correspondence-{}", std::process::id()));
+        let store = CorrespondenceStore::open(&dir).unwrap();
+
+        let mut game = tictactoe::TicTacToe::with_marks(tictactoe::Marks::default().swapped());
+        let _ = game.make_my_turn(1, 1);
+        let _ = game.make_opponent_turn(0, 0);
+        let _ = game.make_my_turn(2, 2);
+        store.save_games(&[SavedGame::new("peer", &game)]).unwrap();
+        for opponent_id in ["peer", "other", "peer"] {
+            let entry = OutboxEntry { opponent_id: opponent_id.to_string(), topic: "t".to_string(), payload: "{}".to_string() };
+            store.queue(&entry).unwrap();
+        }
+
+        let (restored, my_turn) = store.load_games()[0].restore();
+        assert_eq!(store.outbox().unwrap().len(), 3);
+        let unqueued = store.unqueue(2).unwrap();
+        assert_eq!(store.unqueue(3).unwrap(), None);
+        let taken = store.take_for("peer").unwrap();
+        let rest = store.take_for("other").unwrap();
+        let identity = store.identity().unwrap().public();
+        assert_eq!(store.identity().unwrap().public(), identity);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(restored.grid(), game.grid());
+        assert!(!my_turn);
+        assert_eq!(unqueued.map(|entry| entry.opponent_id), Some("other".to_string()));
+        assert_eq!(taken.len(), 2);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn remembers_recent_opponents() {
+        let peer = |peer_id: &str, addresses: usize, last_played: u64| KnownPeer {
+            peer_id: peer_id.to_string(),
+            addresses: (0..addresses).map(|port| format!("/ip4/127.0.0.1/tcp/{}", port)).collect(),
+            last_played,
+        };
+        let mut known = Vec::new();
+        for index in 0..=MAX_KNOWN_PEERS as u64 {
+            remember(&mut known, peer(&format!("peer{}", index), 1, index));
+        }
+        remember(&mut known, peer("peer5", MAX_KNOWN_ADDRESSES + 2, 100));
+
+        assert_eq!(known.len(), MAX_KNOWN_PEERS);
+        assert_eq!(known[0].peer_id, "peer5");
+        assert_eq!(known[0].addresses.first().map(String::as_str), Some("/ip4/127.0.0.1/tcp/0"));
+        assert_eq!(known[0].addresses.len(), MAX_KNOWN_ADDRESSES);
+        assert_eq!(known.iter().filter(|known| known.peer_id == "peer5").count(), 1);
+        assert!(known.iter().all(|known| known.peer_id != "peer0"), "the least recent is forgotten");
+
+        let dir = std::env::temp_dir().join(format!("tictactoe-known-{}", std::process::id()));
+        let store = CorrespondenceStore::open(&dir).unwrap();
+        assert!(store.load_known_peers().is_empty());
+        store.save_known_peers(&known).unwrap();
+        let loaded = store.load_known_peers();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded, known);
+    }
+}
@@ -0,0 +1,136 @@
+//! # Direct
+//!
+//! Invitations, answers and turns concern only one peer, so they go straight to
+//! it over request-response protocol instead of being flooded to everybody on
+//! the topic. Peer acknowledges each message, so sender learns when opponent
+//! cannot be reached. Message keeps its floodsub topic and encoding, receiver
+//! validates it the same way whichever way it came. Older clients do not speak
+//! the protocol and get the message over floodsub, as before.
+
+use async_trait::async_trait;
+use libp2p::futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{self, ProtocolSupport};
+
+pub const PROTOCOL: libp2p::StreamProtocol = libp2p::StreamProtocol::new("/tictactoe/direct/1.0.0");
+/// Features peers without the protocol lack, messages reach them by floodsub
+pub const MISSING_WITHOUT: &[&str] = &["delivery receipts"];
+/// Largest message accepted, game messages are far smaller
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Encoded message with floodsub topic it belongs to
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirectMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Receipt of direct message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ack;
+
+#[derive(Debug, Clone, Default)]
+pub struct DirectCodec;
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+/// Reads bytes prefixed by their length as unsigned varint, longer than given
+/// limit are refused. Older clients frame messages the same way.
+pub(super) async fn read_length_prefixed<T: AsyncRead + Unpin>(io: &mut T, max_len: usize) -> std::io::Result<Vec<u8>> {
+    let mut len = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let mut byte = [0u8];
+        io.read_exact(&mut byte).await?;
+        len |= usize::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            if len > max_len {
+                return Err(invalid_data(format!("message of {} bytes is larger than {}", len, max_len)));
+            }
+            let mut bytes = vec![0; len];
+            io.read_exact(&mut bytes).await?;
+            return Ok(bytes);
+        }
+    }
+    Err(invalid_data("message length does not fit"))
+}
+
+/// Writes bytes prefixed by their length as unsigned varint
+pub(super) async fn write_length_prefixed<T: AsyncWrite + Unpin>(io: &mut T, bytes: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let bytes = bytes.as_ref();
+    let mut len = bytes.len();
+    let mut prefix = Vec::new();
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            prefix.push(byte);
+            break;
+        }
+        prefix.push(byte | 0x80);
+    }
+    io.write_all(&prefix).await?;
+    io.write_all(bytes).await?;
+    io.flush().await
+}
+
+#[async_trait]
+impl request_response::Codec for DirectCodec {
+    type Protocol = libp2p::StreamProtocol;
+    type Request = DirectMessage;
+    type Response = Ack;
+
+    async fn read_request<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T) -> std::io::Result<DirectMessage>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let bytes = read_length_prefixed(io, MAX_MESSAGE_BYTES).await?;
+        serde_json::from_slice(&bytes).map_err(invalid_data)
+    }
+
+    async fn read_response<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T) -> std::io::Result<Ack>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_length_prefixed(io, 0).await?;
+        Ok(Ack)
+    }
+
+    async fn write_request<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T, message: DirectMessage) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = serde_json::to_vec(&message).map_err(invalid_data)?;
+        write_length_prefixed(io, bytes).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T, _: Ack) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_length_prefixed(io, []).await?;
+        io.close().await
+    }
+}
+
+pub fn behaviour() -> request_response::Behaviour<DirectCodec> {
+    let protocols = std::iter::once((PROTOCOL, ProtocolSupport::Full));
+    request_response::Behaviour::new(protocols, request_response::Config::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::request_response::Codec;
+
+    #[tokio::test]
+    async fn message_survives_codec() {
+        let message = DirectMessage { topic: "TicTacToe/a/b".to_string(), payload: "{\"Turn\":{}}".to_string() };
+        let mut written = libp2p::futures::io::Cursor::new(Vec::new());
+        DirectCodec.write_request(&PROTOCOL, &mut written, message.clone()).await.unwrap();
+
+        let mut read = libp2p::futures::io::Cursor::new(written.into_inner());
+        assert_eq!(DirectCodec.read_request(&PROTOCOL, &mut read).await.unwrap(), message);
+    }
+}
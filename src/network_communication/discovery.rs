@@ -0,0 +1,260 @@
+//! # Discovery
+//!
+//! Strategies finding peers to play with. Several strategies can run together,
+//! each peer remembers which of them found it. mDNS and Kademlia report peers
+//! from their swarm behaviours, static list and rendezvous dial known addresses.
+//! Recent opponents are dialed by session itself on start, whatever strategies
+//! are selected.
+//!
+//! Players register at rendezvous point with libp2p rendezvous protocol under
+//! their namespace and discover others registered there. Registration carries
+//! signed peer record, so nobody registers or replaces others.
+
+use itertools::Itertools;
+
+use super::builder::SwarmConfig;
+use super::behaviour::TicTacToeBehaviour;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoveryMethod {
+    /// Multicast on local network
+    Mdns,
+    /// Distributed hash table joined through bootstrap peers
+    Kademlia,
+    /// Addresses listed in config
+    Static,
+    /// Peers announcing themselves through shared rendezvous peer
+    Rendezvous,
+    /// Recent opponents dialed at addresses remembered from last game, not selectable
+    #[serde(skip)]
+    Known,
+    /// Peers dialed with connect command, not selectable
+    #[serde(skip)]
+    Manual,
+}
+
+impl std::fmt::Display for DiscoveryMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryMethod::Mdns => write!(f, "mdns"),
+            DiscoveryMethod::Kademlia => write!(f, "kademlia"),
+            DiscoveryMethod::Static => write!(f, "static"),
+            DiscoveryMethod::Rendezvous => write!(f, "rendezvous"),
+            DiscoveryMethod::Known => write!(f, "known"),
+            DiscoveryMethod::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiscoveryError {
+    /// Address is not a multiaddr ending with /p2p/<peer id>
+    InvalidAddress(String),
+    /// Rendezvous namespace longer than the protocol allows
+    InvalidNamespace(String),
+}
+
+impl std::fmt::Display for DiscoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryError::InvalidAddress(address) => write!(f, "invalid peer address '{}', expected e.g. /ip4/1.2.3.4/tcp/4001/p2p/<peer id>", address),
+            DiscoveryError::InvalidNamespace(namespace) => write!(f, "rendezvous namespace '{}' is too long", namespace),
+        }
+    }
+}
+
+/// Peer found by strategy with address to dial
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub peer: libp2p::PeerId,
+    pub address: libp2p::Multiaddr,
+}
+
+/// Way of finding peers
+pub(crate) trait Discovery: Send {
+    fn method(&self) -> DiscoveryMethod;
+
+    /// Enables swarm behaviours and topics the strategy needs
+    fn configure(&self, _config: &mut SwarmConfig) {}
+
+    /// Called once swarm runs and then periodically, returns peers to dial
+    fn refresh(&mut self, _swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) -> Vec<Found> {
+        Vec::new()
+    }
+}
+
+/// Parses address of peer written as multiaddr ending with its peer id
+pub fn parse_peer_address(address: &str) -> Result<(libp2p::PeerId, libp2p::Multiaddr), DiscoveryError> {
+    let invalid = || DiscoveryError::InvalidAddress(address.to_string());
+    let mut multiaddr: libp2p::Multiaddr = address.parse().map_err(|_| invalid())?;
+    match multiaddr.pop() {
+        Some(libp2p::multiaddr::Protocol::P2p(peer)) => Ok((peer, multiaddr)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Builds strategies selected in settings, invalid addresses are skipped and added to errors
+pub(crate) fn from_settings(settings: &super::Settings, errors: &mut Vec<String>) -> Vec<Box<dyn Discovery>> {
+    let namespace = match libp2p::rendezvous::Namespace::new(settings.rendezvous_namespace.clone()) {
+        Ok(namespace) => Some(namespace),
+        Err(_) if settings.discovery.contains(&DiscoveryMethod::Rendezvous) => {
+            errors.push(DiscoveryError::InvalidNamespace(settings.rendezvous_namespace.clone()).to_string());
+            None
+        }
+        Err(_) => None,
+    };
+    let mut parse_all = |addresses: &[String]| -> Vec<(libp2p::PeerId, libp2p::Multiaddr)> {
+        addresses
+            .iter()
+            .filter_map(|address| parse_peer_address(address).map_err(|error| errors.push(error.to_string())).ok())
+            .collect()
+    };
+
+    settings
+        .discovery
+        .iter()
+        .unique()
+        .filter_map(|method| -> Option<Box<dyn Discovery>> {
+            match method {
+                DiscoveryMethod::Mdns => Some(Box::new(Mdns)),
+                DiscoveryMethod::Kademlia => Some(Box::new(Kademlia { bootstrap: parse_all(&settings.bootstrap_peers) })),
+                DiscoveryMethod::Static => Some(Box::new(StaticPeers { peers: parse_all(&settings.static_peers) })),
+                DiscoveryMethod::Rendezvous => {
+                    let point = settings.rendezvous_point.iter().cloned().collect::<Vec<_>>();
+                    Some(Box::new(Rendezvous { point: parse_all(&point).pop(), namespace: namespace.clone()? }))
+                }
+                DiscoveryMethod::Known | DiscoveryMethod::Manual => None,
+            }
+        })
+        .collect()
+}
+
+/// Peers on local network
+pub struct Mdns;
+
+impl Discovery for Mdns {
+    fn method(&self) -> DiscoveryMethod {
+        DiscoveryMethod::Mdns
+    }
+
+    fn configure(&self, config: &mut SwarmConfig) {
+        config.mdns = true;
+    }
+}
+
+/// Peers joining the same DHT through bootstrap peers
+pub struct Kademlia {
+    pub bootstrap: Vec<(libp2p::PeerId, libp2p::Multiaddr)>,
+}
+
+impl Discovery for Kademlia {
+    fn method(&self) -> DiscoveryMethod {
+        DiscoveryMethod::Kademlia
+    }
+
+    fn configure(&self, config: &mut SwarmConfig) {
+        config.kademlia = true;
+    }
+
+    fn refresh(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) -> Vec<Found> {
+        if let Some(kademlia) = swarm.behaviour_mut().protocols.kademlia.as_mut() {
+            for (peer, address) in &self.bootstrap {
+                kademlia.add_address(peer, address.clone());
+            }
+            // fails only when routing table is empty, the next refresh tries again
+            let _ = kademlia.bootstrap();
+        }
+        Vec::new()
+    }
+}
+
+/// Fixed list of peers, they are dialed whenever they are not connected
+pub struct StaticPeers {
+    pub peers: Vec<(libp2p::PeerId, libp2p::Multiaddr)>,
+}
+
+impl Discovery for StaticPeers {
+    fn method(&self) -> DiscoveryMethod {
+        DiscoveryMethod::Static
+    }
+
+    fn refresh(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) -> Vec<Found> {
+        self.peers
+            .iter()
+            .filter(|(peer, _)| !swarm.is_connected(peer))
+            .map(|(peer, address)| Found { peer: *peer, address: address.clone() })
+            .collect()
+    }
+}
+
+/// Players meeting at shared rendezvous point, none when I am the point
+pub struct Rendezvous {
+    pub point: Option<(libp2p::PeerId, libp2p::Multiaddr)>,
+    pub namespace: libp2p::rendezvous::Namespace,
+}
+
+impl Discovery for Rendezvous {
+    fn method(&self) -> DiscoveryMethod {
+        DiscoveryMethod::Rendezvous
+    }
+
+    fn configure(&self, config: &mut SwarmConfig) {
+        config.rendezvous = self.point.is_some();
+        config.rendezvous_point = self.point.is_none();
+    }
+
+    fn refresh(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) -> Vec<Found> {
+        let (point, address) = match &self.point {
+            Some(point) => point,
+            None => return Vec::new(),
+        };
+        if !swarm.is_connected(point) {
+            return vec![Found { peer: *point, address: address.clone() }];
+        }
+        // registration names addresses others dial, those I listen on
+        for address in swarm.listeners().cloned().collect_vec() {
+            swarm.add_external_address(address);
+        }
+        if let Some(rendezvous) = swarm.behaviour_mut().protocols.rendezvous.as_mut() {
+            // failed registration is tried again on the next refresh
+            let _ = rendezvous.register(self.namespace.clone(), *point, None);
+            rendezvous.discover(Some(self.namespace.clone()), None, None, *point);
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_communication::Settings;
+
+    #[test]
+    fn parses_peer_address() {
+        let peer = libp2p::PeerId::random();
+        let (parsed, address) = parse_peer_address(&format!("/ip4/10.0.0.1/tcp/4001/p2p/{}", peer)).unwrap();
+        assert_eq!(parsed, peer);
+        assert_eq!(address.to_string(), "/ip4/10.0.0.1/tcp/4001");
+        assert!(parse_peer_address("/ip4/10.0.0.1/tcp/4001").is_err());
+        assert!(parse_peer_address("peer").is_err());
+    }
+
+    #[test]
+    fn players_register_at_point() {
+        let namespace = libp2p::rendezvous::Namespace::from_static("tictactoe/lobby");
+        let mut config = SwarmConfig::default();
+        Rendezvous { point: None, namespace: namespace.clone() }.configure(&mut config);
+        assert!(config.rendezvous_point && !config.rendezvous);
+
+        let point = parse_peer_address(&format!("/ip4/10.0.0.1/tcp/4001/p2p/{}", libp2p::PeerId::random())).unwrap();
+        let mut config = SwarmConfig::default();
+        Rendezvous { point: Some(point), namespace }.configure(&mut config);
+        assert!(config.rendezvous && !config.rendezvous_point);
+
+        let settings = Settings { discovery: vec![DiscoveryMethod::Rendezvous], rendezvous_namespace: "x".repeat(256), ..Settings::default() };
+        let mut errors = Vec::new();
+        assert!(from_settings(&settings, &mut errors).is_empty());
+        assert!(errors[0].contains("too long"));
+    }
+}
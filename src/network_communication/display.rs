@@ -0,0 +1,45 @@
+//! # Display
+//!
+//! Text from peers shown in terminal. Nicknames, messages, chat, game ids and
+//! everything else other peer wrote may carry escape sequences which clear the
+//! screen, rewrite earlier lines or change the window title. Every output path
+//! passes such text through [`sanitize_for_display`] before printing it, peer
+//! ids are safe as they are checked by libp2p.
+
+/// Returns true for characters which drive terminal or hide text, e.g. escape,
+/// carriage return or right-to-left override. Line break is allowed.
+pub fn is_unsafe(c: char) -> bool {
+    (c.is_control() && c != '\n')
+        || matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2060}'..='\u{2069}' | '\u{feff}')
+}
+
+/// Returns remote text safe to print, unsafe characters are shown escaped, so
+/// user sees what peer tried, e.g. `\u{1b}[2J`
+pub fn sanitize_for_display(text: &str) -> String {
+    text.chars()
+        .map(|c| if is_unsafe(c) { c.escape_unicode().to_string() } else { c.to_string() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_injection_payloads() {
+        let payloads = [
+            ("\u{1b}[2J\u{1b}[H", "\\u{1b}[2J\\u{1b}[H"),
+            ("\u{1b}]0;pwned\u{7}", "\\u{1b}]0;pwned\\u{7}"),
+            ("\u{1b}]8;;http://evil\u{1b}\\link", "\\u{1b}]8;;http://evil\\u{1b}\\link"),
+            ("\u{9b}31m", "\\u{9b}31m"),
+            ("gg\rYou won!", "gg\\u{d}You won!"),
+            ("abc\u{8}\u{8}\u{8}xyz", "abc\\u{8}\\u{8}\\u{8}xyz"),
+            ("\u{202e}gnp.exe", "\\u{202e}gnp.exe"),
+        ];
+        for (payload, shown) in payloads {
+            assert_eq!(sanitize_for_display(payload), shown);
+            assert!(!sanitize_for_display(payload).chars().any(is_unsafe));
+        }
+        assert_eq!(sanitize_for_display("good game\n❌⭕"), "good game\n❌⭕");
+    }
+}
@@ -0,0 +1,134 @@
+//! # Doctor
+//!
+//! Startup self-test, checks that environment allows playing and tells how to fix it otherwise
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Failed,
+    /// Check does not apply to current setup
+    Skipped,
+}
+
+/// Result of one check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Check {
+        Check { name, status, detail: detail.into() }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Ok => "ok",
+            Status::Warning => "warn",
+            Status::Failed => "FAIL",
+            Status::Skipped => "skip",
+        };
+        write!(f, "[{:4}] {}: {}", status, self.name, self.detail)
+    }
+}
+
+/// Runs all checks, config is read from given path
+pub async fn run(config_path: &std::path::Path) -> Vec<Check> {
+    let (config_check, config) = check_config(config_path);
+    vec![
+        config_check,
+        check_listen().await,
+        check_mdns().await,
+        check_key_file(&config),
+        check_data_dir(&config),
+        Check::new("relay", Status::Skipped, "no relay is configured, only peers on local network are reachable"),
+    ]
+}
+
+fn check_config(path: &std::path::Path) -> (Check, Config) {
+    if !path.exists() {
+        let detail = format!("{} not found, defaults are used; create it or pass --config", path.display());
+        return (Check::new("config", Status::Warning, detail), Config::default());
+    }
+
+    match Config::from_file(path) {
+        Ok(config) => match config.coordinates.clone().validated() {
+            Ok(_) => (Check::new("config", Status::Ok, format!("{} is valid", path.display())), config),
+            Err(error) => {
+                let detail = format!("{}: {}, fix 'coordinates' section", path.display(), error);
+                (Check::new("config", Status::Failed, detail), config)
+            }
+        },
+        Err(error) => {
+            let detail = format!("{}: {}, fix the file or remove it to use defaults", path.display(), error);
+            (Check::new("config", Status::Failed, detail), Config::default())
+        }
+    }
+}
+
+async fn check_listen() -> Check {
+    let key = libp2p::identity::Keypair::generate_ed25519();
+    let mut transport = match super::swarm::create_transport(&key) {
+        Ok(transport) => transport,
+        Err(error) => return Check::new("listen port", Status::Failed, format!("cannot create transport: {}", error)),
+    };
+
+    match libp2p::Transport::listen_on(&mut transport, libp2p::core::transport::ListenerId::next(), super::swarm::listen_address()) {
+        Ok(_) => Check::new("listen port", Status::Ok, format!("can listen on {}", super::swarm::LISTEN_ADDRESS)),
+        Err(error) => {
+            let detail = format!("cannot listen on {}: {}, check firewall and permissions", super::swarm::LISTEN_ADDRESS, error);
+            Check::new("listen port", Status::Failed, detail)
+        }
+    }
+}
+
+async fn check_mdns() -> Check {
+    match super::swarm::create_mdns(libp2p::PeerId::random()) {
+        Ok(_) => Check::new("mdns", Status::Ok, "multicast discovery is available"),
+        Err(error) => {
+            let detail = format!("{}, enable multicast on network interface and allow UDP port 5353", error);
+            Check::new("mdns", Status::Failed, detail)
+        }
+    }
+}
+
+fn check_key_file(config: &Config) -> Check {
+    let dir = match &config.session.correspondence_dir {
+        Some(dir) => dir,
+        None => return Check::new("key file", Status::Skipped, "identity is generated on each start, no key file is used"),
+    };
+
+    match super::correspondence::CorrespondenceStore::open(dir).and_then(|store| store.identity()) {
+        Ok(key) => Check::new("key file", Status::Ok, format!("identity {} is kept in {}", libp2p::PeerId::from(key.public()), dir.display())),
+        Err(error) => {
+            let detail = format!("cannot use key in {}: {}, fix permissions or change correspondence_dir", dir.display(), error);
+            Check::new("key file", Status::Failed, detail)
+        }
+    }
+}
+
+fn check_data_dir(config: &Config) -> Check {
+    let directory = match (&config.session.data_dir, &config.session.stats_file) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(path)) => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        },
+        (None, None) => return Check::new("data dir", Status::Skipped, "no data_dir or stats_file is configured, games are not recorded"),
+    };
+    let probe = directory.join(format!(".tictactoe-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => Check::new("data dir", Status::Ok, format!("{} is writable", directory.display())),
+        Err(error) => {
+            let detail = format!("cannot write into {}: {}, create it or change data_dir or stats_file", directory.display(), error);
+            Check::new("data dir", Status::Failed, detail)
+        }
+    }
+}
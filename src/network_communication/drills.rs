@@ -0,0 +1,132 @@
+//! # Drills
+//!
+//! Positions from finished games where I played worse move than the best one.
+//! Each drill asks for the best move again and is repeated after a day, then
+//! after twice as long with every correct answer in a row. Wrong answer brings
+//! it back within minutes.
+
+use super::replay::Replay;
+#[cfg(feature = "ai")]
+use crate::ai;
+use crate::coords::Coordinates;
+
+/// Drill answered correctly for the first time is repeated after this many seconds
+#[cfg(feature = "ai")]
+const FIRST_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Drill answered wrongly is repeated after this many seconds
+#[cfg(feature = "ai")]
+const RETRY_SECS: u64 = 10 * 60;
+
+/// Longest interval doubles this many times
+#[cfg(feature = "ai")]
+const MAX_STREAK: u32 = 8;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Drill {
+    /// Game cut right before my blunder
+    pub position: Replay,
+    /// Correct answers in a row
+    pub streak: u32,
+    /// Unix time in seconds when drill is due
+    pub due_at: u64,
+}
+
+/// Result of answer to drill
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grade {
+    pub correct: bool,
+    /// Fields where best move is played
+    pub best: Vec<Coordinates>,
+    /// Seconds until drill is shown again
+    pub next_in_secs: u64,
+}
+
+#[cfg(feature = "ai")]
+impl Drill {
+    /// Checks answer against engine and schedules next repetition
+    pub fn answer(&mut self, field: Coordinates, now_secs: u64) -> Grade {
+        let game = self.position.game_after(self.position.moves.len());
+        let scored = ai::scored_moves(&game);
+        let best_evaluation = scored.iter().map(|(_, _, evaluation)| *evaluation).max();
+        let mut best: Vec<Coordinates> = scored
+            .iter()
+            .filter(|(_, _, evaluation)| Some(*evaluation) == best_evaluation)
+            .map(|(field, _, _)| *field)
+            .collect();
+        best.dedup();
+        let correct = best.contains(&field);
+
+        let next_in_secs = if correct {
+            self.streak = (self.streak + 1).min(MAX_STREAK);
+            FIRST_INTERVAL_SECS << (self.streak - 1)
+        } else {
+            self.streak = 0;
+            RETRY_SECS
+        };
+        self.due_at = now_secs + next_in_secs;
+        Grade { correct, best, next_in_secs }
+    }
+}
+
+/// Returns drill for every my move which made the game worse than best play allowed
+#[cfg(feature = "ai")]
+pub fn blunders(replay: &Replay, now_secs: u64) -> Vec<Drill> {
+    replay
+        .moves
+        .iter()
+        .enumerate()
+        .filter(|(_, step)| step.mine)
+        .filter(|(index, step)| {
+            let game = replay.game_after(*index);
+            let scored = ai::scored_moves(&game);
+            let best = scored.iter().map(|(_, _, evaluation)| *evaluation).max();
+            let played = scored
+                .iter()
+                .filter(|(field, mark, _)| *field == (step.x, step.y) && step.mark.map_or(true, |played| played == *mark))
+                .map(|(_, _, evaluation)| *evaluation)
+                .max();
+            played < best
+        })
+        .map(|(index, _)| {
+            let mut position = replay.clone();
+            position.moves.truncate(index);
+            position.moves.iter_mut().for_each(|step| step.comment = None);
+            Drill { position, streak: 0, due_at: now_secs }
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "ai"))]
+mod tests {
+    use super::*;
+    use crate::network_communication::stats::Outcome;
+    use crate::tictactoe::TicTacToe;
+
+    #[test]
+    fn repeats_missed_block() {
+        // I play circles and do not block the top row
+        let mut game = TicTacToe::new();
+        let _ = game.make_opponent_turn(0, 0);
+        let _ = game.make_my_turn(1, 1);
+        let _ = game.make_opponent_turn(0, 1);
+        let _ = game.make_my_turn(2, 2);
+        let _ = game.make_opponent_turn(0, 2);
+        let replay = Replay::new("peer", Outcome::Lost, &game);
+
+        let mut drills = blunders(&replay, 100);
+        assert_eq!(drills.len(), 1);
+        let drill = &mut drills[0];
+        assert_eq!(drill.position.moves.len(), 3);
+
+        let grade = drill.answer((2, 2), 100);
+        assert!(!grade.correct);
+        assert_eq!(grade.best, vec![(0, 2)]);
+        assert_eq!(drill.due_at, 100 + RETRY_SECS);
+
+        drill.answer((0, 2), 200);
+        let grade = drill.answer((0, 2), 300);
+        assert!(grade.correct);
+        assert_eq!(grade.next_in_secs, 2 * FIRST_INTERVAL_SECS);
+    }
+}
@@ -0,0 +1,284 @@
+//! # Events
+//!
+//! What the client tells its frontend: output events and summaries of peers,
+//! games and session they carry.
+
+use crate::network_communication::{
+    banner, discovery, drills, hints, ladder, lobby, nat, netstats, pending, protocol, referee, reload, replay, stats, undo, validation, Coordinates,
+};
+use crate::tictactoe;
+
+/// Short description of one discovered peer
+#[derive(Debug, Clone)]
+pub struct PeerSummary {
+    pub peer_id: String,
+    pub reputation: i64,
+    /// Seconds since peer was discovered or sent a message
+    pub seen_secs_ago: Option<u64>,
+    /// Strategies which found the peer
+    pub discovered_by: Vec<discovery::DiscoveryMethod>,
+}
+
+/// Short description of one game session
+#[derive(Debug, Clone)]
+pub struct GameSummary {
+    pub index: usize,
+    pub opponent_id: String,
+    /// Game topic, it names the game in audit log and notifications
+    pub game_id: String,
+    pub your_turn: bool,
+    /// Average round trip to opponent
+    pub latency_millis: Option<u64>,
+    pub active: bool,
+}
+
+/// Game with start time
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledGame {
+    pub opponent_id: String,
+    /// UTC milliseconds
+    pub start_at: u64,
+    /// False while the time is only proposed
+    pub agreed: bool,
+}
+
+/// Player on ladder of my room
+#[derive(Debug, Clone, PartialEq)]
+pub struct LadderPosition {
+    pub peer_id: String,
+    pub me: bool,
+    /// My win over the player would move me up
+    pub challengeable: bool,
+}
+
+/// Summary of session kept on screen by frontends which have room for it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusLine {
+    /// Peers seen on network
+    pub peers: usize,
+    pub games: usize,
+    /// Opponent of active game
+    pub opponent: Option<String>,
+    pub your_turn: bool,
+    /// I announced open game in lobby
+    pub seeking: bool,
+}
+
+#[derive(Clone)]
+pub enum OutputEvents {
+    /// Session changed, sent only when summary differs from previous one
+    Status(StatusLine),
+    /// Failure of client itself, not of peer or command, e.g. file cannot be written
+    Error(String),
+    ListPeers(Vec<PeerSummary>),
+    StartTrue(tictactoe::Grid, Option<tictactoe::Evaluation>),
+    StartFalse,
+    TurnResolved(tictactoe::Grid, Option<tictactoe::Evaluation>),
+    GameOver,
+    /// Game with peer ended in draw
+    Draw(String),
+    /// My turn won game with peer
+    Won(String),
+    SecurityWarning(String),
+    /// Kind of message ignored from peer who is not my opponent
+    Ignored(String, &'static str),
+    Diagnostics(String, validation::InvalidMessage, validation::Diagnostics),
+    Reminder(u64),
+    OpponentSlow(u64),
+    Nudged(String),
+    /// Swarm was rebuilt and listens on given addresses
+    Reconnected(Vec<libp2p::Multiaddr>),
+    /// Listener bound address after banner was shown
+    ListeningOn(libp2p::Multiaddr),
+    /// Number of recent opponents dialed at remembered addresses
+    KnownPeersDialed(usize),
+    /// Peer dialed by connect command at address
+    Dialing(String, libp2p::Multiaddr),
+    /// My unanswered invitations, messages queued for offline peers and open questions
+    Pending(Vec<pending::Pending>),
+    Cleared(pending::PendingId),
+    NoSuchPending(pending::PendingId),
+    /// Ladder of my room from the top down, none when ladder is not played
+    Ladder(Option<Vec<LadderPosition>>),
+    /// New ladder result conceded by its loser
+    Attested(ladder::Attestation),
+    /// Open games of other players, numbered from 1 for start
+    OpenGames(Vec<lobby::OpenGame>),
+    /// I announced I look for a game
+    Seeking,
+    LeftLobby,
+    /// Number given to start is not any open game
+    NoSuchOpenGame(String),
+    /// Peer which direct message did not reach, with reason
+    Undelivered(String, String),
+    /// Relayed connection to peer became direct, or reason why it stays relayed
+    HolePunch(String, Result<(), String>),
+    /// Peer runs older version, with features unavailable in games with it
+    OlderPeer(String, Vec<&'static str>),
+    /// Peer sent message of newer protocol version, with the version
+    NewerPeer(String, u32),
+    /// Replay sent to peer, chunks acknowledged and all chunks
+    ReplayProgress(String, usize, usize),
+    /// Replay shared by peer with file it was saved to
+    ReplayReceived(String, std::path::PathBuf, replay::Replay),
+    ReplayTransferFailed(String, String),
+    /// I watch game with given id from now on
+    Watching(String),
+    /// I no longer watch game with given id
+    Unwatched(String),
+    /// Game id does not name game of two peers
+    InvalidGameId(String),
+    /// Last move seen in watched game and its playmat after it
+    SpectatedTurn(String, Coordinates, tictactoe::Grid),
+    /// Watched game ended, with its winner, none for draw
+    SpectatedFinished(String, Option<String>),
+    /// I referee game with given id from now on
+    Refereeing(String),
+    StoppedRefereeing(String),
+    /// Player may not referee own game
+    OwnGameRefereed(String),
+    /// I signed verdict and sent it to players of the game
+    VerdictIssued(referee::Verdict),
+    /// Trusted referee ruled on my or watched game
+    Verdict(referee::Verdict),
+    /// Tip on what to do next
+    Hint(hints::Hint),
+    Shutdown,
+    Games(Vec<GameSummary>),
+    /// Games waiting for my turn
+    PendingGames(Vec<GameSummary>),
+    SwitchedGame(usize, tictactoe::Grid),
+    NoSuchGame(usize),
+    /// Game with peer is already running, new one is not started
+    AlreadyPlaying(String),
+    BoardChanged(usize, String),
+    SimulAccepted(usize, String),
+    SimulFull(String),
+    EnginePlayed(Coordinates, tictactoe::Grid, Option<tictactoe::Evaluation>),
+    EngineError(String),
+    /// Turn typed while no game is played
+    NoActiveGame,
+    /// Turn typed before game with opponent started, true when I have to answer invitation
+    GameNotStarted(String, bool),
+    NotYourTurn(String),
+    /// I proposed other start time of game with peer
+    CounterProposed(String, u64),
+    /// Peer agreed on start time of our game
+    ScheduleAgreed(String, u64),
+    Schedule(Vec<ScheduledGame>),
+    /// Agreed start time of game with peer has come
+    ScheduledGameDue(String),
+    /// Opponent and I played turn of the same number, true when mine stands
+    RaceResolved(String, bool),
+    /// Turn typed after game with opponent ended
+    GameFinished(String),
+    /// Field, true when occupied by you, number of turn which occupied it
+    FieldOccupied(Coordinates, bool, usize),
+    OutOfRange(usize, usize),
+    /// Rules of current game do not allow placing the tile
+    WrongMark(tictactoe::Tile),
+    /// Opponent's turn on given field breaks rules of the game or comes out of
+    /// order, it was not played and it is still their turn
+    OpponentInvalidMove(String, Coordinates, protocol::MoveRejection),
+    /// Opponent did not play my turn on given field
+    MoveRejected(String, Coordinates, protocol::MoveRejection),
+    /// Your turn and field where opponent would win afterwards
+    LosingTurn(Coordinates, Coordinates),
+    /// Opponent disconnected and game is adjourned
+    OpponentLeft(String),
+    /// Opponent disconnected and forfeits after given seconds
+    ForfeitPending(String, u64),
+    WonByForfeit(String),
+    /// I did not move in time and lost game against given opponent
+    TurnTimeout(String),
+    /// Opponent did not move in time, I win by forfeit
+    OpponentTimeout(String),
+    /// I gave up game against given opponent
+    Resigned(String),
+    /// Action against given opponent is sent after given seconds unless undone
+    ActionHeld(String, undo::Action, u64),
+    /// Action against given opponent was taken back before it was sent
+    Undone(String, undo::Action),
+    NothingToUndo,
+    /// Game against given opponent ends once held action is sent
+    GameClosing(String),
+    OpponentResigned(String),
+    /// I seemed away, so AI played my move in game against given opponent
+    AutoMoved(String, Coordinates, tictactoe::Grid),
+    /// Opponent seemed away, their client played their last move
+    OpponentAutoMoved(String),
+    GameVoided(String),
+    OpponentReturned(String),
+    /// My invitation to peer was not answered in time
+    InvitationExpired(String),
+    /// Peer withdrew invitation to me
+    InvitationWithdrawn(String),
+    /// Invitation from peer declined because of given reputation
+    InvitationDeclined(String, i64),
+    /// Usage and description of registered commands
+    PluginHelp(Vec<(String, String)>),
+    /// Lines printed by registered command
+    PluginOutput(Vec<String>),
+    /// Registered command with given name failed
+    PluginFailed(String, String),
+    UnknownCommand(String),
+    /// Invitation from peer declined because its variant is not registered
+    UnsupportedVariant(String, String),
+    /// Invitation from peer declined because it lacked correct password
+    WrongPassword(String),
+    /// Round trip to opponent spiked, current and average milliseconds
+    Laggy(String, u64, u64),
+    /// My invite code, true when it should be shown as QR code
+    InviteCode(String, bool),
+    InvalidInvite(String),
+    /// Config file changed
+    ConfigReloaded(reload::Summary),
+    /// Changed config file is invalid, running settings stay
+    ConfigRejected(String),
+    /// Finished game counted from the most recent one
+    Replay(usize, replay::Replay),
+    /// Comment added to game and move
+    Annotated(usize, usize),
+    ReplayFailed(String),
+    ReviewStarted(String),
+    /// Position of reviewed game: number of moves, last move and playmat
+    ReviewPosition(usize, Option<replay::ReplayMove>, tictactoe::Grid),
+    ReviewEnded(String),
+    /// There is no finished game to review
+    NothingToReview,
+    /// Finished games, the most recent first
+    History(Vec<stats::GameRecord>),
+    /// Score against current or last opponent when there is one, and overall score
+    Score(Option<(String, tictactoe::Score)>, tictactoe::Score),
+    /// Position of drill to find best move in, against given opponent
+    Drill(tictactoe::State, String),
+    /// Position being set up
+    SetupPosition(tictactoe::State),
+    SetupRejected(crate::setup::SetupError),
+    /// Command needs setup mode, which is not on
+    NotSettingUp,
+    /// Moves of player to move in set up position with their evaluation
+    SetupAnalysis(Vec<(Coordinates, tictactoe::Tile, tictactoe::Evaluation)>),
+    /// Set up position was saved as drill, number of all drills
+    PuzzleSaved(usize),
+    SetupEnded,
+    DrillGraded(drills::Grade),
+    /// No drill is due, number of all drills
+    NoDrills(usize),
+    /// Message counters per topic
+    NetStats(Vec<netstats::TopicStats>),
+    /// What peers tell about NAT in front of me changed
+    NatStatus(nat::NatStatus),
+    NetInfo(nat::NetInfo),
+    /// My identity, shown at start and by 'whoami'
+    Banner(banner::Banner),
+    /// Audit log of game written to file with given number of entries
+    AuditExported(String, std::path::PathBuf, usize),
+    AuditFailed(String),
+    /// Answered question was answered before or no longer applies
+    StaleAnswer,
+    /// Chat message from peer after hooks processed it
+    Chat(String, String),
+    /// Language set for chat in current game
+    ChatLanguage(Option<String>),
+}
@@ -0,0 +1,220 @@
+//! # External engine
+//!
+//! Text protocol for plugging an external executable in as move chooser.
+//! Every message is one line, client sends:
+//!
+//! - `tictactoe <version>`, engine answers `ready`
+//! - `position <cells> <mark>`, cells are 9 symbols row by row (`x`, `o`, `.`),
+//!   mark is the symbol engine plays, `*` when it may place either one (wild variant)
+//! - `go`, engine answers `move <row> <col> [<mark>]` with zero based coordinates,
+//!   mark is required when engine may place either symbol
+//! - `quit`
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+use crate::coords::{Coordinates, SIZE};
+use crate::tictactoe::{State, Tile};
+
+/// Version sent in handshake
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum EngineError {
+    Spawn(std::io::Error),
+    Io(std::io::Error),
+    Timeout,
+    Closed,
+    Protocol(String),
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Spawn(err) => write!(f, "cannot start engine: {}", err),
+            EngineError::Io(err) => write!(f, "engine communication failed: {}", err),
+            EngineError::Timeout => write!(f, "engine did not answer in time"),
+            EngineError::Closed => write!(f, "engine exited"),
+            EngineError::Protocol(line) => write!(f, "unexpected engine answer '{}'", line),
+        }
+    }
+}
+
+/// Running engine process
+pub struct ExternalEngine {
+    // kept so the process is killed when engine is dropped
+    _child: tokio::process::Child,
+    stdin: tokio::process::ChildStdin,
+    stdout: tokio::io::Lines<tokio::io::BufReader<tokio::process::ChildStdout>>,
+    timeout: std::time::Duration,
+}
+
+impl ExternalEngine {
+    /// Starts engine executable and performs handshake
+    pub async fn spawn(path: &std::path::Path, timeout: std::time::Duration) -> Result<ExternalEngine, EngineError> {
+        Self::from_command(tokio::process::Command::new(path), timeout).await
+    }
+
+    async fn from_command(mut command: tokio::process::Command, timeout: std::time::Duration) -> Result<ExternalEngine, EngineError> {
+        let mut child = command
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(EngineError::Spawn)?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut engine = ExternalEngine {
+            _child: child,
+            stdin,
+            stdout: tokio::io::BufReader::new(stdout).lines(),
+            timeout,
+        };
+
+        engine.send(&format!("tictactoe {}", PROTOCOL_VERSION)).await?;
+        match engine.receive().await?.as_str() {
+            "ready" => Ok(engine),
+            other => Err(EngineError::Protocol(other.to_string())),
+        }
+    }
+
+    /// Asks engine for move in given position
+    pub async fn best_move(&mut self, state: &State, mark: Tile) -> Result<Coordinates, EngineError> {
+        self.best_marked_move(state, &[mark]).await.map(|(field, _)| field)
+    }
+
+    /// Asks engine for move and tile it places, engine chooses from given tiles
+    pub async fn best_marked_move(&mut self, state: &State, marks: &[Tile]) -> Result<(Coordinates, Tile), EngineError> {
+        let position = match marks {
+            [mark] => encode_position(state, *mark),
+            _ => encode_wild_position(state),
+        };
+        self.send(&position).await?;
+        self.send("go").await?;
+        parse_marked_move(&self.receive().await?, marks)
+    }
+
+    /// Tells engine to exit
+    pub async fn quit(mut self) -> Result<(), EngineError> {
+        self.send("quit").await
+    }
+
+    async fn send(&mut self, line: &str) -> Result<(), EngineError> {
+        self.stdin.write_all(format!("{}\n", line).as_bytes()).await.map_err(EngineError::Io)?;
+        self.stdin.flush().await.map_err(EngineError::Io)
+    }
+
+    async fn receive(&mut self) -> Result<String, EngineError> {
+        match tokio::time::timeout(self.timeout, self.stdout.next_line()).await {
+            Err(_) => Err(EngineError::Timeout),
+            Ok(Err(err)) => Err(EngineError::Io(err)),
+            Ok(Ok(None)) => Err(EngineError::Closed),
+            Ok(Ok(Some(line))) => Ok(line.trim().to_string()),
+        }
+    }
+}
+
+fn symbol(tile: Tile) -> char {
+    match tile {
+        Tile::Cross => 'x',
+        Tile::Circle => 'o',
+        Tile::Empty => '.',
+    }
+}
+
+/// Returns `position` line for given playmat
+pub fn encode_position(state: &State, mark: Tile) -> String {
+    let cells: String = state.iter().flatten().map(|tile| symbol(*tile)).collect();
+    format!("position {} {}", cells, symbol(mark))
+}
+
+/// Returns `position` line where engine may place either symbol
+pub fn encode_wild_position(state: &State) -> String {
+    let cells: String = state.iter().flatten().map(|tile| symbol(*tile)).collect();
+    format!("position {} *", cells)
+}
+
+/// Parses `move <row> <col>` answer
+pub fn parse_move(line: &str) -> Result<Coordinates, EngineError> {
+    let invalid = || EngineError::Protocol(line.to_string());
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    match tokens.as_slice() {
+        ["move", row, col] => parse_field(row, col).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses `move <row> <col> [<mark>]` answer, mark must be one of given tiles
+/// and may be left out only when there is just one
+pub fn parse_marked_move(line: &str, marks: &[Tile]) -> Result<(Coordinates, Tile), EngineError> {
+    let invalid = || EngineError::Protocol(line.to_string());
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    let (field, mark) = match (tokens.as_slice(), marks) {
+        (["move", row, col], [mark]) => (parse_field(row, col), Some(*mark)),
+        (["move", row, col, mark], _) => (parse_field(row, col), parse_mark(mark)),
+        _ => return Err(invalid()),
+    };
+    match (field, mark) {
+        (Some(field), Some(mark)) if marks.contains(&mark) => Ok((field, mark)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_mark(mark: &str) -> Option<Tile> {
+    match mark {
+        "x" => Some(Tile::Cross),
+        "o" => Some(Tile::Circle),
+        _ => None,
+    }
+}
+
+fn parse_field(row: &str, col: &str) -> Option<Coordinates> {
+    let row: usize = row.parse().ok()?;
+    let col: usize = col.parse().ok()?;
+    (row < SIZE && col < SIZE).then_some((row, col))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_position() {
+        let mut state = [[Tile::Empty; 3]; 3];
+        state[0][0] = Tile::Cross;
+        state[1][1] = Tile::Circle;
+        assert_eq!(encode_position(&state, Tile::Cross), "position x...o.... x");
+    }
+
+    #[test]
+    fn parses_move() {
+        assert!(matches!(parse_move("move 2 1"), Ok((2, 1))));
+        assert!(matches!(parse_move("move 3 1"), Err(EngineError::Protocol(_))));
+        assert!(matches!(parse_move("bestmove e4"), Err(EngineError::Protocol(_))));
+
+        let both = [Tile::Cross, Tile::Circle];
+        assert!(matches!(parse_marked_move("move 0 2 o", &both), Ok(((0, 2), Tile::Circle))));
+        assert!(matches!(parse_marked_move("move 0 2", &both), Err(EngineError::Protocol(_))));
+        assert!(matches!(parse_marked_move("move 0 2 o", &[Tile::Cross]), Err(EngineError::Protocol(_))));
+    }
+
+    #[tokio::test]
+    async fn plays_with_engine_process() {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "read hello; echo ready; read position; read go; echo 'move 1 2'"]);
+        let mut engine = ExternalEngine::from_command(command, std::time::Duration::from_secs(5)).await.unwrap();
+
+        let state = [[Tile::Empty; 3]; 3];
+        assert!(matches!(engine.best_move(&state, Tile::Circle).await, Ok((1, 2))));
+    }
+
+    #[tokio::test]
+    async fn times_out_on_silent_engine() {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "sleep 5"]);
+        let engine = ExternalEngine::from_command(command, std::time::Duration::from_millis(100)).await;
+        assert!(matches!(engine, Err(EngineError::Timeout)));
+    }
+}
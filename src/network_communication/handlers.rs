@@ -0,0 +1,130 @@
+//! # Handlers
+//!
+//! Reactions of the main loop to commands of user, to messages of peers and to
+//! timers. Every handler gets session, swarm and user interface it needs.
+//! Commands and peer messages are dispatched in their own submodules, handlers
+//! themselves are grouped in submodules by what they deal with.
+
+mod closing;
+mod commands;
+mod connections;
+mod invitations;
+mod messages;
+mod peers;
+mod practice;
+mod spectators;
+mod summary;
+mod timers;
+mod turns;
+
+pub(super) use closing::{quit, send_held};
+pub(super) use commands::{process_input, send_chat};
+pub(super) use invitations::invite_peer;
+pub(super) use messages::resolve_spawned_messages;
+pub(super) use summary::{banner, status_line};
+pub(super) use timers::check_turn_timeouts;
+
+/// Frontend and offline swarm for tests which drive handlers
+#[cfg(test)]
+pub(super) mod testing {
+    use crate::network_communication::behaviour::{PeerMessage, TicTacToeBehaviour};
+    use crate::network_communication::session::UserSession;
+    use crate::network_communication::{builder, channel, input, loadtest, observer, prompt, swarm, Input, OutputEvents, Settings};
+
+    /// Frontend which keeps everything shown to user
+    #[derive(Default)]
+    pub(super) struct Recorder {
+        pub(super) events: std::cell::RefCell<Vec<OutputEvents>>,
+        /// Answer given to every question, none leaves them open
+        pub(super) answer: Option<prompt::Answer>,
+    }
+
+    #[async_trait::async_trait]
+    impl input::Input<Input, OutputEvents> for Recorder {
+        async fn get_input(&mut self) -> Option<Input> {
+            None
+        }
+
+        fn print_to_output(&self, event: OutputEvents) {
+            self.events.borrow_mut().push(event);
+        }
+
+        fn ask(&mut self, _prompt: prompt::Prompt) -> Option<prompt::Answer> {
+            self.answer
+        }
+    }
+
+    impl observer::SwarmObserver for Recorder {}
+
+    /// Session with swarm on memory transport which has no peers
+    pub(in crate::network_communication) async fn offline_session(settings: Settings) -> (UserSession, libp2p::swarm::Swarm<TicTacToeBehaviour>, channel::Receiver<PeerMessage>) {
+        let (sender, receiver) = channel::bounded::<PeerMessage>(64);
+        let network = loadtest::VirtualNetwork {
+            key: libp2p::identity::Keypair::generate_ed25519(),
+            address: "/memory/0".parse().expect("valid memory address"),
+            peers: Vec::new(),
+        };
+        let user_session = builder::SessionBuilder::new(settings).virtual_network(network).build(sender.downgrade());
+        let swarm = swarm::init_swarm(&user_session, sender).await;
+        (user_session, swarm, receiver)
+    }
+
+    /// Starts game with peer in session at given index, I move first
+    pub(in crate::network_communication) fn start_game(user_session: &mut UserSession, index: usize, peer: &str) {
+        let me = user_session.user_peer_id.to_string();
+        let game_session = &mut user_session.sessions[index];
+        game_session.initiate(peer.to_string(), true, &me, crate::tictactoe::Rules::default(), None);
+        game_session.invited_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{offline_session, Recorder};
+    use super::*;
+    use crate::network_communication::behaviour::{GameStatus, PeerMessage};
+    use crate::network_communication::{prompt, protocol, Input, Settings};
+    use crate::tictactoe;
+
+    #[tokio::test]
+    async fn games_with_two_opponents_keep_their_boards() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let mut recorder = Recorder { answer: Some(prompt::Answer::Yes), ..Recorder::default() };
+        let me = user_session.user_peer_id.to_string();
+        let (alice, bob) = (libp2p::PeerId::random().to_string(), libp2p::PeerId::random().to_string());
+
+        assert!(invite_peer(&mut swarm, alice.clone(), tictactoe::Rules::default(), None, None, None, &mut user_session));
+        let accepted = PeerMessage::about(alice.clone(), GameStatus::Start(true));
+        resolve_spawned_messages(&mut recorder, accepted, &mut swarm, &mut user_session);
+        process_input(Some(Input::Turn(0, 0, None)), &mut swarm, &mut user_session, &mut recorder).await;
+
+        // bob invites me while I play alice
+        let init = GameStatus::Init(me, None, tictactoe::Rules::default(), None, None, protocol::Introduction::default());
+        resolve_spawned_messages(&mut recorder, PeerMessage::about(bob.clone(), init), &mut swarm, &mut user_session);
+        let (alice_game, bob_game) = (user_session.session_of(&alice).unwrap(), user_session.session_of(&bob).unwrap());
+        assert_ne!(alice_game, bob_game);
+        assert_eq!(user_session.active, alice_game, "running game stays active");
+
+        let turn = GameStatus::Turn(2, 2, None, None, Some(1), false);
+        resolve_spawned_messages(&mut recorder, PeerMessage::about(bob.clone(), turn), &mut swarm, &mut user_session);
+        process_input(Some(Input::TurnIn(bob_game, 1, 1, None)), &mut swarm, &mut user_session, &mut recorder).await;
+
+        assert_eq!(user_session.sessions[alice_game].game().moves(), &[(0, 0)]);
+        assert_eq!(user_session.sessions[bob_game].game().moves(), &[(2, 2), (1, 1)]);
+        assert_eq!(user_session.sessions[alice_game].opponent_id, alice);
+        assert_eq!(user_session.sessions[bob_game].opponent_id, bob);
+    }
+
+    #[tokio::test]
+    async fn inviting_opponent_again_keeps_running_game() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let alice = libp2p::PeerId::random().to_string();
+        assert!(invite_peer(&mut swarm, alice.clone(), tictactoe::Rules::default(), None, None, None, &mut user_session));
+        user_session.game_session().invited_at = None;
+        assert!(user_session.game_session().make_my_turn(1, 1, None).is_ok());
+
+        assert!(!invite_peer(&mut swarm, alice.clone(), tictactoe::Rules::default(), None, None, None, &mut user_session));
+        assert_eq!(user_session.sessions.playing(), 1);
+        assert_eq!(user_session.sessions[user_session.session_of(&alice).unwrap()].game().moves(), &[(1, 1)]);
+    }
+}
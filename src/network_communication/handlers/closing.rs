@@ -0,0 +1,148 @@
+//! # Closing
+//!
+//! Resigning, holding actions so they can be undone, undoing them and quitting.
+
+use super::invitations::ask;
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::UserSession;
+use crate::network_communication::swarm::{flush_swarm, send_direct};
+use crate::network_communication::{input, prompt, protocol, stats, undo, Input, OutputEvents};
+
+/// Gives up game in given session, opponent is told and wins. Undoable
+/// resignation waits in outgoing queue for undo first.
+pub(super) fn resign_game<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    undoable: bool,
+) {
+    let format = user_session.opponent_format(index);
+    let game_session = &user_session.sessions[index];
+    if !game_session.is_initiated() {
+        user_interface.print_to_output(OutputEvents::NoActiveGame);
+        return;
+    }
+    let opponent_id = game_session.opponent_id.clone();
+    if game_session.closing.is_some() {
+        user_interface.print_to_output(OutputEvents::GameClosing(opponent_id));
+        return;
+    }
+    if game_session.awaiting_answer || game_session.invited_at.is_some() {
+        user_interface.print_to_output(OutputEvents::GameNotStarted(opponent_id, game_session.awaiting_answer));
+        return;
+    }
+    // older clients do not understand it, their own timer ends the game
+    let message = (!game_session.is_bot_game() && format == protocol::WireFormat::Tagged)
+        .then(|| (game_session.topic.clone(), protocol::WireMessage::Resign, format));
+    if undoable && user_session.settings.undo_secs > 0 {
+        hold_action(user_interface, user_session, index, undo::Action::Resign, message, None);
+        return;
+    }
+    if let Some((topic, message, format)) = message {
+        send_direct(swarm, &opponent_id, topic, message, format);
+    }
+    user_interface.print_to_output(OutputEvents::Resigned(opponent_id));
+    user_session.end_game(swarm, index, stats::Outcome::Lost);
+}
+
+/// Puts action on given session into outgoing queue, its message is sent once undo time passes
+pub(super) fn hold_action<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    user_session: &mut UserSession,
+    index: usize,
+    action: undo::Action,
+    message: Option<(libp2p::floodsub::Topic, protocol::WireMessage, protocol::WireFormat)>,
+    question: Option<prompt::Question>,
+) {
+    let seconds = user_session.settings.undo_secs;
+    let game_session = &mut user_session.sessions[index];
+    let opponent_id = game_session.opponent_id.clone();
+    let snapshot = game_session.hold(action);
+    let send_at = std::time::Instant::now() + std::time::Duration::from_secs(seconds);
+    user_session.outgoing.hold(undo::Held { action, opponent_id: opponent_id.clone(), snapshot, message, question, send_at });
+    user_interface.print_to_output(OutputEvents::ActionHeld(opponent_id, action, seconds));
+}
+
+/// Sends held actions whose undo time passed, or all of them, and ends their sessions
+pub(in crate::network_communication) fn send_held<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    all: bool,
+) {
+    let due = if all {
+        user_session.outgoing.take_all()
+    } else {
+        user_session.outgoing.take_due(std::time::Instant::now())
+    };
+    for held in due {
+        // session may have ended meanwhile, e.g. opponent resigned first
+        let index = match user_session.closing_session(&held.opponent_id) {
+            Some(index) => index,
+            None => continue,
+        };
+        if let Some((topic, message, format)) = held.message {
+            send_direct(swarm, &held.opponent_id, topic, message, format);
+        }
+        match held.action {
+            undo::Action::Resign => {
+                user_interface.print_to_output(OutputEvents::Resigned(held.opponent_id));
+                user_session.end_game(swarm, index, stats::Outcome::Lost);
+            }
+            undo::Action::Withdraw => user_session.finish_session(swarm, index),
+        }
+    }
+}
+
+/// Takes back the latest held action before it was sent
+pub(super) fn undo_action<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    let held = match user_session.outgoing.take_last() {
+        Some(held) => held,
+        None => {
+            user_interface.print_to_output(OutputEvents::NothingToUndo);
+            return;
+        }
+    };
+    let index = match user_session.closing_session(&held.opponent_id) {
+        Some(index) => index,
+        None => {
+            user_interface.print_to_output(OutputEvents::NothingToUndo);
+            return;
+        }
+    };
+    user_session.sessions[index].restore_snapshot(held.snapshot);
+    user_interface.print_to_output(OutputEvents::Undone(held.opponent_id, held.action));
+    if let Some(question) = held.question {
+        ask(user_interface, swarm, user_session, question);
+    }
+}
+
+/// Resigns games which cannot be resumed after restart, then lets network
+/// deliver last messages
+pub(in crate::network_communication) async fn quit<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    // client cannot wait for undo any more
+    send_held(user_interface, swarm, user_session, true);
+    // correspondence games continue after restart
+    if user_session.correspondence.is_none() {
+        let running: Vec<usize> = user_session.sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| session.is_initiated() && session.turn_refusal().map_or(true, |refusal| matches!(refusal, OutputEvents::NotYourTurn(_))))
+            .map(|(index, _)| index)
+            .collect();
+        // sessions may be removed, go from the last one
+        for index in running.into_iter().rev() {
+            resign_game(user_interface, swarm, user_session, index, false);
+        }
+    }
+    flush_swarm(swarm).await;
+}
@@ -0,0 +1,249 @@
+//! # Commands
+//!
+//! Dispatching commands of user to handlers, running registered plugin
+//! commands and sending chat.
+
+use super::closing::{resign_game, undo_action};
+use super::invitations::{answer_prompt, clear_pending, counter_propose, initiate_game, invite_peer};
+use super::peers::{list_peers, use_lobby};
+use super::practice::{drill, print_review_position, set_up, start_review};
+use super::spectators::{referee_game, watch_game};
+use super::summary::banner;
+use super::turns::{make_turn, send_nudge, switch_game};
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::{review_topic, GameSession, UserSession};
+use crate::network_communication::swarm::{connect_found, get_peers, publish, reconnect_known};
+use crate::network_communication::{
+    audit, discovery, input, invite, nat, plugin, protocol, Input, OutputEvents, HISTORY_GAMES,
+};
+
+pub(in crate::network_communication) async fn process_input<UserInt: input::Input<Input, OutputEvents>>(input: Option<Input>, swarm : &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session : &mut UserSession
+, user_interface : &mut UserInt) {
+    if input.is_some() {
+        user_session.last_input = std::time::Instant::now();
+    }
+    match input {
+        Some(Input::ListPeers) => { list_peers::<UserInt>(swarm, user_session, user_interface).await }
+        Some(Input::Turn(x, y, mark)) => { make_turn::<UserInt>(swarm, x, y, mark, user_session, user_interface).await }
+        Some(Input::TurnIn(index, x, y, mark)) => match user_session.sessions.get_index(index) {
+            Some(session) if session.is_initiated() => {
+                user_session.active = index;
+                make_turn::<UserInt>(swarm, x, y, mark, user_session, user_interface).await
+            }
+            _ => user_interface.print_to_output(OutputEvents::NoSuchGame(index)),
+        },
+        Some(Input::InitiateGame(peer_id, password, start_at, message)) => match peer_id.strip_prefix('#') {
+            // open game of lobby is played by its rules
+            Some(number) => match number.parse().ok().and_then(|number| user_session.open_games.get(number)).cloned() {
+                Some(open) => {
+                    if !invite_peer(swarm, open.peer_id.clone(), open.rules, password, start_at, message, user_session) {
+                        user_interface.print_to_output(OutputEvents::AlreadyPlaying(open.peer_id));
+                    }
+                }
+                None => user_interface.print_to_output(OutputEvents::NoSuchOpenGame(peer_id)),
+            },
+            None => {
+                let rules = user_session.rules();
+                if let Some(peer_id) = initiate_game(swarm, peer_id, rules, password, start_at, message, user_session).await {
+                    user_interface.print_to_output(OutputEvents::AlreadyPlaying(peer_id));
+                }
+            }
+        },
+        Some(Input::Lobby(command)) => use_lobby(user_interface, swarm, user_session, command),
+        Some(Input::Setup(command)) => set_up(user_interface, swarm, user_session, command).await,
+        Some(Input::InviteCode(qr)) => {
+            let code = invite::generate(&user_session.user_peer_id.to_string());
+            user_interface.print_to_output(OutputEvents::InviteCode(code, qr));
+        }
+        Some(Input::Join(code, password, start_at, message)) => match invite::parse(&code) {
+            Ok(peer_id) => {
+                let rules = user_session.rules();
+                if !invite_peer(swarm, peer_id.clone(), rules, password, start_at, message, user_session) {
+                    user_interface.print_to_output(OutputEvents::AlreadyPlaying(peer_id));
+                }
+            }
+            Err(error) => user_interface.print_to_output(OutputEvents::InvalidInvite(error.to_string())),
+        },
+        Some(Input::Answer(id, answer)) => answer_prompt(user_interface, swarm, user_session, id, answer),
+        Some(Input::CounterPropose(id, start_at)) => counter_propose(user_interface, swarm, user_session, id, start_at),
+        Some(Input::Schedule) => user_interface.print_to_output(OutputEvents::Schedule(user_session.schedule())),
+        Some(Input::Resign) => {
+            let index = user_session.active;
+            resign_game(user_interface, swarm, user_session, index, true)
+        }
+        Some(Input::Undo) => undo_action(user_interface, swarm, user_session),
+        Some(Input::Nudge) => {
+            let format = user_session.opponent_format(user_session.active);
+            send_nudge(swarm, user_session.game_session(), format)
+        }
+        Some(Input::Chat(text)) => {
+            let format = user_session.opponent_format(user_session.active);
+            let text = user_session.moderator.chat(&text);
+            send_chat(swarm, user_session.game_session(), text, format)
+        }
+        Some(Input::ChatBoard(text)) => {
+            let format = user_session.opponent_format(user_session.active);
+            let game_session = user_session.game_session();
+            let board = crate::theme::emoji_board(&game_session.game().grid());
+            let text = if text.is_empty() { board } else { format!("{}\n{}", text, board) };
+            let text = user_session.moderator.chat(&text);
+            send_chat(swarm, user_session.game_session(), text, format)
+        }
+        Some(Input::ChatLanguage(language)) => {
+            user_session.game_session().language = language.clone();
+            user_interface.print_to_output(OutputEvents::ChatLanguage(language));
+        }
+        Some(Input::Replay(game)) => {
+            let game = game.unwrap_or(user_session.replayed_game);
+            match user_session.replay_store().and_then(|store| store.recent(game)) {
+                Ok(replay) => {
+                    user_session.replayed_game = game;
+                    user_interface.print_to_output(OutputEvents::Replay(game, replay));
+                }
+                Err(error) => user_interface.print_to_output(OutputEvents::ReplayFailed(error.to_string())),
+            }
+        }
+        Some(Input::SendReplay(game, peer)) => {
+            let peer_id = match peer {
+                Some(index) => {
+                    let peers = get_peers(swarm).await;
+                    index.parse::<usize>().ok().and_then(|index| peers.get(index)).map(ToString::to_string)
+                }
+                None => user_session.current_opponent(),
+            };
+            match (user_session.replay_store().and_then(|store| store.recent(game)), peer_id.and_then(|peer| peer.parse().ok())) {
+                (Ok(replay), Some(peer)) => {
+                    let content = serde_json::to_string(&replay).expect("cannot jsonify replay");
+                    swarm.behaviour_mut().send_replay(peer, &content);
+                }
+                (Ok(_), None) => user_interface.print_to_output(OutputEvents::ReplayFailed("no peer to send replay to".to_string())),
+                (Err(error), _) => user_interface.print_to_output(OutputEvents::ReplayFailed(error.to_string())),
+            }
+        }
+        Some(Input::Annotate(move_number, comment)) => {
+            let game = user_session.replayed_game;
+            match user_session.replay_store().and_then(|store| store.annotate(game, move_number, &comment)) {
+                Ok(()) => user_interface.print_to_output(OutputEvents::Annotated(game, move_number)),
+                Err(error) => user_interface.print_to_output(OutputEvents::ReplayFailed(error.to_string())),
+            }
+        }
+        Some(Input::History(count)) => {
+            let games = user_session.stats.games.iter().rev().take(count.unwrap_or(HISTORY_GAMES)).cloned().collect();
+            user_interface.print_to_output(OutputEvents::History(games));
+        }
+        Some(Input::Score) => {
+            let board = user_session.stats.score_board();
+            let current = user_session.current_opponent().map(|opponent| {
+                let score = board.against(&opponent);
+                (opponent, score)
+            });
+            user_interface.print_to_output(OutputEvents::Score(current, board.overall()));
+        }
+        Some(Input::Drill(answer)) => drill(user_session, user_interface, answer),
+        Some(Input::Review) => start_review(swarm, user_session, user_interface),
+        Some(Input::ReviewNavigate(step)) => {
+            if let Some(review) = user_session.review.as_mut().filter(|review| review.is_active()) {
+                let position = review.navigate(step);
+                let topic = review_topic(&user_session.user_peer_id.to_string(), &review.peer_id);
+                publish(swarm, topic, protocol::WireMessage::ReviewGoto { position }, protocol::WireFormat::Tagged);
+                print_review_position(user_interface, review);
+            }
+        }
+        Some(Input::EndReview) => {
+            if let Some(review) = user_session.review.take() {
+                let topic = review_topic(&user_session.user_peer_id.to_string(), &review.peer_id);
+                publish(swarm, topic, protocol::WireMessage::ReviewEnd, protocol::WireFormat::Tagged);
+                user_interface.print_to_output(OutputEvents::ReviewEnded(review.peer_id));
+            }
+        }
+        Some(Input::NetStats) => user_interface.print_to_output(OutputEvents::NetStats(user_session.netstats.snapshot())),
+        Some(Input::NetInfo) => {
+            let observer = &swarm.behaviour().nat;
+            let info = nat::NetInfo {
+                peer_id: user_session.user_peer_id.to_string(),
+                listen_addrs: swarm.listeners().cloned().collect(),
+                observed_addrs: observer.observed(),
+                reachable_addr: observer.reachable(),
+                nat: observer.status(),
+            };
+            user_interface.print_to_output(OutputEvents::NetInfo(info));
+        }
+        Some(Input::WhoAmI) => user_interface.print_to_output(OutputEvents::Banner(banner(swarm, user_session))),
+        Some(Input::Pending) => user_interface.print_to_output(OutputEvents::Pending(user_session.pending())),
+        Some(Input::Clear(id)) => clear_pending(user_interface, swarm, user_session, id),
+        Some(Input::Ladder) => user_interface.print_to_output(OutputEvents::Ladder(user_session.ladder_positions())),
+        Some(Input::Watch(game_id)) => watch_game(user_interface, swarm, user_session, game_id),
+        Some(Input::Referee(game_id)) => referee_game(user_interface, swarm, user_session, game_id),
+        Some(Input::ReconnectKnown) => {
+            let dialed = reconnect_known(swarm, user_session);
+            user_interface.print_to_output(OutputEvents::KnownPeersDialed(dialed));
+        }
+        Some(Input::Connect(peer, address)) => {
+            connect_found(swarm, peer, vec![address.clone()], discovery::DiscoveryMethod::Manual);
+            user_interface.print_to_output(OutputEvents::Dialing(peer.to_string(), address));
+        }
+        Some(Input::AuditExport(game_id)) => {
+            let target = std::path::PathBuf::from(format!("{}.audit.jsonl", audit::file_stem(&game_id)));
+            let exported = swarm.behaviour().audit.as_ref().ok_or(audit::AuditError::Disabled)
+                .and_then(|log| log.export(&game_id, &target));
+            match exported {
+                Ok(entries) => user_interface.print_to_output(OutputEvents::AuditExported(game_id, target, entries)),
+                Err(error) => user_interface.print_to_output(OutputEvents::AuditFailed(error.to_string())),
+            }
+        }
+        Some(Input::ListGames) => { user_interface.print_to_output(OutputEvents::Games(user_session.summaries())) }
+        Some(Input::PendingGames) => { user_interface.print_to_output(OutputEvents::PendingGames(user_session.pending_summaries())) }
+        Some(Input::SwitchGame(index)) => { switch_game(user_session, index, user_interface) }
+        Some(Input::Help) if !user_session.plugins.is_empty() => {
+            user_interface.print_to_output(OutputEvents::PluginHelp(user_session.plugins.help()));
+        }
+        Some(Input::Plugin(words)) => run_plugin(swarm, user_session, user_interface, words),
+        _ => {
+        }
+    }
+}
+
+/// Runs registered command, the handler gets commands out of session while it runs
+fn run_plugin<Output: input::Input<Input, OutputEvents>>(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    user_interface : &mut Output,
+    words: Vec<String>,
+) {
+    let (name, args) = match words.split_first() {
+        Some((name, args)) => (name.clone(), args),
+        None => return,
+    };
+    let mut plugins = std::mem::take(&mut user_session.plugins);
+    let command = match plugins.find_mut(&name) {
+        Some(command) => command,
+        None => {
+            user_session.plugins = plugins;
+            user_interface.print_to_output(OutputEvents::UnknownCommand(name));
+            return;
+        }
+    };
+    let mut client = plugin::GameClient { swarm, session: user_session, output: Vec::new() };
+    let result = command.run(&mut client, args);
+    let output = client.output;
+    user_session.plugins = plugins;
+
+    if !output.is_empty() {
+        user_interface.print_to_output(OutputEvents::PluginOutput(output));
+    }
+    if let Err(error) = result {
+        user_interface.print_to_output(OutputEvents::PluginFailed(name, error));
+    }
+}
+
+/// Sends chat message, older clients do not understand it
+pub(in crate::network_communication) fn send_chat(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    game_session: &GameSession,
+    text: String,
+    format: protocol::WireFormat,
+) {
+    if game_session.is_initiated() && format == protocol::WireFormat::Tagged {
+        publish(swarm, game_session.topic.clone(), protocol::WireMessage::Chat { text }, format);
+    }
+}
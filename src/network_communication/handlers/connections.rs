@@ -0,0 +1,118 @@
+//! # Connections
+//!
+//! Reactions to peers connecting, disconnecting and sending messages out of
+//! any running game.
+
+use super::peers::{leave_lobby, remember_opponent};
+use super::turns::{ask_engine, reject_illegal_turn};
+use crate::network_communication::behaviour::{GameStatus, TicTacToeBehaviour};
+use crate::network_communication::session::UserSession;
+use crate::network_communication::{input, stats, DisconnectPolicy, Input, OutputEvents};
+
+/// Tells player once per peer which features its older version lacks
+pub(super) fn warn_older_peer<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    user_session: &mut UserSession,
+    peer_id: &str,
+    features: &[&'static str],
+) {
+    let missing = user_session.note_missing(peer_id, features);
+    if !missing.is_empty() {
+        user_interface.print_to_output(OutputEvents::OlderPeer(peer_id.to_string(), missing));
+    }
+}
+
+/// Applies message to session which is not shown, only notice is printed
+pub(super) fn resolve_background_message<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    sender: String,
+    status: GameStatus,
+) {
+    let game_session = &mut user_session.sessions[index];
+    match status {
+        GameStatus::Turn(x, y, sent_at, mark, _, auto) => {
+            if let Err(reason) = game_session.make_opponent_turn(x, y, sent_at, mark) {
+                reject_illegal_turn(user_interface, swarm, user_session, index, sender, (x, y), reason);
+                return;
+            }
+            if auto {
+                user_interface.print_to_output(OutputEvents::OpponentAutoMoved(sender.clone()));
+            }
+            user_interface.print_to_output(OutputEvents::BoardChanged(index, sender));
+            user_session.notify_move(index);
+            if user_session.sessions[index].game().is_opponent_winner() {
+                user_session.end_game(swarm, index, stats::Outcome::Lost);
+            } else if user_session.sessions[index].game().is_draw() {
+                user_interface.print_to_output(OutputEvents::Draw(user_session.sessions[index].opponent_id.clone()));
+                user_session.end_game(swarm, index, stats::Outcome::Drawn);
+            } else {
+                user_session.save_games();
+                ask_engine(user_session, index);
+            }
+        }
+        GameStatus::Start(true) => {
+            game_session.invited_at = None;
+            game_session.start_turn_clock();
+            if let Some(start_at) = game_session.start_at {
+                user_interface.print_to_output(OutputEvents::ScheduleAgreed(sender.clone(), start_at));
+            }
+            remember_opponent(swarm, user_session, &sender);
+            leave_lobby(swarm, user_session);
+        }
+        GameStatus::Start(false) => user_session.finish_session(swarm, index),
+        GameStatus::Forfeit if game_session.is_initiated() => {
+            user_interface.print_to_output(OutputEvents::OpponentTimeout(sender));
+            user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+        }
+        GameStatus::Resign if game_session.is_initiated() => {
+            user_interface.print_to_output(OutputEvents::OpponentResigned(sender));
+            user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+        }
+        GameStatus::Nudge => user_interface.print_to_output(OutputEvents::BoardChanged(index, sender)),
+        GameStatus::Withdrawn => {
+            user_interface.print_to_output(OutputEvents::InvitationWithdrawn(sender));
+            user_session.finish_session(swarm, index);
+        }
+        _ => {}
+    }
+}
+
+/// Applies disconnect policy when opponent of session leaves or returns
+pub(super) fn resolve_connection_change<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    status: GameStatus,
+) {
+    let policy = user_session.disconnect_policy();
+    let grace = user_session.settings.forfeit_grace_secs;
+    let game_session = &mut user_session.sessions[index];
+    let opponent_id = game_session.opponent_id.clone();
+
+    match (status, policy) {
+        (GameStatus::PeerFound, _) => {
+            if game_session.disconnected_at.take().is_some() {
+                // time opponent was away does not count
+                game_session.start_turn_clock();
+                user_interface.print_to_output(OutputEvents::OpponentReturned(opponent_id));
+            }
+        }
+        (_, _) if game_session.disconnected_at.is_some() => {}
+        (_, DisconnectPolicy::Void) => {
+            user_interface.print_to_output(OutputEvents::GameVoided(opponent_id));
+            user_session.end_game(swarm, index, stats::Outcome::Voided);
+        }
+        (_, DisconnectPolicy::Forfeit) => {
+            game_session.disconnected_at = Some(std::time::Instant::now());
+            user_interface.print_to_output(OutputEvents::ForfeitPending(opponent_id, grace));
+        }
+        (_, DisconnectPolicy::Adjourn) => {
+            game_session.disconnected_at = Some(std::time::Instant::now());
+            user_interface.print_to_output(OutputEvents::OpponentLeft(opponent_id));
+        }
+    }
+}
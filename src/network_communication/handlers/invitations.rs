@@ -0,0 +1,426 @@
+//! # Invitations
+//!
+//! Inviting peers, prompts asking me about their invitations, my answers,
+//! counter-proposals and withdrawing invitations.
+
+use super::closing::hold_action;
+use super::peers::{leave_lobby, remember_opponent};
+use super::practice::start_review;
+#[cfg(feature = "ai")]
+use crate::ai;
+#[cfg(feature = "ai")]
+use crate::network_communication::behaviour::PeerMessage;
+use crate::network_communication::behaviour::{GameStatus, TicTacToeBehaviour};
+use crate::network_communication::session::{game_topic, review_topic, GameSession, UserSession};
+use crate::network_communication::swarm::{get_peers, publish, send_direct};
+use crate::network_communication::{auth, chat, compression, input, pending, prompt, protocol, review, seal, undo, Input, OutputEvents, BOT_ID};
+use crate::tictactoe;
+
+/// Tells invited peer that my invitation no longer stands and drops its session
+pub(super) fn withdraw_invitation(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession, index: usize) {
+    let format = user_session.opponent_format(index);
+    let game_session = &user_session.sessions[index];
+    publish(swarm, game_session.topic.clone(), protocol::WireMessage::Withdrawn, format);
+    user_session.finish_session(swarm, index);
+}
+
+/// Drops pending item, queued turn is taken back in its game and open question is declined
+pub(super) fn clear_pending<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    id: pending::PendingId,
+) {
+    let cleared = match id {
+        pending::PendingId::Invitation(index) => match user_session.sessions.get_index(index) {
+            Some(session) if session.invited_at.is_some() && user_session.settings.undo_secs > 0 => {
+                let format = user_session.opponent_format(index);
+                let message = Some((session.topic.clone(), protocol::WireMessage::Withdrawn, format));
+                hold_action(user_interface, user_session, index, undo::Action::Withdraw, message, None);
+                true
+            }
+            Some(session) if session.invited_at.is_some() => {
+                withdraw_invitation(swarm, user_session, index);
+                true
+            }
+            _ => false,
+        },
+        pending::PendingId::Queued(position) => {
+            let unqueued = user_session.correspondence.as_ref().map(|store| store.unqueue(position)).transpose();
+            match unqueued {
+                Ok(Some(Some(entry))) => {
+                    let index = user_session.session_of(&entry.opponent_id);
+                    let size = index.map_or(crate::coords::SIZE, |index| user_session.sessions[index].game().board_size().size);
+                    let turn = match protocol::decode(entry.payload.as_bytes()) {
+                        Some((protocol::WireMessage::Turn { x, y, .. }, _)) => protocol::from_wire(x, y, size),
+                        _ => None,
+                    };
+                    if let (Some(index), Some(field)) = (index, turn) {
+                        if user_session.sessions[index].take_back_my_turn(field) {
+                            user_session.save_games();
+                        }
+                    }
+                    true
+                }
+                Ok(_) => false,
+                Err(error) => {
+                    user_interface.print_to_output(OutputEvents::Error(format!("Cannot read outbox: {}", error)));
+                    false
+                }
+            }
+        }
+        pending::PendingId::Prompt(prompt_id) if user_session.prompts.get(prompt_id).is_some() => {
+            answer_prompt(user_interface, swarm, user_session, prompt_id, prompt::Answer::No);
+            true
+        }
+        pending::PendingId::Prompt(_) => false,
+    };
+    if cleared {
+        user_interface.print_to_output(OutputEvents::Cleared(id));
+    } else {
+        user_interface.print_to_output(OutputEvents::NoSuchPending(id));
+    }
+}
+
+/// Asks frontend question, answer given right away is applied at once
+pub(super) fn ask<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    question: prompt::Question,
+) {
+    let prompt = user_session.prompts.open(question);
+    let id = prompt.id;
+    if let Some(answer) = user_interface.ask(prompt) {
+        answer_prompt(user_interface, swarm, user_session, id, answer);
+    }
+}
+
+/// Applies answer to the question it was given to, declined invitation waits in
+/// outgoing queue for undo first
+pub(super) fn answer_prompt<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    id: u64,
+    answer: prompt::Answer,
+) {
+    let accept = answer == prompt::Answer::Yes;
+    match user_session.prompts.take(id) {
+        Some(prompt::Question::Invitation(peer_id, proposal)) => match user_session.session_of(&peer_id) {
+            Some(index) => {
+                let format = user_session.opponent_format(index);
+                if !accept && user_session.settings.undo_secs > 0 {
+                    let topic = user_session.sessions[index].topic.clone();
+                    let message = protocol::WireMessage::Answer { accept: false, compression: Vec::new() };
+                    let question = prompt::Question::Invitation(peer_id, proposal);
+                    hold_action(user_interface, user_session, index, undo::Action::Withdraw, Some((topic, message, format)), Some(question));
+                    return;
+                }
+                let game_session = &mut user_session.sessions[index];
+                game_session.awaiting_answer = false;
+                send_answer(swarm, game_session, accept, format);
+                if accept {
+                    game_session.start_turn_clock();
+                    user_session.save_games();
+                    remember_opponent(swarm, user_session, &peer_id);
+                    leave_lobby(swarm, user_session);
+                } else {
+                    user_session.finish_session(swarm, index);
+                }
+            }
+            None => user_interface.print_to_output(OutputEvents::StaleAnswer),
+        },
+        Some(prompt::Question::Reschedule(peer_id, start_at)) => match user_session.session_of(&peer_id) {
+            Some(index) => {
+                let format = user_session.opponent_format(index);
+                let game_session = &mut user_session.sessions[index];
+                if !accept {
+                    let refusal = if game_session.is_initiator() {
+                        protocol::WireMessage::Withdrawn
+                    } else {
+                        protocol::WireMessage::Answer { accept: false, compression: Vec::new() }
+                    };
+                    let topic = game_session.topic.clone();
+                    if user_session.settings.undo_secs > 0 {
+                        let question = prompt::Question::Reschedule(peer_id, start_at);
+                        hold_action(user_interface, user_session, index, undo::Action::Withdraw, Some((topic, refusal, format)), Some(question));
+                    } else {
+                        send_direct(swarm, &peer_id, topic, refusal, format);
+                        user_session.finish_session(swarm, index);
+                    }
+                    return;
+                }
+                // time sent back means agreement, invitee accepts the game with it
+                game_session.start_at = Some(start_at);
+                publish(swarm, game_session.topic.clone(), protocol::WireMessage::Reschedule { start_at }, format);
+                if !game_session.is_initiator() {
+                    game_session.invited_at = None;
+                    send_answer(swarm, game_session, true, format);
+                }
+                user_session.save_games();
+            }
+            None => user_interface.print_to_output(OutputEvents::StaleAnswer),
+        },
+        Some(prompt::Question::Review(peer_id)) => {
+            let proposed = user_session.review.as_ref().is_some_and(|review| {
+                review.peer_id == peer_id && review.state == review::ReviewState::ProposedByPeer
+            });
+            match (proposed, accept) {
+                (true, true) => start_review(swarm, user_session, user_interface),
+                (true, false) => {
+                    user_session.review = None;
+                    let topic = review_topic(&user_session.user_peer_id.to_string(), &peer_id);
+                    publish(swarm, topic, protocol::WireMessage::ReviewAnswer { accept: false }, protocol::WireFormat::Tagged);
+                    user_interface.print_to_output(OutputEvents::ReviewEnded(peer_id));
+                }
+                (false, _) => user_interface.print_to_output(OutputEvents::StaleAnswer),
+            }
+        }
+        None => user_interface.print_to_output(OutputEvents::StaleAnswer),
+    }
+}
+
+/// Answers invitation by proposing other start time, invitation stays open until peer
+/// agrees by sending the time back
+pub(super) fn counter_propose<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    id: u64,
+    start_at: u64,
+) {
+    // other questions have no time to propose, they stay open
+    let peer_id = match user_session.prompts.get(id) {
+        Some(prompt::Question::Invitation(peer_id, ..)) => peer_id.clone(),
+        _ => {
+            user_interface.print_to_output(OutputEvents::StaleAnswer);
+            return;
+        }
+    };
+    user_session.prompts.take(id);
+    let index = match user_session.session_of(&peer_id) {
+        Some(index) => index,
+        None => {
+            user_interface.print_to_output(OutputEvents::StaleAnswer);
+            return;
+        }
+    };
+    let format = user_session.opponent_format(index);
+    let game_session = &mut user_session.sessions[index];
+    game_session.awaiting_answer = false;
+    game_session.invited_at = Some(std::time::Instant::now());
+    game_session.start_at = Some(start_at);
+    publish(swarm, game_session.topic.clone(), protocol::WireMessage::Reschedule { start_at }, format);
+    user_interface.print_to_output(OutputEvents::CounterProposed(peer_id, start_at));
+}
+
+/// Applies start time proposed by peer, the one I proposed coming back means they agree
+pub(super) fn resolve_reschedule<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    sender: String,
+    start_at: u64,
+) {
+    let format = user_session.opponent_format(index);
+    let game_session = &mut user_session.sessions[index];
+    if !game_session.is_initiated() || game_session.opponent_id != sender {
+        return;
+    }
+    if game_session.start_at != Some(start_at) {
+        // peer is still thinking about it, invitation does not expire meanwhile
+        if game_session.invited_at.is_some() {
+            game_session.invited_at = Some(std::time::Instant::now());
+        }
+        ask(user_interface, swarm, user_session, prompt::Question::Reschedule(sender, start_at));
+        return;
+    }
+    if !game_session.is_initiator() && game_session.invited_at.take().is_some() {
+        send_answer(swarm, game_session, true, format);
+        user_session.save_games();
+        user_interface.print_to_output(OutputEvents::ScheduleAgreed(sender, start_at));
+    }
+}
+
+/// Starts game proposed by peer in given session and asks me whether I accept it
+pub(super) fn receive_invitation<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    sender: String,
+    status: GameStatus,
+) {
+    let user_peer_id = user_session.user_peer_id.to_string();
+    let (rules, nonce, start_at, mut introduction) = match status {
+        GameStatus::Init(receiver_id, _, rules, nonce, start_at, introduction) if receiver_id == user_peer_id => {
+            (rules, nonce, start_at, introduction)
+        }
+        _ => return,
+    };
+    let game_session = &mut user_session.sessions[index];
+    game_session.initiate(sender.clone(), false, &user_peer_id, rules, nonce);
+    game_session.awaiting_answer = true;
+    game_session.start_at = start_at;
+    swarm.behaviour_mut().join_game(game_session);
+    // attached message goes through the same hooks as chat
+    let language = user_session.settings.chat_language.clone();
+    introduction.message = introduction.message.map(|message| {
+        let message = user_session.moderator.chat(&message);
+        chat::process(&user_session.chat_hooks, message, language.as_deref())
+    });
+    introduction.nickname = introduction.nickname.map(|nickname| user_session.moderator.nickname(&nickname));
+    let lookalike = introduction.nickname.as_deref().and_then(|nickname| user_session.lookalike(&sender, nickname));
+    let reputation = user_session.stats.reputation(&sender);
+    let proposal = prompt::Proposal { rules, start_at, introduction, reputation, lookalike };
+    ask(user_interface, swarm, user_session, prompt::Question::Invitation(sender, proposal));
+}
+
+/// Auto accepts invitation in simul mode while there is a free board
+pub(super) fn accept_simul_invitation<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    sender: String,
+    rules: tictactoe::Rules,
+    nonce: Option<String>,
+) {
+    let user_peer_id = user_session.user_peer_id.to_string();
+    let format = user_session.wire_format(&sender);
+    let free = if user_session.is_simul_full() { None } else { user_session.free_session(&sender) };
+    match free {
+        Some(index) => {
+            let game_session = &mut user_session.sessions[index];
+            game_session.initiate(sender.clone(), false, &user_peer_id, rules, nonce);
+            swarm.behaviour_mut().join_game(game_session);
+            send_answer(swarm, game_session, true, format);
+            remember_opponent(swarm, user_session, &sender);
+            user_interface.print_to_output(OutputEvents::SimulAccepted(index, sender));
+        }
+        None => {
+            decline_invitation(swarm, user_session, &sender);
+            user_interface.print_to_output(OutputEvents::SimulFull(sender));
+        }
+    }
+}
+
+/// Declines invitation from peer without creating a session
+pub(super) fn decline_invitation(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &UserSession,
+    sender: &str,
+) {
+    let topic = game_topic(sender, &user_session.user_peer_id.to_string());
+    let format = user_session.wire_format(sender);
+    send_direct(swarm, sender, topic, protocol::WireMessage::Answer { accept: false, compression: Vec::new() }, format);
+}
+
+fn send_answer(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    game_session: &GameSession,
+    answer: bool,
+    format: protocol::WireFormat,
+) {
+    if game_session.is_initiated() {
+        let message = protocol::WireMessage::Answer { accept: answer, compression: compression::supported() };
+        send_direct(swarm, &game_session.opponent_id, game_session.topic.clone(), message, format);
+    } else {
+        //Output::print_string("Unknown command");
+    }
+}
+
+/// Invites peer or bot to game, returns peer when I already play them
+pub(super) async fn initiate_game(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    peerId: String,
+    rules: tictactoe::Rules,
+    password: Option<String>,
+    start_at: Option<u64>,
+    message: Option<String>,
+    user_session: &mut UserSession,
+) -> Option<String> {
+
+            if peerId == BOT_ID {
+                return (!start_bot_game(user_session)).then_some(peerId);
+            }
+            // peer is given by index in peer list, or by its id, e.g. clicked in frontend
+            let peers = get_peers(swarm).await;
+            let receiver_peer_id = match peerId.parse::<usize>() {
+                Ok(index) => peers.get(index).map(|peer| peer.to_string()),
+                Err(_) => peers.iter().map(|peer| peer.to_string()).find(|peer| *peer == peerId),
+            };
+            let receiver_peer_id = receiver_peer_id?;
+            (!invite_peer(swarm, receiver_peer_id.clone(), rules, password, start_at, message, user_session)).then_some(receiver_peer_id)
+}
+
+/// Starts game against bot, it accepts and answers turns over internal channel as peer
+/// would over network, so the rest of game loop does not tell them apart.
+/// Returns false when bot game is already running.
+#[cfg(feature = "ai")]
+fn start_bot_game(user_session: &mut UserSession) -> bool {
+    let user_peer_id = user_session.user_peer_id.to_string();
+    // bot searches classic playmat only
+    let rules = tictactoe::Rules { board: None, ..user_session.rules() };
+    let index = match user_session.free_session(BOT_ID) {
+        Some(index) => index,
+        None => return false,
+    };
+    user_session.active = index;
+    let game_session = &mut user_session.sessions[index];
+    game_session.initiate(BOT_ID.to_string(), true, &user_peer_id, rules, None);
+    game_session.bot = Some(ai::BotPlayer::new(game_session.game().marks().swapped(), rules));
+    let accepted = PeerMessage::about(BOT_ID.to_string(), GameStatus::Start(true));
+    let _ = game_session.internal_sender.send(accepted);
+    true
+}
+
+#[cfg(not(feature = "ai"))]
+fn start_bot_game(user_session: &mut UserSession) -> bool {
+    user_session.report("Playing against bot needs the ai feature.".to_string());
+    true
+}
+
+/// Sends game proposal to given peer in the lobby in new session, which becomes
+/// the active one. Returns false when I already play the peer.
+pub(in crate::network_communication) fn invite_peer(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    receiver_peer_id: String,
+    rules: tictactoe::Rules,
+    password: Option<String>,
+    start_at: Option<u64>,
+    message: Option<String>,
+    user_session: &mut UserSession,
+) -> bool {
+    let index = match user_session.free_session(&receiver_peer_id) {
+        Some(index) => index,
+        None => return false,
+    };
+    user_session.active = index;
+    let user_peer_id = user_session.user_peer_id.to_string();
+    let format = user_session.wire_format(&receiver_peer_id);
+    // legacy clients do not seal their messages, their games go without nonce
+    let nonce = (format == protocol::WireFormat::Tagged).then(|| seal::new_nonce(&user_peer_id));
+    let introduction = protocol::Introduction {
+        nickname: user_session.own_nickname(),
+        record: Some(user_session.stats.tally()),
+        message: message.map(|message| user_session.moderator.chat(&message)),
+    };
+    let req = protocol::WireMessage::Propose {
+        sender: receiver_peer_id.clone(),
+        credentials: password.map(|password| auth::sign(&password, &user_peer_id)),
+        rules,
+        nonce: nonce.clone(),
+        start_at,
+        introduction,
+        compression: compression::supported(),
+    };
+    let lobby = user_session.lobby.clone();
+    let game_session = &mut user_session.sessions[index];
+    game_session.initiate(receiver_peer_id.clone(), true, &user_peer_id, rules, nonce);
+    game_session.start_at = start_at;
+    swarm.behaviour_mut().join_game(game_session);
+    send_direct(swarm, &receiver_peer_id, lobby, req, format);
+    true
+}
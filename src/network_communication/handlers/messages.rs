@@ -0,0 +1,375 @@
+//! # Messages
+//!
+//! Dispatching messages of peers and of spawned tasks to handlers.
+
+use super::connections::{resolve_background_message, resolve_connection_change, warn_older_peer};
+use super::invitations::{accept_simul_invitation, decline_invitation, receive_invitation, resolve_reschedule};
+use super::peers::{announce_open_game, check_lobby, leave_lobby, remember_opponent};
+use super::practice::resolve_review_message;
+use super::spectators::{gossip_ladder, resolve_refereed, resolve_spectated, resolve_verdict};
+use super::timers::{check_forfeits, check_hints, check_invitations, check_reminders, check_schedule};
+use super::turns::{
+    ask_engine, evaluate_if, flush_outbox, play_engine_move, reject_illegal_turn, resolve_clock_message, resolve_opponent_turn, resolve_race,
+    resume_game, send_ping, send_resume,
+};
+use crate::network_communication::behaviour::{GameStatus, PeerMessage, TicTacToeBehaviour};
+use crate::network_communication::session::UserSession;
+use crate::network_communication::swarm::connect_found;
+use crate::network_communication::{
+    audit, chat, clock, input, lobby, observer, protocol, stats, validation, Input, OutputEvents,
+};
+
+pub(in crate::network_communication) fn resolve_spawned_messages<Output: input::Input<Input, OutputEvents> + observer::SwarmObserver>(
+    user_interface : &mut Output,
+    message: PeerMessage,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    let PeerMessage { sender, status, format } = message;
+    if let Some(format) = format {
+        user_session.note_format(&sender, format);
+        if format == protocol::WireFormat::Legacy {
+            warn_older_peer(user_interface, user_session, &sender, protocol::LEGACY_MISSING);
+        }
+    }
+
+    if let GameStatus::Invalid(validation::InvalidMessage::UnsupportedVersion(version), _) = status {
+        // newer client is not misbehaving, it is not counted against reputation
+        user_interface.print_to_output(OutputEvents::NewerPeer(sender, version));
+        return;
+    }
+
+    if let GameStatus::Invalid(error, diagnostics) = status {
+        user_session.stats.record_violation(&sender);
+        user_session.save_stats();
+        user_interface.print_to_output(OutputEvents::Diagnostics(sender, error, diagnostics));
+        return;
+    }
+
+    if let GameStatus::ReminderTick = status {
+        let settings = user_session.settings.clone();
+        check_reminders::<Output>(user_interface, user_session.game_session(), &settings);
+        check_forfeits(user_interface, swarm, user_session);
+        check_invitations(user_interface, swarm, user_session);
+        check_schedule(user_interface, user_session);
+        check_hints(user_interface, swarm, user_session);
+        check_lobby(swarm, user_session);
+        return;
+    }
+
+    if let GameStatus::PeerLost | GameStatus::PeerFound = status {
+        if let GameStatus::PeerFound = status {
+            user_interface.on_peer_discovered(&sender);
+        } else {
+            user_interface.on_peer_lost(&sender);
+        }
+        if let Some(index) = user_session.session_of(&sender) {
+            resolve_connection_change(user_interface, swarm, user_session, index, status);
+        }
+        return;
+    }
+
+    if let GameStatus::Discovered(method, addresses) = status {
+        if let Ok(peer) = sender.parse() {
+            connect_found(swarm, peer, addresses, method);
+        }
+        return;
+    }
+
+    if let GameStatus::NatStatus(nat) = status {
+        user_interface.print_to_output(OutputEvents::NatStatus(nat));
+        return;
+    }
+
+    if let GameStatus::TopicJoined = status {
+        flush_outbox(swarm, user_session, &sender);
+        if let Some(index) = user_session.session_of(&sender).filter(|index| user_session.sessions[*index].resuming) {
+            user_session.sessions[index].resuming = false;
+            send_resume(swarm, user_session, index);
+        }
+        return;
+    }
+
+    if let GameStatus::Attested(attestation) = status {
+        if user_session.add_result(attestation.clone()) {
+            user_interface.print_to_output(OutputEvents::Attested(attestation));
+        }
+        return;
+    }
+
+    if let GameStatus::LadderJoined = status {
+        gossip_ladder(swarm, user_session);
+        return;
+    }
+
+    if let GameStatus::Lobby(message) = status {
+        let message = match message {
+            lobby::LobbyMessage::Seeking { rules, nickname, ttl_secs } => {
+                let nickname = nickname.map(|nickname| user_session.moderator.nickname(&nickname));
+                lobby::LobbyMessage::Seeking { rules, nickname, ttl_secs }
+            }
+            message => message,
+        };
+        user_session.open_games.receive(&sender, message, std::time::Instant::now());
+        return;
+    }
+
+    if let GameStatus::LobbyJoined = status {
+        if user_session.seeking.is_some() {
+            announce_open_game(swarm, user_session);
+        }
+        return;
+    }
+
+    if let GameStatus::Undelivered(reason) = status {
+        user_interface.print_to_output(OutputEvents::Undelivered(sender, reason));
+        return;
+    }
+
+    if let GameStatus::HolePunch(result) = status {
+        user_interface.print_to_output(OutputEvents::HolePunch(sender, result));
+        return;
+    }
+
+    if let GameStatus::OlderPeer(features) = status {
+        warn_older_peer(user_interface, user_session, &sender, features);
+        return;
+    }
+
+    if let GameStatus::ReplayProgress(sent, total) = status {
+        user_interface.print_to_output(OutputEvents::ReplayProgress(sender, sent, total));
+        return;
+    }
+
+    if let GameStatus::ReplayReceived(replay) = status {
+        // saved in the same form as line of replay file
+        let finished_at = replay.finished_at.unwrap_or_else(clock::now_millis);
+        let target = std::path::PathBuf::from(format!("replay-{}-{}.json", audit::file_stem(&sender), finished_at));
+        let line = serde_json::to_string(&replay).expect("cannot jsonify replay");
+        match std::fs::write(&target, line) {
+            Ok(()) => user_interface.print_to_output(OutputEvents::ReplayReceived(sender, target, replay)),
+            Err(error) => user_interface.print_to_output(OutputEvents::ReplayTransferFailed(sender, error.to_string())),
+        }
+        return;
+    }
+
+    if let GameStatus::ReplayTransferFailed(reason) = status {
+        user_interface.print_to_output(OutputEvents::ReplayTransferFailed(sender, reason));
+        return;
+    }
+
+    if let GameStatus::Spectated(game_id, rules, moves) = status {
+        resolve_refereed(user_interface, swarm, user_session, &sender, &game_id, rules, &moves);
+        resolve_spectated(user_interface, swarm, user_session, &sender, game_id, rules, moves);
+        return;
+    }
+
+    if let GameStatus::Verdict(verdict) = status {
+        resolve_verdict(user_interface, user_session, &sender, verdict);
+        return;
+    }
+
+    if let GameStatus::Resume(moves) = status {
+        if let Some(index) = user_session.session_of(&sender) {
+            resume_game(swarm, user_session, index, moves);
+        }
+        return;
+    }
+
+    if let GameStatus::EngineFailed(error) = status {
+        user_interface.print_to_output(OutputEvents::EngineError(error));
+        return;
+    }
+
+    if let GameStatus::Ping(..) | GameStatus::Pong(..) = status {
+        if let Some(index) = user_session.session_of(&sender) {
+            resolve_clock_message(user_interface, swarm, user_session, index, status);
+        }
+        return;
+    }
+
+    if let GameStatus::Chat(text) = status {
+        if let Some(index) = user_session.session_of(&sender) {
+            let session_language = user_session.sessions[index].language.as_deref();
+            let language = session_language.or(user_session.settings.chat_language.as_deref());
+            let text = user_session.moderator.chat(&text);
+            let text = chat::process(&user_session.chat_hooks, text, language);
+            user_interface.print_to_output(OutputEvents::Chat(sender, text));
+        }
+        return;
+    }
+
+    if let GameStatus::Review(message) = status {
+        resolve_review_message(user_interface, swarm, user_session, sender, message);
+        return;
+    }
+
+    if let GameStatus::EngineMove(x, y, mark) = status {
+        if let Some(index) = user_session.session_of(&sender).filter(|index| user_session.sessions[*index].is_your_turn()) {
+            play_engine_move(user_interface, swarm, user_session, index, x, y, mark);
+        }
+        return;
+    }
+
+    let user_peer_id = user_session.user_peer_id.to_string();
+    let index = match (user_session.session_of(&sender), &status) {
+        (Some(index), _) => index,
+        (None, GameStatus::Init(receiver_id, ..)) if *receiver_id != user_peer_id => return,
+        (None, GameStatus::Init(_, credentials, ..)) if !user_session.admits(&sender, credentials.as_ref()) => {
+            decline_invitation(swarm, user_session, &sender);
+            user_interface.print_to_output(OutputEvents::WrongPassword(sender));
+            return;
+        }
+        (None, GameStatus::Init(..)) if user_session.is_disreputable(&sender) => {
+            decline_invitation(swarm, user_session, &sender);
+            let reputation = user_session.stats.reputation(&sender);
+            user_interface.print_to_output(OutputEvents::InvitationDeclined(sender, reputation));
+            return;
+        }
+        (None, GameStatus::Init(_, _, rules, ..)) if !user_session.variants.contains(rules.variant.name()) => {
+            let variant = rules.variant.name().to_string();
+            decline_invitation(swarm, user_session, &sender);
+            user_interface.print_to_output(OutputEvents::UnsupportedVariant(sender, variant));
+            return;
+        }
+        // larger playmats are played by clients with gomoku enabled
+        (None, GameStatus::Init(_, _, rules, ..)) if rules.board.is_some() && !user_session.variants.contains(crate::gomoku::NAME) => {
+            let board = rules.board.map(|board| board.to_string()).unwrap_or_default();
+            decline_invitation(swarm, user_session, &sender);
+            user_interface.print_to_output(OutputEvents::UnsupportedVariant(sender, board));
+            return;
+        }
+        (None, GameStatus::Init(_, _, rules, nonce, ..)) if user_session.is_simul() => {
+            let (rules, nonce) = (*rules, nonce.clone());
+            accept_simul_invitation(user_interface, swarm, user_session, sender, rules, nonce);
+            return;
+        }
+        // invitation gets its own session, running games go on
+        (None, GameStatus::Init(..)) => match user_session.free_session(&sender) {
+            Some(index) => index,
+            None => return,
+        },
+        // once game starts, only opponent can influence the session
+        (None, _) if user_session.is_playing() => {
+            user_interface.print_to_output(OutputEvents::Ignored(sender.clone(), status.kind()));
+            user_session.stats.record_violation(&sender);
+            user_session.save_stats();
+            if user_session.settings.security_warnings {
+                user_interface.print_to_output(OutputEvents::SecurityWarning(sender));
+            }
+            return;
+        }
+        (None, _) => user_session.active,
+    };
+
+    if let GameStatus::Reschedule(start_at) = status {
+        resolve_reschedule(user_interface, swarm, user_session, index, sender, start_at);
+        return;
+    }
+
+    if let GameStatus::Turn(.., Some(number), _) = status {
+        if user_session.sessions[index].is_race(number) {
+            resolve_race(user_interface, swarm, user_session, index);
+            return;
+        }
+    }
+
+    if let GameStatus::InvalidMove(field, reason) = status {
+        user_interface.print_to_output(OutputEvents::MoveRejected(sender, field, reason));
+        return;
+    }
+
+    if let GameStatus::Init(..) = status {
+        receive_invitation(user_interface, swarm, user_session, index, sender, status);
+        return;
+    }
+
+    if index != user_session.active {
+        resolve_background_message(user_interface, swarm, user_session, index, sender, status);
+        return;
+    }
+
+    let eval_bar = user_session.settings.eval_bar;
+    let format = user_session.opponent_format(index);
+    let game_session = user_session.game_session();
+    match status {
+        GameStatus::Start(true) => {
+            game_session.invited_at = None;
+            // time waiting for answer does not count into the first turn
+            game_session.start_turn_clock();
+            if let Some(start_at) = game_session.start_at {
+                user_interface.print_to_output(OutputEvents::ScheduleAgreed(sender.clone(), start_at));
+            }
+            send_ping(swarm, game_session, format);
+            let evaluation = evaluate_if(eval_bar, game_session.game(), true);
+            user_interface.print_to_output(OutputEvents::StartTrue(game_session.game().grid(), evaluation));
+            ask_engine(user_session, index);
+            remember_opponent(swarm, user_session, &sender);
+            leave_lobby(swarm, user_session);
+        }
+        GameStatus::Start(false) => {
+            user_interface.print_to_output(OutputEvents::StartFalse);
+            user_session.finish_session(swarm, index);
+        }
+        GameStatus::Turn(x, y, sent_at, mark, _, auto) => match resolve_opponent_turn::<Output>(x, y, sent_at, mark, game_session, user_interface, eval_bar) {
+            Ok(outcome) => {
+                if auto {
+                    user_interface.print_to_output(OutputEvents::OpponentAutoMoved(sender));
+                }
+                user_session.notify_move(index);
+                match outcome {
+                    Some(outcome) => user_session.end_game(swarm, index, outcome),
+                    None => {
+                        user_session.save_games();
+                        ask_engine(user_session, index);
+                    }
+                }
+            }
+            Err(reason) => reject_illegal_turn(user_interface, swarm, user_session, index, sender, (x, y), reason),
+        },
+        GameStatus::Nudge => user_interface.print_to_output(OutputEvents::Nudged(sender)),
+        GameStatus::Withdrawn => {
+            user_interface.print_to_output(OutputEvents::InvitationWithdrawn(sender));
+            user_session.finish_session(swarm, index);
+        }
+        GameStatus::Forfeit if game_session.is_initiated() => {
+            user_interface.print_to_output(OutputEvents::OpponentTimeout(sender));
+            user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+        }
+        GameStatus::Resign if game_session.is_initiated() => {
+            user_interface.print_to_output(OutputEvents::OpponentResigned(sender));
+            user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+        }
+        GameStatus::Init(..)
+        | GameStatus::Invalid(..)
+        | GameStatus::ReminderTick
+        | GameStatus::PeerLost
+        | GameStatus::PeerFound
+        | GameStatus::TopicJoined
+        | GameStatus::Resume(_)
+        | GameStatus::Reschedule(_)
+        | GameStatus::Forfeit
+        | GameStatus::Resign
+        | GameStatus::Discovered(..)
+        | GameStatus::NatStatus(_)
+        | GameStatus::Ping(..)
+        | GameStatus::Pong(..)
+        | GameStatus::Chat(..)
+        | GameStatus::Review(..)
+        | GameStatus::EngineMove(..)
+        | GameStatus::EngineFailed(..)
+        | GameStatus::Attested(_)
+        | GameStatus::LadderJoined
+        | GameStatus::Lobby(_)
+        | GameStatus::LobbyJoined
+        | GameStatus::Undelivered(_)
+        | GameStatus::HolePunch(_)
+        | GameStatus::OlderPeer(_)
+        | GameStatus::ReplayProgress(..)
+        | GameStatus::ReplayReceived(_)
+        | GameStatus::ReplayTransferFailed(_)
+        | GameStatus::Spectated(..)
+        | GameStatus::Verdict(_)
+        | GameStatus::InvalidMove(..) => {}
+    };
+}
@@ -0,0 +1,94 @@
+//! # Peers
+//!
+//! Peers I can play with: listing them, announcing and withdrawing my open game
+//! and remembering opponents once their game starts.
+
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::UserSession;
+use crate::network_communication::swarm::get_peers;
+use crate::network_communication::{input, lobby, Input, LobbyCommand, OutputEvents, PeerSummary};
+use itertools::Itertools;
+
+pub(super) async fn list_peers<Output: input::Input<Input, OutputEvents>>(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &UserSession,
+    user_interface : &mut Output,
+) {
+    let last_seen = swarm.behaviour().last_seen.clone();
+    let found_by = swarm.behaviour().found_by.clone();
+    let peers = get_peers(swarm).await
+        .iter()
+        .map(|peerId| PeerSummary {
+            peer_id: peerId.to_string(),
+            reputation: user_session.stats.reputation(&peerId.to_string()),
+            seen_secs_ago: last_seen.get(*peerId).map(|seen| seen.elapsed().as_secs()),
+            discovered_by: found_by.get(*peerId).map(|methods| methods.iter().copied().collect()).unwrap_or_default(),
+        })
+        .collect_vec();
+    user_interface.print_to_output(OutputEvents::ListPeers(peers));
+    //Output::print_string(format!("Discovered {} peers:", peers.len()).as_str());
+
+    //peers
+      //  .iter()
+      //  .enumerate()
+      //  .for_each(|(i, el)| Output::print_string(format!("{}: {}", i, el).as_str()));
+}
+
+/// Lists, announces or withdraws open games of lobby
+pub(super) fn use_lobby<Output: input::Input<Input, OutputEvents>>(
+    user_interface: &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    command: LobbyCommand,
+) {
+    match command {
+        LobbyCommand::List => {
+            user_session.open_games.expire(std::time::Instant::now());
+            user_interface.print_to_output(OutputEvents::OpenGames(user_session.open_games.list()));
+        }
+        LobbyCommand::Seek => {
+            announce_open_game(swarm, user_session);
+            user_interface.print_to_output(OutputEvents::Seeking);
+        }
+        LobbyCommand::Leave => {
+            leave_lobby(swarm, user_session);
+            user_interface.print_to_output(OutputEvents::LeftLobby);
+        }
+    }
+}
+
+/// Announces I look for a game with my rules, again when it is due for refresh
+pub(super) fn announce_open_game(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession) {
+    user_session.seeking = Some(std::time::Instant::now());
+    let message = lobby::LobbyMessage::seeking(user_session.rules(), user_session.own_nickname());
+    let payload = serde_json::to_string(&message).expect("cannot jsonify lobby message");
+    swarm.behaviour_mut().publish(lobby::seeking_topic(&user_session.lobby), payload);
+}
+
+/// Withdraws my open game, e.g. once other game starts
+pub(super) fn leave_lobby(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession) {
+    if user_session.seeking.take().is_none() {
+        return;
+    }
+    let payload = serde_json::to_string(&lobby::LobbyMessage::Left).expect("cannot jsonify lobby message");
+    swarm.behaviour_mut().publish(lobby::seeking_topic(&user_session.lobby), payload);
+}
+
+/// Refreshes my open game and drops those of others not refreshed in time
+pub(super) fn check_lobby(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession) {
+    let now = std::time::Instant::now();
+    user_session.open_games.expire(now);
+    if user_session.seeking.is_some_and(|announced_at| now - announced_at >= lobby::REFRESH_PERIOD) {
+        announce_open_game(swarm, user_session);
+    }
+}
+
+/// Remembers addresses of opponent whose game starts
+pub(super) fn remember_opponent(swarm: &libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession, peer_id: &str) {
+    let addresses = peer_id
+        .parse::<libp2p::PeerId>()
+        .ok()
+        .and_then(|peer| swarm.behaviour().addresses.get(&peer).cloned())
+        .unwrap_or_default();
+    user_session.remember_opponent(peer_id, &addresses);
+}
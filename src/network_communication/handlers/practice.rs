@@ -0,0 +1,190 @@
+//! # Practice
+//!
+//! Setting up positions, drills and reviews of finished games.
+
+use super::invitations::{ask, initiate_game};
+#[cfg(feature = "ai")]
+use crate::ai;
+use crate::coords::Coordinates;
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::{review_topic, UserSession};
+use crate::network_communication::swarm::publish;
+use crate::network_communication::{clock, drills, input, prompt, protocol, replay, review, stats, Input, OutputEvents, SetupCommand, PUZZLE_ID};
+use crate::tictactoe;
+
+/// Edits position in setup mode, analyzes it, saves it as drill or proposes game from it
+pub(super) async fn set_up<Output: input::Input<Input, OutputEvents>>(
+    user_interface: &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    command: SetupCommand,
+) {
+    let mut position = match (user_session.setup, &command) {
+        (Some(position), _) => position,
+        (None, SetupCommand::Begin) => [[tictactoe::Tile::Empty; crate::coords::SIZE]; crate::coords::SIZE],
+        (None, _) => {
+            user_interface.print_to_output(OutputEvents::NotSettingUp);
+            return;
+        }
+    };
+    // set up positions are 3x3
+    let rules = tictactoe::Rules { from_position: Some(position), board: None, ..user_session.rules() };
+    match command {
+        SetupCommand::Begin => {}
+        SetupCommand::Place((x, y), tile) => position[x][y] = tile,
+        SetupCommand::End => {
+            user_session.setup = None;
+            user_interface.print_to_output(OutputEvents::SetupEnded);
+            return;
+        }
+        SetupCommand::Analyze | SetupCommand::Puzzle | SetupCommand::Propose(_) => {
+            let game = match crate::setup::game_to_move(&position, rules) {
+                Ok(game) => game,
+                Err(error) => {
+                    user_interface.print_to_output(OutputEvents::SetupRejected(error));
+                    return;
+                }
+            };
+            match command {
+                #[cfg(feature = "ai")]
+                SetupCommand::Analyze => user_interface.print_to_output(OutputEvents::SetupAnalysis(ai::scored_moves(&game))),
+                #[cfg(not(feature = "ai"))]
+                SetupCommand::Analyze => user_interface.print_to_output(OutputEvents::Error("Analysis needs the ai feature.".to_string())),
+                SetupCommand::Puzzle => {
+                    let now_secs = clock::now_millis() / 1000;
+                    let position = replay::Replay::new(PUZZLE_ID, stats::Outcome::Voided, &game);
+                    user_session.stats.add_drills(vec![drills::Drill { position, streak: 0, due_at: now_secs }]);
+                    user_session.save_stats();
+                    user_interface.print_to_output(OutputEvents::PuzzleSaved(user_session.stats.drills.len()));
+                }
+                SetupCommand::Propose(peer) => {
+                    if let Some(peer) = initiate_game(swarm, peer, rules, None, None, None, user_session).await {
+                        user_interface.print_to_output(OutputEvents::AlreadyPlaying(peer));
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+    }
+    user_session.setup = Some(position);
+    user_interface.print_to_output(OutputEvents::SetupPosition(position));
+}
+
+/// Grades answer to shown drill, otherwise adds blunders of finished games and shows the most overdue drill
+#[cfg(feature = "ai")]
+pub(super) fn drill<Output: input::Input<Input, OutputEvents>>(
+    user_session: &mut UserSession,
+    user_interface : &mut Output,
+    answer: Option<Coordinates>,
+) {
+    let now_secs = clock::now_millis() / 1000;
+    if let (Some(field), Some(index)) = (answer, user_session.drill.take()) {
+        let grade = user_session.stats.drills[index].answer(field, now_secs);
+        user_session.save_stats();
+        user_interface.print_to_output(OutputEvents::DrillGraded(grade));
+        return;
+    }
+
+    match user_session.replay_store().and_then(|store| store.load()) {
+        Ok(replays) => {
+            for replay in &replays {
+                user_session.stats.add_drills(drills::blunders(replay, now_secs));
+            }
+            user_session.save_stats();
+        }
+        Err(error) => user_interface.print_to_output(OutputEvents::ReplayFailed(error.to_string())),
+    }
+    match user_session.stats.due_drill(now_secs) {
+        Some(index) => {
+            let position = &user_session.stats.drills[index].position;
+            let grid = position.game_after(position.moves.len()).get_state();
+            user_session.drill = Some(index);
+            user_interface.print_to_output(OutputEvents::Drill(grid, position.opponent_id.clone()));
+        }
+        None => user_interface.print_to_output(OutputEvents::NoDrills(user_session.stats.drills.len())),
+    }
+}
+
+#[cfg(not(feature = "ai"))]
+pub(super) fn drill<Output: input::Input<Input, OutputEvents>>(
+    _user_session: &mut UserSession,
+    user_interface : &mut Output,
+    _answer: Option<Coordinates>,
+) {
+    user_interface.print_to_output(OutputEvents::Error("Drills need the ai feature.".to_string()));
+}
+
+/// Proposes review of last game, or joins review proposed by its opponent
+pub(super) fn start_review<Output: input::Input<Input, OutputEvents>>(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    user_interface : &mut Output,
+) {
+    let last_game = match &user_session.last_game {
+        Some(last_game) => last_game.clone(),
+        None => {
+            user_interface.print_to_output(OutputEvents::NothingToReview);
+            return;
+        }
+    };
+    let topic = review_topic(&user_session.user_peer_id.to_string(), &last_game.opponent_id);
+
+    match user_session.review.as_mut() {
+        Some(review) if review.state == review::ReviewState::ProposedByPeer => {
+            review.state = review::ReviewState::Active;
+            publish(swarm, topic, protocol::WireMessage::ReviewAnswer { accept: true }, protocol::WireFormat::Tagged);
+            user_interface.print_to_output(OutputEvents::ReviewStarted(review.peer_id.clone()));
+            print_review_position(user_interface, review);
+        }
+        Some(review) if review.is_active() => print_review_position(user_interface, review),
+        _ => {
+            user_session.review = Some(review::Review::new(last_game, review::ReviewState::ProposedByMe));
+            publish(swarm, topic, protocol::WireMessage::ReviewPropose, protocol::WireFormat::Tagged);
+        }
+    }
+}
+
+/// Applies review message of last opponent, messages of other peers are ignored
+pub(super) fn resolve_review_message<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    sender: String,
+    message: review::ReviewMessage,
+) {
+    let last_game = match &user_session.last_game {
+        Some(last_game) if last_game.opponent_id == sender => last_game.clone(),
+        _ => return,
+    };
+
+    match message {
+        review::ReviewMessage::Propose => {
+            user_session.review = Some(review::Review::new(last_game, review::ReviewState::ProposedByPeer));
+            ask(user_interface, swarm, user_session, prompt::Question::Review(sender));
+        }
+        review::ReviewMessage::Answer(true) => {
+            if let Some(review) = user_session.review.as_mut().filter(|review| review.state == review::ReviewState::ProposedByMe) {
+                review.state = review::ReviewState::Active;
+                user_interface.print_to_output(OutputEvents::ReviewStarted(sender));
+                print_review_position(user_interface, review);
+            }
+        }
+        review::ReviewMessage::Answer(false) | review::ReviewMessage::End => {
+            if user_session.review.take().is_some() {
+                user_interface.print_to_output(OutputEvents::ReviewEnded(sender));
+            }
+        }
+        review::ReviewMessage::Goto(position) => {
+            if let Some(review) = user_session.review.as_mut().filter(|review| review.is_active()) {
+                review.navigate(review::ReviewStep::Goto(position));
+                print_review_position(user_interface, review);
+            }
+        }
+    }
+}
+
+pub(super) fn print_review_position<Output: input::Input<Input, OutputEvents>>(user_interface : &mut Output, review: &review::Review) {
+    let (last_move, state) = review.shown();
+    user_interface.print_to_output(OutputEvents::ReviewPosition(review.position(), last_move, state));
+}
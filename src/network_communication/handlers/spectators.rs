@@ -0,0 +1,191 @@
+//! # Spectators
+//!
+//! Games watched or refereed by other peers and results gossiped to room's
+//! ladder.
+
+use crate::coords::Coordinates;
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::{GameSession, UserSession};
+use crate::network_communication::swarm::{publish, send_direct};
+use crate::network_communication::{clock, input, protocol, referee, spectate, Input, OutputEvents};
+use crate::tictactoe;
+
+/// Publishes every result I know on ladder topic, each one in own message as
+/// floodsub does not carry large ones
+pub(super) fn gossip_ladder(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession) {
+    let (topic, ladder) = match (user_session.ladder_topic(), &user_session.ladder) {
+        (Some(topic), Some(ladder)) => (topic, ladder),
+        _ => return,
+    };
+    for attestation in ladder.attestations() {
+        let payload = serde_json::to_string(attestation).expect("cannot jsonify attestation");
+        swarm.behaviour_mut().publish(topic.clone(), payload);
+    }
+}
+
+/// Publishes all moves of game to its spectators, the latest one is mine
+pub(super) fn publish_to_spectators(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, game_session: &GameSession) {
+    let game = game_session.game();
+    let moves = game.moves().iter().map(|&(x, y)| {
+        let (wire_x, wire_y) = protocol::to_wire((x, y));
+        protocol::SpectatedMove { x: wire_x, y: wire_y, mark: game.tile(x, y) }
+    });
+    // tiles of set up position are the first moves
+    let rules = tictactoe::Rules { from_position: None, ..game_session.game().rules() };
+    let game_id = game_session.topic.id().to_string();
+    let message = protocol::WireMessage::Spectated { game: game_id.clone(), rules, moves: moves.collect() };
+    let payload = protocol::encode(&message, protocol::WireFormat::Tagged);
+    swarm.behaviour_mut().protocols.floodsub.publish(spectate::watch_topic(&game_id), payload.into_bytes());
+}
+
+/// Starts watching game of other peers, or stops when it is watched already
+pub(super) fn watch_game<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    game_id: String,
+) {
+    if spectate::players(&game_id).is_none() {
+        user_interface.print_to_output(OutputEvents::InvalidGameId(game_id));
+        return;
+    }
+    let topic = spectate::watch_topic(&game_id);
+    if user_session.watched.remove(&game_id).is_some() {
+        if !user_session.refereed.contains_key(&game_id) {
+            swarm.behaviour_mut().protocols.floodsub.unsubscribe(topic);
+        }
+        user_interface.print_to_output(OutputEvents::Unwatched(game_id));
+        return;
+    }
+    swarm.behaviour_mut().protocols.floodsub.subscribe(topic);
+    user_session.watched.insert(game_id.clone(), spectate::Replica::default());
+    user_interface.print_to_output(OutputEvents::Watching(game_id));
+}
+
+/// Plays moves published by player into replica of watched game, which is left once it ends
+pub(super) fn resolve_spectated<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    sender: &str,
+    game_id: String,
+    rules: tictactoe::Rules,
+    moves: Vec<(Coordinates, tictactoe::Tile)>,
+) {
+    // only players of the game publish its moves
+    let is_player = spectate::players(&game_id).is_some_and(|(initiator, invitee)| sender == initiator || sender == invitee);
+    let replica = match user_session.watched.get_mut(&game_id) {
+        Some(replica) if is_player => replica,
+        _ => return,
+    };
+    let new_moves = match replica.sync(rules, &moves) {
+        Ok(new_moves) => new_moves,
+        Err(_) => return,
+    };
+    if let Some((field, _)) = new_moves.last() {
+        user_interface.print_to_output(OutputEvents::SpectatedTurn(game_id.clone(), *field, replica.game().grid()));
+    }
+    if replica.is_finished() {
+        let winner = replica.winner(&game_id).map(str::to_string);
+        user_session.watched.remove(&game_id);
+        if !user_session.refereed.contains_key(&game_id) {
+            swarm.behaviour_mut().protocols.floodsub.unsubscribe(spectate::watch_topic(&game_id));
+        }
+        user_interface.print_to_output(OutputEvents::SpectatedFinished(game_id, winner));
+    }
+}
+
+/// Starts refereeing game of other peers, or stops when it is refereed already
+pub(super) fn referee_game<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    game_id: String,
+) {
+    let user_peer_id = user_session.user_peer_id.to_string();
+    match spectate::players(&game_id) {
+        None => {
+            user_interface.print_to_output(OutputEvents::InvalidGameId(game_id));
+            return;
+        }
+        Some((initiator, invitee)) if initiator == user_peer_id || invitee == user_peer_id => {
+            user_interface.print_to_output(OutputEvents::OwnGameRefereed(game_id));
+            return;
+        }
+        Some(_) => {}
+    }
+    let topic = spectate::watch_topic(&game_id);
+    if user_session.refereed.remove(&game_id).is_some() {
+        if !user_session.watched.contains_key(&game_id) {
+            swarm.behaviour_mut().protocols.floodsub.unsubscribe(topic);
+        }
+        user_interface.print_to_output(OutputEvents::StoppedRefereeing(game_id));
+        return;
+    }
+    swarm.behaviour_mut().protocols.floodsub.subscribe(topic);
+    user_session.refereed.insert(game_id.clone(), referee::Refereed::default());
+    user_interface.print_to_output(OutputEvents::Refereeing(game_id));
+}
+
+/// Judges moves published by player of refereed game. Once there is ruling,
+/// signed verdict goes to both players and to spectators on watch topic.
+pub(super) fn resolve_refereed<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    sender: &str,
+    game_id: &str,
+    rules: tictactoe::Rules,
+    moves: &[(Coordinates, tictactoe::Tile)],
+) {
+    let refereed = match user_session.refereed.get_mut(game_id) {
+        Some(refereed) => refereed,
+        None => return,
+    };
+    let ruling = match refereed.observe(game_id, sender, rules, moves, clock::now_millis()) {
+        Some(ruling) => ruling,
+        None => return,
+    };
+    let moves = refereed.moves().to_vec();
+    user_session.refereed.remove(game_id);
+    let topic = spectate::watch_topic(game_id);
+    if !user_session.watched.contains_key(game_id) {
+        swarm.behaviour_mut().protocols.floodsub.unsubscribe(topic.clone());
+    }
+
+    let verdict = match referee::Verdict::sign(&user_session.user_key, game_id, ruling, moves) {
+        Ok(verdict) => verdict,
+        Err(error) => {
+            user_interface.print_to_output(OutputEvents::Error(format!("Cannot sign verdict: {}", error)));
+            return;
+        }
+    };
+    if let Some(dir) = &user_session.settings.data_dir {
+        if let Err(error) = referee::record(dir, &verdict) {
+            user_interface.print_to_output(OutputEvents::Error(format!("Cannot record verdict: {}", error)));
+        }
+    }
+    let message = protocol::WireMessage::Verdict(verdict.clone());
+    if let Some((initiator, invitee)) = spectate::players(game_id) {
+        for player in [initiator, invitee] {
+            send_direct(swarm, player, topic.clone(), message.clone(), protocol::WireFormat::Tagged);
+        }
+    }
+    publish(swarm, topic, message, protocol::WireFormat::Tagged);
+    user_interface.print_to_output(OutputEvents::VerdictIssued(verdict));
+}
+
+/// Shows verdict sent by trusted referee about my game or game I watch
+pub(super) fn resolve_verdict<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    user_session: &UserSession,
+    sender: &str,
+    verdict: referee::Verdict,
+) {
+    let trusted = sender == verdict.referee && user_session.settings.referees.contains(&verdict.referee);
+    let user_peer_id = user_session.user_peer_id.to_string();
+    let mine = spectate::players(&verdict.game).is_some_and(|(initiator, invitee)| initiator == user_peer_id || invitee == user_peer_id);
+    if trusted && (mine || user_session.watched.contains_key(&verdict.game)) {
+        user_interface.print_to_output(OutputEvents::Verdict(verdict));
+    }
+}
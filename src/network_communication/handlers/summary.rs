@@ -0,0 +1,29 @@
+//! # Summary
+//!
+//! Summaries of my identity and of my sessions which frontends show.
+
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::{GameSession, UserSession};
+use crate::network_communication::{banner, StatusLine};
+
+/// Returns summary of session for frontends which keep it on screen
+pub(in crate::network_communication) fn status_line(swarm: &libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession) -> StatusLine {
+    let active = user_session.sessions.get_index(user_session.active).filter(|session| session.is_initiated());
+    StatusLine {
+        peers: swarm.behaviour().last_seen.len(),
+        games: user_session.sessions.iter().filter(|session| session.is_initiated()).count(),
+        opponent: active.map(|session| session.opponent_id.clone()),
+        your_turn: active.is_some_and(GameSession::is_your_turn),
+        seeking: user_session.seeking.is_some(),
+    }
+}
+
+/// Returns my identity with addresses I currently listen on
+pub(in crate::network_communication) fn banner(swarm: &libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession) -> banner::Banner {
+    banner::Banner {
+        peer_id: user_session.user_peer_id.to_string(),
+        fingerprint: banner::fingerprint(&user_session.user_key.public()),
+        nickname: user_session.own_nickname(),
+        listen_addrs: swarm.listeners().cloned().collect(),
+    }
+}
@@ -0,0 +1,199 @@
+//! # Timers
+//!
+//! Periodic checks of the main loop: forfeits, turn timeouts, expired
+//! invitations, scheduled games, reminders and hints.
+
+use super::invitations::withdraw_invitation;
+#[cfg(feature = "ai")]
+use super::turns::play_my_turn;
+#[cfg(feature = "ai")]
+use crate::ai;
+use crate::network_communication::behaviour::TicTacToeBehaviour;
+use crate::network_communication::session::{GameSession, UserSession};
+use crate::network_communication::swarm::publish;
+use crate::network_communication::{clock, hints, input, prompt, protocol, stats, DisconnectPolicy, Input, OutputEvents, Settings};
+
+/// Ends games whose disconnected opponent did not return within grace period
+pub(super) fn check_forfeits<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    if user_session.disconnect_policy() != DisconnectPolicy::Forfeit {
+        return;
+    }
+
+    let grace = std::time::Duration::from_secs(user_session.settings.forfeit_grace_secs);
+    let forfeited: Vec<usize> = user_session.sessions
+        .iter()
+        .enumerate()
+        .filter(|(_, session)| matches!(session.disconnected_at, Some(since) if since.elapsed() >= grace))
+        .map(|(index, _)| index)
+        .collect();
+
+    // sessions may be removed, go from the last one
+    for index in forfeited.into_iter().rev() {
+        let opponent_id = user_session.sessions[index].opponent_id.clone();
+        user_interface.print_to_output(OutputEvents::WonByForfeit(opponent_id));
+        user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+    }
+}
+
+/// Ends games whose player on turn ran out of time, I forfeit my own turns
+pub(in crate::network_communication) fn check_turn_timeouts<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    let timeout = match user_session.turn_timeout() {
+        Some(timeout) => timeout,
+        None => return,
+    };
+
+    let now = std::time::Instant::now();
+    #[cfg(feature = "ai")]
+    {
+        let away: Vec<usize> = user_session.sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| user_session.auto_move_due(session, timeout).is_some_and(|due| due <= now))
+            .map(|(index, _)| index)
+            .collect();
+        // sessions may be removed, go from the last one
+        for index in away.into_iter().rev() {
+            play_auto_move(user_interface, swarm, user_session, index);
+        }
+    }
+
+    let expired: Vec<usize> = user_session.sessions
+        .iter()
+        .enumerate()
+        .filter(|(_, session)| session.turn_deadline(timeout).is_some_and(|deadline| deadline <= now))
+        .map(|(index, _)| index)
+        .collect();
+
+    // sessions may be removed, go from the last one
+    for index in expired.into_iter().rev() {
+        let format = user_session.opponent_format(index);
+        let game_session = &user_session.sessions[index];
+        let opponent_id = game_session.opponent_id.clone();
+        if game_session.is_your_turn() {
+            // older clients do not understand it, their own timer ends the game
+            if format == protocol::WireFormat::Tagged {
+                publish(swarm, game_session.topic.clone(), protocol::WireMessage::Forfeit, format);
+            }
+            user_interface.print_to_output(OutputEvents::TurnTimeout(opponent_id));
+            user_session.end_game(swarm, index, stats::Outcome::Lost);
+        } else {
+            user_interface.print_to_output(OutputEvents::OpponentTimeout(opponent_id));
+            user_session.end_game(swarm, index, stats::Outcome::WonByForfeit);
+        }
+    }
+}
+
+/// Plays the best move for me when I seem away, opponent is told it was not mine
+#[cfg(feature = "ai")]
+fn play_auto_move<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+) {
+    let opponent_id = user_session.sessions[index].opponent_id.clone();
+    let ((x, y), _) = match ai::best_move(user_session.sessions[index].game()) {
+        Some(best) => best,
+        None => return,
+    };
+    if let Ok(game) = play_my_turn(swarm, user_session, index, x, y, None, true) {
+        user_interface.print_to_output(OutputEvents::AutoMoved(opponent_id.clone(), (x, y), game.grid()));
+        if game.is_draw() {
+            user_interface.print_to_output(OutputEvents::Draw(opponent_id));
+        }
+    }
+}
+
+/// Withdraws my invitations which were not answered in time
+pub(super) fn check_invitations<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    let timeout = match user_session.settings.invitation_timeout_secs {
+        Some(seconds) => std::time::Duration::from_secs(seconds),
+        None => return,
+    };
+
+    let expired: Vec<usize> = user_session.sessions
+        .iter()
+        .enumerate()
+        .filter(|(_, session)| matches!(session.invited_at, Some(since) if since.elapsed() >= timeout))
+        .map(|(index, _)| index)
+        .collect();
+
+    // sessions may be removed, go from the last one
+    for index in expired.into_iter().rev() {
+        user_interface.print_to_output(OutputEvents::InvitationExpired(user_session.sessions[index].opponent_id.clone()));
+        withdraw_invitation(swarm, user_session, index);
+    }
+}
+
+/// Tells about agreed games whose start time has come
+pub(super) fn check_schedule<Output: input::Input<Input, OutputEvents>>(user_interface : &mut Output, user_session: &mut UserSession) {
+    let now = clock::now_millis();
+    for game_session in user_session.sessions.iter_mut() {
+        if game_session.is_scheduled() && !game_session.start_reminded && game_session.start_at.is_some_and(|start_at| start_at <= now) {
+            game_session.start_reminded = true;
+            game_session.start_turn_clock();
+            user_interface.print_to_output(OutputEvents::ScheduledGameDue(game_session.opponent_id.clone()));
+        }
+    }
+}
+
+pub(super) fn check_reminders<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    game_session: &mut GameSession,
+    settings: &Settings,
+) {
+    let waiting = match game_session.turn_duration() {
+        Some(waiting) if game_session.is_initiated() && !game_session.reminded => waiting,
+        _ => return,
+    };
+
+    let (limit, event): (Option<u64>, fn(u64) -> OutputEvents) = if game_session.is_your_turn() {
+        (settings.reminder_minutes, OutputEvents::Reminder)
+    } else {
+        (settings.opponent_reminder_minutes, OutputEvents::OpponentSlow)
+    };
+
+    if let Some(minutes) = limit {
+        if waiting >= std::time::Duration::from_secs(minutes * 60) {
+            game_session.reminded = true;
+            user_interface.print_to_output(event(minutes));
+        }
+    }
+}
+
+pub(super) fn check_hints<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+) {
+    if !user_session.settings.hints {
+        return;
+    }
+    let active = user_session.sessions.get_index(user_session.active).filter(|session| session.is_initiated());
+    let situation = hints::Situation {
+        idle: user_session.last_input.elapsed(),
+        peers: swarm.behaviour().last_seen.len(),
+        games: user_session.sessions.iter().filter(|session| session.is_initiated()).count(),
+        invitations: user_session
+            .prompts
+            .iter()
+            .filter(|prompt| matches!(prompt.question, prompt::Question::Invitation(..)))
+            .count(),
+        my_turn: active.is_some_and(GameSession::is_your_turn),
+    };
+    if let Some(hint) = user_session.hints.next(&situation, std::time::Instant::now()) {
+        user_interface.print_to_output(OutputEvents::Hint(hint));
+    }
+}
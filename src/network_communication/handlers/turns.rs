@@ -0,0 +1,482 @@
+//! # Turns
+//!
+//! Playing my turns, resolving opponent's turns and races, resuming games,
+//! engine moves and clock messages.
+
+use super::spectators::publish_to_spectators;
+#[cfg(feature = "ai")]
+use crate::ai;
+use crate::coords::Coordinates;
+use crate::gomoku::BoardSize;
+use crate::network_communication::behaviour::{GameStatus, PeerMessage, TicTacToeBehaviour};
+use crate::network_communication::session::{GameSession, UserSession};
+use crate::network_communication::swarm::{publish, send_direct};
+use crate::network_communication::{clock, correspondence, input, protocol, stats, Input, OutputEvents};
+use crate::tictactoe;
+
+/// Answers opponent's ping and adds clock and latency samples from their pongs
+pub(super) fn resolve_clock_message<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    status: GameStatus,
+) {
+    let received_at = clock::now_millis();
+    let format = user_session.opponent_format(index);
+    let game_session = &mut user_session.sessions[index];
+    match status {
+        GameStatus::Ping(ping_sent_at) => {
+            let pong = protocol::WireMessage::Pong { ping_sent_at, received_at, sent_at: clock::now_millis() };
+            publish(swarm, game_session.topic.clone(), pong, format);
+        }
+        GameStatus::Pong(ping_sent_at, ping_received_at, pong_sent_at) => {
+            let sample = clock::Sample::new(ping_sent_at, ping_received_at, pong_sent_at, received_at);
+            game_session.clock.add(sample);
+            let average = game_session.latency.average().unwrap_or_default();
+            if game_session.latency.record(sample.round_trip) {
+                let opponent_id = game_session.opponent_id.clone();
+                user_interface.print_to_output(OutputEvents::Laggy(opponent_id, sample.round_trip, average));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Asks external engine for my move in given session, answer arrives as internal message
+pub(super) fn ask_engine(user_session: &mut UserSession, index: usize) {
+    let engine = match &user_session.engine {
+        Some(engine) => engine.clone(),
+        None => return,
+    };
+
+    let game_session = &mut user_session.sessions[index];
+    // engines read classic playmat only
+    if game_session.game().board_size() != BoardSize::CLASSIC {
+        return;
+    }
+    let state = game_session.game().get_state();
+    let marks = game_session.game().my_marks();
+    let opponent_id = game_session.opponent_id.clone();
+    let internal_sender = game_session.internal_sender.clone();
+
+    game_session.tasks.spawn(async move {
+        let status = match engine.lock().await.best_marked_move(&state, &marks).await {
+            Ok(((x, y), mark)) => GameStatus::EngineMove(x, y, mark),
+            Err(error) => GameStatus::EngineFailed(error.to_string()),
+        };
+        let _ = internal_sender.send(PeerMessage::about(opponent_id, status));
+    });
+}
+
+pub(super) fn play_engine_move<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    x: usize,
+    y: usize,
+    mark: tictactoe::Tile,
+) {
+    let eval_bar = user_session.settings.eval_bar;
+    let opponent_id = user_session.sessions[index].opponent_id.clone();
+    match play_my_turn(swarm, user_session, index, x, y, Some(mark), false) {
+        Ok(game) => {
+            let evaluation = evaluate_if(eval_bar, &game, false);
+            user_interface.print_to_output(OutputEvents::EnginePlayed((x, y), game.grid(), evaluation));
+            if game.is_draw() {
+                user_interface.print_to_output(OutputEvents::Draw(opponent_id));
+            }
+        }
+        Err(_) => user_interface.print_to_output(OutputEvents::EngineError(format!("engine chose illegal move ({}, {})", x, y))),
+    }
+}
+
+/// Returns evaluation from my point of view when eval bar is enabled and position can be searched
+#[cfg(feature = "ai")]
+pub(super) fn evaluate_if(enabled: bool, game: &tictactoe::TicTacToe, my_turn: bool) -> Option<tictactoe::Evaluation> {
+    if enabled && ai::is_searchable(game) {
+        Some(ai::evaluate(game, my_turn))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(feature = "ai"))]
+pub(super) fn evaluate_if(_enabled: bool, _game: &tictactoe::TicTacToe, _my_turn: bool) -> Option<tictactoe::Evaluation> {
+    None
+}
+
+pub(super) fn switch_game<Output: input::Input<Input, OutputEvents>>(
+    user_session: &mut UserSession,
+    index: usize,
+    user_interface : &mut Output,
+) {
+    match user_session.sessions.get_index(index) {
+        Some(session) if session.is_initiated() => {
+            user_session.active = index;
+            user_interface.print_to_output(OutputEvents::SwitchedGame(index, session.game().grid()));
+        }
+        _ => user_interface.print_to_output(OutputEvents::NoSuchGame(index)),
+    }
+}
+
+/// Applies opponent's turn, returns outcome when it ended the game
+pub(super) fn resolve_opponent_turn<Output: input::Input<Input, OutputEvents>>(
+    x: usize,
+    y: usize,
+    sent_at: Option<u64>,
+    mark: Option<tictactoe::Tile>,
+    game_session: &mut GameSession,
+    user_interface : &mut Output,
+    eval_bar: bool,
+) -> Result<Option<stats::Outcome>, protocol::MoveRejection> {
+    game_session.make_opponent_turn(x, y, sent_at, mark)?;
+    let evaluation = evaluate_if(eval_bar, game_session.game(), true);
+    user_interface.print_to_output(OutputEvents::TurnResolved(game_session.game().grid(), evaluation));
+
+    if game_session.game().is_opponent_winner() {
+        user_interface.print_to_output(OutputEvents::GameOver);
+        return Ok(Some(stats::Outcome::Lost));
+    }
+    if game_session.game().is_draw() {
+        user_interface.print_to_output(OutputEvents::Draw(game_session.opponent_id.clone()));
+        return Ok(Some(stats::Outcome::Drawn));
+    }
+    Ok(None)
+}
+
+/// Ignores opponent's turn breaking rules of the game, tells them why and counts it against them
+pub(super) fn reject_illegal_turn<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    sender: String,
+    field: Coordinates,
+    reason: protocol::MoveRejection,
+) {
+    user_session.stats.record_violation(&sender);
+    user_session.save_stats();
+    let format = user_session.opponent_format(index);
+    let game_session = &user_session.sessions[index];
+    // older clients would not understand it
+    if format == protocol::WireFormat::Tagged && !game_session.is_bot_game() {
+        let (x, y) = protocol::to_wire(field);
+        let message = protocol::WireMessage::InvalidMove { x, y, reason };
+        send_direct(swarm, &game_session.opponent_id, game_session.topic.clone(), message, format);
+    }
+    user_interface.print_to_output(OutputEvents::OpponentInvalidMove(sender, field, reason));
+}
+
+/// Starts clock synchronization exchange, older clients do not understand it
+pub(super) fn send_ping(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    game_session: &GameSession,
+    format: protocol::WireFormat,
+) {
+    if format == protocol::WireFormat::Tagged && !game_session.is_bot_game() {
+        publish(swarm, game_session.topic.clone(), protocol::WireMessage::Ping { sent_at: clock::now_millis() }, format);
+    }
+}
+
+/// Settles turns of the same number by protocol rule, initiator's turn stands. Invitee
+/// takes its turn back and asks for the initiator's one as after reconnect.
+pub(super) fn resolve_race<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+) {
+    let game_session = &mut user_session.sessions[index];
+    let opponent_id = game_session.opponent_id.clone();
+    if game_session.is_initiator() {
+        user_interface.print_to_output(OutputEvents::RaceResolved(opponent_id, true));
+        return;
+    }
+    game_session.turns.yield_race();
+    game_session.warned_turn = None;
+    user_session.save_games();
+    user_interface.print_to_output(OutputEvents::RaceResolved(opponent_id, false));
+    send_resume(swarm, user_session, index);
+}
+
+/// Tells opponent how many moves I know, so turn lost while network was down is sent again
+pub(super) fn send_resume(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession, index: usize) {
+    let format = user_session.opponent_format(index);
+    let game_session = &user_session.sessions[index];
+    if format == protocol::WireFormat::Tagged {
+        let moves = game_session.game().moves().len();
+        publish(swarm, game_session.topic.clone(), protocol::WireMessage::Resume { moves }, format);
+    }
+}
+
+/// Compares game with opponent's one, resends my last turn it missed or asks for its one
+pub(super) fn resume_game(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession, index: usize, peer_moves: usize) {
+    let format = user_session.opponent_format(index);
+    let game_session = &user_session.sessions[index];
+    let game = game_session.game();
+    match game.moves().len().cmp(&peer_moves) {
+        std::cmp::Ordering::Greater if !game_session.is_your_turn() => {
+            if let Some(&(x, y)) = game.moves().last() {
+                let mark = if game.rules().is_standard() { None } else { Some(game.tile(x, y)) };
+                let (x, y) = protocol::to_wire((x, y));
+                let number = Some(game.moves().len());
+                let turn = protocol::WireMessage::Turn { x, y, sent_at: Some(clock::now_millis()), mark, number, auto: false };
+                let payload = swarm.behaviour_mut().encode(&game_session.topic, &turn, format);
+                swarm.behaviour_mut().republish(game_session.topic.clone(), payload);
+            }
+        }
+        std::cmp::Ordering::Less => send_resume(swarm, user_session, index),
+        _ => {}
+    }
+}
+
+pub(super) fn send_nudge(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    game_session: &GameSession,
+    format: protocol::WireFormat,
+) {
+    if game_session.is_initiated() && !game_session.is_your_turn() {
+        publish(swarm, game_session.topic.clone(), protocol::WireMessage::Nudge, format);
+    }
+}
+
+pub(super) async fn make_turn<Output: input::Input<Input, OutputEvents>>(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    x : usize,
+    y : usize,
+    mark: Option<tictactoe::Tile>,
+    user_session: &mut UserSession,
+    user_interface: &mut Output,
+) {
+    let refusal = if user_session.game_session().is_initiated() {
+        user_session.game_session().turn_refusal()
+    } else {
+        Some(match &user_session.last_game {
+            Some(last_game) => OutputEvents::GameFinished(last_game.opponent_id.clone()),
+            None => OutputEvents::NoActiveGame,
+        })
+    };
+    if let Some(refusal) = refusal {
+        user_interface.print_to_output(refusal);
+        return;
+    }
+
+    if let Some(mark) = mark.filter(|mark| !user_session.game_session().game().my_marks().contains(mark)) {
+        user_interface.print_to_output(OutputEvents::WrongMark(mark));
+        return;
+    }
+    if user_session.settings.teach && !review_turn(user_session.game_session(), x, y, mark, user_interface) {
+        return;
+    }
+    make_one_turn(swarm, user_session, x, y, mark, user_interface).await;
+}
+
+/// Explains why turn cannot be played or warns when it loses immediately,
+/// returns true when turn should be played
+fn review_turn<Output: input::Input<Input, OutputEvents>>(
+    game_session: &mut GameSession,
+    x: usize,
+    y: usize,
+    mark: Option<tictactoe::Tile>,
+    user_interface: &mut Output,
+) -> bool {
+    let game = game_session.game();
+    if let Some(refusal) = field_refusal(game, x, y) {
+        user_interface.print_to_output(refusal);
+        return false;
+    }
+
+    let mut after = game.clone();
+    let _ = after.make_my_mark(x, y, mark.unwrap_or(game.marks().you));
+    let threat = if after.am_i_winner() {
+        None
+    } else {
+        after.opponent_winning_moves().first().copied()
+    };
+    match threat {
+        Some(field) if game_session.warned_turn != Some((x, y)) => {
+            game_session.warned_turn = Some((x, y));
+            user_interface.print_to_output(OutputEvents::LosingTurn((x, y), field));
+            false
+        }
+        _ => {
+            game_session.warned_turn = None;
+            true
+        }
+    }
+}
+
+/// Explains why field cannot be played, none when it is free
+fn field_refusal(game: &tictactoe::TicTacToe, x: usize, y: usize) -> Option<OutputEvents> {
+    let size = game.board_size().size;
+    if x >= size || y >= size {
+        return Some(OutputEvents::OutOfRange(x, y));
+    }
+    game.move_number(x, y).map(|number| {
+        let yours = game.tile(x, y) == game.marks().you;
+        OutputEvents::FieldOccupied((x, y), yours, number)
+    })
+}
+
+/// Plays my turn in given session and sends it to opponent, returns game after the turn
+pub(super) fn play_my_turn(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    index: usize,
+    x: usize,
+    y: usize,
+    mark: Option<tictactoe::Tile>,
+    auto: bool,
+) -> Result<tictactoe::TicTacToe, tictactoe::GameError> {
+    let format = user_session.opponent_format(index);
+    let game_session = &mut user_session.sessions[index];
+    game_session.make_my_turn(x, y, mark)?;
+    let game = game_session.game().clone();
+    if game_session.is_bot_game() {
+        #[cfg(feature = "ai")]
+        game_session.pass_to_bot(x, y, mark.unwrap_or(game.marks().you));
+        if game.am_i_winner() {
+            user_session.end_game(swarm, index, stats::Outcome::Won);
+        } else if game.is_draw() {
+            user_session.end_game(swarm, index, stats::Outcome::Drawn);
+        }
+        return Ok(game);
+    }
+
+    // tile is sent only when rules let players choose it, older clients understand such turns
+    let mark = if game.rules().is_standard() { None } else { mark.or(Some(game.marks().you)) };
+    let (wire_x, wire_y) = protocol::to_wire((x, y));
+    let number = Some(game.moves().len());
+    let turn = protocol::WireMessage::Turn { x: wire_x, y: wire_y, sent_at: Some(clock::now_millis()), mark, number, auto };
+    match (&user_session.correspondence, game_session.disconnected_at) {
+        (Some(store), Some(_)) => {
+            let entry = correspondence::OutboxEntry {
+                opponent_id: game_session.opponent_id.clone(),
+                topic: game_session.topic.id().to_string(),
+                payload: swarm.behaviour_mut().encode(&game_session.topic, &turn, format),
+            };
+            if let Err(error) = store.queue(&entry) {
+                // game session is still borrowed, report would borrow the whole session
+                let _ = user_session.errors.send(format!("Cannot queue turn: {}", error));
+            }
+        }
+        _ => {
+            send_direct(swarm, &game_session.opponent_id, game_session.topic.clone(), turn, format);
+            send_ping(swarm, game_session, format);
+        }
+    }
+    if user_session.settings.spectators {
+        publish_to_spectators(swarm, game_session);
+    }
+
+    if game_session.game().am_i_winner() {
+        user_session.end_game(swarm, index, stats::Outcome::Won);
+    } else if game_session.game().is_draw() {
+        user_session.end_game(swarm, index, stats::Outcome::Drawn);
+    } else {
+        user_session.save_games();
+    }
+    Ok(game)
+}
+
+/// Sends turns queued while peer was away, now that it joined game topic again
+pub(super) fn flush_outbox(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &UserSession,
+    peer_id: &str,
+) {
+    let store = match &user_session.correspondence {
+        Some(store) => store,
+        None => return,
+    };
+    match store.take_for(peer_id) {
+        Ok(entries) => {
+            for entry in entries {
+                let topic = libp2p::floodsub::Topic::new(entry.topic);
+                swarm.behaviour_mut().republish(topic, entry.payload);
+            }
+        }
+        Err(error) => user_session.report(format!("Cannot read outbox: {}", error)),
+    }
+}
+
+async fn make_one_turn<Output: input::Input<Input, OutputEvents>>(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    x: usize,
+    y: usize,
+    mark: Option<tictactoe::Tile>,
+    user_interface: &mut Output,
+) {
+    let index = user_session.active;
+    let opponent_id = user_session.sessions[index].opponent_id.clone();
+    match play_my_turn(swarm, user_session, index, x, y, mark, false) {
+        Ok(game) => {
+            if game.am_i_winner() {
+                user_interface.print_to_output(OutputEvents::Won(opponent_id));
+            } else if game.is_draw() {
+                user_interface.print_to_output(OutputEvents::Draw(opponent_id));
+            }
+        }
+        Err(tictactoe::GameError::OccupiedField | tictactoe::GameError::InvalidValue) => {
+            if let Some(refusal) = field_refusal(user_session.sessions[index].game(), x, y) {
+                user_interface.print_to_output(refusal);
+            }
+        }
+        Err(tictactoe::GameError::WrongMark) => {
+            // checked by make_turn before
+        }
+        Err(tictactoe::GameError::Finished) => {
+            // finished games end their session
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{offline_session, start_game, Recorder};
+    use super::*;
+    use crate::network_communication::Settings;
+
+    #[tokio::test]
+    async fn occupied_field_is_reported_without_teach_mode() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let mut recorder = Recorder::default();
+        start_game(&mut user_session, 0, "peer");
+        make_turn(&mut swarm, 1, 1, None, &mut user_session, &mut recorder).await;
+        assert!(user_session.game_session().make_opponent_turn(0, 0, None, None).is_ok());
+
+        make_turn(&mut swarm, 0, 0, None, &mut user_session, &mut recorder).await;
+        let events = recorder.events.borrow();
+        assert!(matches!(events.last(), Some(OutputEvents::FieldOccupied((0, 0), false, 2))));
+        assert_eq!(user_session.game_session().game().moves(), &[(1, 1), (0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn out_of_range_field_is_reported_without_teach_mode() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let mut recorder = Recorder::default();
+        start_game(&mut user_session, 0, "peer");
+
+        make_turn(&mut swarm, 3, 1, None, &mut user_session, &mut recorder).await;
+        assert!(matches!(recorder.events.borrow().last(), Some(OutputEvents::OutOfRange(3, 1))));
+        assert!(user_session.game_session().game().moves().is_empty());
+    }
+
+    #[tokio::test]
+    async fn winning_turn_is_announced() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let mut recorder = Recorder::default();
+        start_game(&mut user_session, 0, "peer");
+        for (mine, theirs) in [((0, 0), (1, 0)), ((0, 1), (1, 1))] {
+            make_turn(&mut swarm, mine.0, mine.1, None, &mut user_session, &mut recorder).await;
+            assert!(user_session.game_session().make_opponent_turn(theirs.0, theirs.1, None, None).is_ok());
+        }
+
+        make_turn(&mut swarm, 0, 2, None, &mut user_session, &mut recorder).await;
+        assert!(matches!(recorder.events.borrow().last(), Some(OutputEvents::Won(peer)) if peer == "peer"));
+        assert!(!user_session.game_session().is_initiated());
+    }
+}
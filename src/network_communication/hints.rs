@@ -0,0 +1,88 @@
+//! # Hints
+//!
+//! Tips for players who seem stuck, chosen by few rules over what the session
+//! is doing: nobody around, invitation waiting for answer or my turn running
+//! long. Hints come only after a while without any command and each one is
+//! repeated at most once per interval, so they do not drown real events.
+
+use std::time::{Duration, Instant};
+
+/// How long player has to be inactive before any hint is given
+pub const IDLE_BEFORE_HINT: Duration = Duration::from_secs(60);
+/// How long the same hint is not repeated
+pub const HINT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hint {
+    /// No peer is around, list peers or join one by invite code
+    FindPeers,
+    /// Invitation waits for yes or no
+    AnswerInvitation,
+    /// It is my turn, this is how to play it
+    TurnSyntax,
+}
+
+/// Rules in order of precedence, only one hint is given at a time
+const RULES: [Hint; 3] = [Hint::AnswerInvitation, Hint::TurnSyntax, Hint::FindPeers];
+
+/// State of session hints are chosen from
+#[derive(Debug, Clone, Default)]
+pub struct Situation {
+    /// Time since my last command
+    pub idle: Duration,
+    pub peers: usize,
+    pub games: usize,
+    pub invitations: usize,
+    pub my_turn: bool,
+}
+
+impl Hint {
+    fn applies(&self, situation: &Situation) -> bool {
+        match self {
+            Hint::AnswerInvitation => situation.invitations > 0,
+            Hint::TurnSyntax => situation.my_turn,
+            Hint::FindPeers => situation.peers == 0 && situation.games == 0,
+        }
+    }
+}
+
+/// Hints given so far with time they were given
+#[derive(Debug, Default)]
+pub struct Hints {
+    given: std::collections::HashMap<Hint, Instant>,
+}
+
+impl Hints {
+    /// Returns hint for situation, none when player is active or it was given recently
+    pub fn next(&mut self, situation: &Situation, now: Instant) -> Option<Hint> {
+        if situation.idle < IDLE_BEFORE_HINT {
+            return None;
+        }
+        let hint = RULES.iter().copied().filter(|hint| hint.applies(situation)).find(|hint| {
+            self.given.get(hint).map_or(true, |given| now.duration_since(*given) >= HINT_INTERVAL)
+        })?;
+        self.given.insert(hint, now);
+        Some(hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_follow_situation_and_are_throttled() {
+        let mut hints = Hints::default();
+        let now = Instant::now();
+        let mut situation = Situation { idle: IDLE_BEFORE_HINT, ..Situation::default() };
+        assert_eq!(hints.next(&Situation::default(), now), None, "active player gets no hints");
+        assert_eq!(hints.next(&situation, now), Some(Hint::FindPeers));
+        assert_eq!(hints.next(&situation, now), None, "the same hint is not repeated right away");
+
+        situation.invitations = 1;
+        situation.my_turn = true;
+        assert_eq!(hints.next(&situation, now), Some(Hint::AnswerInvitation));
+        assert_eq!(hints.next(&situation, now), Some(Hint::TurnSyntax));
+        assert_eq!(hints.next(&situation, now + HINT_INTERVAL), Some(Hint::AnswerInvitation));
+    }
+}
@@ -0,0 +1,128 @@
+//! # History
+//!
+//! Window of recent output events, frontends may keep a handle to fill their history panes
+
+use async_trait::async_trait;
+
+use super::input::Input;
+use super::observer::SwarmObserver;
+use super::OutputEvents;
+
+/// Ring buffer of output events, clones share the same buffer
+#[derive(Clone)]
+pub struct History {
+    events: std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<OutputEvents>>>,
+    capacity: usize,
+}
+
+impl History {
+    /// Creates history keeping at most given number of events
+    pub fn new(capacity: usize) -> History {
+        History {
+            events: Default::default(),
+            capacity,
+        }
+    }
+
+    /// Adds event, the oldest one is dropped when history is full
+    pub fn push(&self, event: OutputEvents) {
+        let mut events = self.events.lock().expect("history lock poisoned");
+        events.push_back(event);
+        while events.len() > self.capacity {
+            events.pop_front();
+        }
+    }
+
+    /// Returns recorded events, the oldest first
+    pub fn events(&self) -> Vec<OutputEvents> {
+        self.events.lock().expect("history lock poisoned").iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().expect("history lock poisoned").len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// User interface which records every output event into history
+pub struct Recorder<'a, UserInt> {
+    inner: &'a mut UserInt,
+    history: History,
+}
+
+impl<'a, UserInt: Input<super::Input, OutputEvents>> Recorder<'a, UserInt> {
+    pub fn new(inner: &'a mut UserInt, history: History) -> Self {
+        Recorder { inner, history }
+    }
+
+    /// Prints recorded events again without recording them twice
+    pub fn replay(&self) {
+        for event in self.history.events() {
+            self.inner.print_to_output(event);
+        }
+    }
+}
+
+#[async_trait]
+impl<UserInt: Input<super::Input, OutputEvents> + Send> Input<super::Input, OutputEvents> for Recorder<'_, UserInt> {
+    async fn get_input(&mut self) -> Option<super::Input> {
+        self.inner.get_input().await
+    }
+
+    fn print_to_output(&self, event: OutputEvents) {
+        // status line is replaced, not part of history
+        if !matches!(event, OutputEvents::Status(_)) {
+            self.history.push(event.clone());
+        }
+        self.inner.print_to_output(event);
+    }
+
+    fn ask(&mut self, prompt: super::prompt::Prompt) -> Option<super::prompt::Answer> {
+        self.inner.ask(prompt)
+    }
+}
+
+impl<UserInt: SwarmObserver> SwarmObserver for Recorder<'_, UserInt> {
+    fn on_peer_discovered(&mut self, peer_id: &str) {
+        self.inner.on_peer_discovered(peer_id);
+    }
+
+    fn on_peer_lost(&mut self, peer_id: &str) {
+        self.inner.on_peer_lost(peer_id);
+    }
+
+    fn on_listen_addr(&mut self, address: &str) {
+        self.inner.on_listen_addr(address);
+    }
+
+    fn on_config_reloaded(&mut self, config: &crate::config::Config) {
+        self.inner.on_config_reloaded(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_last_events() {
+        let history = History::new(2);
+        let handle = history.clone();
+        history.push(OutputEvents::Reminder(1));
+        history.push(OutputEvents::Reminder(2));
+        history.push(OutputEvents::Reminder(3));
+
+        let minutes: Vec<u64> = handle
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                OutputEvents::Reminder(minutes) => Some(minutes),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(minutes, vec![2, 3]);
+    }
+}
@@ -1,173 +1,209 @@
-use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
 use async_trait::async_trait;
 use tokio::io::AsyncBufReadExt;
 
+/// Writes line of output, see [`Stdio::write_line`]
+macro_rules! outln {
+    ($out:expr, $($arg:tt)*) => {
+        $out.write_line(format!($($arg)*))
+    };
+}
+
+mod commands;
+mod render;
+
+pub use commands::Commands;
+
+/// Frontend of the client. Stdio reads typed lines, terminal UI also turns clicks
+/// on playmat into the same inputs as typed commands.
 #[async_trait]
 pub trait Input<InputType, OutputType> {
     async fn get_input(&mut self) -> Option<InputType>;
     fn print_to_output(&self, outputType : OutputType);
+    /// Shows question to user. Frontend answers right away, e.g. from modal dialog,
+    /// or returns none and sends answer with prompt id from get_input later.
+    fn ask(&mut self, prompt : super::prompt::Prompt) -> Option<super::prompt::Answer>;
 }
 
 pub struct Stdio {
-    stdin : tokio::io::BufReader<tokio::io::Stdin>
+    stdin : tokio::io::Lines<tokio::io::BufReader<tokio::io::Stdin>>,
+    theme : crate::theme::Theme,
+    labels : crate::coords::Labels,
+    dates : crate::dates::DateFormat,
+    /// Prompts waiting for y or n, the last one is answered first
+    prompts : Vec<super::prompt::Prompt>,
+    /// Events held back by move delay of theme with time they are shown at
+    delayed : std::cell::RefCell<std::collections::VecDeque<(tokio::time::Instant, crate::network_communication::OutputEvents)>>,
+    /// Lines kept for other frontend instead of printing them, e.g. log pane of TUI
+    captured : Option<std::cell::RefCell<Vec<String>>>,
+    /// Rows of playmat of the active game, turns are parsed within it
+    board_size : std::cell::Cell<usize>,
 }
 
 #[async_trait]
 impl Input<crate::network_communication::Input, crate::network_communication::OutputEvents> for Stdio {
     async fn get_input(&mut self) -> Option<crate::network_communication::Input> {
-        let s = &mut self.stdin;
-        let line = s.lines().next_line().await.expect("can get line").expect("can read line from stdin");
-        Self::process_input(line.as_str())
+        loop {
+            let due = self.delayed.get_mut().front().map(|(at, _)| *at);
+            let read = match due {
+                Some(at) => tokio::select! {
+                    line = self.stdin.next_line() => Some(line),
+                    _ = tokio::time::sleep_until(at) => None,
+                },
+                None => Some(self.stdin.next_line().await),
+            };
+            match read {
+                Some(line) => {
+                    let line = line.expect("can get line").expect("can read line from stdin");
+                    return self.process_input(line.as_str());
+                }
+                None => self.render_due(),
+            }
+        }
     }
 
     fn print_to_output(&self, outputType : crate::network_communication::OutputEvents) {
-        match outputType {
-    super::OutputEvents::ListPeers(peers) => {
-        std::println!("Discovered {} peers.", peers.len());
-        peers.iter().enumerate().for_each(|(i, peer)| println!("{}: {}", i, peer));
-    },
-    super::OutputEvents::GameProposal(peer_id) => {
-        println!("<{}>: Do you want to play TicTacToe with me? y[es] or n[o] ?", peer_id);
-    }
-    super::OutputEvents::StartTrue(grid) => {
-        Self::print_table(grid);
-        println!("Make turn with command 'turn x y'");
-    },
-    super::OutputEvents::StartFalse => {
-        println!("No.");
-    },
-    super::OutputEvents::TurnResolved(grid) => {
-        Self::print_table(grid);
-        println!("your turn");
-    },
-    super::OutputEvents::GameOver => println!("You lose, game over!"),
-}
-    }
-}
-
-impl Stdio {
-    pub fn new() -> Self {
-        Stdio { stdin: tokio::io::BufReader::new(tokio::io::stdin()) }
-    }
-
-    fn print_table(grid : [[char; 3]; 3]) {
-        println!("  1   2   3");
-        println!("A {} | {} | {}", grid[0][0], grid[0][1], grid[0][2]);
-        println!("  ---------");
-        println!("B {} | {} | {}", grid[1][0], grid[1][1], grid[1][2]);
-        println!("  ---------");
-        println!("C {} | {} | {}", grid[2][0], grid[2][1], grid[2][2]);
-    }
-
-    fn print_string(text: &str) {
-        println!("{}", text);
-    }
-
-    fn print_help() {
-        println!("Available commands: ");
-    
-        Commands::iter()
-        .map(|comm| comm.description())
-        .for_each(|(name, desc)| println!("{:20} - {}", name, desc));
+        if let super::OutputEvents::TurnResolved(..) = outputType {
+            if !self.theme.move_delay.is_zero() {
+                outln!(self, "Opponent is moving...");
+                self.delay(outputType, self.theme.move_delay);
+                return;
+            }
+        }
+        if !self.delayed.borrow().is_empty() {
+            // keeps order of events, they wait behind delayed move
+            self.delay(outputType, std::time::Duration::ZERO);
+            return;
+        }
+        self.render(outputType);
     }
 
-    /*fn process_coords(line: &str) -> Option<crate::network_communication::Coordinates> {
-        match parse_coords(line) {
-            Ok(coords) => Some(coords),
-            Err(crate::network_communication::CoordinatesError::InvalidFormat) => { 
-                println!("Invalid format, use format 'turn <A|B|C> <1|2|3>'");
-                None
-            },
-            Err(crate::network_communication::CoordinatesError::InvalidValue) => {
-                println!("Invalid range, use values in format 'turn <A|B|C> <1|2|3>'");
-                None
-            },
-        }
-        
-    }*/
-
-    fn process_input(line : &str) -> Option<crate::network_communication::Input> {
-        match line {
-            cmd if cmd.starts_with(Commands::Help.to_string()) => { Self::print_help(); None }
-            cmd if cmd.starts_with(Commands::Peers.to_string()) => { Some(crate::network_communication::Input::ListPeers) }
-            cmd if cmd.starts_with(Commands::Turn.to_string()) => {
-                parse_coords(line).map(|(x, y)| crate::network_communication::Input::Turn(x, y) )
-            }
-            cmd if cmd.starts_with(Commands::Start.to_string()) => { 
-                cmd.strip_prefix("start ")
-                .map(|index| index.parse()).unwrap().ok()
-                .map(crate::network_communication::Input::InitiateGame)
+    fn ask(&mut self, prompt : super::prompt::Prompt) -> Option<super::prompt::Answer> {
+        match &prompt.question {
+            super::prompt::Question::Invitation(peer_id, proposal) => {
+                let introduction = &proposal.introduction;
+                match &introduction.nickname {
+                    Some(nickname) => outln!(self, "Invitation to TicTacToe from {} <{}>", super::display::sanitize_for_display(nickname), peer_id),
+                    None => outln!(self, "Invitation to TicTacToe from <{}>", peer_id),
+                }
+                if let Some(lookalike) = &proposal.lookalike {
+                    outln!(self, "  Beware, nickname looks like {}, peer may pass for someone else", super::display::sanitize_for_display(lookalike));
+                }
+                let record = match introduction.record {
+                    Some(record) => format!("{} won, {} lost by their word, ", record.won, record.lost),
+                    None => String::new(),
+                };
+                outln!(self, "  {}reputation {}{}", record, proposal.reputation, Self::reputation_marker(proposal.reputation));
+                let variant = match proposal.rules.variant {
+                    crate::tictactoe::Variant::Standard => "standard",
+                    crate::tictactoe::Variant::Wild => "wild, place either symbol, any line wins",
+                };
+                outln!(self, "  rules: {}", variant);
+                if let Some(board) = proposal.rules.board {
+                    outln!(self, "  playmat: {}", board);
+                }
+                if let Some(position) = proposal.rules.from_position {
+                    outln!(self, "  starts from position:");
+                    self.print_table(&position);
+                }
+                if let Some(start_at) = proposal.start_at {
+                    outln!(self, "  starts at: {}", self.dates.absolute(start_at));
+                }
+                if let Some(message) = &introduction.message {
+                    outln!(self, "  message: {}", super::display::sanitize_for_display(message));
+                }
+                match proposal.start_at {
+                    Some(_) => outln!(self, "Do you want to play? y[es], n[o] or counter <date> <time> ?"),
+                    None => outln!(self, "Do you want to play? y[es] or n[o] ?"),
+                }
             }
-            cmd if cmd == "y" || cmd == "yes" => {
-                Some(crate::network_communication::Input::Yes)
+            super::prompt::Question::Reschedule(peer_id, start_at) => {
+                outln!(self, "<{}> proposes to play at {} instead, agree? y[es] or n[o] ?", peer_id, self.dates.absolute(*start_at));
             }
-            cmd if cmd == "n" || cmd == "no" => {Some(crate::network_communication::Input::No) }
-            _ => {
-                None
+            super::prompt::Question::Review(peer_id) => {
+                outln!(self, "<{}> wants to review your last game, join? y[es] or n[o] ?", peer_id);
             }
         }
+        self.prompts.push(prompt);
+        None
     }
 }
 
-#[derive(Debug, EnumIter)]
-pub enum Commands {
-    Help,
-    Start,
-    Peers,
-    Turn,
+impl super::observer::SwarmObserver for Stdio {
+    fn on_config_reloaded(&mut self, config : &crate::config::Config) {
+        self.theme = crate::theme::Theme::from_config(&config.theme);
+        if let Ok(labels) = config.coordinates.clone().validated() {
+            self.labels = labels;
+        }
+        if let Ok(dates) = config.dates.clone().validated() {
+            self.dates = dates;
+        }
+    }
 }
 
-impl Commands {
-    pub fn to_string(&self) -> &'static str {
-        match self {
-            Commands::Help => "help",
-            Commands::Start => "start",
-            Commands::Peers => "peers",
-            Commands::Turn => "turn",
+impl Stdio {
+    pub fn new(theme : crate::theme::Theme, labels : crate::coords::Labels, dates : crate::dates::DateFormat) -> Self {
+        Stdio {
+            stdin: tokio::io::BufReader::new(tokio::io::stdin()).lines(),
+            theme,
+            labels,
+            dates,
+            prompts: Vec::new(),
+            delayed: Default::default(),
+            captured: None,
+            board_size: std::cell::Cell::new(crate::coords::SIZE),
         }
     }
 
-    fn description(&self) -> (&'static str, &'static str) {
-        match self {
-            Commands::Help => ("help", "prints help."),
-            Commands::Start => ("start <peer_index>", "sends peer with index <peer_index> offer to play."),
-            Commands::Peers => ("peers", "writes <index> : <peer_id> for all active peers."),
-            Commands::Turn => ("turn <row> <col>", "sends turn to opponent"),
+    /// Creates console which parses commands and renders events into lines
+    /// taken by [`Stdio::take_lines`], terminal is left to the caller
+    pub fn captured(theme : crate::theme::Theme, labels : crate::coords::Labels, dates : crate::dates::DateFormat) -> Self {
+        Stdio { captured: Some(Default::default()), ..Stdio::new(theme, labels, dates) }
+    }
+
+    /// Prints line, or keeps it when output is captured
+    fn write_line(&self, line : String) {
+        match &self.captured {
+            Some(captured) => captured.borrow_mut().push(line),
+            None => println!("{}", line),
         }
     }
-}
 
-fn parse_coords(line: &str) -> Option<crate::network_communication::Coordinates> {
-    let rest = line.strip_prefix("turn ");
-    let coords : Vec<&str> = rest.unwrap().split_whitespace().collect();
+    /// Prints failure to stderr, or keeps it with other lines when output is captured
+    fn write_error(&self, error : String) {
+        match &self.captured {
+            Some(captured) => captured.borrow_mut().push(format!("Error: {}", error)),
+            None => eprintln!("{}", error),
+        }
+    }
 
-    if coords.len() != 2 {
-        println!("Invalid number of arguments. Expected: 2.");
-        return None;
+    /// Returns lines written since last call, each line of multi-line text separately
+    pub fn take_lines(&self) -> Vec<String> {
+        match &self.captured {
+            Some(captured) => captured.take().iter().flat_map(|line| line.lines().map(str::to_string).collect::<Vec<_>>()).collect(),
+            None => Vec::new(),
+        }
     }
-    
-    let x = coords[0].parse::<char>();
-    let y = coords[1].parse::<usize>();
-    
-    match (x, y) {
-        (Ok(x), Ok(y)) => convert_coords(x, y),
-        (_, _) => {println!("Error while parsing arguments."); None}
+
+    /// Returns time when the next delayed event is due
+    pub fn next_due(&self) -> Option<tokio::time::Instant> {
+        self.delayed.borrow().front().map(|(at, _)| *at)
     }
-}
 
-fn convert_coords(x: char, y: usize) -> Option<crate::network_communication::Coordinates> {
-    let x= match x {
-        'A' => Some(0),
-        'B' => Some(1),
-        'C' => Some(2),
-        _ => return None,
-    };
+    /// Queues event to be shown after delay, but never before events queued earlier
+    fn delay(&self, outputType : crate::network_communication::OutputEvents, delay : std::time::Duration) {
+        let mut delayed = self.delayed.borrow_mut();
+        let now = tokio::time::Instant::now();
+        let after = delayed.back().map_or(now, |(at, _)| (*at).max(now));
+        delayed.push_back((after + delay, outputType));
+    }
 
-    if (1..=3).contains(&y)  {
-        Some((x.unwrap(), y-1))
-    } else {
-        println!("Value is not valid, use value 1-3.");
-        None
+    /// Shows delayed events whose time has come
+    pub fn render_due(&mut self) {
+        let now = tokio::time::Instant::now();
+        while self.delayed.get_mut().front().is_some_and(|(at, _)| *at <= now) {
+            if let Some((_, outputType)) = self.delayed.get_mut().pop_front() {
+                self.render(outputType);
+            }
+        }
     }
 }
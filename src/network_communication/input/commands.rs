@@ -0,0 +1,501 @@
+//! # Commands
+//!
+//! Typed commands of stdio frontend, their help and parsing of typed lines
+//! into inputs of the client.
+
+use crate::network_communication::{Coordinates, CoordinatesError, Input, LobbyCommand, SetupCommand};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+impl super::Stdio {
+    /// Answers the most recent question
+    fn answer(&mut self, answer : crate::network_communication::prompt::Answer) -> Option<Input> {
+        match self.prompts.pop() {
+            Some(prompt) => Some(Input::Answer(prompt.id, answer)),
+            None => {
+                outln!(self, "There is no question to answer.");
+                None
+            }
+        }
+    }
+
+    /// Proposes other start time for the latest invitation
+    fn counter(&mut self, start_at : u64) -> Option<Input> {
+        let is_invitation = |prompt : &crate::network_communication::prompt::Prompt| matches!(prompt.question, crate::network_communication::prompt::Question::Invitation(..));
+        match self.prompts.iter().rposition(is_invitation) {
+            Some(index) => Some(Input::CounterPropose(self.prompts.remove(index).id, start_at)),
+            None => {
+                outln!(self, "There is no invitation to answer.");
+                None
+            }
+        }
+    }
+
+    /// Splits trailing 'at <date> <time>' from arguments, none when the time is invalid
+    fn split_start_time<'a>(&self, args : Vec<&'a str>) -> Option<(Vec<&'a str>, Option<u64>)> {
+        match args.iter().position(|arg| *arg == "at") {
+            Some(at) => match self.dates.parse(&args[at + 1..].join(" ")) {
+                Ok(start_at) => Some((args[..at].to_vec(), Some(start_at))),
+                Err(error) => {
+                    outln!(self, "Invalid start time: {}.", error);
+                    None
+                }
+            },
+            None => Some((args, None)),
+        }
+    }
+
+    /// Splits trailing '-- <message>' attached to invitation from command
+    fn split_message(cmd : &str) -> (&str, Option<String>) {
+        match cmd.split_once(" -- ") {
+            Some((cmd, message)) if !message.trim().is_empty() => (cmd, Some(message.trim().to_string())),
+            Some((cmd, _)) => (cmd, None),
+            None => (cmd, None),
+        }
+    }
+
+    fn print_help(&self) {
+        outln!(self, "Available commands: ");
+    
+        Commands::iter()
+        .map(|comm| comm.description())
+        .for_each(|(name, desc)| outln!(self, "{:20} - {}", name, desc));
+    }
+
+    /// Commands shown in banner, enough to start first game
+    pub(super) fn print_quick_actions(&self) {
+        [Commands::Peers, Commands::Start, Commands::InviteCode, Commands::Join, Commands::Turn, Commands::Help]
+        .iter()
+        .map(|comm| comm.description())
+        .for_each(|(name, desc)| outln!(self, "  {:20} - {}", name, desc));
+    }
+
+    fn process_coords(&self, line: &str) -> Option<Coordinates> {
+        let args : Vec<&str> = line.strip_prefix("turn").unwrap_or_default().split_whitespace().collect();
+        // spoken field, e.g. 'turn top left' or 'turn center'
+        if let Some(field) = (1..=args.len().min(2)).rev().find_map(|count| self.labels.parse_callout(&args[..count].join(" ")).ok()) {
+            return Some(field);
+        }
+        let coords : Vec<&str> = args.into_iter().take(2).collect();
+
+        if coords.len() != 2 {
+            outln!(self, "Invalid number of arguments. Expected: 2.");
+            return None;
+        }
+
+        let size = self.board_size.get();
+        match self.labels.parse_sized(coords[0], coords[1], size) {
+            Ok(coords) => Some(coords),
+            Err(CoordinatesError::InvalidFormat) => { 
+                outln!(self, "Invalid format, use format '{}'", self.labels.turn_syntax_sized(size));
+                None
+            },
+            Err(CoordinatesError::InvalidValue) => {
+                outln!(self, "Invalid range, use values in format '{}'", self.labels.turn_syntax_sized(size));
+                None
+            },
+        }
+    }
+
+    pub fn process_input(&mut self, line : &str) -> Option<Input> {
+        match line {
+            cmd if cmd.starts_with(Commands::Help.to_string()) => {
+                self.print_help();
+                Some(Input::Help)
+            }
+            cmd if cmd.starts_with(Commands::Peers.to_string()) => { Some(Input::ListPeers) }
+            cmd if cmd.starts_with(Commands::Nudge.to_string()) => { Some(Input::Nudge) }
+            cmd if cmd == Commands::Resign.to_string() => Some(Input::Resign),
+            cmd if cmd == Commands::Undo.to_string() => Some(Input::Undo),
+            cmd if cmd == Commands::Quit.to_string() => Some(Input::Quit),
+            cmd if cmd.starts_with(Commands::Log.to_string()) => { Some(Input::Log) }
+            cmd if cmd.starts_with(Commands::InviteCode.to_string()) => {
+                Some(Input::InviteCode(cmd.split_whitespace().any(|arg| arg == "--qr")))
+            }
+            cmd if cmd.starts_with(Commands::Join.to_string()) => {
+                let (cmd, message) = Self::split_message(cmd);
+                let (args, start_at) = self.split_start_time(cmd.split_whitespace().skip(1).collect())?;
+                let code = args.first()?.to_string();
+                Some(Input::Join(code, args.get(1).map(|password| password.to_string()), start_at, message))
+            }
+            cmd if cmd.starts_with(Commands::Counter.to_string()) => {
+                match self.dates.parse(cmd.strip_prefix("counter").unwrap_or_default()) {
+                    Ok(start_at) => self.counter(start_at),
+                    Err(error) => {
+                        outln!(self, "Invalid start time: {}.", error);
+                        None
+                    }
+                }
+            }
+            cmd if cmd == Commands::Schedule.to_string() => Some(Input::Schedule),
+            cmd if cmd.starts_with(Commands::Say.to_string()) => {
+                cmd.strip_prefix("say ").map(str::trim).map(|text| {
+                    let board = text.strip_prefix("--board").filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+                    match board {
+                        Some(text) => Input::ChatBoard(text.trim().to_string()),
+                        None => Input::Chat(text.to_string()),
+                    }
+                })
+            }
+            cmd if cmd.starts_with(Commands::Lang.to_string()) => {
+                let language = cmd.split_whitespace().nth(1).map(str::to_string);
+                Some(Input::ChatLanguage(language))
+            }
+            cmd if cmd.starts_with(Commands::Score.to_string()) => Some(Input::Score),
+            cmd if cmd.starts_with(Commands::History.to_string()) => {
+                let count = cmd.split_whitespace().nth(1).and_then(|count| count.parse().ok());
+                Some(Input::History(count))
+            }
+            cmd if cmd.starts_with(Commands::Replay.to_string()) => {
+                let game = cmd.split_whitespace().nth(1).and_then(|game| game.parse().ok());
+                Some(Input::Replay(game))
+            }
+            cmd if cmd.starts_with(Commands::SendReplay.to_string()) => {
+                let args : Vec<&str> = cmd.split_whitespace().skip(1).collect();
+                let game = args.first()?.parse().ok()?;
+                Some(Input::SendReplay(game, args.get(1).map(|peer| peer.to_string())))
+            }
+            cmd if cmd.starts_with(Commands::Annotate.to_string()) => {
+                let (number, comment) = cmd.strip_prefix("annotate ")?.trim().split_once(' ')?;
+                let comment = comment.trim().trim_matches('"').to_string();
+                Some(Input::Annotate(number.parse().ok()?, comment))
+            }
+            cmd if cmd.starts_with(Commands::Setup.to_string()) => {
+                let args : Vec<&str> = cmd.split_whitespace().skip(1).collect();
+                let command = match args.as_slice() {
+                    [] => SetupCommand::Begin,
+                    ["analyze"] => SetupCommand::Analyze,
+                    ["puzzle"] => SetupCommand::Puzzle,
+                    ["propose", index] => SetupCommand::Propose(index.to_string()),
+                    ["end"] => SetupCommand::End,
+                    [row, col, tile] => {
+                        let tile = match *tile {
+                            "x" => crate::tictactoe::Tile::Cross,
+                            "o" => crate::tictactoe::Tile::Circle,
+                            "-" => crate::tictactoe::Tile::Empty,
+                            other => {
+                                outln!(self, "Unknown symbol '{}', use x, o or - to clear the field.", other);
+                                return None;
+                            }
+                        };
+                        match self.labels.parse(row, col) {
+                            Ok(field) => SetupCommand::Place(field, tile),
+                            Err(_) => {
+                                outln!(self, "Invalid field, use format 'setup {} x'", self.labels.turn_syntax().trim_start_matches("turn "));
+                                return None;
+                            }
+                        }
+                    }
+                    _ => {
+                        outln!(self, "Use 'setup', 'setup <row> <col> x|o|-', 'setup analyze', 'setup puzzle', 'setup propose <peer_index>' or 'setup end'.");
+                        return None;
+                    }
+                };
+                Some(Input::Setup(command))
+            }
+            cmd if cmd.starts_with(Commands::Drill.to_string()) => {
+                let args : Vec<&str> = cmd.split_whitespace().skip(1).collect();
+                match args.as_slice() {
+                    [] => Some(Input::Drill(None)),
+                    [row, col] => match self.labels.parse(row, col) {
+                        Ok(field) => Some(Input::Drill(Some(field))),
+                        Err(_) => {
+                            outln!(self, "Invalid field, use format 'drill {}'", self.labels.turn_syntax().trim_start_matches("turn "));
+                            None
+                        }
+                    },
+                    _ => {
+                        outln!(self, "Invalid number of arguments. Expected: 0 or 2.");
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Review.to_string()) => {
+                match cmd.split_whitespace().nth(1) {
+                    Some("end") => Some(Input::EndReview),
+                    _ => Some(Input::Review),
+                }
+            }
+            cmd if cmd == Commands::Next.to_string() => {
+                Some(Input::ReviewNavigate(crate::network_communication::review::ReviewStep::Next))
+            }
+            cmd if cmd == Commands::Prev.to_string() => {
+                Some(Input::ReviewNavigate(crate::network_communication::review::ReviewStep::Prev))
+            }
+            cmd if cmd.starts_with(Commands::Goto.to_string()) => {
+                cmd.split_whitespace().nth(1)
+                .and_then(|position| position.parse().ok())
+                .map(|position| Input::ReviewNavigate(crate::network_communication::review::ReviewStep::Goto(position)))
+            }
+            cmd if cmd == Commands::NetStats.to_string() => Some(Input::NetStats),
+            cmd if cmd == Commands::NetInfo.to_string() => Some(Input::NetInfo),
+            cmd if cmd == Commands::WhoAmI.to_string() => Some(Input::WhoAmI),
+            cmd if cmd == Commands::ReconnectKnown.to_string() => Some(Input::ReconnectKnown),
+            cmd if cmd == Commands::Pending.to_string() => Some(Input::Pending),
+            cmd if cmd == Commands::Ladder.to_string() => Some(Input::Ladder),
+            cmd if cmd.starts_with(Commands::Lobby.to_string()) => {
+                let command = match cmd.split_whitespace().nth(1) {
+                    None => LobbyCommand::List,
+                    Some("seek") => LobbyCommand::Seek,
+                    Some("leave") => LobbyCommand::Leave,
+                    Some(_) => {
+                        outln!(self, "Use 'lobby', 'lobby seek' or 'lobby leave'.");
+                        return None;
+                    }
+                };
+                Some(Input::Lobby(command))
+            }
+            cmd if cmd.starts_with(Commands::Watch.to_string()) => {
+                match cmd.split_whitespace().nth(1) {
+                    Some(game_id) => Some(Input::Watch(game_id.to_string())),
+                    None => {
+                        outln!(self, "Use 'watch <game-id>', game ids are listed by 'games' of its players.");
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Referee.to_string()) => {
+                match cmd.split_whitespace().nth(1) {
+                    Some(game_id) => Some(Input::Referee(game_id.to_string())),
+                    None => {
+                        outln!(self, "Use 'referee <game-id>', game ids are listed by 'games' of its players.");
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Clear.to_string()) => {
+                match cmd.split_whitespace().nth(1).and_then(crate::network_communication::pending::PendingId::parse) {
+                    Some(id) => {
+                        if let crate::network_communication::pending::PendingId::Prompt(prompt_id) = id {
+                            self.prompts.retain(|prompt| prompt.id != prompt_id);
+                        }
+                        Some(Input::Clear(id))
+                    }
+                    None => {
+                        outln!(self, "Use 'clear <id>' with id listed by 'pending', e.g. 'clear i0'.");
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Reconnect.to_string()) => {
+                match cmd.split_whitespace().skip(1).map(str::parse).collect::<Result<Vec<libp2p::Multiaddr>, _>>() {
+                    Ok(addresses) => Some(Input::Reconnect(addresses)),
+                    Err(error) => {
+                        outln!(self, "Invalid listen address: {}.", error);
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Connect.to_string()) => {
+                let address = cmd.split_whitespace().nth(1).unwrap_or_default();
+                match crate::network_communication::discovery::parse_peer_address(address) {
+                    Ok((peer, address)) => Some(Input::Connect(peer, address)),
+                    Err(error) => {
+                        outln!(self, "{}.", error);
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Audit.to_string()) => {
+                match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+                    [_, "export", game_id] => Some(Input::AuditExport(game_id.to_string())),
+                    _ => {
+                        outln!(self, "Use 'audit export <game-id>', game ids are listed by 'games'.");
+                        None
+                    }
+                }
+            }
+            cmd if cmd.starts_with(Commands::Games.to_string()) => {
+                match cmd.split_whitespace().nth(1) {
+                    Some("--pending") => Some(Input::PendingGames),
+                    _ => Some(Input::ListGames),
+                }
+            }
+            cmd if cmd.starts_with(Commands::Game.to_string()) => {
+                cmd.strip_prefix("game ")
+                .and_then(|index| index.trim().parse().ok())
+                .map(Input::SwitchGame)
+            }
+            cmd if cmd.starts_with(Commands::Turn.to_string()) => {
+                // '@<index>' addresses turn to other than the active game
+                let game = match cmd.split_whitespace().find_map(|arg| arg.strip_prefix('@')) {
+                    None => None,
+                    Some(index) => match index.parse::<usize>() {
+                        Ok(index) => Some(index),
+                        Err(_) => {
+                            outln!(self, "Invalid game '@{}', use index listed by 'games'.", index);
+                            return None;
+                        }
+                    },
+                };
+                let line = cmd.split_whitespace().filter(|arg| !arg.starts_with('@')).collect::<Vec<_>>().join(" ");
+                let mark = match line.split_whitespace().nth(3) {
+                    None => None,
+                    Some("x") => Some(crate::tictactoe::Tile::Cross),
+                    Some("o") => Some(crate::tictactoe::Tile::Circle),
+                    Some(other) => {
+                        outln!(self, "Unknown symbol '{}', use x or o.", other);
+                        return None;
+                    }
+                };
+                self.process_coords(&line).map(|(x, y)| match game {
+                    Some(index) => Input::TurnIn(index, x, y, mark),
+                    None => Input::Turn(x, y, mark),
+                })
+            }
+            cmd if cmd.starts_with(Commands::Start.to_string()) => { 
+                let (cmd, message) = Self::split_message(cmd);
+                let (args, start_at) = self.split_start_time(cmd.split_whitespace().skip(1).collect())?;
+                let index = args.first()?.to_string();
+                Some(Input::InitiateGame(index, args.get(1).map(|password| password.to_string()), start_at, message))
+            }
+            cmd if cmd == "y" || cmd == "yes" => self.answer(crate::network_communication::prompt::Answer::Yes),
+            cmd if cmd == "n" || cmd == "no" => self.answer(crate::network_communication::prompt::Answer::No),
+            cmd if cmd.trim().is_empty() => None,
+            cmd => match self.labels.parse_callout(cmd) {
+                // field called out alone, e.g. by voice control wrapper
+                Ok((x, y)) => Some(Input::Turn(x, y, None)),
+                Err(_) => Some(Input::Plugin(cmd.split_whitespace().map(str::to_string).collect())),
+            },
+        }
+    }
+}
+
+#[derive(Debug, EnumIter)]
+pub enum Commands {
+    Help,
+    Start,
+    Peers,
+    Turn,
+    Nudge,
+    Resign,
+    Undo,
+    Games,
+    Game,
+    Log,
+    InviteCode,
+    Join,
+    Counter,
+    Schedule,
+    Say,
+    Lang,
+    Replay,
+    SendReplay,
+    Annotate,
+    History,
+    Score,
+    Drill,
+    Review,
+    Next,
+    Prev,
+    Goto,
+    Audit,
+    NetStats,
+    NetInfo,
+    WhoAmI,
+    Reconnect,
+    ReconnectKnown,
+    Connect,
+    Pending,
+    Clear,
+    Ladder,
+    Lobby,
+    Watch,
+    Referee,
+    Setup,
+    Quit,
+}
+
+impl Commands {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            Commands::Help => "help",
+            Commands::Start => "start",
+            Commands::Peers => "peers",
+            Commands::Turn => "turn",
+            Commands::Nudge => "nudge",
+            Commands::Resign => "resign",
+            Commands::Undo => "undo",
+            Commands::Games => "games",
+            Commands::Game => "game",
+            Commands::Log => "log",
+            Commands::InviteCode => "invite-code",
+            Commands::Join => "join",
+            Commands::Counter => "counter",
+            Commands::Schedule => "schedule",
+            Commands::Say => "say",
+            Commands::Lang => "lang",
+            Commands::Replay => "replay",
+            Commands::SendReplay => "send-replay",
+            Commands::Annotate => "annotate",
+            Commands::History => "history",
+            Commands::Score => "score",
+            Commands::Drill => "drill",
+            Commands::Review => "review",
+            Commands::Next => "next",
+            Commands::Prev => "prev",
+            Commands::Goto => "goto",
+            Commands::Audit => "audit",
+            Commands::NetStats => "netstats",
+            Commands::NetInfo => "netinfo",
+            Commands::WhoAmI => "whoami",
+            Commands::Reconnect => "reconnect",
+            Commands::ReconnectKnown => "reconnect-known",
+            Commands::Connect => "connect",
+            Commands::Pending => "pending",
+            Commands::Clear => "clear",
+            Commands::Ladder => "ladder",
+            Commands::Lobby => "lobby",
+            Commands::Watch => "watch",
+            Commands::Referee => "referee",
+            Commands::Setup => "setup",
+            Commands::Quit => "quit",
+        }
+    }
+
+    fn description(&self) -> (&'static str, &'static str) {
+        match self {
+            Commands::Help => ("help", "prints help."),
+            Commands::Start => ("start <peer_index> [<password>] [at <date> <time>] [-- <message>]", "sends peer with index <peer_index>, or with given peer id, offer to play, optionally later and with message. 'start bot' plays against built-in AI, 'start #<n>' joins open game <n> listed by 'lobby'."),
+            Commands::Peers => ("peers", "writes <index> : <peer_id> for all active peers."),
+            Commands::Turn => ("turn <row> <col> [x|o] [@<game>]", "sends turn to opponent, symbol can be chosen in wild variant. With @<game> the turn goes to game with that index, which becomes active. Field can be called out in words, e.g. 'turn top left', or alone, e.g. 'center'."),
+            Commands::Nudge => ("nudge", "reminds opponent that it is their turn."),
+            Commands::Resign => ("resign", "gives up current game, opponent wins."),
+            Commands::Undo => ("undo", "takes back resignation, declined or withdrawn invitation within few seconds."),
+            Commands::Games => ("games [--pending]", "lists active games, or only those awaiting your move."),
+            Commands::Game => ("game <index>", "switches to game with index <index>."),
+            Commands::Log => ("log", "prints recent messages again."),
+            Commands::InviteCode => ("invite-code [--qr]", "prints your invite code, optionally as QR code."),
+            Commands::Join => ("join <code> [<password>] [at <date> <time>] [-- <message>]", "sends offer to play to peer with invite code <code>."),
+            Commands::Counter => ("counter <date> <time>", "answers invitation by proposing other start time, e.g. 2024-03-09 18:00."),
+            Commands::Schedule => ("schedule", "lists games with agreed or proposed start time."),
+            Commands::Say => ("say [--board] <text>", "sends chat message to opponent, with current position as emoji board."),
+            Commands::Lang => ("lang [<code>]", "sets language for chat in current game, none for default."),
+            Commands::Replay => ("replay [<n>]", "shows n-th most recent finished game, the last one by default."),
+            Commands::SendReplay => ("send-replay <n> [<peer_index>]", "sends n-th most recent finished game to current opponent or to peer with index."),
+            Commands::Annotate => ("annotate <move> \"<text>\"", "comments move of the replayed game."),
+            Commands::History => ("history [<n>]", "lists n most recent finished games with their time, 10 by default."),
+            Commands::Score => ("score", "shows score against current or last opponent and overall."),
+            Commands::Drill => ("drill [<row> <col>]", "shows position where you blundered, answer with the best move."),
+            Commands::Review => ("review [end]", "reviews last game together with its opponent, or ends the review."),
+            Commands::Next => ("next", "shows next move of reviewed game to both players."),
+            Commands::Prev => ("prev", "shows previous move of reviewed game to both players."),
+            Commands::Goto => ("goto <move>", "shows position after given move of reviewed game to both players."),
+            Commands::Audit => ("audit export <game-id>", "writes signed log of messages exchanged in game to a file."),
+            Commands::NetStats => ("netstats", "shows message and traffic counters of each game."),
+            Commands::NetInfo => ("netinfo", "shows your addresses, how peers see you and whether peers on internet can dial you."),
+            Commands::WhoAmI => ("whoami", "shows your peer id, fingerprint, nickname and addresses."),
+            Commands::Reconnect => ("reconnect [<address>...]", "restarts network, optionally listening on new addresses, games continue."),
+            Commands::ReconnectKnown => ("reconnect-known", "dials recent opponents at their last addresses, without waiting for discovery."),
+            Commands::Connect => ("connect <address>", "dials peer on other network, e.g. /ip4/1.2.3.4/tcp/4001/p2p/<peer id> or through relay /ip4/1.2.3.4/tcp/4001/p2p/<relay id>/p2p-circuit/p2p/<peer id>."),
+            Commands::Pending => ("pending", "lists my unanswered invitations, messages queued for offline opponents and open questions."),
+            Commands::Clear => ("clear <id>", "drops pending item, queued turn is taken back and question is declined."),
+            Commands::Ladder => ("ladder", "shows ladder of my room, win over player up to two positions above swaps us."),
+            Commands::Lobby => ("lobby [seek|leave]", "lists players of my room looking for a game, 'lobby seek' announces I look for one with my rules, 'start #<n>' plays open game <n>."),
+            Commands::Quit => ("quit", "resigns running games, unless they continue after restart, and exits."),
+            Commands::Watch => ("watch <game-id>", "shows moves of game played by other peers as they come, again to stop watching."),
+            Commands::Referee => ("referee <game-id>", "checks every move of game played by other peers and sends them signed verdict, again to stop."),
+            Commands::Setup => ("setup [<row> <col> x|o|-]", "sets up position by hand, then 'setup analyze', 'setup puzzle', 'setup propose <peer_index>' or 'setup end'."),
+        }
+    }
+}
@@ -0,0 +1,559 @@
+//! # Rendering
+//!
+//! Turns output events into lines of stdio frontend: playmats, tables of games
+//! and peers, evaluations and messages.
+
+use super::Commands;
+use crate::network_communication::{GameSummary, OutputEvents, PUZZLE_ID};
+
+impl super::Stdio {
+    /// Writes event as lines, playmat rows are labelled by theme
+    pub(super) fn render(&self, outputType : OutputEvents) {
+        match outputType {
+    // terminal has no room to keep it on screen
+    OutputEvents::Status(_) => {}
+    OutputEvents::Error(error) => self.write_error(error),
+    OutputEvents::ListPeers(peers) => {
+        outln!(self, "Discovered {} peers.", peers.len());
+        peers.iter().enumerate().for_each(|(i, peer)| outln!(self, "{}: {}{}{}{}",
+            i,
+            peer.peer_id,
+            Self::reputation_marker(peer.reputation),
+            peer.seen_secs_ago.map(|secs| format!(" (seen {}s ago)", secs)).unwrap_or_default(),
+            if peer.discovered_by.is_empty() { String::new() } else { format!(" via {}", peer.discovered_by.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")) }));
+    },
+    OutputEvents::StartTrue(grid, evaluation) => {
+        self.board_size.set(grid.len());
+        self.print_table(&grid);
+        self.print_evaluation(evaluation);
+        outln!(self, "Make turn with command '{}'", self.labels.turn_syntax_sized(grid.len()));
+    },
+    OutputEvents::StartFalse => {
+        outln!(self, "No.");
+    },
+    OutputEvents::TurnResolved(grid, evaluation) => {
+        self.board_size.set(grid.len());
+        self.print_table(&grid);
+        self.print_evaluation(evaluation);
+        outln!(self, "your turn");
+    },
+    OutputEvents::GameOver => outln!(self, "You lose, game over!"),
+    OutputEvents::Draw(peer_id) => outln!(self, "Draw with <{}>, playmat is full.", peer_id),
+    OutputEvents::Won(peer_id) => outln!(self, "You won against <{}>!", peer_id),
+    OutputEvents::SecurityWarning(peer_id) => {
+        outln!(self, "Warning: ignored game message from {}, who is not your opponent.", peer_id);
+    }
+    OutputEvents::Ignored(peer_id, kind) => outln!(self, "Ignored {} from <{}>, peer is not your opponent.", kind, peer_id),
+    OutputEvents::Diagnostics(peer_id, error, diagnostics) => {
+        outln!(self, "Rejected message from {}: {} ({} malformed, {} invalid so far).",
+            peer_id, error, diagnostics.malformed, diagnostics.invalid);
+    }
+    OutputEvents::Reminder(minutes) => {
+        outln!(self, "It has been your turn for {} minutes, use 'turn <row> <col>'.", minutes);
+    }
+    OutputEvents::OpponentSlow(minutes) => {
+        outln!(self, "Opponent is taking a while ({} minutes), you can ping them with 'nudge'.", minutes);
+    }
+    OutputEvents::Nudged(peer_id) => outln!(self, "<{}>: It is your turn!", peer_id),
+    OutputEvents::Shutdown => outln!(self, "Network stopped, exiting."),
+    OutputEvents::ListeningOn(address) => outln!(self, "Listening also on {}", address),
+    OutputEvents::Pending(items) if items.is_empty() => outln!(self, "Nothing is pending."),
+    OutputEvents::Pending(items) => {
+        outln!(self, "{} pending items, drop one with 'clear <id>':", items.len());
+        for item in &items {
+            let description = match item {
+                crate::network_communication::pending::Pending::Invitation(_, peer_id, seconds) => {
+                    format!("my invitation of <{}>, waiting {} s", peer_id, seconds)
+                }
+                crate::network_communication::pending::Pending::Queued(_, peer_id, kind) => format!("{} queued for offline <{}>", kind, peer_id),
+                crate::network_communication::pending::Pending::Prompt(prompt) => match &prompt.question {
+                    crate::network_communication::prompt::Question::Invitation(peer_id, _) => format!("invitation from <{}>", peer_id),
+                    crate::network_communication::prompt::Question::Reschedule(peer_id, _) => format!("other start time from <{}>", peer_id),
+                    crate::network_communication::prompt::Question::Review(peer_id) => format!("review proposed by <{}>", peer_id),
+                },
+            };
+            outln!(self, "  {:5} {}", item.id().to_string(), description);
+        }
+    }
+    OutputEvents::Cleared(id) => outln!(self, "Dropped {}.", id),
+    OutputEvents::NoSuchPending(id) => outln!(self, "Nothing pending has id {}, see 'pending'.", id),
+    OutputEvents::Ladder(None) => outln!(self, "Ladder is played only in a room with ladder_file set in config."),
+    OutputEvents::Ladder(Some(positions)) if positions.is_empty() => outln!(self, "Ladder has no results yet."),
+    OutputEvents::Ladder(Some(positions)) => {
+        for (position, player) in positions.iter().enumerate() {
+            let note = if player.me {
+                " (me)"
+            } else if player.challengeable {
+                " (may be challenged)"
+            } else {
+                ""
+            };
+            outln!(self, "{:3}. <{}>{}", position + 1, crate::network_communication::display::sanitize_for_display(&player.peer_id), note);
+        }
+    }
+    OutputEvents::Attested(attestation) => {
+        outln!(self, "Ladder: <{}> beat <{}>.", crate::network_communication::display::sanitize_for_display(&attestation.winner), attestation.loser);
+    }
+    OutputEvents::Hint(crate::network_communication::hints::Hint::FindPeers) => {
+        outln!(self, "Hint: nobody is around yet. '{}' lists players found on network, '{}' reaches a friend by invite code.",
+            Commands::Peers.to_string(), Commands::Join.to_string());
+    }
+    OutputEvents::Hint(crate::network_communication::hints::Hint::AnswerInvitation) => {
+        outln!(self, "Hint: invitation waits for your answer, 'yes' accepts it, 'no' declines, '{}' lists open questions.",
+            Commands::Pending.to_string());
+    }
+    OutputEvents::Hint(crate::network_communication::hints::Hint::TurnSyntax) => {
+        outln!(self, "Hint: it is your turn, play it with '{}'.", self.labels.turn_syntax());
+    }
+    OutputEvents::Undelivered(peer_id, reason) => {
+        outln!(self, "Cannot reach <{}> directly ({}), message was broadcast instead.", peer_id, crate::network_communication::display::sanitize_for_display(&reason));
+    }
+    OutputEvents::HolePunch(peer_id, Ok(())) => {
+        outln!(self, "Connection to <{}> is direct now, it no longer goes through relay.", peer_id);
+    }
+    OutputEvents::HolePunch(peer_id, Err(reason)) => {
+        outln!(self, "Cannot connect to <{}> directly ({}), game goes through relay.", peer_id, crate::network_communication::display::sanitize_for_display(&reason));
+    }
+    OutputEvents::OlderPeer(peer_id, features) => {
+        outln!(self, "<{}> runs older version of the game, unavailable with them: {}.", peer_id, features.join(", "));
+    }
+    OutputEvents::NewerPeer(peer_id, version) => {
+        outln!(self, "<{}> runs newer version of the game (protocol {}), its message was ignored. Update the game to play with them.", peer_id, version);
+    }
+    OutputEvents::KnownPeersDialed(0) => {
+        outln!(self, "No known opponent to dial, opponents are remembered in correspondence directory.");
+    }
+    OutputEvents::KnownPeersDialed(dialed) => outln!(self, "Dialing {} known opponents.", dialed),
+    OutputEvents::OpenGames(games) if games.is_empty() => outln!(self, "Nobody looks for a game, 'lobby seek' announces I do."),
+    OutputEvents::OpenGames(games) => {
+        for (index, game) in games.iter().enumerate() {
+            let nickname = match &game.nickname {
+                Some(nickname) => format!(" {}", crate::network_communication::display::sanitize_for_display(nickname)),
+                None => String::new(),
+            };
+            let board = game.rules.board.map(|board| format!(", {}", board)).unwrap_or_default();
+            let position = if game.rules.from_position.is_some() { ", from position" } else { "" };
+            outln!(self, "#{} : <{}>{} plays {}{}{}", index + 1, game.peer_id, nickname, game.rules.variant.name(), board, position);
+        }
+    }
+    OutputEvents::Seeking => outln!(self, "Looking for a game, players of my room see it in 'lobby' until game starts or 'lobby leave'."),
+    OutputEvents::LeftLobby => outln!(self, "No longer looking for a game."),
+    OutputEvents::NoSuchOpenGame(number) => outln!(self, "No open game {}, 'lobby' lists them.", number),
+    OutputEvents::Dialing(peer_id, address) => {
+        outln!(self, "Dialing <{}> at {}, 'peers' lists them once connected.", peer_id, address);
+    }
+    OutputEvents::Reconnected(addresses) => {
+        outln!(self, "Network restarted on {}, games resume once opponents reconnect.",
+            addresses.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+    }
+    OutputEvents::Games(games) => {
+        outln!(self, "{} active games.", games.len());
+        games.iter().for_each(|game| self.print_game(game));
+    }
+    OutputEvents::PendingGames(games) => {
+        outln!(self, "{} games await your move.", games.len());
+        games.iter().for_each(|game| self.print_game(game));
+    }
+    OutputEvents::SwitchedGame(index, grid) => {
+        self.board_size.set(grid.len());
+        outln!(self, "Game {}:", index);
+        self.print_table(&grid);
+    }
+    OutputEvents::NoSuchGame(index) => outln!(self, "There is no game {}, list games with 'games'.", index),
+    OutputEvents::AlreadyPlaying(peer_id) => {
+        outln!(self, "You already play <{}>, find the game with 'games' and switch to it with 'game <number>'.", peer_id);
+    }
+    OutputEvents::BoardChanged(index, peer_id) => {
+        outln!(self, "Game {}: <{}> moved, switch with 'game {}'.", index, peer_id, index);
+    }
+    OutputEvents::SimulAccepted(index, peer_id) => outln!(self, "Game {}: accepted challenge from <{}>.", index, peer_id),
+    OutputEvents::SimulFull(peer_id) => outln!(self, "Declined challenge from <{}>, all boards are taken.", peer_id),
+    OutputEvents::EnginePlayed((x, y), grid, evaluation) => {
+        outln!(self, "Engine played {}{}.", self.labels.row(x), self.labels.col(y));
+        self.print_table(&grid);
+        self.print_evaluation(evaluation);
+    }
+    OutputEvents::EngineError(error) => outln!(self, "Engine error: {}", error),
+    OutputEvents::NoActiveGame => {
+        outln!(self, "You are not playing any game, list peers with 'peers' and invite one with 'start <peer_index>'.");
+    }
+    OutputEvents::GameNotStarted(peer_id, true) => {
+        outln!(self, "Game with <{}> has not started yet, answer their invitation with y[es] or n[o].", peer_id);
+    }
+    OutputEvents::GameNotStarted(peer_id, false) => {
+        outln!(self, "Game with <{}> has not started yet, wait until they accept your invitation.", peer_id);
+    }
+    OutputEvents::CounterProposed(peer_id, start_at) => {
+        outln!(self, "Proposed to play with <{}> at {}, waiting for their answer.", peer_id, self.dates.absolute(start_at));
+    }
+    OutputEvents::ScheduleAgreed(peer_id, start_at) => {
+        outln!(self, "<{}> agreed to play at {}, see 'schedule'.", peer_id, self.dates.absolute(start_at));
+    }
+    OutputEvents::Schedule(games) => {
+        if games.is_empty() {
+            outln!(self, "No games are scheduled.");
+        }
+        games.iter().for_each(|game| outln!(self, "{} <{}>{}",
+            self.dates.absolute(game.start_at),
+            game.opponent_id,
+            if game.agreed { "" } else { " (proposed)" }));
+    }
+    OutputEvents::ScheduledGameDue(peer_id) => {
+        outln!(self, "It is time for your game with <{}>.", peer_id);
+    }
+    OutputEvents::RaceResolved(peer_id, true) => {
+        outln!(self, "You and <{}> played at the same time, you started the game, so your turn stands.", peer_id);
+    }
+    OutputEvents::RaceResolved(peer_id, false) => {
+        outln!(self, "You and <{}> played at the same time, they started the game, so their turn stands.", peer_id);
+        outln!(self, "Your turn was taken back, waiting for their turn.");
+    }
+    OutputEvents::NotYourTurn(peer_id) => {
+        outln!(self, "It is <{}>'s turn, wait for their move or remind them with 'nudge'.", peer_id);
+    }
+    OutputEvents::GameFinished(peer_id) => {
+        outln!(self, "Game with <{}> is already over, go through it with 'review' or invite a peer with 'start <peer_index>'.", peer_id);
+    }
+    OutputEvents::FieldOccupied((x, y), yours, number) => {
+        outln!(self, "Field {}{} is already taken by {} on move {}, choose an empty one.",
+            self.labels.row(x), self.labels.col(y), if yours { "you" } else { "opponent" }, number);
+    }
+    OutputEvents::OutOfRange(x, y) => {
+        outln!(self, "Field ({}, {}) is outside of playmat, use '{}'.", x, y, self.labels.turn_syntax());
+    }
+    OutputEvents::LosingTurn((x, y), (threat_x, threat_y)) => {
+        outln!(self, "After {}{} opponent wins at {}{}, repeat the turn to play it anyway.",
+            self.labels.row(x), self.labels.col(y), self.labels.row(threat_x), self.labels.col(threat_y));
+    }
+    OutputEvents::OpponentLeft(peer_id) => outln!(self, "<{}> disconnected, game is adjourned until they return.", peer_id),
+    OutputEvents::ForfeitPending(peer_id, seconds) => {
+        outln!(self, "<{}> disconnected, they forfeit unless back within {} seconds.", peer_id, seconds);
+    }
+    OutputEvents::WonByForfeit(peer_id) => outln!(self, "<{}> did not return, you win by forfeit!", peer_id),
+    OutputEvents::TurnTimeout(peer_id) => outln!(self, "You did not move in time and lost the game against <{}>.", peer_id),
+    OutputEvents::OpponentTimeout(peer_id) => outln!(self, "<{}> did not move in time, you win by forfeit!", peer_id),
+    OutputEvents::Resigned(peer_id) => outln!(self, "You resigned the game against <{}>.", peer_id),
+    OutputEvents::ActionHeld(peer_id, action, seconds) => {
+        let action = match action {
+            crate::network_communication::undo::Action::Resign => "resign the game against",
+            crate::network_communication::undo::Action::Withdraw => "drop invitation of",
+        };
+        outln!(self, "You {} <{}> in {} seconds, type 'undo' to take it back.", action, peer_id, seconds);
+    }
+    OutputEvents::Undone(peer_id, action) => {
+        let action = match action {
+            crate::network_communication::undo::Action::Resign => "game against",
+            crate::network_communication::undo::Action::Withdraw => "invitation of",
+        };
+        outln!(self, "Taken back, the {} <{}> goes on.", action, peer_id);
+    }
+    OutputEvents::NothingToUndo => outln!(self, "There is nothing to undo."),
+    OutputEvents::GameClosing(peer_id) => outln!(self, "Game against <{}> is ending, type 'undo' to continue it.", peer_id),
+    OutputEvents::OpponentResigned(peer_id) => outln!(self, "<{}> resigned, you win!", peer_id),
+    OutputEvents::AutoMoved(peer_id, (x, y), grid) => {
+        outln!(self, "You seem away, AI played {}{} for you against <{}>.", self.labels.row(x), self.labels.col(y), peer_id);
+        self.print_table(&grid);
+    }
+    OutputEvents::OpponentAutoMoved(peer_id) => outln!(self, "<{}> seems away, their client played the last move for them.", peer_id),
+    OutputEvents::GameVoided(peer_id) => outln!(self, "<{}> disconnected, game is void.", peer_id),
+    OutputEvents::OpponentReturned(peer_id) => outln!(self, "<{}> is back, game continues.", peer_id),
+    OutputEvents::InvitationExpired(peer_id) => outln!(self, "<{}> did not answer, invitation withdrawn.", peer_id),
+    OutputEvents::InvitationWithdrawn(peer_id) => outln!(self, "<{}> withdrew the invitation, it has expired.", peer_id),
+    OutputEvents::Laggy(peer_id, round_trip, average) => {
+        outln!(self, "Laggy connection to <{}>: {} ms round trip, usually {} ms.", peer_id, round_trip, average);
+    }
+    OutputEvents::InviteCode(code, qr) => {
+        outln!(self, "Your invite code: {}", code);
+        if let Some(qr) = qr.then(|| crate::network_communication::invite::qr(&code)).flatten() {
+            outln!(self, "{}", qr);
+        } else if qr {
+            outln!(self, "QR code is not available in this build.");
+        }
+    }
+    OutputEvents::InvalidInvite(error) => outln!(self, "Cannot join: {}.", error),
+    OutputEvents::ConfigReloaded(summary) => {
+        if !summary.applied.is_empty() {
+            outln!(self, "Config reloaded, changed: {}.", summary.applied.join(", "));
+        }
+        if !summary.needs_restart.is_empty() {
+            outln!(self, "Restart to apply: {}.", summary.needs_restart.join(", "));
+        }
+    }
+    OutputEvents::WrongMark(mark) => outln!(self, "You cannot place {} in this game.", self.theme.symbol(mark)),
+    OutputEvents::OpponentInvalidMove(peer_id, (x, y), reason) => {
+        outln!(self, "<{}> played {}{} but {}, turn ignored and it is still their turn.", peer_id, self.labels.row(x), self.labels.col(y), reason)
+    }
+    OutputEvents::MoveRejected(peer_id, (x, y), reason) => {
+        outln!(self, "<{}> did not accept your turn {}{}: {}.", peer_id, self.labels.row(x), self.labels.col(y), reason)
+    }
+    OutputEvents::ConfigRejected(error) => outln!(self, "Config change ignored, {}.", error),
+    OutputEvents::Replay(game, replay) => {
+        outln!(self, "Game {} against <{}>, {:?}{}:", game, crate::network_communication::display::sanitize_for_display(&replay.opponent_id), replay.outcome,
+            replay.finished_at.map(|at| format!(", {}", self.dates.relative(at, crate::network_communication::clock::now_millis()))).unwrap_or_default());
+        for (number, (step, grid)) in replay.positions().into_iter().enumerate() {
+            outln!(self, "{}. {} {}{}{}", number + 1, if step.mine { "you" } else { "opponent" },
+                self.labels.row(step.x), self.labels.col(step.y),
+                step.comment.map(|comment| format!(" - {}", crate::network_communication::display::sanitize_for_display(&comment))).unwrap_or_default());
+            self.print_table(&grid);
+        }
+    }
+    OutputEvents::ReplayProgress(peer_id, sent, total) if sent == total => {
+        outln!(self, "Replay sent to <{}>.", peer_id);
+    }
+    OutputEvents::ReplayProgress(peer_id, sent, total) => outln!(self, "Sending replay to <{}>: {}/{}.", peer_id, sent, total),
+    OutputEvents::ReplayReceived(peer_id, path, replay) => {
+        outln!(self, "<{}> shared their game against <{}>, {:?} for them, saved to {}:", peer_id, crate::network_communication::display::sanitize_for_display(&replay.opponent_id), replay.outcome, path.display());
+        for (number, (step, grid)) in replay.positions().into_iter().enumerate() {
+            outln!(self, "{}. {} {}{}{}", number + 1, if step.mine { "they" } else { "opponent" },
+                self.labels.row(step.x), self.labels.col(step.y),
+                step.comment.map(|comment| format!(" - {}", crate::network_communication::display::sanitize_for_display(&comment))).unwrap_or_default());
+            self.print_table(&grid);
+        }
+    }
+    OutputEvents::ReplayTransferFailed(peer_id, reason) => outln!(self, "Replay transfer with <{}> failed: {}.", peer_id, crate::network_communication::display::sanitize_for_display(&reason)),
+    OutputEvents::Watching(game_id) => outln!(self, "Watching game {}, playmat is shown after next move of either player.", game_id),
+    OutputEvents::Unwatched(game_id) => outln!(self, "No longer watching game {}.", game_id),
+    OutputEvents::InvalidGameId(game_id) => outln!(self, "'{}' is not id of any game, ids are listed by 'games' of its players.", game_id),
+    OutputEvents::SpectatedTurn(game_id, (x, y), grid) => {
+        outln!(self, "Game {}: {}{} played.", crate::network_communication::display::sanitize_for_display(&game_id), self.labels.row(x), self.labels.col(y));
+        self.print_table(&grid);
+    }
+    OutputEvents::SpectatedFinished(game_id, winner) => match winner {
+        Some(winner) => outln!(self, "Game {} is over, <{}> won.", crate::network_communication::display::sanitize_for_display(&game_id), crate::network_communication::display::sanitize_for_display(&winner)),
+        None => outln!(self, "Game {} is over, it is a draw.", crate::network_communication::display::sanitize_for_display(&game_id)),
+    },
+    OutputEvents::Refereeing(game_id) => outln!(self, "Refereeing game {}, its verdict is sent to both players once it ends.", game_id),
+    OutputEvents::StoppedRefereeing(game_id) => outln!(self, "No longer refereeing game {}.", game_id),
+    OutputEvents::OwnGameRefereed(game_id) => outln!(self, "Game {} is yours, it needs other referee.", game_id),
+    OutputEvents::VerdictIssued(verdict) => {
+        outln!(self, "Verdict on game {}: {}, sent to its players.", crate::network_communication::display::sanitize_for_display(&verdict.game), crate::network_communication::display::sanitize_for_display(&verdict.ruling.to_string()))
+    }
+    OutputEvents::Verdict(verdict) => {
+        outln!(self, "Referee <{}> ruled on game {}: {}.", verdict.referee, crate::network_communication::display::sanitize_for_display(&verdict.game), crate::network_communication::display::sanitize_for_display(&verdict.ruling.to_string()))
+    }
+    OutputEvents::Annotated(game, number) => outln!(self, "Move {} of game {} annotated.", number, game),
+    OutputEvents::History(games) => {
+        outln!(self, "Last {} finished games:", games.len());
+        let now = crate::network_communication::clock::now_millis();
+        for (number, game) in games.iter().enumerate() {
+            outln!(self, "{}. {:?} against <{}>{}", number + 1, game.outcome, game.opponent_id,
+                game.finished_at.map(|at| format!(", {}", self.dates.relative(at, now))).unwrap_or_default());
+        }
+    }
+    OutputEvents::Score(current, overall) => {
+        let format_score = |score: crate::tictactoe::Score| format!("{} won, {} lost, {} drawn", score.wins, score.losses, score.draws);
+        if let Some((opponent, score)) = current {
+            outln!(self, "Against <{}>: {}.", opponent, format_score(score));
+        }
+        outln!(self, "Overall: {}.", format_score(overall));
+    }
+    OutputEvents::ReplayFailed(error) => outln!(self, "Replay failed: {}.", error),
+    OutputEvents::ReviewStarted(peer_id) => outln!(self, "Reviewing last game with <{}>, use next, prev and goto <move>.", peer_id),
+    OutputEvents::ReviewPosition(position, last_move, grid) => {
+        match last_move {
+            Some(step) => outln!(self, "Move {}: {} {}{}{}", position, if step.mine { "you" } else { "opponent" },
+                self.labels.row(step.x), self.labels.col(step.y),
+                step.comment.map(|comment| format!(" - {}", crate::network_communication::display::sanitize_for_display(&comment))).unwrap_or_default()),
+            None => outln!(self, "Start of the game."),
+        }
+        self.print_table(&grid);
+    }
+    OutputEvents::ReviewEnded(peer_id) => outln!(self, "Review with <{}> ended.", peer_id),
+    OutputEvents::NothingToReview => outln!(self, "There is no finished game to review."),
+    OutputEvents::Drill(grid, opponent_id) if opponent_id == PUZZLE_ID => {
+        outln!(self, "Find the best move in position you set up:");
+        self.print_table(&grid);
+    }
+    OutputEvents::Drill(grid, opponent_id) => {
+        outln!(self, "Find the best move, you played worse one against <{}>:", opponent_id);
+        self.print_table(&grid);
+    }
+    OutputEvents::SetupPosition(grid) => {
+        self.print_table(&grid);
+        let to_move = if crate::setup::first_to_move(&grid) { "first player" } else { "second player" };
+        outln!(self, "Setting up, {} moves next. Place with 'setup {} x|o|-'.", to_move, self.labels.turn_syntax().trim_start_matches("turn "));
+    }
+    OutputEvents::SetupRejected(error) => outln!(self, "Position cannot be used: {}.", error),
+    OutputEvents::NotSettingUp => outln!(self, "Start setting up position with 'setup'."),
+    OutputEvents::SetupAnalysis(moves) => {
+        outln!(self, "Moves of player to move:");
+        for ((x, y), mark, evaluation) in moves {
+            let result = match evaluation {
+                crate::tictactoe::Evaluation::Win => "wins",
+                crate::tictactoe::Evaluation::Draw => "draws",
+                crate::tictactoe::Evaluation::Loss => "loses",
+            };
+            outln!(self, "  {}{} {} {}", self.labels.row(x), self.labels.col(y), self.theme.symbol(mark), result);
+        }
+    }
+    OutputEvents::PuzzleSaved(count) => outln!(self, "Position saved as drill, {} drills in total.", count),
+    OutputEvents::SetupEnded => outln!(self, "Setup ended."),
+    OutputEvents::DrillGraded(grade) => {
+        let best : Vec<String> = grade.best.iter().map(|(x, y)| format!("{}{}", self.labels.row(*x), self.labels.col(*y))).collect();
+        outln!(self, "{} Best: {}. Drill comes back in {}.", if grade.correct { "Correct!" } else { "Wrong." },
+            best.join(", "), crate::dates::duration(grade.next_in_secs));
+    }
+    OutputEvents::NoDrills(count) => outln!(self, "No drill is due, {} drills in total.", count),
+    OutputEvents::AuditExported(game_id, path, entries) => {
+        outln!(self, "Audit log of game {} with {} messages written to {}.", game_id, entries, path.display());
+    }
+    OutputEvents::NetStats(topics) => {
+        outln!(self, "Traffic on {} topics.", topics.len());
+        for stats in topics {
+            outln!(self, "{}: {} B sent, {} B received, {} duplicates dropped, {} retransmissions{}",
+                stats.topic, stats.bytes_sent, stats.bytes_received, stats.duplicates_dropped, stats.retransmissions,
+                stats.average_latency_millis().map(|millis| format!(", {} ms latency", millis)).unwrap_or_default());
+            outln!(self, "  sent: {}", Self::message_counts(&stats.sent));
+            outln!(self, "  received: {}", Self::message_counts(&stats.received));
+        }
+    }
+    OutputEvents::NatStatus(status) => outln!(self, "{}.", status),
+    OutputEvents::NetInfo(info) => {
+        outln!(self, "Your peer id: {}", info.peer_id);
+        for address in &info.listen_addrs {
+            outln!(self, "  listening on {}", address);
+        }
+        for address in &info.observed_addrs {
+            outln!(self, "  seen by peers as {}", address);
+        }
+        if let Some(address) = &info.reachable_addr {
+            outln!(self, "  dialed back by peers at {}", address);
+        }
+        outln!(self, "{}.", info.nat);
+    }
+    OutputEvents::Banner(banner) => {
+        match &banner.nickname {
+            Some(nickname) => outln!(self, "You are {} <{}>", nickname, banner.peer_id),
+            None => outln!(self, "Your peer id: {}", banner.peer_id),
+        }
+        outln!(self, "  fingerprint {}", banner.fingerprint);
+        for address in &banner.listen_addrs {
+            outln!(self, "  listening on {}", address);
+        }
+        outln!(self, "Quick actions:");
+        self.print_quick_actions();
+    }
+    OutputEvents::StaleAnswer => outln!(self, "That question no longer needs an answer."),
+    OutputEvents::AuditFailed(error) => outln!(self, "Cannot export audit log: {}.", error),
+    OutputEvents::Chat(peer_id, text) => outln!(self, "<{}> says: {}", peer_id, crate::network_communication::display::sanitize_for_display(&text)),
+    OutputEvents::ChatLanguage(language) => match language {
+        Some(language) => outln!(self, "Chat in this game is processed for language '{}'.", language),
+        None => outln!(self, "Chat in this game uses default language."),
+    },
+    OutputEvents::WrongPassword(peer_id) => outln!(self, "Declined invitation from <{}>, wrong password.", peer_id),
+    OutputEvents::PluginHelp(commands) => {
+        commands.iter().for_each(|(usage, description)| outln!(self, "{:20} - {}", usage, description));
+    }
+    OutputEvents::PluginOutput(lines) => lines.iter().for_each(|line| outln!(self, "{}", crate::network_communication::display::sanitize_for_display(line))),
+    OutputEvents::PluginFailed(name, error) => outln!(self, "Command {} failed: {}.", name, crate::network_communication::display::sanitize_for_display(&error)),
+    OutputEvents::UnknownCommand(name) => outln!(self, "Unknown command '{}', type 'help' for the list.", name),
+    OutputEvents::UnsupportedVariant(peer_id, variant) => {
+        outln!(self, "Declined invitation from <{}>, variant {} is not supported.", peer_id, crate::network_communication::display::sanitize_for_display(&variant));
+    }
+    OutputEvents::InvitationDeclined(peer_id, reputation) => {
+        outln!(self, "Declined invitation from <{}>, their reputation is {}.", peer_id, reputation);
+    }
+}
+    }
+
+    pub(super) fn print_table<Row : AsRef<[crate::tictactoe::Tile]>>(&self, grid : &[Row]) {
+        self.table_lines(grid).into_iter().for_each(|line| self.write_line(line));
+    }
+
+    /// Returns widths of row labels and of fields in table of playmat with given number of rows
+    fn table_widths(&self, size : usize) -> (usize, usize) {
+        let widest = |labels : Vec<String>| labels.iter().map(|label| label.chars().count()).max().unwrap_or(1);
+        // fields are as wide as the widest label, e.g. column 10
+        (widest(self.labels.row_names(size)), widest(self.labels.col_names(size)))
+    }
+
+    /// Returns field shown at given line and character of lines from [`Stdio::table_lines`],
+    /// none when it is label or grid
+    pub fn field_at(&self, size : usize, line : usize, column : usize) -> Option<crate::coords::Coordinates> {
+        let (label_width, width) = self.table_widths(size);
+        let lines_per_row = if self.theme.grid.row_line(size, width).is_some() { 2 } else { 1 };
+        let chars_per_field = width + self.theme.grid.column_separator().chars().count();
+        let line = line.checked_sub(1)?;
+        let column = column.checked_sub(label_width + 1)?;
+        let (x, y) = (line / lines_per_row, column / chars_per_field);
+        (line % lines_per_row == 0 && column % chars_per_field < width && x < size && y < size).then_some((x, y))
+    }
+
+    /// Returns lines of grid with labels of rows and columns in theme
+    pub fn table_lines<Row : AsRef<[crate::tictactoe::Tile]>>(&self, grid : &[Row]) -> Vec<String> {
+        let mut lines = Vec::new();
+        let separator = self.theme.grid.column_separator();
+        let gap = " ".repeat(separator.chars().count());
+        let (rows, cols) = (self.labels.row_names(grid.len()), self.labels.col_names(grid.len()));
+        let (label_width, width) = self.table_widths(grid.len());
+        let indent = " ".repeat(label_width + 1);
+        let header : Vec<String> = cols.iter().map(|col| format!("{:<1$}", col, width)).collect();
+        lines.push(format!("{}{}", indent, header.join(&gap)));
+        for (index, row) in grid.iter().enumerate() {
+            let fields : Vec<String> = row.as_ref().iter().map(|tile| format!("{:<1$}", self.theme.symbol(*tile), width)).collect();
+            lines.push(format!("{:<2$} {}", rows[index], fields.join(separator), label_width));
+            if index + 1 < grid.len() {
+                if let Some(line) = self.theme.grid.row_line(grid.len(), width) {
+                    lines.push(format!("{}{}", indent, line));
+                }
+            }
+        }
+        lines
+    }
+
+    fn print_game(&self, game : &GameSummary) {
+        outln!(self, "{}{}: {} ({}{}) {}",
+            if game.active { "*" } else { " " },
+            game.index,
+            game.opponent_id,
+            if game.your_turn { "your turn" } else { "waiting" },
+            game.latency_millis.map(|millis| format!(", {} ms", millis)).unwrap_or_default(),
+            game.game_id);
+    }
+
+    fn message_counts(counts : &std::collections::BTreeMap<&'static str, u64>) -> String {
+        if counts.is_empty() {
+            return "none".to_string();
+        }
+        counts.iter().map(|(kind, count)| format!("{} {}", kind, count)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn print_evaluation(&self, evaluation : Option<crate::tictactoe::Evaluation>) {
+        let (bar, text) = match evaluation {
+            Some(crate::tictactoe::Evaluation::Win) => ("██████████", "you win with best play"),
+            Some(crate::tictactoe::Evaluation::Draw) => ("█████░░░░░", "draw with best play"),
+            Some(crate::tictactoe::Evaluation::Loss) => ("░░░░░░░░░░", "you lose with best play"),
+            None => return,
+        };
+        outln!(self, "[{}] {}", bar, text);
+    }
+
+    pub(super) fn reputation_marker(reputation : i64) -> &'static str {
+        match reputation {
+            r if r < 0 => " (!)",
+            r if r > 0 => " (+)",
+            _ => "",
+        }
+    }
+
+    fn print_string(&self, text: &str) {
+        outln!(self, "{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Stdio;
+
+    #[tokio::test]
+    async fn finds_field_at_table_position() {
+        // header, then rows with separator lines between them
+        let console = Stdio::new(crate::theme::Theme::classic(), crate::coords::Labels::default(), Default::default());
+        assert_eq!(console.field_at(3, 3, 6), Some((1, 1)));
+        assert_eq!(console.field_at(3, 3, 4), None, "grid");
+        assert_eq!(console.field_at(3, 2, 6), None, "row separator");
+        assert_eq!(console.field_at(3, 0, 6), None, "header");
+        assert_eq!(console.field_at(3, 5, 2), Some((2, 0)));
+        assert_eq!(console.field_at(3, 7, 2), None, "below playmat");
+    }
+}
@@ -0,0 +1,75 @@
+//! # Invite
+//!
+//! Invite codes which let a friend on the same network join without picking
+//! from the peers list. Code is peer id with a scheme prefix, so it can be
+//! pasted or scanned from a terminal QR code.
+
+/// Prefix of every invite code
+pub const SCHEME: &str = "tictactoe:";
+
+#[derive(Debug, PartialEq)]
+pub enum InviteError {
+    MissingScheme,
+    InvalidPeerId,
+}
+
+impl std::fmt::Display for InviteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InviteError::MissingScheme => write!(f, "invite code must start with '{}'", SCHEME),
+            InviteError::InvalidPeerId => write!(f, "invite code does not contain valid peer id"),
+        }
+    }
+}
+
+/// Returns invite code of given peer
+pub fn generate(peer_id: &str) -> String {
+    format!("{}{}", SCHEME, peer_id)
+}
+
+/// Returns peer id from invite code
+pub fn parse(code: &str) -> Result<String, InviteError> {
+    let peer_id = code.trim().strip_prefix(SCHEME).ok_or(InviteError::MissingScheme)?;
+    // peer ids are base58 encoded
+    let is_base58 = |c: char| c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l');
+    if peer_id.is_empty() || !peer_id.chars().all(is_base58) {
+        return Err(InviteError::InvalidPeerId);
+    }
+    Ok(peer_id.to_string())
+}
+
+/// Renders code as QR code made of unicode half blocks
+#[cfg(feature = "qr")]
+pub fn qr(code: &str) -> Option<String> {
+    let qr = qrcode::QrCode::new(code).ok()?;
+    Some(
+        qr.render::<qrcode::render::unicode::Dense1x2>()
+            .dark_color(qrcode::render::unicode::Dense1x2::Light)
+            .light_color(qrcode::render::unicode::Dense1x2::Dark)
+            .build(),
+    )
+}
+
+/// QR codes are not built in, only the code is shown
+#[cfg(not(feature = "qr"))]
+pub fn qr(_code: &str) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_roundtrip() {
+        let peer_id = "12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN";
+        assert_eq!(parse(&generate(peer_id)), Ok(peer_id.to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert_eq!(parse("12D3KooW"), Err(InviteError::MissingScheme));
+        assert_eq!(parse("tictactoe:"), Err(InviteError::InvalidPeerId));
+        assert_eq!(parse("tictactoe:not/a/peer"), Err(InviteError::InvalidPeerId));
+    }
+}
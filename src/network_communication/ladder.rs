@@ -0,0 +1,193 @@
+//! # Ladder
+//!
+//! Challenge ladder of a room, lighter than any rating. Every finished game is
+//! attested by its loser, who signs the result with own identity, so the winner
+//! cannot claim a win nobody conceded. Attestations are gossiped one by one on
+//! ladder topic of the room and each peer keeps all it has seen. Positions are
+//! not sent at all, every peer replays the same attestations in the same order
+//! and arrives at the same ladder.
+//!
+//! Players enter at the bottom with their first result. Player may challenge
+//! anyone at most two positions above, challenger who wins swaps positions with
+//! the loser. Other results are kept but move nobody.
+
+use sha2::{Digest, Sha256};
+
+use super::auth::{from_hex, to_hex};
+
+/// How many positions above may challenged player stand
+pub const CHALLENGE_REACH: usize = 2;
+
+#[derive(Debug)]
+pub enum LadderError {
+    /// Signature does not match result or key does not belong to the loser
+    BadSignature,
+    Signing(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for LadderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LadderError::BadSignature => write!(f, "result is not signed by its loser"),
+            LadderError::Signing(err) => write!(f, "cannot sign result: {}", err),
+            LadderError::Io(err) => write!(f, "cannot access ladder: {}", err),
+        }
+    }
+}
+
+/// Result of one game conceded by its loser
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attestation {
+    /// Nonce of the game, the same game is attested only once
+    pub game: String,
+    pub winner: String,
+    pub loser: String,
+    /// UTC milliseconds when the game ended
+    pub at_millis: u64,
+    /// Protobuf encoded public key of loser, hex encoded
+    pub key: String,
+    pub signature: String,
+}
+
+impl Attestation {
+    /// Returns result of game with given nonce lost by owner of key
+    pub fn sign(key: &libp2p::identity::Keypair, game: &str, winner: &str, at_millis: u64) -> Result<Attestation, LadderError> {
+        let mut attestation = Attestation {
+            game: game.to_string(),
+            winner: winner.to_string(),
+            loser: libp2p::PeerId::from(key.public()).to_string(),
+            at_millis,
+            key: to_hex(&key.public().encode_protobuf()),
+            signature: String::new(),
+        };
+        let signature = key.sign(&attestation.signed_bytes()).map_err(|error| LadderError::Signing(error.to_string()))?;
+        attestation.signature = to_hex(&signature);
+        Ok(attestation)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        format!("{}\n{}\n{}\n{}", self.game, self.winner, self.loser, self.at_millis).into_bytes()
+    }
+
+    /// Returns true when loser signed the result
+    pub fn verify(&self) -> bool {
+        let public = from_hex(&self.key).and_then(|key| libp2p::identity::PublicKey::try_decode_protobuf(&key).ok());
+        match (public, from_hex(&self.signature)) {
+            (Some(public), Some(signature)) => {
+                public.verify(&self.signed_bytes(), &signature) && libp2p::PeerId::from(public).to_string() == self.loser
+            }
+            _ => false,
+        }
+    }
+
+    /// Order in which all peers replay results, ties are broken by hash of the game
+    fn order(&self) -> (u64, Vec<u8>) {
+        (self.at_millis, Sha256::digest(self.game.as_bytes()).to_vec())
+    }
+}
+
+/// Results seen in the room, the oldest first
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Ladder {
+    attestations: Vec<Attestation>,
+}
+
+impl Ladder {
+    /// Reads ladder from file, empty one when file is missing or broken
+    pub fn load(path: &std::path::Path) -> Ladder {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), LadderError> {
+        let json = serde_json::to_string_pretty(self).expect("cannot jsonify ladder");
+        std::fs::write(path, json).map_err(LadderError::Io)
+    }
+
+    pub fn attestations(&self) -> &[Attestation] {
+        &self.attestations
+    }
+
+    /// Adds result, returns false when it was already known
+    pub fn add(&mut self, attestation: Attestation) -> Result<bool, LadderError> {
+        if !attestation.verify() {
+            return Err(LadderError::BadSignature);
+        }
+        if self.attestations.iter().any(|known| known.game == attestation.game && known.loser == attestation.loser) {
+            return Ok(false);
+        }
+        let index = self.attestations.partition_point(|known| known.order() <= attestation.order());
+        self.attestations.insert(index, attestation);
+        Ok(true)
+    }
+
+    /// Returns peers from the top of ladder down
+    pub fn positions(&self) -> Vec<String> {
+        let mut positions: Vec<String> = Vec::new();
+        for attestation in &self.attestations {
+            for player in [&attestation.winner, &attestation.loser] {
+                if !positions.contains(player) {
+                    positions.push(player.clone());
+                }
+            }
+            let winner = positions.iter().position(|player| *player == attestation.winner);
+            let loser = positions.iter().position(|player| *player == attestation.loser);
+            if let (Some(winner), Some(loser)) = (winner, loser) {
+                if winner > loser && winner - loser <= CHALLENGE_REACH {
+                    positions.swap(winner, loser);
+                }
+            }
+        }
+        positions
+    }
+}
+
+/// Returns true when challenger's win over opponent would move challenger up,
+/// players who are not on ladder yet may challenge only each other
+pub fn may_challenge(positions: &[String], challenger: &str, opponent: &str) -> bool {
+    let challenger = positions.iter().position(|player| player == challenger).unwrap_or(positions.len());
+    match positions.iter().position(|player| player == opponent) {
+        Some(opponent) => challenger > opponent && challenger - opponent <= CHALLENGE_REACH,
+        None => challenger == positions.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenger_swaps_with_loser() {
+        let keys: Vec<_> = (0..4).map(|_| libp2p::identity::Keypair::generate_ed25519()).collect();
+        let ids: Vec<String> = keys.iter().map(|key| libp2p::PeerId::from(key.public()).to_string()).collect();
+        let lose = |loser: usize, winner: usize, at: u64| Attestation::sign(&keys[loser], &format!("game{}", at), &ids[winner], at).unwrap();
+
+        let mut ladder = Ladder::default();
+        // results arrive out of order, all peers replay them by time
+        assert!(ladder.add(lose(3, 2, 2)).unwrap());
+        assert!(ladder.add(lose(1, 0, 1)).unwrap());
+        assert!(!ladder.add(lose(1, 0, 1)).unwrap(), "the same result is added once");
+        assert_eq!(ladder.positions(), vec![ids[0].clone(), ids[1].clone(), ids[2].clone(), ids[3].clone()]);
+
+        // 3 is three positions below 0, nothing moves
+        ladder.add(lose(0, 3, 3)).unwrap();
+        assert_eq!(ladder.positions()[0], ids[0]);
+        // 2 challenges 0 and wins
+        ladder.add(lose(0, 2, 4)).unwrap();
+        assert_eq!(ladder.positions(), vec![ids[2].clone(), ids[1].clone(), ids[0].clone(), ids[3].clone()]);
+
+        let positions = ladder.positions();
+        assert!(may_challenge(&positions, &ids[3], &ids[1]));
+        assert!(!may_challenge(&positions, &ids[3], &ids[2]));
+        assert!(!may_challenge(&positions, &ids[2], &ids[1]), "challenge goes only up");
+        assert!(may_challenge(&positions, "newcomer", "other newcomer"));
+
+        let mut forged = lose(1, 3, 5);
+        forged.winner = ids[1].clone();
+        forged.loser = ids[3].clone();
+        assert!(matches!(ladder.add(forged), Err(LadderError::BadSignature)));
+    }
+}
@@ -0,0 +1,380 @@
+//! # Load test
+//!
+//! Developer mode running virtual players in one process. Players talk over memory
+//! transport instead of TCP and mDNS and play random games against each other, while
+//! counters track throughput, lost or duplicated turns and sessions which never end.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use libp2p::Transport as _;
+
+use super::{input, invite, observer, prompt, Input, OutputEvents, Settings};
+use crate::tictactoe;
+
+/// In-process network of one virtual player
+#[derive(Clone)]
+pub struct VirtualNetwork {
+    pub key: libp2p::identity::Keypair,
+    /// Memory address player listens on
+    pub address: libp2p::Multiaddr,
+    /// Other players, they are dialed at start and whenever connection drops
+    pub peers: Vec<(libp2p::PeerId, libp2p::Multiaddr)>,
+}
+
+/// Creates encrypted and multiplexed memory transport
+pub(super) fn memory_transport(key: &libp2p::identity::Keypair) -> super::swarm::Transport {
+    let noise = libp2p::noise::Config::new(key).expect("signing noise keys cannot fail");
+    libp2p::core::transport::MemoryTransport::default()
+        .upgrade(libp2p::core::upgrade::Version::V1)
+        .authenticate(noise)
+        .multiplex(libp2p::yamux::Config::default())
+        .boxed()
+}
+
+/// Dials players which are not connected, floodsub does not know their memory addresses
+pub(super) fn reconnect(swarm: &mut libp2p::swarm::Swarm<super::behaviour::TicTacToeBehaviour>, network: &VirtualNetwork) {
+    for (peer, address) in &network.peers {
+        if swarm.is_connected(peer) {
+            continue;
+        }
+        if swarm.dial(address.clone()).is_ok() {
+            let behaviour = swarm.behaviour_mut();
+            behaviour.protocols.floodsub.add_node_to_partial_view(*peer);
+            behaviour.last_seen.insert(*peer, std::time::Instant::now());
+        }
+    }
+}
+
+/// Counters shared by all players
+#[derive(Debug, Default)]
+struct Metrics {
+    games_started: AtomicU64,
+    games_finished: AtomicU64,
+    draws: AtomicU64,
+    turns_sent: AtomicU64,
+    turns_received: AtomicU64,
+    duplicated: AtomicU64,
+    rejected: AtomicU64,
+    ignored_invitations: AtomicU64,
+    leaked_sessions: AtomicU64,
+}
+
+/// Result of load test
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub players: usize,
+    pub duration: std::time::Duration,
+    pub games_started: u64,
+    pub games_finished: u64,
+    /// Games ending with full board, client has no draw result so their sessions stay open
+    pub draws: u64,
+    pub turns_sent: u64,
+    pub turns_received: u64,
+    /// Turns shown again without change of playmat
+    pub duplicated: u64,
+    /// Turns and messages rejected by receiver
+    pub rejected: u64,
+    /// Invitations declined because invited player was already playing
+    pub ignored_invitations: u64,
+    /// Sessions still open although their player is idle
+    pub leaked_sessions: u64,
+}
+
+impl Report {
+    /// Turns sent but never received
+    pub fn dropped(&self) -> u64 {
+        self.turns_sent.saturating_sub(self.turns_received)
+    }
+
+    /// Received turns per second
+    pub fn throughput(&self) -> f64 {
+        self.turns_received as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} players for {} s", self.players, self.duration.as_secs())?;
+        writeln!(f, "games: {} started, {} finished, {} draws", self.games_started, self.games_finished, self.draws)?;
+        writeln!(f, "turns: {} sent, {} received, {:.1} per second", self.turns_sent, self.turns_received, self.throughput())?;
+        writeln!(f, "dropped: {}, duplicated: {}, rejected: {}", self.dropped(), self.duplicated, self.rejected)?;
+        write!(f, "ignored invitations: {}, leaked sessions: {}", self.ignored_invitations, self.leaked_sessions)
+    }
+}
+
+impl Metrics {
+    fn report(&self, players: usize, duration: std::time::Duration) -> Report {
+        Report {
+            players,
+            duration,
+            games_started: self.games_started.load(Ordering::Relaxed),
+            games_finished: self.games_finished.load(Ordering::Relaxed),
+            draws: self.draws.load(Ordering::Relaxed),
+            turns_sent: self.turns_sent.load(Ordering::Relaxed),
+            turns_received: self.turns_received.load(Ordering::Relaxed),
+            duplicated: self.duplicated.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            ignored_invitations: self.ignored_invitations.load(Ordering::Relaxed),
+            leaked_sessions: self.leaked_sessions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn count(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Time players get after test ends to finish running games before sessions are counted
+const SETTLE_TIME: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Runs given number of players for given time and reports what happened
+pub async fn run(players: usize, duration: std::time::Duration) -> Report {
+    let keys: Vec<libp2p::identity::Keypair> = (0..players).map(|_| libp2p::identity::Keypair::generate_ed25519()).collect();
+    // memory ports are global to process, start far from zero
+    let base = 10_000 + u64::from(std::process::id() % 10_000) * 100;
+    let addresses: Vec<libp2p::Multiaddr> = (0..players)
+        .map(|index| format!("/memory/{}", base + index as u64).parse().expect("valid memory address"))
+        .collect();
+    let peer_ids: Vec<libp2p::PeerId> = keys.iter().map(|key| libp2p::PeerId::from(key.public())).collect();
+
+    let metrics = Arc::new(Metrics::default());
+    let started = tokio::time::Instant::now();
+    let stop_at = started + duration;
+
+    let mut virtual_players: Vec<VirtualPlayer> = (0..players)
+        .map(|index| {
+            let opponents = peer_ids.iter().enumerate().filter(|(other, _)| *other != index).map(|(_, peer)| peer.to_string());
+            VirtualPlayer::new(opponents.collect(), index as u64, stop_at, metrics.clone())
+        })
+        .collect();
+
+    let games = virtual_players.iter_mut().enumerate().map(|(index, player)| {
+        let network = VirtualNetwork {
+            key: keys[index].clone(),
+            address: addresses[index].clone(),
+            peers: (0..players)
+                .filter(|other| *other != index)
+                .map(|other| (peer_ids[other], addresses[other].clone()))
+                .collect(),
+        };
+        let settings = Settings {
+            invitation_timeout_secs: Some(2),
+            ..Settings::default()
+        };
+        let extensions = super::Extensions { virtual_network: Some(network), ..Default::default() };
+        super::start_with(player, settings, extensions)
+    });
+
+    // players never quit on their own, they are dropped once the report is collected
+    let _ = tokio::time::timeout(duration + SETTLE_TIME * 2, libp2p::futures::future::join_all(games)).await;
+    metrics.report(players, duration)
+}
+
+/// Scripted user interface inviting random opponents and playing random turns
+pub struct VirtualPlayer {
+    opponents: Vec<String>,
+    events: tokio::sync::mpsc::UnboundedReceiver<OutputEvents>,
+    events_sender: tokio::sync::mpsc::UnboundedSender<OutputEvents>,
+    pending: std::collections::VecDeque<Input>,
+    /// Mirror of running game, none when idle
+    game: Option<tictactoe::TicTacToe>,
+    /// Invitation sent and not answered yet
+    inviting: bool,
+    /// Sessions were listed after test ended
+    reported: bool,
+    stop_at: tokio::time::Instant,
+    rng: u64,
+    metrics: Arc<Metrics>,
+}
+
+impl VirtualPlayer {
+    fn new(opponents: Vec<String>, seed: u64, stop_at: tokio::time::Instant, metrics: Arc<Metrics>) -> VirtualPlayer {
+        let (events_sender, events) = tokio::sync::mpsc::unbounded_channel();
+        VirtualPlayer {
+            opponents,
+            events,
+            events_sender,
+            pending: std::collections::VecDeque::new(),
+            game: None,
+            inviting: false,
+            reported: false,
+            stop_at,
+            rng: super::clock::now_millis() ^ (seed + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+            metrics,
+        }
+    }
+
+    /// Xorshift, good enough for picking fields
+    fn random(&mut self, bound: usize) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % bound.max(1) as u64) as usize
+    }
+
+    fn is_idle(&self) -> bool {
+        self.game.is_none() && !self.inviting
+    }
+
+    fn next_deadline(&mut self) -> tokio::time::Instant {
+        let now = tokio::time::Instant::now();
+        if now < self.stop_at && self.is_idle() {
+            now + std::time::Duration::from_millis(10 + self.random(40) as u64)
+        } else if now < self.stop_at {
+            self.stop_at
+        } else if !self.reported {
+            self.stop_at + SETTLE_TIME
+        } else {
+            now + std::time::Duration::from_secs(3600)
+        }
+    }
+
+    fn on_deadline(&mut self) {
+        let now = tokio::time::Instant::now();
+        if now >= self.stop_at + SETTLE_TIME && !self.reported {
+            self.reported = true;
+            self.pending.push_back(Input::ListGames);
+        } else if now < self.stop_at && self.is_idle() && !self.opponents.is_empty() {
+            let pick = self.random(self.opponents.len());
+            let opponent = self.opponents[pick].clone();
+            self.inviting = true;
+            self.pending.push_back(Input::Join(invite::generate(&opponent), None, None, None));
+        }
+    }
+
+    fn handle(&mut self, event: OutputEvents) {
+        match event {
+            OutputEvents::StartTrue(..) => {
+                count(&self.metrics.games_started);
+                self.inviting = false;
+                self.game = Some(tictactoe::TicTacToe::new());
+                self.play();
+            }
+            OutputEvents::StartFalse
+            | OutputEvents::InvitationExpired(_)
+            | OutputEvents::InvitationWithdrawn(_)
+            | OutputEvents::InvitationDeclined(..)
+            | OutputEvents::WrongPassword(_) => {
+                self.inviting = false;
+                self.game = None;
+            }
+            OutputEvents::TurnResolved(state, _) => self.on_opponent_turn(state),
+            OutputEvents::GameOver => {
+                count(&self.metrics.games_finished);
+                self.game = None;
+            }
+            OutputEvents::Diagnostics(..) | OutputEvents::FieldOccupied(..) | OutputEvents::OutOfRange(..) => {
+                count(&self.metrics.rejected);
+            }
+            OutputEvents::Games(games) => {
+                let expected = usize::from(!self.is_idle());
+                let leaked = games.len().saturating_sub(expected) as u64;
+                self.metrics.leaked_sessions.fetch_add(leaked, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_opponent_turn(&mut self, state: tictactoe::Grid) {
+        count(&self.metrics.turns_received);
+        let game = match self.game.as_mut() {
+            Some(game) => game,
+            None => return,
+        };
+
+        let mirror = game.grid();
+        let played = (0..3)
+            .flat_map(|x| (0..3).map(move |y| (x, y)))
+            .find(|&(x, y)| mirror[x][y] == tictactoe::Tile::Empty && state[x][y] != tictactoe::Tile::Empty);
+        match played {
+            Some((x, y)) => {
+                let _ = game.make_opponent_turn(x, y);
+            }
+            None => {
+                count(&self.metrics.duplicated);
+                return;
+            }
+        }
+
+        // game over event follows win, draw is counted by opponent who filled the playmat
+        if game.is_opponent_winner() {
+            return;
+        }
+        if Self::free_fields(game).is_empty() {
+            self.game = None;
+        } else {
+            self.play();
+        }
+    }
+
+    /// Plays random free field of running game
+    fn play(&mut self) {
+        let free = match self.game.as_ref() {
+            Some(game) => Self::free_fields(game),
+            None => return,
+        };
+        if free.is_empty() {
+            return;
+        }
+        let (x, y) = free[self.random(free.len())];
+        let game = self.game.as_mut().expect("game is running");
+        let _ = game.make_my_turn(x, y);
+        self.pending.push_back(Input::Turn(x, y, None));
+        count(&self.metrics.turns_sent);
+
+        // finished game is counted by opponent on game over
+        if game.am_i_winner() {
+            self.game = None;
+        } else if Self::free_fields(game).is_empty() {
+            count(&self.metrics.draws);
+            self.game = None;
+        }
+    }
+
+    fn free_fields(game: &tictactoe::TicTacToe) -> Vec<(usize, usize)> {
+        let state = game.get_state();
+        (0..3)
+            .flat_map(|x| (0..3).map(move |y| (x, y)))
+            .filter(|&(x, y)| state[x][y] == tictactoe::Tile::Empty)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl input::Input<Input, OutputEvents> for VirtualPlayer {
+    async fn get_input(&mut self) -> Option<Input> {
+        loop {
+            if let Some(input) = self.pending.pop_front() {
+                return Some(input);
+            }
+            let deadline = self.next_deadline();
+            tokio::select! {
+                event = self.events.recv() => self.handle(event?),
+                _ = tokio::time::sleep_until(deadline) => self.on_deadline(),
+            }
+        }
+    }
+
+    fn print_to_output(&self, event: OutputEvents) {
+        let _ = self.events_sender.send(event);
+    }
+
+    /// Accepts invitation while not playing, other questions are declined
+    fn ask(&mut self, prompt: prompt::Prompt) -> Option<prompt::Answer> {
+        match prompt.question {
+            prompt::Question::Invitation(..) if self.is_idle() => {
+                self.game = Some(tictactoe::TicTacToe::new());
+                Some(prompt::Answer::Yes)
+            }
+            prompt::Question::Invitation(..) => {
+                count(&self.metrics.ignored_invitations);
+                Some(prompt::Answer::No)
+            }
+            _ => Some(prompt::Answer::No),
+        }
+    }
+}
+
+impl observer::SwarmObserver for VirtualPlayer {}
@@ -0,0 +1,150 @@
+//! # Lobby
+//!
+//! Players looking for a game beyond the local network. Player who seeks a game
+//! announces it with rules it wants on seeking topic of its room, which reaches
+//! every peer found by mDNS, Kademlia or rendezvous. It announces again whenever
+//! new peer subscribes to the topic, so players joining later see it too.
+//! Listing keeps open games in order they were seen, so `start #<n>` invites the
+//! one listed under that number.
+//!
+//! Announcement is valid for TTL it carries and its host refreshes it well
+//! before then, so open game of client which quit expires from the listing.
+//! TTL counts from receiving on local clock, clocks of peers need not agree.
+
+use crate::tictactoe::Rules;
+use std::time::{Duration, Instant};
+
+/// How long my announcement is valid
+pub const ANNOUNCEMENT_TTL: Duration = Duration::from_secs(60);
+/// How often I announce my open game again, two announcements may get lost
+pub const REFRESH_PERIOD: Duration = Duration::from_secs(20);
+/// Longest TTL accepted from peer, so its entry cannot stay forever
+const MAX_TTL: Duration = Duration::from_secs(600);
+
+/// Returns topic where players of lobby topic announce open games
+pub fn seeking_topic(lobby: &libp2p::floodsub::Topic) -> libp2p::floodsub::Topic {
+    libp2p::floodsub::Topic::new(format!("{}/seeking", lobby.id()))
+}
+
+/// Message on seeking topic
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LobbyMessage {
+    /// Sender looks for opponent to a game with these rules
+    Seeking {
+        #[serde(default, skip_serializing_if = "Rules::is_standard")]
+        rules: Rules,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nickname: Option<String>,
+        /// Seconds announcement is valid, peers before TTL did not send it
+        #[serde(default = "default_ttl_secs")]
+        ttl_secs: u64,
+    },
+    /// Sender no longer looks for a game
+    Left,
+}
+
+impl LobbyMessage {
+    /// Returns announcement of my open game
+    pub fn seeking(rules: Rules, nickname: Option<String>) -> LobbyMessage {
+        LobbyMessage::Seeking { rules, nickname, ttl_secs: ANNOUNCEMENT_TTL.as_secs() }
+    }
+}
+
+fn default_ttl_secs() -> u64 {
+    ANNOUNCEMENT_TTL.as_secs()
+}
+
+/// Player waiting for opponent
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenGame {
+    pub peer_id: String,
+    pub nickname: Option<String>,
+    pub rules: Rules,
+}
+
+/// Open games of other players, the oldest first, with time they expire
+#[derive(Debug, Default)]
+pub struct OpenGames {
+    games: Vec<(OpenGame, Instant)>,
+}
+
+impl OpenGames {
+    /// Applies message of peer, announcement replaces its earlier entry in place
+    pub fn receive(&mut self, peer_id: &str, message: LobbyMessage, now: Instant) {
+        match message {
+            LobbyMessage::Seeking { rules, nickname, ttl_secs } => {
+                let game = OpenGame { peer_id: peer_id.to_string(), nickname, rules };
+                let expires_at = now + Duration::from_secs(ttl_secs).min(MAX_TTL);
+                match self.games.iter_mut().find(|(game, _)| game.peer_id == peer_id) {
+                    Some(known) => *known = (game, expires_at),
+                    None => self.games.push((game, expires_at)),
+                }
+            }
+            LobbyMessage::Left => self.remove(peer_id),
+        }
+    }
+
+    /// Drops open game of peer, e.g. once it started to play
+    pub fn remove(&mut self, peer_id: &str) {
+        self.games.retain(|(game, _)| game.peer_id != peer_id);
+    }
+
+    /// Drops open games not refreshed in time, e.g. their host quit
+    pub fn expire(&mut self, now: Instant) {
+        self.games.retain(|(_, expires_at)| *expires_at > now);
+    }
+
+    /// Returns open game listed under number counted from 1
+    pub fn get(&self, number: usize) -> Option<&OpenGame> {
+        number.checked_sub(1).and_then(|index| self.games.get(index)).map(|(game, _)| game)
+    }
+
+    pub fn list(&self) -> Vec<OpenGame> {
+        self.games.iter().map(|(game, _)| game.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_open_games_in_order_seen() {
+        let now = Instant::now();
+        let mut open = OpenGames::default();
+        open.receive("alice", LobbyMessage::seeking(Rules::default(), None), now);
+        open.receive("bob", LobbyMessage::seeking(Rules::default(), None), now);
+        let nickname = Some("Bobby".to_string());
+        open.receive("bob", LobbyMessage::seeking(Rules::default(), nickname.clone()), now);
+        assert_eq!(open.get(2).map(|game| &game.nickname), Some(&nickname));
+        open.receive("alice", LobbyMessage::Left, now);
+        assert_eq!(open.get(1).map(|game| game.peer_id.as_str()), Some("bob"));
+        assert_eq!(open.get(0), None);
+        assert_eq!(open.list().len(), 1);
+    }
+
+    #[test]
+    fn expires_games_not_refreshed() {
+        let now = Instant::now();
+        let mut open = OpenGames::default();
+        open.receive("alice", LobbyMessage::seeking(Rules::default(), None), now);
+        open.receive("bob", LobbyMessage::seeking(Rules::default(), None), now);
+        open.receive("alice", LobbyMessage::seeking(Rules::default(), None), now + REFRESH_PERIOD);
+        open.expire(now + ANNOUNCEMENT_TTL);
+        assert_eq!(open.list().iter().map(|game| game.peer_id.as_str()).collect::<Vec<_>>(), ["alice"]);
+
+        let pinned = LobbyMessage::Seeking { rules: Rules::default(), nickname: None, ttl_secs: u64::MAX };
+        open.receive("mallory", pinned, now);
+        open.expire(now + MAX_TTL);
+        assert!(open.list().is_empty());
+    }
+
+    #[test]
+    fn standard_rules_stay_off_the_wire() {
+        let json = serde_json::to_string(&LobbyMessage::seeking(Rules::default(), None)).unwrap();
+        assert_eq!(json, r#"{"type":"seeking","ttl_secs":60}"#);
+        let legacy: LobbyMessage = serde_json::from_str(r#"{"type":"seeking"}"#).unwrap();
+        assert_eq!(legacy, LobbyMessage::seeking(Rules::default(), None));
+    }
+}
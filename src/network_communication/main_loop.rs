@@ -0,0 +1,434 @@
+//! # Main loop
+//!
+//! Runs swarm and waits for user's commands, messages of peers and timers,
+//! each is passed to its handler. Network is restarted when its channel closes
+//! and swarm is rebuilt on user's request.
+
+use super::behaviour::{PeerMessage, TicTacToeBehaviour};
+use super::handlers::{banner, check_turn_timeouts, process_input, quit, resolve_spawned_messages, send_held, status_line};
+use super::input::Input as _;
+use super::observer::SwarmObserver;
+use super::session::UserSession;
+use super::swarm::{init_swarm, prune_partial_view, reconnect_known, refresh_discovery};
+use super::{
+    builder, channel, chat, external_engine, history, input, loadtest, observer, reload, validation, Extensions, Input, OutputEvents, Settings,
+    StatusLine,
+};
+use libp2p::futures::StreamExt;
+use tokio::sync::mpsc;
+
+/// How many times network is restarted after internal channel closes
+const MAX_RESTARTS: u32 = 3;
+
+/// Messages waiting for main loop before chatter about peers is dropped
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// How often floodsub view is reconciled with discovered peers
+const PRUNE_PERIOD: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// What main loop does after handling an event
+#[derive(Debug, PartialEq)]
+enum LoopControl {
+    Continue,
+    Restart,
+    /// Rebuild swarm on user's request, sessions stay
+    Reconnect,
+    /// Leave on user's request once last messages are delivered
+    Quit,
+    Shutdown,
+}
+
+pub(super) async fn run<UserInt: input::Input<Input, OutputEvents> + observer::SwarmObserver + Send>(
+    user__interface : &mut history::Recorder<'_, UserInt>,
+    settings: Settings,
+    extensions: Extensions,
+) {
+
+    let (response_sender, response_rcv) = channel::bounded(CHANNEL_CAPACITY);
+    let (config_sender, config_rcv) = mpsc::unbounded_channel();
+    let (error_sender, error_rcv) = mpsc::unbounded_channel();
+    #[cfg(feature = "reload")]
+    let config_watcher = extensions.config_path.and_then(|path| {
+        reload::ConfigWatcher::spawn(path, config_sender)
+            .map_err(|error| user__interface.print_to_output(OutputEvents::Error(format!("Cannot watch config: {}", error))))
+            .ok()
+    });
+    // sender is dropped, so config branch of the loop never fires
+    #[cfg(not(feature = "reload"))]
+    let config_watcher: Option<reload::ConfigWatcher> = {
+        drop((extensions.config_path, config_sender));
+        None
+    };
+    #[cfg_attr(not(feature = "plugins"), allow(unused_mut))]
+    let mut plugins = extensions.plugins;
+    #[cfg(feature = "plugins")]
+    for path in &settings.plugins {
+        if let Err(error) = plugins.load(path) {
+            user__interface.print_to_output(OutputEvents::Error(error.to_string()));
+        }
+    }
+    let mut builder = builder::SessionBuilder::new(settings).chat_hooks(extensions.chat_hooks).errors(error_sender);
+    if let Some(netstats) = extensions.netstats {
+        builder = builder.netstats(netstats);
+    }
+    if let Some(variants) = extensions.variants {
+        builder = builder.variants(variants);
+    }
+    builder = builder.plugins(plugins);
+    let is_virtual = extensions.virtual_network.is_some();
+    if let Some(network) = extensions.virtual_network {
+        builder = builder.virtual_network(network);
+    }
+    let mut user_session = builder.build(response_sender.downgrade());
+    if !is_virtual {
+        if let Err(error) = user_session.restore_correspondence() {
+            user__interface.print_to_output(OutputEvents::Error(format!("Cannot open correspondence games: {}", error)));
+        }
+    }
+    if user_session.correspondence.is_some() {
+        user__interface.print_to_output(OutputEvents::PendingGames(user_session.pending_summaries()));
+    }
+    if let Some(path) = user_session.settings.engine.clone() {
+        let timeout = std::time::Duration::from_secs(user_session.settings.engine_timeout_secs);
+        match external_engine::ExternalEngine::spawn(&path, timeout).await {
+            Ok(engine) => user_session.engine = Some(std::sync::Arc::new(tokio::sync::Mutex::new(engine))),
+            Err(error) => user__interface.print_to_output(OutputEvents::EngineError(error.to_string())),
+        }
+    }
+
+    let mut swarm = init_swarm(&user_session, response_sender).await;
+    // opponents of running games need not wait for discovery
+    reconnect_known(&mut swarm, &user_session);
+    // banner waits for the first address, unless there is none to wait for
+    let banner_shown = user_session.swarm_config.listen_addrs.is_empty();
+    if banner_shown {
+        user__interface.print_to_output(OutputEvents::Banner(banner(&swarm, &user_session)));
+    }
+    let events = Events { responses: response_rcv, errors: error_rcv, config: config_rcv, config_watcher };
+    serve(user__interface, &mut user_session, swarm, events, banner_shown).await
+}
+
+/// Sources of events main loop waits for besides user and swarm
+struct Events {
+    /// Messages of behaviour and of session tasks
+    responses: channel::Receiver<PeerMessage>,
+    /// Failures outside of handlers
+    errors: mpsc::UnboundedReceiver<String>,
+    /// Changes of config file
+    config: mpsc::UnboundedReceiver<()>,
+    config_watcher: Option<reload::ConfigWatcher>,
+}
+
+/// Handles events until user quits or network cannot be restarted anymore
+async fn serve<UserInt: input::Input<Input, OutputEvents> + observer::SwarmObserver + Send>(
+    user_interface : &mut history::Recorder<'_, UserInt>,
+    user_session: &mut UserSession,
+    mut swarm: libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    mut events: Events,
+    mut banner_shown: bool,
+) {
+    let mut restarts = 0;
+    let mut prune_timer = tokio::time::interval(PRUNE_PERIOD);
+    let mut status = StatusLine::default();
+    loop {
+        let current = status_line(&swarm, user_session);
+        if current != status {
+            status = current;
+            user_interface.print_to_output(OutputEvents::Status(status.clone()));
+        }
+        let turn_deadline = user_session.next_turn_deadline();
+        let held_due = user_session.outgoing.next_due();
+        let control = tokio::select! {
+            // command line message
+            input = user_interface.get_input() => {
+                match input {
+                    Some(Input::Log) => {
+                        user_interface.replay();
+                        LoopControl::Continue
+                    }
+                    Some(Input::Quit) => LoopControl::Quit,
+                    Some(Input::Reconnect(addresses)) => {
+                        if !addresses.is_empty() {
+                            user_session.swarm_config.listen_addrs = addresses;
+                        }
+                        LoopControl::Reconnect
+                    }
+                    input => {
+                        process_input(input, &mut swarm, user_session, user_interface).await;
+                        LoopControl::Continue
+                    }
+                }
+            },
+            // spawned message from internal process
+            response = events.responses.recv() => match response {
+                Some(message) => {
+                    let sender = message.sender.clone();
+                    let handled = validation::contain(|| resolve_spawned_messages(user_interface, message, &mut swarm, user_session));
+                    if let Err(error) = handled {
+                        on_handler_failure(user_interface, &mut swarm, user_session, sender, error);
+                    }
+                    if let Some(warning) = events.responses.drop_warning() {
+                        user_interface.print_to_output(OutputEvents::Error(warning));
+                    }
+                    LoopControl::Continue
+                }
+                None => on_channel_closed(&mut restarts),
+            },
+            // finished background task of active game session
+            result = user_session.game_session().tasks.reap() => {
+                match result {
+                    Err(error) if error.is_panic() => {
+                        user_interface.print_to_output(OutputEvents::Error(format!("Session task failed: {}", error)));
+                    }
+                    _ => {}
+                }
+                LoopControl::Continue
+            },
+            // failure outside of handlers, e.g. file which cannot be saved
+            Some(error) = events.errors.recv() => {
+                user_interface.print_to_output(OutputEvents::Error(error));
+                LoopControl::Continue
+            },
+            // config file changed
+            Some(()) = events.config.recv() => {
+                if let Some(watcher) = events.config_watcher.as_mut() {
+                    reload_config(user_interface, watcher, user_session);
+                }
+                LoopControl::Continue
+            },
+            // player on turn ran out of time
+            _ = tokio::time::sleep_until(turn_deadline.map(tokio::time::Instant::from_std).unwrap_or_else(tokio::time::Instant::now)), if turn_deadline.is_some() => {
+                check_turn_timeouts(user_interface, &mut swarm, user_session);
+                LoopControl::Continue
+            },
+            // held action was not undone in time
+            _ = tokio::time::sleep_until(held_due.map(tokio::time::Instant::from_std).unwrap_or_else(tokio::time::Instant::now)), if held_due.is_some() => {
+                send_held(user_interface, &mut swarm, user_session, false);
+                LoopControl::Continue
+            },
+            _ = prune_timer.tick() => {
+                prune_partial_view(&mut swarm);
+                refresh_discovery(&mut swarm, user_session);
+                if let Some(network) = &user_session.virtual_network {
+                    loadtest::reconnect(&mut swarm, network);
+                }
+                LoopControl::Continue
+            },
+            event = swarm.select_next_some() => {
+                let changed = match event {
+                    libp2p::swarm::SwarmEvent::NewListenAddr { address, .. } => {
+                        user_interface.on_listen_addr(&address.to_string());
+                        if !banner_shown {
+                            banner_shown = true;
+                            user_interface.print_to_output(OutputEvents::Banner(banner(&swarm, user_session)));
+                        } else {
+                            user_interface.print_to_output(OutputEvents::ListeningOn(address.clone()));
+                        }
+                        swarm.behaviour_mut().nat.on_listen(&address)
+                    }
+                    libp2p::swarm::SwarmEvent::ConnectionEstablished {
+                        endpoint: libp2p::core::ConnectedPoint::Listener { send_back_addr, .. },
+                        ..
+                    } => swarm.behaviour_mut().nat.on_inbound(&send_back_addr),
+                    _ => None,
+                };
+                if let Some(nat) = changed {
+                    user_interface.print_to_output(OutputEvents::NatStatus(nat));
+                }
+                LoopControl::Continue
+            },
+        };
+
+        match control {
+            LoopControl::Continue => {}
+            LoopControl::Restart => {
+                let error = format!("Internal channel closed, restarting network ({}/{})", restarts, MAX_RESTARTS);
+                user_interface.print_to_output(OutputEvents::Error(error));
+                let (response_sender, receiver) = channel::bounded(CHANNEL_CAPACITY);
+                events.responses = receiver;
+                user_session.internal_sender = response_sender.downgrade();
+                for session in user_session.sessions.iter_mut() {
+                    session.restart_tasks(response_sender.downgrade());
+                }
+                swarm = init_swarm(user_session, response_sender).await;
+            }
+            LoopControl::Reconnect => {
+                // old swarm has to release its listen addresses first, its sender keeps channel open
+                let response_sender = swarm.behaviour().response_sender.clone();
+                drop(swarm);
+                swarm = init_swarm(user_session, response_sender).await;
+                for session in user_session.sessions.iter_mut().filter(|session| session.is_initiated()) {
+                    session.resuming = true;
+                }
+                refresh_discovery(&mut swarm, user_session);
+                reconnect_known(&mut swarm, user_session);
+                user_interface.print_to_output(OutputEvents::Reconnected(user_session.swarm_config.listen_addrs.clone()));
+            }
+            LoopControl::Quit => {
+                quit(user_interface, &mut swarm, user_session).await;
+                user_interface.print_to_output(OutputEvents::Shutdown);
+                return;
+            }
+            LoopControl::Shutdown => {
+                user_interface.print_to_output(OutputEvents::Shutdown);
+                return;
+            }
+        }
+    }
+}
+
+/// Reports message whose handler panicked, only the game of its sender is ended,
+/// other games go on
+fn on_handler_failure<Output: input::Input<Input, OutputEvents>>(
+    user_interface : &mut Output,
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    user_session: &mut UserSession,
+    sender: String,
+    error: validation::InvalidMessage,
+) {
+    let diagnostics = &mut swarm.behaviour_mut().diagnostics;
+    diagnostics.record(&error);
+    let diagnostics = *diagnostics;
+    user_interface.print_to_output(OutputEvents::Diagnostics(sender.clone(), error, diagnostics));
+    // handler stopped halfway, its game is not played on
+    if let Some(opponent_id) = user_session.drop_broken_session(swarm, &sender) {
+        user_interface.print_to_output(OutputEvents::Error(format!("Game with <{}> was ended, it could not be played on after failure", opponent_id)));
+    }
+}
+
+/// Applies safe changes of config file to running session
+fn reload_config<Output: input::Input<Input, OutputEvents> + observer::SwarmObserver>(
+    user_interface : &mut Output,
+    watcher: &mut reload::ConfigWatcher,
+    user_session: &mut UserSession,
+) {
+    match watcher.reload(&user_session.settings) {
+        Ok((_, _, summary)) if summary.is_empty() => {}
+        Ok((config, settings, summary)) => {
+            user_session.moderator = chat::Moderator::new(&settings.text_limits, &settings.chat_filter);
+            user_session.settings = settings;
+            user_interface.on_config_reloaded(&config);
+            user_interface.print_to_output(OutputEvents::ConfigReloaded(summary));
+        }
+        Err(error) => user_interface.print_to_output(OutputEvents::ConfigRejected(error.to_string())),
+    }
+}
+
+/// Decides whether network is restarted or client shuts down
+fn on_channel_closed(restarts: &mut u32) -> LoopControl {
+    if *restarts >= MAX_RESTARTS {
+        LoopControl::Shutdown
+    } else {
+        *restarts += 1;
+        LoopControl::Restart
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::handlers::testing::{offline_session, start_game};
+    use super::*;
+
+    /// Frontend which quits once network was restarted
+    #[derive(Default)]
+    struct QuitAfterRestart {
+        events: std::cell::RefCell<Vec<OutputEvents>>,
+    }
+
+    impl QuitAfterRestart {
+        fn restarted(&self) -> bool {
+            self.events
+                .borrow()
+                .iter()
+                .any(|event| matches!(event, OutputEvents::Error(error) if error.starts_with("Internal channel closed")))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl input::Input<Input, OutputEvents> for QuitAfterRestart {
+        async fn get_input(&mut self) -> Option<Input> {
+            while !self.restarted() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            Some(Input::Quit)
+        }
+
+        fn print_to_output(&self, event: OutputEvents) {
+            self.events.borrow_mut().push(event);
+        }
+
+        fn ask(&mut self, _prompt: super::super::prompt::Prompt) -> Option<super::super::prompt::Answer> {
+            None
+        }
+    }
+
+    impl observer::SwarmObserver for QuitAfterRestart {}
+
+
+    #[test]
+    fn restarts_after_channel_closes() {
+        let mut restarts = 0;
+        assert_eq!(on_channel_closed(&mut restarts), LoopControl::Restart);
+        assert_eq!(restarts, 1);
+    }
+
+    #[test]
+    fn shuts_down_after_too_many_restarts() {
+        let mut restarts = 0;
+        for _ in 0..MAX_RESTARTS {
+            assert_eq!(on_channel_closed(&mut restarts), LoopControl::Restart);
+        }
+        assert_eq!(on_channel_closed(&mut restarts), LoopControl::Shutdown);
+        assert_eq!(restarts, MAX_RESTARTS);
+    }
+
+    #[tokio::test]
+    async fn closed_channel_yields_none() {
+        let (sender, mut receiver) = channel::bounded::<PeerMessage>(CHANNEL_CAPACITY);
+        drop(sender);
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn sessions_do_not_keep_closed_channel_open() {
+        let (mut user_session, mut swarm, receiver) = offline_session(Settings::default()).await;
+        // running game has reminder ticker with its own sender
+        start_game(&mut user_session, 0, "peer");
+        // network drops the last sender of the channel
+        swarm.behaviour_mut().response_sender = channel::bounded(1).0;
+
+        let events = Events {
+            responses: receiver,
+            errors: mpsc::unbounded_channel().1,
+            config: mpsc::unbounded_channel().1,
+            config_watcher: None,
+        };
+        let mut frontend = QuitAfterRestart::default();
+        let mut recorder = history::Recorder::new(&mut frontend, history::History::new(16));
+        let served = serve(&mut recorder, &mut user_session, swarm, events, true);
+        tokio::time::timeout(std::time::Duration::from_secs(10), served).await.expect("main loop exits");
+
+        assert!(frontend.restarted());
+        assert!(matches!(frontend.events.borrow().last(), Some(OutputEvents::Shutdown)));
+    }
+
+    #[tokio::test]
+    async fn failed_handler_ends_only_game_of_sender() {
+        let (mut user_session, mut swarm, _receiver) = offline_session(Settings::default()).await;
+        let mut frontend = QuitAfterRestart::default();
+        for opponent in ["alice", "bob"] {
+            let index = user_session.free_session(opponent).unwrap();
+            start_game(&mut user_session, index, opponent);
+        }
+
+        let failure = validation::contain(|| panic!("handler of bob's message failed")).unwrap_err();
+        on_handler_failure(&mut frontend, &mut swarm, &mut user_session, "bob".to_string(), failure);
+        assert_eq!(user_session.session_of("bob"), None);
+        assert!(user_session.session_of("alice").is_some());
+
+        let failure = validation::contain(|| panic!("handler of internal message failed")).unwrap_err();
+        on_handler_failure(&mut frontend, &mut swarm, &mut user_session, String::new(), failure);
+        assert!(user_session.session_of("alice").is_some(), "internal message does not end active game");
+        assert!(matches!(frontend.events.borrow().last(), Some(OutputEvents::Diagnostics(sender, ..)) if sender.is_empty()));
+    }
+}
@@ -0,0 +1,265 @@
+//! # Migrate
+//!
+//! Identity and history moved to other computer. Archive bundles config file
+//! with files it points to, that is stats, ladder with rating and correspondence
+//! directory with identity key and unfinished games. It is encrypted by key
+//! derived from passphrase. Import writes files next to the new config, paths of
+//! the old computer keep only their part relative to its config, and never
+//! overwrites existing ones. Written files can be read only by their owner.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::Hmac;
+use sha2::Sha256;
+
+use super::auth::{from_hex, to_hex};
+use super::correspondence::{write_private, FILES, KEY_FILE};
+use crate::config::{Config, ConfigError};
+
+/// Start of every archive, tells it apart from other files
+const MAGIC: &[u8] = b"tictactoe-identity-1\n";
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 12;
+/// Rounds of key derivation, they make guessing passphrase slow
+const ROUNDS: u32 = 100_000;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(PathBuf, std::io::Error),
+    Config(ConfigError),
+    /// Config has no correspondence_dir, so identity is not kept on disk
+    NoIdentity,
+    EmptyPassphrase,
+    /// System gave no random bytes for salt
+    NoRandom,
+    /// File is not archive, it is damaged or passphrase is wrong
+    Unreadable,
+    /// File would be overwritten
+    Exists(PathBuf),
+    /// Imported config points outside of its directory
+    OutsideConfig(PathBuf),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveError::Io(path, err) => write!(f, "cannot access {}: {}", path.display(), err),
+            ArchiveError::Config(err) => write!(f, "{}", err),
+            ArchiveError::NoIdentity => write!(f, "no identity to export, set correspondence_dir in config and start client once"),
+            ArchiveError::EmptyPassphrase => write!(f, "passphrase must not be empty"),
+            ArchiveError::NoRandom => write!(f, "system random source is not available"),
+            ArchiveError::Unreadable => write!(f, "wrong passphrase or damaged archive"),
+            ArchiveError::Exists(path) => write!(f, "{} already exists, move it away first", path.display()),
+            ArchiveError::OutsideConfig(path) => write!(f, "archive points to {} outside of config directory", path.display()),
+        }
+    }
+}
+
+/// Everything moved to other computer
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Archive {
+    /// Content of config file
+    pub config: String,
+    pub stats: Option<String>,
+    pub ladder: Option<String>,
+    /// Files of correspondence directory by name, hex encoded
+    pub correspondence: BTreeMap<String, String>,
+}
+
+impl Archive {
+    /// Collects files of client using given config file
+    pub fn collect(config_path: &Path) -> Result<Archive, ArchiveError> {
+        let settings = Config::from_file(config_path).map_err(ArchiveError::Config)?.session;
+        let config = std::fs::read_to_string(config_path).map_err(|err| ArchiveError::Io(config_path.to_path_buf(), err))?;
+        let dir = settings.correspondence_dir.ok_or(ArchiveError::NoIdentity)?;
+        let mut correspondence = BTreeMap::new();
+        for name in FILES {
+            if let Some(content) = read_optional(&dir.join(name))? {
+                correspondence.insert(name.to_string(), to_hex(&content));
+            }
+        }
+        if !correspondence.contains_key(KEY_FILE) {
+            return Err(ArchiveError::NoIdentity);
+        }
+
+        let text = |path: Option<PathBuf>| -> Result<Option<String>, ArchiveError> {
+            match path.map(|path| read_optional(&path)).transpose()?.flatten() {
+                Some(content) => Ok(Some(String::from_utf8_lossy(&content).into_owned())),
+                None => Ok(None),
+            }
+        };
+        Ok(Archive { config, stats: text(settings.stats_file)?, ladder: text(settings.ladder_file)?, correspondence })
+    }
+
+    /// Writes files into directory of given config path and config pointing to
+    /// them to the path itself. Nothing is written when any of them exists.
+    pub fn restore(&self, config_path: &Path) -> Result<Vec<PathBuf>, ArchiveError> {
+        let parse_error = |err| ArchiveError::Config(ConfigError::Parse(err));
+        let mut config: serde_json::Value = serde_json::from_str(&self.config).map_err(parse_error)?;
+        let settings = serde_json::from_value::<Config>(config.clone()).map_err(parse_error)?.session;
+        let base = config_path.parent().unwrap_or(Path::new(""));
+        let dir = relocate(base, &settings.correspondence_dir.ok_or(ArchiveError::NoIdentity)?)?;
+        config["session"]["correspondence_dir"] = serde_json::json!(dir);
+        let mut files = Vec::new();
+        for (key, path, content) in [("stats_file", settings.stats_file, &self.stats), ("ladder_file", settings.ladder_file, &self.ladder)] {
+            if let Some(path) = path {
+                let path = relocate(base, &path)?;
+                config["session"][key] = serde_json::json!(path);
+                if let Some(content) = content {
+                    files.push((path, content.clone().into_bytes()));
+                }
+            }
+        }
+        let config = serde_json::to_string_pretty(&config).expect("cannot jsonify config");
+        files.insert(0, (config_path.to_path_buf(), config.into_bytes()));
+        for name in FILES {
+            if let Some(hex) = self.correspondence.get(name) {
+                files.push((dir.join(name), from_hex(hex).ok_or(ArchiveError::Unreadable)?));
+            }
+        }
+
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(ArchiveError::Exists(path.clone()));
+        }
+        for (path, content) in &files {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent).map_err(|err| ArchiveError::Io(parent.to_path_buf(), err))?;
+            }
+            write_private(path, content).map_err(|err| ArchiveError::Io(path.clone(), err))?;
+        }
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+
+    /// Encrypts archive with key derived from passphrase
+    pub fn seal(&self, passphrase: &str) -> Result<Vec<u8>, ArchiveError> {
+        let mut salt = [0u8; SALT_BYTES];
+        let mut nonce = [0u8; NONCE_BYTES];
+        getrandom::getrandom(&mut salt).map_err(|_| ArchiveError::NoRandom)?;
+        getrandom::getrandom(&mut nonce).map_err(|_| ArchiveError::NoRandom)?;
+        let plain = serde_json::to_vec(self).expect("cannot jsonify archive");
+        let sealed = cipher(passphrase, &salt)?
+            .encrypt(&Nonce::from(nonce), plain.as_slice())
+            .expect("archive is too large to encrypt");
+        Ok([MAGIC, &salt, &nonce, &sealed].concat())
+    }
+
+    /// Decrypts archive sealed with the same passphrase
+    pub fn open(bytes: &[u8], passphrase: &str) -> Result<Archive, ArchiveError> {
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .filter(|rest| rest.len() > SALT_BYTES + NONCE_BYTES)
+            .ok_or(ArchiveError::Unreadable)?;
+        let (salt, rest) = rest.split_at(SALT_BYTES);
+        let (nonce, sealed) = rest.split_at(NONCE_BYTES);
+        let nonce: [u8; NONCE_BYTES] = nonce.try_into().expect("nonce was split at its length");
+        let plain = cipher(passphrase, salt)?
+            .decrypt(&Nonce::from(nonce), sealed)
+            .map_err(|_| ArchiveError::Unreadable)?;
+        serde_json::from_slice(&plain).map_err(|_| ArchiveError::Unreadable)
+    }
+}
+
+/// Writes encrypted archive of client using given config file, returns what it holds
+pub fn export(config_path: &Path, archive_path: &Path, passphrase: &str) -> Result<Archive, ArchiveError> {
+    if archive_path.exists() {
+        return Err(ArchiveError::Exists(archive_path.to_path_buf()));
+    }
+    let archive = Archive::collect(config_path)?;
+    let sealed = archive.seal(passphrase)?;
+    write_private(archive_path, &sealed).map_err(|err| ArchiveError::Io(archive_path.to_path_buf(), err))?;
+    Ok(archive)
+}
+
+/// Restores client from encrypted archive, config goes to given path. Returns written files.
+pub fn import(archive_path: &Path, config_path: &Path, passphrase: &str) -> Result<Vec<PathBuf>, ArchiveError> {
+    let bytes = std::fs::read(archive_path).map_err(|err| ArchiveError::Io(archive_path.to_path_buf(), err))?;
+    Archive::open(&bytes, passphrase)?.restore(config_path)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, ArchiveError> {
+    if passphrase.is_empty() {
+        return Err(ArchiveError::EmptyPassphrase);
+    }
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, ROUNDS, &mut key);
+    Ok(ChaCha20Poly1305::new(&Key::from(key)))
+}
+
+/// Returns where file of imported config goes, absolute path of the old computer
+/// keeps only its name and path leaving directory of config is refused
+fn relocate(base: &Path, path: &Path) -> Result<PathBuf, ArchiveError> {
+    let relative = match path.file_name() {
+        Some(name) if path.is_absolute() => Path::new(name),
+        _ => path,
+    };
+    if !relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(ArchiveError::OutsideConfig(path.to_path_buf()));
+    }
+    Ok(base.join(relative))
+}
+
+/// Reads file, none when it does not exist
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>, ArchiveError> {
+    match std::fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(ArchiveError::Io(path.to_path_buf(), err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_identity_and_history() {
+        let dir = std::env::temp_dir().join(format!("tictactoe-migrate-{}", std::process::id()));
+        let games = dir.join("games");
+        std::fs::create_dir_all(&games).unwrap();
+        let config = serde_json::json!({
+            "session": {
+                "correspondence_dir": games,
+                "stats_file": dir.join("stats.json"),
+                "ladder_file": dir.join("ladder.json"),
+            }
+        });
+        let config_path = dir.join("tictactoe.json");
+        std::fs::write(&config_path, config.to_string()).unwrap();
+        assert!(matches!(Archive::collect(&config_path), Err(ArchiveError::NoIdentity)));
+        std::fs::write(games.join(KEY_FILE), [0u8, 1, 255]).unwrap();
+        std::fs::write(dir.join("stats.json"), "{\"games\":[]}").unwrap();
+
+        let archive_path = dir.join("identity.archive");
+        let exported = export(&config_path, &archive_path, "correct horse").unwrap();
+        assert_eq!(exported.ladder, None);
+        assert!(matches!(import(&archive_path, &config_path, "wrong"), Err(ArchiveError::Unreadable)));
+        assert!(matches!(import(&archive_path, &config_path, "correct horse"), Err(ArchiveError::Exists(_))));
+
+        std::fs::remove_dir_all(&games).unwrap();
+        std::fs::remove_file(dir.join("stats.json")).unwrap();
+        std::fs::remove_file(&config_path).unwrap();
+        let written = import(&archive_path, &config_path, "correct horse");
+        let restored = Archive::collect(&config_path);
+        #[cfg(unix)]
+        let key_mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(games.join(KEY_FILE)).unwrap().permissions());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(written.unwrap().len(), 3);
+        // config is written again with paths next to it, here they stay the same
+        let restored = restored.unwrap();
+        assert_eq!((restored.stats, restored.correspondence), (exported.stats, exported.correspondence));
+        #[cfg(unix)]
+        assert_eq!(key_mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn restores_next_to_new_config() {
+        let base = Path::new("home");
+        assert_eq!(relocate(base, Path::new("/old/home/games")).unwrap(), base.join("games"));
+        assert_eq!(relocate(base, Path::new("./data/stats.json")).unwrap(), base.join("data/stats.json"));
+        assert!(matches!(relocate(base, Path::new("../games")), Err(ArchiveError::OutsideConfig(_))));
+        assert!(matches!(relocate(base, Path::new("/")), Err(ArchiveError::OutsideConfig(_))));
+    }
+}
@@ -0,0 +1,222 @@
+//! # Nat
+//!
+//! Tells whether peers on internet can dial me. AutoNAT asks connected peers to
+//! dial me back and its answer is trusted over anything else. Until it has one,
+//! identify tells address peers see me at: public address which none of my
+//! interfaces has means NAT translates my connections, my own public address
+//! means it does not. Connection opened to me from internet proves that peers
+//! can dial me.
+
+/// What peers and connections told about my addresses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NatStatus {
+    /// No peer told me how it sees me yet
+    Unknown,
+    /// Only peers on local network told me how they see me
+    LocalOnly,
+    /// Peers see me at public address of my own interface
+    Untranslated,
+    /// Peers see me at public address none of my interfaces has
+    Translated,
+    /// Peer on internet opened connection to me
+    DialedFromInternet,
+    /// AutoNAT peers dialed me back at public address
+    Reachable,
+    /// AutoNAT peers could not dial me back
+    Unreachable,
+}
+
+impl std::fmt::Display for NatStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NatStatus::Unknown => write!(f, "NAT status is not known yet, no peer reported how it sees you"),
+            NatStatus::LocalOnly => write!(f, "Only peers on local network reported how they see you, NAT status on internet is not known"),
+            NatStatus::Untranslated => write!(f, "Peers see you at your own public address, no NAT translates your connections"),
+            NatStatus::Translated => write!(
+                f,
+                "You are behind NAT; add players to static_peers or meet them at rendezvous_point to play over the internet"
+            ),
+            NatStatus::DialedFromInternet => write!(f, "Peer on internet connected to you, players on internet can dial you"),
+            NatStatus::Reachable => write!(f, "Peers on internet dialed you back, players on internet can dial you"),
+            NatStatus::Unreachable => write!(
+                f,
+                "Peers on internet cannot dial you back; add players to static_peers, meet them at rendezvous_point or listen through relays"
+            ),
+        }
+    }
+}
+
+/// Connectivity report of 'netinfo' command
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetInfo {
+    pub peer_id: String,
+    pub listen_addrs: Vec<libp2p::Multiaddr>,
+    /// Public addresses peers see me at
+    pub observed_addrs: Vec<libp2p::Multiaddr>,
+    /// Public address AutoNAT peers dialed me back at
+    pub reachable_addr: Option<libp2p::Multiaddr>,
+    pub nat: NatStatus,
+}
+
+/// Collects addresses peers see me at and connections opened to me
+#[derive(Debug)]
+pub struct NatObserver {
+    listen_ips: std::collections::HashSet<std::net::IpAddr>,
+    /// Public addresses peers observed me at with peers which reported them
+    observed: std::collections::HashMap<libp2p::Multiaddr, std::collections::HashSet<libp2p::PeerId>>,
+    local_peers: bool,
+    inbound_public: bool,
+    /// Last answer of AutoNAT probes, none until they have one
+    autonat: Option<libp2p::autonat::NatStatus>,
+    status: NatStatus,
+}
+
+impl Default for NatObserver {
+    fn default() -> Self {
+        NatObserver {
+            listen_ips: std::collections::HashSet::new(),
+            observed: std::collections::HashMap::new(),
+            local_peers: false,
+            inbound_public: false,
+            autonat: None,
+            status: NatStatus::Unknown,
+        }
+    }
+}
+
+impl NatObserver {
+    pub fn status(&self) -> NatStatus {
+        self.status
+    }
+
+    /// Returns public addresses peers see me at, the most reported first
+    pub fn observed(&self) -> Vec<libp2p::Multiaddr> {
+        let mut observed: Vec<_> = self.observed.iter().collect();
+        observed.sort_by_key(|(address, peers)| (std::cmp::Reverse(peers.len()), address.to_string()));
+        observed.into_iter().map(|(address, _)| address.clone()).collect()
+    }
+
+    /// Returns public address AutoNAT peers dialed me back at
+    pub fn reachable(&self) -> Option<libp2p::Multiaddr> {
+        match &self.autonat {
+            Some(libp2p::autonat::NatStatus::Public(address)) => Some(address.clone()),
+            _ => None,
+        }
+    }
+
+    /// Notes address swarm listens on, returns new status when it changed
+    pub fn on_listen(&mut self, address: &libp2p::Multiaddr) -> Option<NatStatus> {
+        self.listen_ips.extend(ip(address));
+        self.update()
+    }
+
+    /// Notes address peer sees me at, returns new status when it changed
+    pub fn on_observed(&mut self, peer: libp2p::PeerId, address: libp2p::Multiaddr) -> Option<NatStatus> {
+        if is_public(&address) {
+            self.observed.entry(address).or_default().insert(peer);
+        } else {
+            self.local_peers = true;
+        }
+        self.update()
+    }
+
+    /// Notes connection peer opened to me, returns new status when it changed.
+    /// Connection through relay does not count, the relay was dialed instead of me.
+    pub fn on_inbound(&mut self, remote: &libp2p::Multiaddr) -> Option<NatStatus> {
+        let relayed = remote.iter().any(|protocol| matches!(protocol, libp2p::multiaddr::Protocol::P2pCircuit));
+        self.inbound_public |= is_public(remote) && !relayed;
+        self.update()
+    }
+
+    /// Notes answer of AutoNAT probes, returns new status when it changed
+    pub fn on_autonat(&mut self, status: libp2p::autonat::NatStatus) -> Option<NatStatus> {
+        self.autonat = Some(status).filter(|status| *status != libp2p::autonat::NatStatus::Unknown);
+        self.update()
+    }
+
+    fn update(&mut self) -> Option<NatStatus> {
+        let has_public_ip = self.observed.keys().filter_map(ip).any(|ip| self.listen_ips.contains(&ip));
+        let status = if let Some(autonat) = &self.autonat {
+            if autonat.is_public() {
+                NatStatus::Reachable
+            } else {
+                NatStatus::Unreachable
+            }
+        } else if self.inbound_public {
+            NatStatus::DialedFromInternet
+        } else if has_public_ip {
+            NatStatus::Untranslated
+        } else if !self.observed.is_empty() {
+            NatStatus::Translated
+        } else if self.local_peers {
+            NatStatus::LocalOnly
+        } else {
+            NatStatus::Unknown
+        };
+        (status != self.status).then(|| {
+            self.status = status;
+            status
+        })
+    }
+}
+
+fn ip(address: &libp2p::Multiaddr) -> Option<std::net::IpAddr> {
+    match address.iter().next() {
+        Some(libp2p::multiaddr::Protocol::Ip4(ip)) => Some(ip.into()),
+        Some(libp2p::multiaddr::Protocol::Ip6(ip)) => Some(ip.into()),
+        _ => None,
+    }
+}
+
+/// Returns true for address routable on internet
+pub fn is_public(address: &libp2p::Multiaddr) -> bool {
+    match ip(address) {
+        Some(std::net::IpAddr::V4(ip)) => {
+            // 100.64.0.0/10 is shared by carrier-grade NAT
+            let shared = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast() || shared)
+        }
+        Some(std::net::IpAddr::V6(ip)) => {
+            let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+            let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_nat_from_observed_address() {
+        let address = |address: &str| address.parse::<libp2p::Multiaddr>().unwrap();
+        let mut observer = NatObserver::default();
+        assert_eq!(observer.on_listen(&address("/ip4/192.168.1.5/tcp/4001")), None);
+        assert_eq!(observer.on_observed(libp2p::PeerId::random(), address("/ip4/192.168.1.5/tcp/4001")), Some(NatStatus::LocalOnly));
+        assert_eq!(observer.on_observed(libp2p::PeerId::random(), address("/ip4/203.0.113.7/tcp/51000")), Some(NatStatus::Translated));
+        assert_eq!(observer.on_inbound(&address("/ip4/10.0.0.2/tcp/52000")), None);
+        assert_eq!(observer.observed(), vec![address("/ip4/203.0.113.7/tcp/51000")]);
+        assert_eq!(observer.on_inbound(&address("/ip4/198.51.100.1/tcp/52000")), Some(NatStatus::DialedFromInternet));
+
+        let mut observer = NatObserver::default();
+        observer.on_listen(&address("/ip4/203.0.113.7/tcp/4001"));
+        assert_eq!(observer.on_observed(libp2p::PeerId::random(), address("/ip4/203.0.113.7/tcp/51000")), Some(NatStatus::Untranslated));
+    }
+
+    #[test]
+    fn autonat_answer_wins() {
+        let address = |address: &str| address.parse::<libp2p::Multiaddr>().unwrap();
+        let mut observer = NatObserver::default();
+        observer.on_inbound(&address("/ip4/198.51.100.1/tcp/52000"));
+        assert_eq!(observer.on_autonat(libp2p::autonat::NatStatus::Private), Some(NatStatus::Unreachable));
+        let public = address("/ip4/203.0.113.7/tcp/4001");
+        assert_eq!(observer.on_autonat(libp2p::autonat::NatStatus::Public(public.clone())), Some(NatStatus::Reachable));
+        assert_eq!(observer.reachable(), Some(public));
+
+        // unknown answer falls back to what connections told
+        assert_eq!(observer.on_autonat(libp2p::autonat::NatStatus::Unknown), Some(NatStatus::DialedFromInternet));
+        assert_eq!(observer.reachable(), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! # Netstats
+//!
+//! Message counters per topic maintained by network behaviour. Clones share the
+//! same counters, so frontends and metrics exporters can keep a handle and read
+//! the numbers shown by 'netstats' command.
+
+use super::protocol::{self, WireMessage};
+
+/// Counters of one topic
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopicStats {
+    pub topic: String,
+    /// Sent messages by type
+    pub sent: std::collections::BTreeMap<&'static str, u64>,
+    /// Received messages by type, unreadable ones are counted as invalid
+    pub received: std::collections::BTreeMap<&'static str, u64>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Repeated turns which were not passed to the game
+    pub duplicates_dropped: u64,
+    /// Queued messages sent again once peer came back
+    pub retransmissions: u64,
+    latency_total_millis: u64,
+    latency_samples: u64,
+}
+
+impl TopicStats {
+    /// Returns average one-way delay estimated from clock synchronization round trips
+    pub fn average_latency_millis(&self) -> Option<u64> {
+        self.latency_total_millis.checked_div(self.latency_samples)
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    topics: std::collections::BTreeMap<String, TopicStats>,
+    /// Turns received per topic and sender, repeated turn is a duplicate
+    seen_turns: std::collections::HashSet<(String, String, Vec<u8>)>,
+}
+
+impl Counters {
+    fn topic(&mut self, topic: &str) -> &mut TopicStats {
+        self.topics
+            .entry(topic.to_string())
+            .or_insert_with(|| TopicStats { topic: topic.to_string(), ..Default::default() })
+    }
+}
+
+/// Shared message counters
+#[derive(Clone, Default)]
+pub struct NetStats {
+    counters: std::sync::Arc<std::sync::Mutex<Counters>>,
+}
+
+impl NetStats {
+    fn counters(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters.lock().expect("netstats lock poisoned")
+    }
+
+    /// Counts message published to topic
+    pub fn on_sent(&self, topic: &str, payload: &[u8]) {
+        let mut counters = self.counters();
+        let stats = counters.topic(topic);
+        *stats.sent.entry(kind(payload)).or_default() += 1;
+        stats.bytes_sent += payload.len() as u64;
+    }
+
+    /// Counts message which is sent again
+    pub fn on_retransmitted(&self, topic: &str) {
+        self.counters().topic(topic).retransmissions += 1;
+    }
+
+    /// Counts message received at given local time, returns false when it repeats
+    /// earlier turn of the same sender and should be dropped
+    pub fn on_received(&self, topic: &str, sender: &str, payload: &[u8], now_millis: u64) -> bool {
+        let message = protocol::decode(payload).map(|(message, _)| message);
+        let mut counters = self.counters();
+        if let Some(WireMessage::Turn { .. }) = message {
+            let key = (topic.to_string(), sender.to_string(), payload.to_vec());
+            if !counters.seen_turns.insert(key) {
+                counters.topic(topic).duplicates_dropped += 1;
+                return false;
+            }
+        }
+
+        let stats = counters.topic(topic);
+        *stats.received.entry(message.as_ref().map_or("invalid", WireMessage::kind)).or_default() += 1;
+        stats.bytes_received += payload.len() as u64;
+        if let Some(WireMessage::Pong { ping_sent_at, received_at, sent_at }) = message {
+            let round_trip = now_millis.saturating_sub(ping_sent_at).saturating_sub(sent_at.saturating_sub(received_at));
+            stats.latency_total_millis += round_trip / 2;
+            stats.latency_samples += 1;
+        }
+        true
+    }
+
+    /// Returns counters of all topics
+    pub fn snapshot(&self) -> Vec<TopicStats> {
+        self.counters().topics.values().cloned().collect()
+    }
+}
+
+fn kind(payload: &[u8]) -> &'static str {
+    protocol::decode(payload).map_or("invalid", |(message, _)| message.kind())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_repeated_turn() {
+        let netstats = NetStats::default();
+        let handle = netstats.clone();
+        let turn = WireMessage::Turn { x: 1, y: 1, sent_at: Some(5), mark: None, number: None, auto: false };
+        let turn = protocol::encode(&turn, protocol::WireFormat::Tagged);
+        let pong = WireMessage::Pong { ping_sent_at: 1000, received_at: 50, sent_at: 60 };
+        let pong = protocol::encode(&pong, protocol::WireFormat::Tagged);
+
+        assert!(netstats.on_received("game", "peer", turn.as_bytes(), 0));
+        assert!(!netstats.on_received("game", "peer", turn.as_bytes(), 0));
+        assert!(netstats.on_received("game", "peer", pong.as_bytes(), 1110));
+        netstats.on_sent("game", turn.as_bytes());
+        netstats.on_retransmitted("game");
+
+        let stats = &handle.snapshot()[0];
+        assert_eq!(stats.received.get("turn"), Some(&1));
+        assert_eq!(stats.sent.get("turn"), Some(&1));
+        assert_eq!(stats.duplicates_dropped, 1);
+        assert_eq!(stats.retransmissions, 1);
+        assert_eq!(stats.bytes_received, (turn.len() + pong.len()) as u64);
+        assert_eq!(stats.average_latency_millis(), Some(50));
+    }
+}
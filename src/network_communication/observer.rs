@@ -0,0 +1,17 @@
+//! # Observer
+//!
+//! Connection lifecycle hooks for embedders, every hook does nothing by default
+
+pub trait SwarmObserver {
+    /// Peer appeared on local network
+    fn on_peer_discovered(&mut self, _peer_id: &str) {}
+
+    /// Peer is no longer reachable on local network
+    fn on_peer_lost(&mut self, _peer_id: &str) {}
+
+    /// Client started listening on given address
+    fn on_listen_addr(&mut self, _address: &str) {}
+
+    /// Config file changed, running settings were already updated
+    fn on_config_reloaded(&mut self, _config: &crate::config::Config) {}
+}
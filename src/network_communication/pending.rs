@@ -0,0 +1,79 @@
+//! # Pending
+//!
+//! Asynchronous state waiting for someone, which otherwise lives only in memory
+//! or in outbox: my invitations without answer, messages queued for offline
+//! opponents and questions I did not answer yet. Items are listed with ids which
+//! hold until the list changes, e.g. "i0" for invitation of the first game.
+
+use super::prompt::Prompt;
+
+/// Id of pending item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PendingId {
+    /// My invitation, index of its game
+    Invitation(usize),
+    /// Message in outbox, position counted from 1
+    Queued(usize),
+    /// Question by its prompt id
+    Prompt(u64),
+}
+
+impl PendingId {
+    /// Parses id as it is listed, none when it is not any id
+    pub fn parse(id: &str) -> Option<PendingId> {
+        let (kind, number) = id.split_at(id.char_indices().nth(1)?.0);
+        match kind {
+            "i" => number.parse().ok().map(PendingId::Invitation),
+            "o" => number.parse().ok().map(PendingId::Queued),
+            "p" => number.parse().ok().map(PendingId::Prompt),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PendingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PendingId::Invitation(index) => write!(f, "i{}", index),
+            PendingId::Queued(position) => write!(f, "o{}", position),
+            PendingId::Prompt(id) => write!(f, "p{}", id),
+        }
+    }
+}
+
+/// Item waiting for someone
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pending {
+    /// My invitation of peer in game with given index, seconds since it was sent
+    Invitation(usize, String, u64),
+    /// Message of given type queued for offline peer, position in outbox
+    Queued(usize, String, &'static str),
+    /// Question I did not answer yet
+    Prompt(Prompt),
+}
+
+impl Pending {
+    pub fn id(&self) -> PendingId {
+        match self {
+            Pending::Invitation(index, ..) => PendingId::Invitation(*index),
+            Pending::Queued(position, ..) => PendingId::Queued(*position),
+            Pending::Prompt(prompt) => PendingId::Prompt(prompt.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_roundtrip() {
+        for id in [PendingId::Invitation(0), PendingId::Queued(12), PendingId::Prompt(3)] {
+            assert_eq!(PendingId::parse(&id.to_string()), Some(id));
+        }
+        assert_eq!(PendingId::parse("x1"), None);
+        assert_eq!(PendingId::parse("i"), None);
+        assert_eq!(PendingId::parse("o-1"), None);
+        assert_eq!(PendingId::parse(""), None);
+    }
+}
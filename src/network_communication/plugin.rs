@@ -0,0 +1,199 @@
+//! # Plugin
+//!
+//! Commands added by embedders, e.g. tournament formats or house rules. Words
+//! which are not built-in commands are looked up among registered ones, their
+//! handlers drive the client through [`GameClient`]. With `plugins` feature,
+//! commands can also come from dynamic libraries listed in settings.
+
+use super::behaviour::TicTacToeBehaviour;
+use super::{GameSummary, Settings, UserSession};
+use strum::IntoEnumIterator;
+
+/// Answers to invitation, parser reads them before commands
+const ANSWERS: [&str; 4] = ["y", "yes", "n", "no"];
+
+/// Symbol every plugin library exports, `fn(&mut Plugins)` registering its commands
+pub const REGISTER_SYMBOL: &[u8] = b"tictactoe_register";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginError {
+    /// Parser takes name for built-in command
+    Reserved(String),
+    Duplicate(String),
+    /// Library cannot be loaded or does not export register function
+    Load(String, String),
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginError::Reserved(name) => write!(f, "'{}' would be read as built-in command", name),
+            PluginError::Duplicate(name) => write!(f, "command '{}' is already registered", name),
+            PluginError::Load(path, err) => write!(f, "cannot load plugin {}: {}", path, err),
+        }
+    }
+}
+
+/// Command typed as its name followed by arguments
+pub trait Command: Send {
+    fn name(&self) -> &str;
+    /// Arguments shown by help, e.g. "<peer_index> [<rounds>]"
+    fn usage(&self) -> &str {
+        ""
+    }
+    fn description(&self) -> &str;
+    /// Runs command, error is shown to user
+    fn run(&mut self, client: &mut GameClient<'_>, args: &[String]) -> Result<(), String>;
+}
+
+/// Registered commands
+#[derive(Default)]
+pub struct Plugins {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl Plugins {
+    /// Adds command, names registered before and names starting with built-in
+    /// command, which parser would take for the built-in one, are refused
+    pub fn register(&mut self, command: Box<dyn Command>) -> Result<(), PluginError> {
+        let name = command.name().to_string();
+        let builtin = super::input::Commands::iter().any(|builtin| name.starts_with(builtin.to_string()));
+        if builtin || ANSWERS.contains(&name.as_str()) {
+            return Err(PluginError::Reserved(name));
+        }
+        if self.commands.iter().any(|known| known.name() == name) {
+            return Err(PluginError::Duplicate(name));
+        }
+        self.commands.push(command);
+        Ok(())
+    }
+
+    /// Loads library and lets it register its commands. Library has to be built by
+    /// the same compiler against the same version of this crate, it is never unloaded.
+    #[cfg(feature = "plugins")]
+    pub fn load(&mut self, path: &std::path::Path) -> Result<(), PluginError> {
+        let error = |err: libloading::Error| PluginError::Load(path.display().to_string(), err.to_string());
+        // SAFETY: plugin libraries are trusted like the binary itself, see above
+        unsafe {
+            let library = libloading::Library::new(path).map_err(error)?;
+            let register = library.get::<fn(&mut Plugins)>(REGISTER_SYMBOL).map_err(error)?;
+            register(self);
+            // registered commands point into the library
+            std::mem::forget(library);
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Returns usage and description of every command
+    pub fn help(&self) -> Vec<(String, String)> {
+        self.commands
+            .iter()
+            .map(|command| {
+                let usage = format!("{} {}", command.name(), command.usage());
+                (usage.trim_end().to_string(), command.description().to_string())
+            })
+            .collect()
+    }
+
+    pub(super) fn find_mut(&mut self, name: &str) -> Option<&mut Box<dyn Command>> {
+        self.commands.iter_mut().find(|command| command.name() == name)
+    }
+}
+
+/// Client as seen by command handlers
+pub struct GameClient<'a> {
+    pub(super) swarm: &'a mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    pub(super) session: &'a mut UserSession,
+    /// Lines printed by handler
+    pub(super) output: Vec<String>,
+}
+
+impl GameClient<'_> {
+    pub fn peer_id(&self) -> String {
+        self.session.user_peer_id.to_string()
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.session.settings
+    }
+
+    /// Returns running games
+    pub fn games(&self) -> Vec<GameSummary> {
+        self.session.summaries()
+    }
+
+    /// Returns finished games and peers' records
+    pub fn stats(&self) -> &super::stats::Stats {
+        &self.session.stats
+    }
+
+    /// Invites peer to game with rules from settings, returns false when I already play them
+    pub fn invite(&mut self, peer_id: String, password: Option<String>) -> bool {
+        let rules = self.session.rules();
+        super::handlers::invite_peer(self.swarm, peer_id, rules, password, None, None, self.session)
+    }
+
+    /// Sends chat message to opponent of current game
+    pub fn say(&mut self, text: String) {
+        let format = self.session.opponent_format(self.session.active);
+        super::handlers::send_chat(self.swarm, self.session.game_session(), text, format);
+    }
+
+    /// Shows line to user once handler returns
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Knockout;
+
+    impl Command for Knockout {
+        fn name(&self) -> &str {
+            "knockout"
+        }
+
+        fn description(&self) -> &str {
+            "starts knockout tournament."
+        }
+
+        fn run(&mut self, client: &mut GameClient<'_>, _args: &[String]) -> Result<(), String> {
+            client.print("no players yet");
+            Ok(())
+        }
+    }
+
+    struct Named(&'static str);
+
+    impl Command for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn description(&self) -> &str {
+            ""
+        }
+
+        fn run(&mut self, _client: &mut GameClient<'_>, _args: &[String]) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn refuses_taken_names() {
+        let mut plugins = Plugins::default();
+        plugins.register(Box::new(Knockout)).unwrap();
+        assert_eq!(plugins.register(Box::new(Named("knockout"))), Err(PluginError::Duplicate("knockout".to_string())));
+        assert_eq!(plugins.register(Box::new(Named("turn"))), Err(PluginError::Reserved("turn".to_string())));
+        assert_eq!(plugins.register(Box::new(Named("reviewer"))), Err(PluginError::Reserved("reviewer".to_string())));
+        assert_eq!(plugins.help(), vec![("knockout".to_string(), "starts knockout tournament.".to_string())]);
+        assert!(plugins.find_mut("knockout").is_some());
+    }
+}
@@ -0,0 +1,109 @@
+//! # Prompt
+//!
+//! Questions waiting for user's decision. Frontend renders each one, e.g. as a
+//! dialog, and answers it right away or later with its id, so answer always
+//! belongs to the question it was given to, not to whatever game is active.
+
+use super::protocol::Introduction;
+use crate::tictactoe::Rules;
+
+/// Game offered in invitation, everything invited peer sees before answering
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Proposal {
+    pub rules: Rules,
+    /// Start time in UTC milliseconds, none when game may start right away
+    pub start_at: Option<u64>,
+    pub introduction: Introduction,
+    /// Reputation of proposer in my stats
+    pub reputation: i64,
+    /// Whose nickname the proposer's one looks the same as, mine or other peer's
+    pub lookalike: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Question {
+    /// Peer invites me to game
+    Invitation(String, Proposal),
+    /// Peer proposes other start time of game between us
+    Reschedule(String, u64),
+    /// Opponent of last game wants to review it together
+    Review(String),
+}
+
+impl Question {
+    /// Returns peer who asked
+    pub fn peer_id(&self) -> &str {
+        match self {
+            Question::Invitation(peer_id, ..) | Question::Reschedule(peer_id, _) | Question::Review(peer_id) => peer_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prompt {
+    pub id: u64,
+    pub question: Question,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    Yes,
+    No,
+}
+
+/// Questions asked and not answered yet
+#[derive(Debug, Default)]
+pub struct Prompts {
+    next_id: u64,
+    pending: Vec<Prompt>,
+}
+
+impl Prompts {
+    /// Returns new prompt with question, it waits for answer from now
+    pub fn open(&mut self, question: Question) -> Prompt {
+        self.next_id += 1;
+        let prompt = Prompt { id: self.next_id, question };
+        self.pending.push(prompt.clone());
+        prompt
+    }
+
+    /// Returns prompts waiting for answer, the oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Prompt> {
+        self.pending.iter()
+    }
+
+    /// Returns question of prompt which waits for answer
+    pub fn get(&self, id: u64) -> Option<&Question> {
+        self.pending.iter().find(|prompt| prompt.id == id).map(|prompt| &prompt.question)
+    }
+
+    /// Returns question answered by prompt id, none when it was answered or closed before
+    pub fn take(&mut self, id: u64) -> Option<Question> {
+        let index = self.pending.iter().position(|prompt| prompt.id == id)?;
+        Some(self.pending.remove(index).question)
+    }
+
+    /// Drops questions of peer which no longer need answer, e.g. withdrawn invitation
+    pub fn close(&mut self, peer_id: &str) {
+        self.pending.retain(|prompt| prompt.question.peer_id() != peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_belongs_to_its_question() {
+        let mut prompts = Prompts::default();
+        let invitation = prompts.open(Question::Invitation("alice".to_string(), Proposal::default()));
+        let review = prompts.open(Question::Review("bob".to_string()));
+        assert_ne!(invitation.id, review.id);
+
+        assert_eq!(prompts.take(review.id), Some(Question::Review("bob".to_string())));
+        assert_eq!(prompts.take(review.id), None, "question is answered only once");
+
+        prompts.close("alice");
+        assert_eq!(prompts.take(invitation.id), None);
+    }
+}
@@ -0,0 +1,537 @@
+//! # Protocol
+//!
+//! Messages exchanged between peers. Current clients send tagged envelope with
+//! protocol version, untagged messages of older clients are still understood
+//! and sent back to peers which use them. Envelope is routed by its message
+//! type only, it never falls back to untagged messages, and envelope of version
+//! this client does not know is rejected. Untagged messages must have exactly
+//! the fields of one old message, so one message cannot pass for other.
+//!
+//! Turn coordinates on the wire are row-major and 0-based with origin in the
+//! top-left field as printed: `x` is row counted downwards, `y` is column
+//! counted to the right. Field `(x, y)` of playmat with `size` rows has index
+//! `x * size + y`. Frontends convert through the helpers below instead of
+//! relying on their own layout.
+//!
+//! Turns carry their move number counted from 1. When both players send turn
+//! with the same number, e.g. after duplicated answer, turn of the initiator
+//! stands. Invitee takes its own turn back and sends resume with number of moves
+//! before it, initiator answers with its turn again.
+//!
+//! Invitation and its answer list codecs their sender reads, large messages of
+//! the game are then compressed as described in [`super::compression`].
+
+use super::compression::{self, Codec};
+use super::referee::Verdict;
+use super::seal::Seal;
+use super::stats::Tally;
+use crate::coords::Coordinates;
+use crate::tictactoe::{GameError, Rules, Tile};
+
+/// Version put into every envelope
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The oldest envelope version understood
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// Features older clients without envelope lack, messages for them are dropped
+/// or refused, so player is told once per peer
+pub const LEGACY_MISSING: &[&str] = &[
+    "clock sync",
+    "chat",
+    "shared review",
+    "resume after reconnect",
+    "wild and set up games",
+    "scheduled start",
+    "message seals",
+    "forfeit notice",
+    "resign notice",
+];
+
+/// Game message on the wire
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WireMessage {
+    /// Invitation, sender is id of invited peer
+    Propose {
+        sender: String,
+        /// Proof of password for protected games
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        credentials: Option<Credentials>,
+        /// Rules both players follow, standard ones are left out
+        #[serde(default, skip_serializing_if = "Rules::is_standard")]
+        rules: Rules,
+        /// Nonce of proposed game which both players bind into seals of its messages
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        nonce: Option<String>,
+        /// Proposed start time in UTC milliseconds, game may be played right away when none
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        start_at: Option<u64>,
+        /// Who proposes, shown to invited peer before answering, older clients get none
+        #[serde(default, skip_serializing_if = "Introduction::is_empty")]
+        introduction: Introduction,
+        /// Codecs proposer reads, older clients list none
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        compression: Vec<Codec>,
+    },
+    Answer {
+        accept: bool,
+        /// Codecs invited peer reads, older clients list none
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        compression: Vec<Codec>,
+    },
+    Turn {
+        x: usize,
+        y: usize,
+        /// Sender clock in milliseconds when turn was sent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sent_at: Option<u64>,
+        /// Placed tile, sent only in variants where players choose it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mark: Option<Tile>,
+        /// Move number counted from 1, older clients leave it out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        number: Option<usize>,
+        /// Played by client of away player instead of them
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        auto: bool,
+    },
+    Nudge,
+    Withdrawn,
+    /// Clock synchronization request, times are milliseconds of sender clock
+    Ping { sent_at: u64 },
+    Pong { ping_sent_at: u64, received_at: u64, sent_at: u64 },
+    /// Chat message to opponent
+    Chat { text: String },
+    /// Shared review of last finished game
+    ReviewPropose,
+    ReviewAnswer { accept: bool },
+    /// Position shown in review, number of moves played
+    ReviewGoto { position: usize },
+    ReviewEnd,
+    /// Number of moves sender knows, sent after its network was rebuilt
+    Resume { moves: usize },
+    /// Other start time of proposed game, the same time sent back means agreement
+    Reschedule { start_at: u64 },
+    /// Sender did not move in time and lost the game
+    Forfeit,
+    /// Sender gave up the game
+    Resign,
+    /// All moves of game with given id, published to its spectators after move of sender
+    Spectated {
+        game: String,
+        #[serde(default, skip_serializing_if = "Rules::is_standard")]
+        rules: Rules,
+        moves: Vec<SpectatedMove>,
+    },
+    /// Result of game signed by its referee, sent to players and spectators
+    Verdict(Verdict),
+    /// Turn of receiver was not played, it is still its turn
+    InvalidMove { x: usize, y: usize, reason: MoveRejection },
+}
+
+impl WireMessage {
+    /// Returns true when older clients understand the message, they know only standard rules
+    pub fn has_legacy_form(&self) -> bool {
+        match self {
+            WireMessage::Propose { rules, start_at, .. } => rules.is_standard() && start_at.is_none(),
+            WireMessage::Turn { mark, .. } => mark.is_none(),
+            WireMessage::Answer { .. } | WireMessage::Nudge | WireMessage::Withdrawn => true,
+            _ => false,
+        }
+    }
+
+    /// Returns name of message type as it is tagged on the wire
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WireMessage::Propose { .. } => "propose",
+            WireMessage::Answer { .. } => "answer",
+            WireMessage::Turn { .. } => "turn",
+            WireMessage::Nudge => "nudge",
+            WireMessage::Withdrawn => "withdrawn",
+            WireMessage::Ping { .. } => "ping",
+            WireMessage::Pong { .. } => "pong",
+            WireMessage::Chat { .. } => "chat",
+            WireMessage::ReviewPropose => "review_propose",
+            WireMessage::ReviewAnswer { .. } => "review_answer",
+            WireMessage::ReviewGoto { .. } => "review_goto",
+            WireMessage::ReviewEnd => "review_end",
+            WireMessage::Resume { .. } => "resume",
+            WireMessage::Reschedule { .. } => "reschedule",
+            WireMessage::Forfeit => "forfeit",
+            WireMessage::Resign => "resign",
+            WireMessage::Spectated { .. } => "spectated",
+            WireMessage::Verdict(_) => "verdict",
+            WireMessage::InvalidMove { .. } => "invalid_move",
+        }
+    }
+
+    /// Returns codecs sender reads, only invitation and its answer list them
+    pub fn compression(&self) -> Option<&[Codec]> {
+        match self {
+            WireMessage::Propose { compression, .. } | WireMessage::Answer { compression, .. } => Some(compression),
+            _ => None,
+        }
+    }
+}
+
+/// Salted hash of game password, hex encoded
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Credentials {
+    pub salt: String,
+    pub hash: String,
+}
+
+/// Proposer's own words about themselves and the game, nothing of it is verified
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Introduction {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// Games won and lost as proposer recorded them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<Tally>,
+    /// Message attached to invitation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Introduction {
+    pub fn is_empty(&self) -> bool {
+        *self == Introduction::default()
+    }
+}
+
+/// Move of watched game with tile placed by it
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SpectatedMove {
+    pub x: usize,
+    pub y: usize,
+    pub mark: Tile,
+}
+
+/// Why turn of opponent was not played
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveRejection {
+    OutOfPlaymat,
+    OccupiedField,
+    WrongMark,
+    GameOver,
+    NotYourTurn,
+}
+
+impl From<GameError> for MoveRejection {
+    fn from(error: GameError) -> Self {
+        match error {
+            GameError::InvalidValue => MoveRejection::OutOfPlaymat,
+            GameError::OccupiedField => MoveRejection::OccupiedField,
+            GameError::WrongMark => MoveRejection::WrongMark,
+            GameError::Finished => MoveRejection::GameOver,
+        }
+    }
+}
+
+impl From<crate::turns::TurnError> for MoveRejection {
+    fn from(error: crate::turns::TurnError) -> Self {
+        match error {
+            crate::turns::TurnError::NotYourTurn => MoveRejection::NotYourTurn,
+            crate::turns::TurnError::Game(error) => error.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for MoveRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveRejection::OutOfPlaymat => write!(f, "field is out of playmat"),
+            MoveRejection::OccupiedField => write!(f, "field is already occupied"),
+            MoveRejection::WrongMark => write!(f, "rules do not allow this symbol"),
+            MoveRejection::GameOver => write!(f, "game is over"),
+            MoveRejection::NotYourTurn => write!(f, "it was not their turn"),
+        }
+    }
+}
+
+/// Encoding used by peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WireFormat {
+    /// Envelope with version
+    Tagged,
+    /// Untagged message of clients before envelope
+    Legacy,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Envelope {
+    version: u32,
+    message: WireMessage,
+    /// Proof that message belongs to game, older clients ignore it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seal: Option<Seal>,
+}
+
+/// Version of envelope, read even when its message is not understood
+#[derive(serde::Deserialize)]
+struct Versioned {
+    version: u32,
+}
+
+/// Messages of older clients, type is recognized by field names
+mod legacy {
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Request {
+        pub sender: String,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Answer {
+        pub accept: bool,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct MyTurn {
+        pub x: usize,
+        pub y: usize,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Nudge {
+        pub nudge: bool,
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Withdrawn {
+        pub withdrawn: bool,
+    }
+}
+
+/// Converts board coordinates (row, column) to wire `(x, y)`
+pub fn to_wire((row, col): Coordinates) -> (usize, usize) {
+    (row, col)
+}
+
+/// Converts wire `(x, y)` to board coordinates, none when outside of board with given rows
+pub fn from_wire(x: usize, y: usize, size: usize) -> Option<Coordinates> {
+    if x < size && y < size {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+/// Returns row-major index of wire coordinates
+pub fn wire_index(x: usize, y: usize, size: usize) -> usize {
+    x * size + y
+}
+
+/// Converts row-major index to wire coordinates, none when outside of board
+pub fn from_wire_index(index: usize, size: usize) -> Option<(usize, usize)> {
+    if index < size * size {
+        Some((index / size, index % size))
+    } else {
+        None
+    }
+}
+
+/// Serializes message in given format, messages without legacy form are always tagged
+pub fn encode(message: &WireMessage, format: WireFormat) -> String {
+    encode_sealed(message, format, None)
+}
+
+/// Serializes message with seal, legacy messages are sent without it
+pub fn encode_sealed(message: &WireMessage, format: WireFormat, seal: Option<Seal>) -> String {
+    let json = match format {
+        WireFormat::Legacy if message.has_legacy_form() => match message {
+            WireMessage::Propose { sender, .. } => serde_json::to_string(&legacy::Request { sender: sender.clone() }),
+            WireMessage::Answer { accept, .. } => serde_json::to_string(&legacy::Answer { accept: *accept }),
+            WireMessage::Turn { x, y, .. } => serde_json::to_string(&legacy::MyTurn { x: *x, y: *y }),
+            WireMessage::Nudge => serde_json::to_string(&legacy::Nudge { nudge: true }),
+            WireMessage::Withdrawn => serde_json::to_string(&legacy::Withdrawn { withdrawn: true }),
+            _ => unreachable!("message has no legacy form"),
+        },
+        _ => serde_json::to_string(&Envelope { version: PROTOCOL_VERSION, message: message.clone(), seal }),
+    };
+    json.expect("cannot jsonify message")
+}
+
+/// Parses message in any known format
+pub fn decode(data: &[u8]) -> Option<(WireMessage, WireFormat)> {
+    decode_sealed(data).map(|(message, format, _)| (message, format))
+}
+
+/// Parses message in any known format together with its seal, compressed one is inflated first
+pub fn decode_sealed(data: &[u8]) -> Option<(WireMessage, WireFormat, Option<Seal>)> {
+    match compression::decompress(data) {
+        Some(inflated) => decode_uncompressed(&inflated),
+        None => decode_uncompressed(data),
+    }
+}
+
+fn decode_uncompressed(data: &[u8]) -> Option<(WireMessage, WireFormat, Option<Seal>)> {
+    match version(data) {
+        Some(version) if is_supported(version) => {
+            let envelope = serde_json::from_slice::<Envelope>(data).ok()?;
+            Some((envelope.message, WireFormat::Tagged, envelope.seal))
+        }
+        Some(_) => None,
+        None => decode_legacy(data).map(|message| (message, WireFormat::Legacy, None)),
+    }
+}
+
+/// Returns version of envelope, none for untagged message
+pub fn version(data: &[u8]) -> Option<u32> {
+    serde_json::from_slice::<Versioned>(data).ok().map(|versioned| versioned.version)
+}
+
+/// Returns true when envelope of given version is understood
+pub fn is_supported(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+fn decode_legacy(data: &[u8]) -> Option<WireMessage> {
+    if let Ok(request) = serde_json::from_slice::<legacy::Request>(data) {
+        return Some(WireMessage::Propose {
+            sender: request.sender,
+            credentials: None,
+            rules: Rules::default(),
+            nonce: None,
+            start_at: None,
+            introduction: Introduction::default(),
+            compression: Vec::new(),
+        });
+    }
+
+    if let Ok(answer) = serde_json::from_slice::<legacy::Answer>(data) {
+        return Some(WireMessage::Answer { accept: answer.accept, compression: Vec::new() });
+    }
+
+    if let Ok(turn) = serde_json::from_slice::<legacy::MyTurn>(data) {
+        return Some(WireMessage::Turn { x: turn.x, y: turn.y, sent_at: None, mark: None, number: None, auto: false });
+    }
+
+    if serde_json::from_slice::<legacy::Nudge>(data).is_ok() {
+        return Some(WireMessage::Nudge);
+    }
+
+    if serde_json::from_slice::<legacy::Withdrawn>(data).is_ok() {
+        return Some(WireMessage::Withdrawn);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tagged_roundtrip() {
+        let message = WireMessage::Turn { x: 1, y: 2, sent_at: None, mark: None, number: None, auto: false };
+        let json = encode(&message, WireFormat::Tagged);
+        assert_eq!(json, r#"{"version":2,"message":{"type":"turn","x":1,"y":2}}"#);
+        assert_eq!(decode(json.as_bytes()), Some((message, WireFormat::Tagged)));
+    }
+
+    #[test]
+    fn ping_stays_tagged_for_legacy_peer() {
+        let json = encode(&WireMessage::Ping { sent_at: 7 }, WireFormat::Legacy);
+        assert_eq!(json, r#"{"version":2,"message":{"type":"ping","sent_at":7}}"#);
+    }
+
+    #[test]
+    fn coordinates_roundtrip() {
+        for size in [crate::coords::SIZE, crate::gomoku::MAX_SIZE] {
+            for row in 0..size {
+                for col in 0..size {
+                    let (x, y) = to_wire((row, col));
+                    assert_eq!(from_wire(x, y, size), Some((row, col)));
+                    assert_eq!(from_wire_index(wire_index(x, y, size), size), Some((x, y)));
+                }
+            }
+        }
+        // top-right field is first row, last column
+        assert_eq!(to_wire((0, 2)), (0, 2));
+        assert_eq!(wire_index(0, 2, 3), 2);
+        assert_eq!(wire_index(1, 0, 10), 10);
+        assert_eq!(from_wire(3, 0, 3), None);
+        assert_eq!(from_wire(3, 0, 10), Some((3, 0)));
+        assert_eq!(from_wire_index(9, 3), None);
+    }
+
+    #[test]
+    fn routes_envelope_by_version_and_type() {
+        assert_eq!(decode(br#"{"version":3,"message":{"type":"turn","x":1,"y":2}}"#), None);
+        assert_eq!(version(br#"{"version":3,"message":{"type":"teleport"}}"#), Some(3));
+        // envelope does not fall back to untagged messages
+        assert_eq!(decode(br#"{"version":2,"message":{"type":"teleport"},"accept":true}"#), None);
+        assert_eq!(decode(br#"{"accept":true,"x":1,"y":2}"#), None);
+    }
+
+    #[test]
+    fn speaks_legacy_format() {
+        let answer = WireMessage::Answer { accept: true, compression: compression::supported() };
+        assert_eq!(encode(&answer, WireFormat::Legacy), r#"{"accept":true}"#);
+        let propose = WireMessage::Propose {
+            sender: "peer".to_string(),
+            credentials: None,
+            rules: Rules::default(),
+            nonce: None,
+            start_at: None,
+            introduction: Introduction::default(),
+            compression: Vec::new(),
+        };
+        assert_eq!(decode(br#"{"sender":"peer"}"#), Some((propose, WireFormat::Legacy)));
+    }
+
+    #[test]
+    fn wild_turn_stays_tagged_for_legacy_peer() {
+        let message = WireMessage::Turn { x: 0, y: 1, sent_at: None, mark: Some(Tile::Circle), number: None, auto: false };
+        let json = encode(&message, WireFormat::Legacy);
+        assert_eq!(json, r#"{"version":2,"message":{"type":"turn","x":0,"y":1,"mark":"circle"}}"#);
+        assert_eq!(decode(json.as_bytes()), Some((message, WireFormat::Tagged)));
+    }
+
+    #[test]
+    fn introduction_is_left_out_for_legacy_peer() {
+        let introduction = Introduction {
+            nickname: Some("alice".to_string()),
+            record: Some(Tally { won: 3, lost: 1 }),
+            message: Some("good luck".to_string()),
+        };
+        let message = WireMessage::Propose {
+            sender: "peer".to_string(),
+            credentials: None,
+            rules: Rules::default(),
+            nonce: None,
+            start_at: None,
+            introduction,
+            compression: Vec::new(),
+        };
+        assert_eq!(encode(&message, WireFormat::Legacy), r#"{"sender":"peer"}"#);
+        let json = encode(&message, WireFormat::Tagged);
+        assert_eq!(
+            json,
+            r#"{"version":2,"message":{"type":"propose","sender":"peer","introduction":{"nickname":"alice","record":{"won":3,"lost":1},"message":"good luck"}}}"#
+        );
+        assert_eq!(decode(json.as_bytes()), Some((message, WireFormat::Tagged)));
+    }
+
+    #[test]
+    fn decodes_compressed_message() {
+        let moves = (0..100).map(|index| SpectatedMove { x: index % 3, y: index / 3 % 3, mark: Tile::Cross }).collect();
+        let message = WireMessage::Spectated { game: "TicTacToe/alice/bob".to_string(), rules: Rules::default(), moves };
+        let json = encode(&message, WireFormat::Tagged);
+        let compressed = compression::compress(json.clone(), Codec::Deflate);
+        assert!(compressed.len() < json.len());
+        assert_eq!(decode(compressed.as_bytes()), Some((message, WireFormat::Tagged)));
+
+        let answer = encode(&WireMessage::Answer { accept: true, compression: vec![Codec::Deflate] }, WireFormat::Tagged);
+        assert_eq!(answer, r#"{"version":2,"message":{"type":"answer","accept":true,"compression":["deflate"]}}"#);
+    }
+}
@@ -0,0 +1,199 @@
+//! # Referee
+//!
+//! Trusted third peer judging game of other peers, e.g. in tournament. Referee
+//! follows watch topic of the game as spectator does, but it checks every move
+//! against the rules on its own, notes when it saw each move and does not let
+//! players rewrite moves already played, so take back is not possible. Once the
+//! game ends or player breaks the rules, referee signs verdict with own identity
+//! and sends it to both players and to spectators who still watch. Players
+//! show only verdicts of referees they trust.
+
+use super::auth::{from_hex, to_hex};
+use super::protocol;
+use super::spectate::{self, Replica};
+use crate::coords::Coordinates;
+use crate::tictactoe::{Rules, Tile};
+
+/// File in data directory with issued verdicts, one JSON per line
+pub const VERDICTS_FILE: &str = "verdicts.jsonl";
+
+/// Move of refereed game with time referee saw it, in wire coordinates
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TimedMove {
+    pub x: usize,
+    pub y: usize,
+    pub mark: Tile,
+    /// UTC milliseconds of referee clock
+    pub at_millis: u64,
+}
+
+/// How refereed game ended
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Ruling {
+    Won { winner: String },
+    Drawn,
+    /// Player broke the rules and lost, with what they did
+    Disqualified { offender: String, reason: String },
+}
+
+impl std::fmt::Display for Ruling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ruling::Won { winner } => write!(f, "<{}> won", winner),
+            Ruling::Drawn => write!(f, "draw"),
+            Ruling::Disqualified { offender, reason } => write!(f, "<{}> disqualified, {}", offender, reason),
+        }
+    }
+}
+
+/// Result of game signed by its referee
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Verdict {
+    /// Id of the game, its topic
+    pub game: String,
+    pub ruling: Ruling,
+    pub moves: Vec<TimedMove>,
+    pub referee: String,
+    /// Protobuf encoded public key of referee, hex encoded
+    pub key: String,
+    pub signature: String,
+}
+
+impl Verdict {
+    /// Returns verdict issued by owner of key
+    pub fn sign(key: &libp2p::identity::Keypair, game: &str, ruling: Ruling, moves: Vec<TimedMove>) -> Result<Verdict, String> {
+        let mut verdict = Verdict {
+            game: game.to_string(),
+            ruling,
+            moves,
+            referee: libp2p::PeerId::from(key.public()).to_string(),
+            key: to_hex(&key.public().encode_protobuf()),
+            signature: String::new(),
+        };
+        let signature = key.sign(&verdict.signed_bytes()).map_err(|error| error.to_string())?;
+        verdict.signature = to_hex(&signature);
+        Ok(verdict)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&(&self.game, &self.ruling, &self.moves, &self.referee)).expect("cannot jsonify verdict")
+    }
+
+    /// Returns true when referee signed the verdict
+    pub fn verify(&self) -> bool {
+        let public = from_hex(&self.key).and_then(|key| libp2p::identity::PublicKey::try_decode_protobuf(&key).ok());
+        match (public, from_hex(&self.signature)) {
+            (Some(public), Some(signature)) => {
+                public.verify(&self.signed_bytes(), &signature) && libp2p::PeerId::from(public).to_string() == self.referee
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Game judged by this peer
+#[derive(Debug, Clone, Default)]
+pub struct Refereed {
+    replica: Replica,
+    /// Rules of the first move seen, players may not change them
+    rules: Option<Rules>,
+    moves: Vec<TimedMove>,
+}
+
+impl Refereed {
+    /// Checks all moves of game published by sender after its own move, new
+    /// ones are timed by now. Moves of other peers are ignored. Returns ruling
+    /// once the game ends or player breaks the rules.
+    pub fn observe(
+        &mut self,
+        game_id: &str,
+        sender: &str,
+        rules: Rules,
+        moves: &[(Coordinates, Tile)],
+        now: u64,
+    ) -> Option<Ruling> {
+        let (initiator, invitee) = spectate::players(game_id).filter(|(initiator, invitee)| sender == *initiator || sender == *invitee)?;
+        let disqualify = |offender: &str, reason: &str| Some(Ruling::Disqualified { offender: offender.to_string(), reason: reason.to_string() });
+        if *self.rules.get_or_insert(rules) != rules {
+            return disqualify(sender, "changed rules of the game");
+        }
+        let known = self.moves.len();
+        let same = |&(field, mark): &(Coordinates, Tile), timed: &TimedMove| (protocol::to_wire(field), mark) == ((timed.x, timed.y), timed.mark);
+        if moves.len() < known || !moves.iter().zip(&self.moves).all(|(turn, timed)| same(turn, timed)) {
+            return disqualify(sender, "rewrote moves already played");
+        }
+        // sender publishes after its own move, initiator plays odd moves
+        if moves.len() > known && (moves.len() % 2 == 0) == (sender == initiator) {
+            return disqualify(sender, "moved out of turn");
+        }
+
+        for (index, &(field, mark)) in moves.iter().enumerate().skip(known) {
+            if let Err(error) = self.replica.sync(rules, &moves[..=index]) {
+                let offender = if index % 2 == 0 { initiator } else { invitee };
+                return disqualify(offender, &error.to_string());
+            }
+            let (x, y) = protocol::to_wire(field);
+            self.moves.push(TimedMove { x, y, mark, at_millis: now });
+        }
+        if !self.replica.is_finished() {
+            return None;
+        }
+        Some(match self.replica.winner(game_id) {
+            Some(winner) => Ruling::Won { winner: winner.to_string() },
+            None => Ruling::Drawn,
+        })
+    }
+
+    /// Moves seen so far with their times
+    pub fn moves(&self) -> &[TimedMove] {
+        &self.moves
+    }
+}
+
+/// Appends verdict to verdicts file in given directory, which is created when missing
+pub fn record(dir: &std::path::Path, verdict: &Verdict) -> std::io::Result<()> {
+    use std::io::Write;
+    std::fs::create_dir_all(dir)?;
+    let line = serde_json::to_string(verdict).expect("cannot jsonify verdict");
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(VERDICTS_FILE))
+        .and_then(|mut file| writeln!(file, "{}", line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn judges_moves_and_signs_verdict() {
+        let game_id = format!("{}/alice/bob", super::super::LOBBY_TOPIC);
+        let (x, o) = (Tile::Cross, Tile::Circle);
+        let moves = [((0, 0), x), ((1, 0), o), ((0, 1), x), ((1, 1), o), ((0, 2), x)];
+        let mut refereed = Refereed::default();
+        assert_eq!(refereed.observe(&game_id, "alice", Rules::default(), &moves[..1], 10), None);
+        assert_eq!(refereed.observe(&game_id, "alice", Rules::default(), &moves[..3], 20), None, "missed move is caught up");
+        assert_eq!(refereed.moves().iter().map(|timed| timed.at_millis).collect::<Vec<_>>(), [10, 20, 20]);
+        let won = refereed.observe(&game_id, "alice", Rules::default(), &moves, 30);
+        assert_eq!(won, Some(Ruling::Won { winner: "alice".to_string() }));
+
+        let disqualified = |offender: &str, reason: &str| Some(Ruling::Disqualified { offender: offender.to_string(), reason: reason.to_string() });
+        let mut refereed = Refereed::default();
+        assert_eq!(refereed.observe(&game_id, "bob", Rules::default(), &moves[..1], 10), disqualified("bob", "moved out of turn"));
+        let mut refereed = Refereed::default();
+        refereed.observe(&game_id, "alice", Rules::default(), &moves[..1], 10);
+        let rewritten = [((2, 2), x), ((1, 0), o)];
+        assert_eq!(refereed.observe(&game_id, "bob", Rules::default(), &rewritten, 20), disqualified("bob", "rewrote moves already played"));
+        let overwritten = [((0, 0), x), ((0, 0), o)];
+        let ruling = refereed.observe(&game_id, "bob", Rules::default(), &overwritten, 20);
+        assert!(matches!(ruling, Some(Ruling::Disqualified { offender, .. }) if offender == "bob"));
+
+        let key = libp2p::identity::Keypair::generate_ed25519();
+        let mut verdict = Verdict::sign(&key, &game_id, won.unwrap(), refereed.moves().to_vec()).unwrap();
+        assert!(verdict.verify());
+        verdict.ruling = Ruling::Won { winner: "bob".to_string() };
+        assert!(!verdict.verify());
+    }
+}
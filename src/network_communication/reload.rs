@@ -0,0 +1,150 @@
+//! # Reload
+//!
+//! Watches config file and applies settings which are safe to change while running.
+//! File is watched only with `reload` feature.
+
+#[cfg(feature = "reload")]
+use notify::Watcher;
+
+use super::Settings;
+use crate::config::{Config, ConfigError};
+
+/// Settings which take effect only after restart, their changes are reported but not applied
+pub const RESTART_SETTINGS: &[&str] = &[
+    "audit_dir",
+    "bootstrap_peers",
+    "correspondence_dir",
+    "discovery",
+    "engine",
+    "engine_timeout_secs",
+    "history_size",
+    "ladder_file",
+    "plugins",
+    "relay_server",
+    "relays",
+    "rendezvous_namespace",
+    "rendezvous_point",
+    "room",
+    "simul_limit",
+    "static_peers",
+    "stats_file",
+];
+
+/// What changed in config file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub applied: Vec<String>,
+    pub needs_restart: Vec<String>,
+}
+
+impl Summary {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.needs_restart.is_empty()
+    }
+}
+
+/// Config file watch, remembers config last read from the file
+pub struct ConfigWatcher {
+    path: std::path::PathBuf,
+    last: Config,
+    #[cfg(feature = "reload")]
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Starts watching file, unit is sent whenever it may have changed
+    #[cfg(feature = "reload")]
+    pub fn spawn(path: std::path::PathBuf, sender: tokio::sync::mpsc::UnboundedSender<()>) -> notify::Result<ConfigWatcher> {
+        let file_name = path.file_name().map(std::ffi::OsStr::to_os_string);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let touches_config = event.is_ok_and(|event| {
+                !event.kind.is_access() && event.paths.iter().any(|changed| changed.file_name() == file_name.as_deref())
+            });
+            if touches_config {
+                let _ = sender.send(());
+            }
+        })?;
+
+        // editors often replace the file, so its directory is watched
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        };
+        watcher.watch(&directory, notify::RecursiveMode::NonRecursive)?;
+
+        let last = Config::from_file(&path).unwrap_or_default();
+        Ok(ConfigWatcher { path, last, _watcher: watcher })
+    }
+
+    /// Reads file again, returns new config with running settings updated by changes in the file
+    pub fn reload(&mut self, running: &Settings) -> Result<(Config, Settings, Summary), ConfigError> {
+        let config = Config::from_file(&self.path)?;
+        config.coordinates.clone().validated().map_err(ConfigError::Invalid)?;
+        config.dates.clone().validated().map_err(ConfigError::Invalid)?;
+
+        let (settings, mut summary) = apply_settings(running, &self.last.session, &config.session);
+        if to_value(&config.theme) != to_value(&self.last.theme) {
+            summary.applied.push("theme".to_string());
+        }
+        if config.coordinates != self.last.coordinates {
+            summary.applied.push("coordinates".to_string());
+        }
+        if config.dates != self.last.dates {
+            summary.applied.push("dates".to_string());
+        }
+        self.last = config.clone();
+        Ok((config, settings, summary))
+    }
+}
+
+/// Applies differences between old and new file settings to running ones. Options
+/// given on command line stay unless the file changes the same setting.
+pub fn apply_settings(running: &Settings, old: &Settings, new: &Settings) -> (Settings, Summary) {
+    let (old, new) = (to_map(old), to_map(new));
+    let mut settings = to_map(running);
+    let mut summary = Summary::default();
+    for (key, value) in new {
+        if old.get(&key) == Some(&value) {
+            continue;
+        }
+        if RESTART_SETTINGS.contains(&key.as_str()) {
+            summary.needs_restart.push(key);
+        } else {
+            settings.insert(key.clone(), value);
+            summary.applied.push(key);
+        }
+    }
+    let settings = serde_json::from_value(serde_json::Value::Object(settings)).expect("settings survive json roundtrip");
+    (settings, summary)
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).expect("cannot jsonify config")
+}
+
+fn to_map(settings: &Settings) -> serde_json::Map<String, serde_json::Value> {
+    match to_value(settings) {
+        serde_json::Value::Object(map) => map,
+        _ => unreachable!("settings are a struct"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_only_safe_changes() {
+        let old = Settings::default();
+        let running = Settings { eval_bar: true, ..Settings::default() };
+        let new = Settings { teach: true, simul_limit: Some(4), ..Settings::default() };
+
+        let (settings, summary) = apply_settings(&running, &old, &new);
+        assert!(settings.teach);
+        assert!(settings.eval_bar, "command line option is kept");
+        assert_eq!(settings.simul_limit, None);
+        assert_eq!(summary.applied, vec!["teach"]);
+        assert_eq!(summary.needs_restart, vec!["simul_limit"]);
+        assert!(apply_settings(&running, &old, &old).1.is_empty());
+    }
+}
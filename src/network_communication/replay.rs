@@ -0,0 +1,229 @@
+//! # Replay
+//!
+//! Finished games kept in a JSON lines file, one game per line. Every move may
+//! carry a comment added after the game.
+
+use super::stats::Outcome;
+use crate::tictactoe;
+
+/// Replay file kept in data directory
+pub const DATA_DIR_FILE: &str = "games.jsonl";
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// No replay file is configured
+    Disabled,
+    NoSuchGame(usize),
+    NoSuchMove(usize),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Disabled => write!(f, "replays are not stored, set replay_file or data_dir in config"),
+            ReplayError::NoSuchGame(game) => write!(f, "there is no game {}", game),
+            ReplayError::NoSuchMove(number) => write!(f, "there is no move {}", number),
+            ReplayError::Io(err) => write!(f, "cannot access replays: {}", err),
+        }
+    }
+}
+
+/// One move with its metadata
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ReplayMove {
+    pub x: usize,
+    pub y: usize,
+    /// True when I played the move
+    pub mine: bool,
+    /// Placed tile, kept only in variants where players choose it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark: Option<tictactoe::Tile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// One finished game
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Replay {
+    pub opponent_id: String,
+    pub outcome: Outcome,
+    /// True when I played crosses
+    pub crosses: bool,
+    #[serde(default, skip_serializing_if = "tictactoe::Rules::is_standard")]
+    pub rules: tictactoe::Rules,
+    pub moves: Vec<ReplayMove>,
+    /// UTC milliseconds since unix epoch, missing in games recorded by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+impl Replay {
+    /// Creates replay of given game
+    pub fn new(opponent_id: &str, outcome: Outcome, game: &tictactoe::TicTacToe) -> Replay {
+        let marks = game.marks();
+        let crosses = marks.you == tictactoe::Tile::Cross;
+        let rules = game.rules();
+        let moves = game.moves().iter().enumerate().map(|(index, &(x, y))| {
+            if rules.is_standard() {
+                ReplayMove { x, y, mine: game.tile(x, y) == marks.you, mark: None, comment: None }
+            } else {
+                // tile does not tell who played, crosses move first and players alternate
+                ReplayMove { x, y, mine: (index % 2 == 0) == crosses, mark: Some(game.tile(x, y)), comment: None }
+            }
+        });
+        Replay {
+            opponent_id: opponent_id.to_string(),
+            outcome,
+            crosses,
+            rules,
+            moves: moves.collect(),
+            finished_at: Some(super::clock::now_millis()),
+        }
+    }
+
+    /// Returns every move together with playmat after it
+    pub fn positions(&self) -> Vec<(ReplayMove, tictactoe::Grid)> {
+        let mut game = self.start();
+        self.moves
+            .iter()
+            .map(|step| {
+                Self::play(&mut game, step);
+                (step.clone(), game.grid())
+            })
+            .collect()
+    }
+
+    /// Returns game after given number of moves, seen by me
+    pub fn game_after(&self, count: usize) -> tictactoe::TicTacToe {
+        let mut game = self.start();
+        self.moves.iter().take(count).for_each(|step| Self::play(&mut game, step));
+        game
+    }
+
+    fn start(&self) -> tictactoe::TicTacToe {
+        let marks = if self.crosses {
+            tictactoe::Marks::default().swapped()
+        } else {
+            tictactoe::Marks::default()
+        };
+        tictactoe::TicTacToe::with_rules(marks, self.rules)
+    }
+
+    fn play(game: &mut tictactoe::TicTacToe, step: &ReplayMove) {
+        let _ = match (step.mine, step.mark) {
+            (true, Some(mark)) => game.make_my_mark(step.x, step.y, mark),
+            (true, None) => game.make_my_turn(step.x, step.y).map(|_| ()),
+            (false, Some(mark)) => game.make_opponent_mark(step.x, step.y, mark),
+            (false, None) => game.make_opponent_turn(step.x, step.y).map(|_| ()),
+        };
+    }
+}
+
+/// Replay file
+pub struct ReplayStore {
+    path: std::path::PathBuf,
+}
+
+impl ReplayStore {
+    pub fn new(path: &std::path::Path) -> ReplayStore {
+        ReplayStore { path: path.to_path_buf() }
+    }
+
+    /// Store in data directory, which is created when missing
+    pub fn in_dir(dir: &std::path::Path) -> Result<ReplayStore, ReplayError> {
+        std::fs::create_dir_all(dir).map_err(ReplayError::Io)?;
+        Ok(ReplayStore::new(&dir.join(DATA_DIR_FILE)))
+    }
+
+    /// Appends finished game
+    pub fn append(&self, replay: &Replay) -> Result<(), ReplayError> {
+        use std::io::Write;
+        let line = serde_json::to_string(replay).expect("cannot jsonify replay");
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(ReplayError::Io)
+    }
+
+    /// Loads all games, the oldest first. Missing file means no games, broken lines are skipped.
+    pub fn load(&self) -> Result<Vec<Replay>, ReplayError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(ReplayError::Io(err)),
+        }
+    }
+
+    /// Returns game counted from the most recent one, 1 is the last game
+    pub fn recent(&self, game: usize) -> Result<Replay, ReplayError> {
+        let replays = self.load()?;
+        game.checked_sub(1)
+            .and_then(|back| replays.iter().rev().nth(back))
+            .cloned()
+            .ok_or(ReplayError::NoSuchGame(game))
+    }
+
+    /// Sets comment of move in game counted from the most recent one, moves are counted from 1
+    pub fn annotate(&self, game: usize, move_number: usize, comment: &str) -> Result<(), ReplayError> {
+        let mut replays = self.load()?;
+        let count = replays.len();
+        let replay = game
+            .checked_sub(1)
+            .filter(|back| *back < count)
+            .map(|back| &mut replays[count - 1 - back])
+            .ok_or(ReplayError::NoSuchGame(game))?;
+        let step = move_number
+            .checked_sub(1)
+            .and_then(|index| replay.moves.get_mut(index))
+            .ok_or(ReplayError::NoSuchMove(move_number))?;
+        step.comment = Some(comment.to_string());
+
+        let content: String = replays
+            .iter()
+            .map(|replay| serde_json::to_string(replay).expect("cannot jsonify replay") + "\n")
+            .collect();
+        std::fs::write(&self.path, content).map_err(ReplayError::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotates_stored_game() {
+        let path = std::env::temp_dir().join(format!("tictactoe-replays-{}.jsonl", std::process::id()));
+        let store = ReplayStore::new(&path);
+        let mut game = tictactoe::TicTacToe::with_marks(tictactoe::Marks::default().swapped());
+        let _ = game.make_my_turn(1, 1);
+        let _ = game.make_opponent_turn(0, 0);
+        store.append(&Replay::new("older", Outcome::Lost, &tictactoe::TicTacToe::new())).unwrap();
+        store.append(&Replay::new("peer", Outcome::Won, &game)).unwrap();
+
+        store.annotate(1, 2, "should have blocked here").unwrap();
+        assert!(matches!(store.annotate(1, 3, "no such move"), Err(ReplayError::NoSuchMove(3))));
+        assert!(matches!(store.annotate(3, 1, "no such game"), Err(ReplayError::NoSuchGame(3))));
+
+        let replay = store.recent(1).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.opponent_id, "peer");
+        assert_eq!(replay.moves[1].comment.as_deref(), Some("should have blocked here"));
+        let positions = replay.positions();
+        assert_eq!(positions[0].1[1][1], tictactoe::Tile::Cross);
+        assert_eq!(positions[1].1[0][0], tictactoe::Tile::Circle);
+    }
+
+    #[test]
+    fn creates_data_dir() {
+        let dir = std::env::temp_dir().join(format!("tictactoe-data-{}", std::process::id())).join("games");
+        let store = ReplayStore::in_dir(&dir).unwrap();
+        store.append(&Replay::new("peer", Outcome::Drawn, &tictactoe::TicTacToe::new())).unwrap();
+        let replays = store.load().unwrap();
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+        assert_eq!(replays.len(), 1);
+        assert_eq!(replays[0].opponent_id, "peer");
+    }
+}
@@ -0,0 +1,107 @@
+//! # Review
+//!
+//! Post-game review shared by both players. Navigation of one player is sent to
+//! the other one, so both look at the same position of the finished game.
+
+use super::replay::{Replay, ReplayMove};
+use crate::tictactoe;
+
+/// Review message received from peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewMessage {
+    Propose,
+    Answer(bool),
+    /// Position to show, number of moves played
+    Goto(usize),
+    End,
+}
+
+/// Navigation command of user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewStep {
+    Next,
+    Prev,
+    Goto(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReviewState {
+    ProposedByMe,
+    ProposedByPeer,
+    Active,
+}
+
+/// Review of finished game with one peer
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub peer_id: String,
+    pub state: ReviewState,
+    replay: Replay,
+    positions: Vec<(ReplayMove, tictactoe::Grid)>,
+    /// Number of moves played in shown position
+    position: usize,
+}
+
+impl Review {
+    pub fn new(replay: Replay, state: ReviewState) -> Review {
+        Review {
+            peer_id: replay.opponent_id.clone(),
+            state,
+            positions: replay.positions(),
+            replay,
+            position: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == ReviewState::Active
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves to other position, returns the new one
+    pub fn navigate(&mut self, step: ReviewStep) -> usize {
+        self.position = match step {
+            ReviewStep::Next => self.position + 1,
+            ReviewStep::Prev => self.position.saturating_sub(1),
+            ReviewStep::Goto(position) => position,
+        }
+        .min(self.positions.len());
+        self.position
+    }
+
+    /// Returns last played move and playmat of shown position
+    pub fn shown(&self) -> (Option<ReplayMove>, tictactoe::Grid) {
+        match self.position.checked_sub(1).and_then(|index| self.positions.get(index)) {
+            Some((step, grid)) => (Some(step.clone()), grid.clone()),
+            None => (None, self.replay.game_after(0).grid()),
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_communication::stats::Outcome;
+
+    #[test]
+    fn navigation_stays_within_game() {
+        let mut game = tictactoe::TicTacToe::new();
+        let _ = game.make_my_turn(0, 0);
+        let _ = game.make_opponent_turn(1, 1);
+        let mut review = Review::new(Replay::new("peer", Outcome::Lost, &game), ReviewState::Active);
+
+        assert_eq!(review.navigate(ReviewStep::Prev), 0);
+        assert_eq!(review.shown().0, None);
+        assert_eq!(review.navigate(ReviewStep::Next), 1);
+        assert_eq!(review.shown().0.map(|step| (step.x, step.y)), Some((0, 0)));
+        assert_eq!(review.navigate(ReviewStep::Goto(7)), 2);
+        assert_eq!(review.shown().1[1][1], tictactoe::Tile::Cross);
+    }
+}
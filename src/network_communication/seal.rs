@@ -0,0 +1,180 @@
+//! # Seal
+//!
+//! Game messages are signed together with game topic, nonce chosen by inviting
+//! peer for each game and sequence number of the sender. Receiver rejects
+//! message with nonce of other game or with sequence number it has already seen,
+//! so turn captured and sent again later, in the same game or in a later one
+//! between the same peers, does not change the game.
+
+use sha2::{Digest, Sha256};
+
+use super::auth::{from_hex, to_hex};
+use super::protocol::WireMessage;
+
+/// Proof attached to tagged message on game topic
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Seal {
+    pub nonce: String,
+    pub seq: u64,
+    /// Protobuf encoded public key of sender, hex encoded
+    pub key: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SealError {
+    /// Signature does not match message or key does not belong to sender
+    BadSignature,
+    /// Nonce belongs to other game between the same peers
+    WrongGame,
+    /// Sequence number was already seen in this game
+    Replayed(u64),
+    /// Message of game with nonce is not sealed
+    Missing,
+}
+
+impl std::fmt::Display for SealError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SealError::BadSignature => write!(f, "signature does not match"),
+            SealError::WrongGame => write!(f, "message belongs to other game"),
+            SealError::Replayed(seq) => write!(f, "message {} was already received", seq),
+            SealError::Missing => write!(f, "message is not signed"),
+        }
+    }
+}
+
+/// Returns fresh nonce for game proposed by given peer
+pub fn new_nonce(peer_id: &str) -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+    to_hex(&Sha256::digest(format!("{}:{}", peer_id, nanos).as_bytes())[..16])
+}
+
+fn signed_bytes(game_id: &str, nonce: &str, seq: u64, message: &WireMessage) -> Vec<u8> {
+    let message = serde_json::to_string(message).expect("cannot jsonify message");
+    format!("{}\n{}\n{}\n{}", game_id, nonce, seq, message).into_bytes()
+}
+
+/// Nonce of one game with sequence numbers of its messages
+#[derive(Debug)]
+struct GameSeal {
+    nonce: String,
+    next_seq: u64,
+    /// Sequence numbers seen from each peer
+    seen: std::collections::HashMap<String, std::collections::HashSet<u64>>,
+}
+
+/// Seals of running games keyed by game topic
+pub struct Seals {
+    key: libp2p::identity::Keypair,
+    games: std::collections::HashMap<String, GameSeal>,
+}
+
+impl Seals {
+    pub fn new(key: libp2p::identity::Keypair) -> Seals {
+        Seals { key, games: std::collections::HashMap::new() }
+    }
+
+    /// Starts sealing messages of game. My sequence numbers start at current time,
+    /// so they do not repeat after restart.
+    pub fn start(&mut self, game_id: &str, nonce: &str, now_millis: u64) {
+        if self.games.get(game_id).is_some_and(|game| game.nonce == nonce) {
+            return;
+        }
+        let game = GameSeal { nonce: nonce.to_string(), next_seq: now_millis, seen: Default::default() };
+        self.games.insert(game_id.to_string(), game);
+    }
+
+    pub fn end(&mut self, game_id: &str) {
+        self.games.remove(game_id);
+    }
+
+    /// Returns seal for my message on game topic, none when game is not sealed
+    pub fn seal(&mut self, game_id: &str, message: &WireMessage) -> Option<Seal> {
+        let game = self.games.get_mut(game_id)?;
+        let seq = game.next_seq;
+        game.next_seq += 1;
+        let signature = self.key.sign(&signed_bytes(game_id, &game.nonce, seq, message)).ok()?;
+        Some(Seal {
+            nonce: game.nonce.clone(),
+            seq,
+            key: to_hex(&self.key.public().encode_protobuf()),
+            signature: to_hex(&signature),
+        })
+    }
+
+    /// Checks message received from peer on game topic. Every message of game with
+    /// nonce must be sealed, otherwise seal stripped from captured turn would pass.
+    /// Games of older clients have no nonce and their messages are not sealed.
+    pub fn check(&mut self, game_id: &str, sender: &str, message: &WireMessage, seal: Option<&Seal>) -> Result<(), SealError> {
+        let seal = match seal {
+            Some(seal) => seal,
+            None if self.games.contains_key(game_id) => return Err(SealError::Missing),
+            None => return Ok(()),
+        };
+
+        let public = from_hex(&seal.key).and_then(|key| libp2p::identity::PublicKey::try_decode_protobuf(&key).ok());
+        let signature = from_hex(&seal.signature);
+        let valid = match (public, signature) {
+            (Some(public), Some(signature)) => {
+                public.verify(&signed_bytes(game_id, &seal.nonce, seal.seq, message), &signature)
+                    && libp2p::PeerId::from(public).to_string() == sender
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(SealError::BadSignature);
+        }
+
+        let game = match self.games.get_mut(game_id) {
+            Some(game) => game,
+            None => return Ok(()),
+        };
+        if seal.nonce != game.nonce {
+            return Err(SealError::WrongGame);
+        }
+        if !game.seen.entry(sender.to_string()).or_default().insert(seal.seq) {
+            return Err(SealError::Replayed(seal.seq));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_replayed_traffic() {
+        let alice_key = libp2p::identity::Keypair::generate_ed25519();
+        let alice = libp2p::PeerId::from(alice_key.public()).to_string();
+        let mut alice_seals = Seals::new(alice_key);
+        let mut bob_seals = Seals::new(libp2p::identity::Keypair::generate_ed25519());
+        let game_id = "TicTacToe/alice/bob";
+        alice_seals.start(game_id, "first", 100);
+        bob_seals.start(game_id, "first", 200);
+
+        let turn = WireMessage::Turn { x: 1, y: 1, sent_at: None, mark: None, number: None, auto: false };
+        let seal = alice_seals.seal(game_id, &turn).unwrap();
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, Some(&seal)), Ok(()));
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, Some(&seal)), Err(SealError::Replayed(100)));
+
+        let moved = WireMessage::Turn { x: 0, y: 0, sent_at: None, mark: None, number: None, auto: false };
+        assert_eq!(bob_seals.check(game_id, &alice, &moved, Some(&seal)), Err(SealError::BadSignature));
+        assert_eq!(bob_seals.check(game_id, "mallory", &turn, Some(&seal)), Err(SealError::BadSignature));
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, None), Err(SealError::Missing));
+
+        // the same peers play again, turn of the first game is captured and sent again
+        bob_seals.end(game_id);
+        bob_seals.start(game_id, "second", 300);
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, Some(&seal)), Err(SealError::WrongGame));
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, None), Err(SealError::Missing), "seal was stripped");
+
+        // games of older clients have no nonce
+        bob_seals.end(game_id);
+        assert_eq!(bob_seals.check(game_id, &alice, &turn, None), Ok(()));
+    }
+}
@@ -0,0 +1,579 @@
+//! # Session
+//!
+//! State of the client between events: games with their opponents, prompts,
+//! pending invitations, stats and everything else loaded from settings.
+//! Handlers change it, swarm only reads it when it is built.
+
+use super::behaviour::{PeerMessage, TicTacToeBehaviour};
+#[cfg(feature = "webhook")]
+use super::webhook;
+use super::{
+    audit, auth, builder, channel, chat, clock, correspondence, discovery, external_engine, hints, ladder, ladder_topic, loadtest, lobby, netstats,
+    pending, plugin, prompt, protocol, referee, replay, review, spectate, stats, undo, DisconnectPolicy, GameSummary, LadderPosition,
+    ScheduledGame, Settings, BOT_ID, PUZZLE_ID,
+};
+use crate::tictactoe;
+use tokio::sync::mpsc;
+
+pub(super) use game::{game_topic, review_topic, GameSession};
+pub(super) use map::SessionMap;
+
+mod game;
+mod map;
+
+pub struct UserSession {
+    pub(super) user_key: libp2p::identity::Keypair,
+    pub(super) user_peer_id: libp2p::PeerId,
+    /// Game sessions by opponent, commands go to the active one
+    pub(super) sessions: SessionMap,
+    pub(super) active: usize,
+    pub(super) lobby: libp2p::floodsub::Topic,
+    pub(super) settings: Settings,
+    pub(super) internal_sender: channel::WeakSender<PeerMessage>,
+    pub(super) engine: Option<std::sync::Arc<tokio::sync::Mutex<external_engine::ExternalEngine>>>,
+    pub(super) stats: stats::Stats,
+    /// Peers detected as older clients, they get untagged messages
+    pub(super) legacy_peers: std::collections::HashSet<String>,
+    /// Features peers of older versions lack, each is reported once
+    pub(super) missing_features: std::collections::HashMap<String, std::collections::HashSet<&'static str>>,
+    /// Games of other peers I watch, by game id
+    pub(super) watched: std::collections::HashMap<String, spectate::Replica>,
+    /// Games of other peers I referee, by game id
+    pub(super) refereed: std::collections::HashMap<String, referee::Refereed>,
+    /// Limits and filter applied to chat and nicknames, incoming text gets
+    /// them before other hooks
+    pub(super) moderator: chat::Moderator,
+    /// When I last announced my open game in lobby, none when I do not seek one
+    pub(super) seeking: Option<std::time::Instant>,
+    /// Open games of other players in lobby
+    pub(super) open_games: lobby::OpenGames,
+    /// Peers by look of nickname they introduced themselves with
+    pub(super) nicknames: std::collections::HashMap<String, (String, String)>,
+    pub(super) chat_hooks: Vec<Box<dyn chat::TextHook>>,
+    /// In-process network used instead of TCP and mDNS in load test
+    pub(super) virtual_network: Option<loadtest::VirtualNetwork>,
+    /// Game shown by last replay command, counted from the most recent one
+    pub(super) replayed_game: usize,
+    /// Last finished game, it can be reviewed together with its opponent
+    pub(super) last_game: Option<replay::Replay>,
+    /// Drill shown by last drill command, it waits for answer
+    #[cfg(feature = "ai")]
+    pub(super) drill: Option<usize>,
+    /// Position being set up, none outside of setup mode
+    pub(super) setup: Option<tictactoe::State>,
+    pub(super) review: Option<review::Review>,
+    /// Questions frontend was asked and did not answer yet
+    pub(super) prompts: prompt::Prompts,
+    /// Resignations and withdrawn invitations which can still be undone
+    pub(super) outgoing: undo::OutgoingQueue,
+    /// Games kept on disk, none when correspondence is not configured
+    pub(super) correspondence: Option<correspondence::CorrespondenceStore>,
+    /// Recent opponents kept in correspondence directory, the most recent first
+    pub(super) known_peers: Vec<correspondence::KnownPeer>,
+    /// Results of room's ladder, none when ladder is not played
+    pub(super) ladder: Option<ladder::Ladder>,
+    /// When I entered last command
+    pub(super) last_input: std::time::Instant,
+    pub(super) hints: hints::Hints,
+    /// Message counters, survive network restarts
+    pub(super) netstats: netstats::NetStats,
+    pub(super) swarm_config: builder::SwarmConfig,
+    pub(super) discovery: Vec<Box<dyn discovery::Discovery>>,
+    /// Variants I accept invitations to
+    pub(super) variants: crate::game::Registry,
+    pub(super) plugins: plugin::Plugins,
+    /// Failures outside of handlers, e.g. when file cannot be saved, main loop shows them
+    pub(super) errors: mpsc::UnboundedSender<String>,
+}
+
+impl UserSession {
+    pub(super) fn report(&self, error: String) {
+        let _ = self.errors.send(error);
+    }
+
+    /// Returns my nickname within limits sent to peers
+    pub(super) fn own_nickname(&self) -> Option<String> {
+        self.settings.nickname.as_deref().map(|nickname| self.moderator.nickname(nickname)).filter(|nickname| !nickname.is_empty())
+    }
+
+    /// Remembers nickname peer introduced itself with, returns whose nickname
+    /// it looks the same as when it is mine or other peer's
+    pub(super) fn lookalike(&mut self, peer_id: &str, nickname: &str) -> Option<String> {
+        let look = chat::skeleton(nickname);
+        if let Some(own) = self.own_nickname().filter(|own| chat::skeleton(own) == look) {
+            return Some(format!("your {}", own));
+        }
+        match self.nicknames.get(&look) {
+            Some((owner, original)) if owner != peer_id => Some(format!("{} of <{}>", original, owner)),
+            Some(_) => None,
+            None => {
+                self.nicknames.insert(look, (peer_id.to_string(), nickname.to_string()));
+                None
+            }
+        }
+    }
+
+    /// Remembers message format used by peer
+    pub(super) fn note_format(&mut self, peer_id: &str, format: protocol::WireFormat) {
+        match format {
+            protocol::WireFormat::Legacy => self.legacy_peers.insert(peer_id.to_string()),
+            protocol::WireFormat::Tagged => self.legacy_peers.remove(peer_id),
+        };
+    }
+
+    /// Returns how long player on turn may think, none for unlimited turns
+    pub(super) fn turn_timeout(&self) -> Option<std::time::Duration> {
+        // correspondence games go on for days, restored ones would forfeit right away
+        if self.correspondence.is_some() {
+            return None;
+        }
+        self.settings.turn_timeout_secs.map(std::time::Duration::from_secs)
+    }
+
+    /// Returns the earliest time any player on turn runs out of time, or my move is played for me
+    pub(super) fn next_turn_deadline(&self) -> Option<std::time::Instant> {
+        let timeout = self.turn_timeout()?;
+        let sessions = self.sessions.iter();
+        let deadlines = sessions.filter_map(|session| self.auto_move_due(session, timeout).or(session.turn_deadline(timeout)));
+        deadlines.min()
+    }
+
+    /// Returns when my move in session is played for me, none when I play it myself
+    #[cfg(feature = "ai")]
+    pub(super) fn auto_move_due(&self, session: &GameSession, timeout: std::time::Duration) -> Option<std::time::Instant> {
+        let margin = std::time::Duration::from_secs(self.settings.auto_move_secs?);
+        // ladder results are attested by their loser, only players play them
+        if self.ladder_topic().is_some() || !session.is_your_turn() {
+            return None;
+        }
+        let deadline = session.turn_deadline(timeout)?;
+        Some(deadline.checked_sub(margin).unwrap_or(deadline))
+    }
+
+    #[cfg(not(feature = "ai"))]
+    pub(super) fn auto_move_due(&self, _session: &GameSession, _timeout: std::time::Duration) -> Option<std::time::Instant> {
+        None
+    }
+
+    /// Returns opponent of active game, or of the last finished one, bot and puzzles are none
+    pub(super) fn current_opponent(&self) -> Option<String> {
+        let opponent = match self.sessions.get_index(self.active).filter(|session| session.is_initiated()) {
+            Some(session) => Some(session.opponent_id.clone()),
+            None => self.last_game.as_ref().map(|game| game.opponent_id.clone()),
+        };
+        opponent.filter(|opponent| opponent != BOT_ID && opponent != PUZZLE_ID)
+    }
+
+    /// Remembers features peer lacks, returns those not reported yet
+    pub(super) fn note_missing(&mut self, peer_id: &str, features: &[&'static str]) -> Vec<&'static str> {
+        let known = self.missing_features.entry(peer_id.to_string()).or_default();
+        features.iter().copied().filter(|feature| known.insert(feature)).collect()
+    }
+
+    /// Returns format understood by peer, unknown peers get the current one
+    pub(super) fn wire_format(&self, peer_id: &str) -> protocol::WireFormat {
+        if self.legacy_peers.contains(peer_id) {
+            protocol::WireFormat::Legacy
+        } else {
+            protocol::WireFormat::Tagged
+        }
+    }
+
+    /// Returns format understood by opponent of given session
+    pub(super) fn opponent_format(&self, index: usize) -> protocol::WireFormat {
+        self.wire_format(&self.sessions[index].opponent_id)
+    }
+
+    pub(super) fn game_session(&mut self) -> &mut GameSession {
+        &mut self.sessions[self.active]
+    }
+
+    /// Returns index of session played against given peer
+    pub(super) fn session_of(&self, peer_id: &str) -> Option<usize> {
+        self.sessions.position(peer_id)
+    }
+
+    /// Returns index of session with given opponent whose action waits in outgoing queue
+    pub(super) fn closing_session(&self, opponent_id: &str) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|session| session.closing.is_some() && session.opponent_id == opponent_id)
+    }
+
+    pub(super) fn is_playing(&self) -> bool {
+        self.sessions.playing() > 0
+    }
+
+    pub(super) fn is_simul(&self) -> bool {
+        self.settings.simul_limit.is_some()
+    }
+
+    /// Returns session for new game with opponent, none when I already play them.
+    /// Game becomes the active one unless other game is played there.
+    pub(super) fn free_session(&mut self, opponent_id: &str) -> Option<usize> {
+        let index = self.sessions.open(opponent_id, &self.internal_sender)?;
+        if !self.sessions[self.active].is_initiated() {
+            self.active = index;
+        }
+        Some(index)
+    }
+
+    /// Returns true when all boards of simul are taken
+    pub(super) fn is_simul_full(&self) -> bool {
+        self.settings.simul_limit.is_some_and(|limit| self.sessions.playing() >= limit)
+    }
+
+    /// Ends session, its board is removed unless it is the last one
+    pub(super) fn finish_session(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, index: usize) {
+        swarm.behaviour_mut().leave_game(&self.sessions[index]);
+        self.prompts.close(&self.sessions[index].opponent_id);
+
+        if self.sessions.close(index) && self.active >= index && self.active > 0 {
+            self.active -= 1;
+        }
+        self.save_games();
+    }
+
+    /// Ends session of sender whose message handler panicked, it may be left half
+    /// updated, e.g. with turn flipped but not saved. Internal messages concern no
+    /// single game, none is ended for them. Returns opponent of the ended game.
+    pub(super) fn drop_broken_session(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, sender: &str) -> Option<String> {
+        let index = self.session_of(sender)?;
+        let opponent_id = self.sessions[index].opponent_id.clone();
+        self.finish_session(swarm, index);
+        Some(opponent_id)
+    }
+
+    /// Records outcome of session into stats and replays and ends it
+    pub(super) fn end_game(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, index: usize, outcome: stats::Outcome) {
+        let game_session = &self.sessions[index];
+        // games against bot do not count into my record
+        if !game_session.is_bot_game() {
+            self.stats.record(&game_session.opponent_id, outcome, clock::now_millis());
+            self.save_stats();
+        }
+        if outcome == stats::Outcome::Lost {
+            self.concede(swarm, index);
+        }
+        let game_session = &self.sessions[index];
+        let replay = replay::Replay::new(&game_session.opponent_id, outcome, game_session.game());
+        match self.replay_store().and_then(|store| store.append(&replay)) {
+            Ok(()) | Err(replay::ReplayError::Disabled) => {}
+            Err(error) => self.report(format!("Cannot save replay: {}", error)),
+        }
+        self.replayed_game = 1;
+
+        // peers stay on review topic of their last game
+        let user_peer_id = self.user_peer_id.to_string();
+        if let Some(previous) = self.last_game.take() {
+            swarm.behaviour_mut().protocols.floodsub.unsubscribe(review_topic(&user_peer_id, &previous.opponent_id));
+        }
+        swarm.behaviour_mut().protocols.floodsub.subscribe(review_topic(&user_peer_id, &replay.opponent_id));
+        self.last_game = Some(replay);
+        self.review = None;
+        self.finish_session(swarm, index);
+    }
+
+    /// Signs my loss in ladder game and gossips it to the room, games without nonce
+    /// of older clients are not attested
+    pub(super) fn concede(&mut self, swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, index: usize) {
+        let (topic, nonce) = match (self.ladder_topic(), &self.sessions[index].nonce) {
+            (Some(topic), Some(nonce)) => (topic, nonce.clone()),
+            _ => return,
+        };
+        let winner = self.sessions[index].opponent_id.clone();
+        let attestation = match ladder::Attestation::sign(&self.user_key, &nonce, &winner, clock::now_millis()) {
+            Ok(attestation) => attestation,
+            Err(error) => {
+                self.report(format!("Cannot attest ladder result: {}", error));
+                return;
+            }
+        };
+        let payload = serde_json::to_string(&attestation).expect("cannot jsonify attestation");
+        if self.add_result(attestation) {
+            swarm.behaviour_mut().publish(topic, payload);
+        }
+    }
+
+    /// Returns rules of games I propose
+    pub(super) fn rules(&self) -> tictactoe::Rules {
+        let board = self.settings.board.filter(|board| board.validated().is_ok());
+        tictactoe::Rules { variant: self.settings.variant, board, ..tictactoe::Rules::default() }
+    }
+
+    /// Returns ladder topic of my room when ladder is played
+    pub(super) fn ladder_topic(&self) -> Option<libp2p::floodsub::Topic> {
+        self.ladder.as_ref()?;
+        self.settings.room.as_deref().map(ladder_topic)
+    }
+
+    /// Adds result to ladder and saves it, returns false when it was known or is not valid
+    pub(super) fn add_result(&mut self, attestation: ladder::Attestation) -> bool {
+        let (ladder, path) = match (&mut self.ladder, &self.settings.ladder_file) {
+            (Some(ladder), Some(path)) => (ladder, path),
+            _ => return false,
+        };
+        let added = ladder.add(attestation).and_then(|added| {
+            if added {
+                ladder.save(path)?;
+            }
+            Ok(added)
+        });
+        match added {
+            Ok(added) => added,
+            Err(error) => {
+                self.report(format!("Ladder result rejected: {}", error));
+                false
+            }
+        }
+    }
+
+    /// Returns ladder from the top down, none when ladder is not played
+    pub(super) fn ladder_positions(&self) -> Option<Vec<LadderPosition>> {
+        let positions = self.ladder.as_ref()?.positions();
+        let user_peer_id = self.user_peer_id.to_string();
+        let entries = positions
+            .iter()
+            .map(|peer_id| LadderPosition {
+                peer_id: peer_id.clone(),
+                me: *peer_id == user_peer_id,
+                challengeable: ladder::may_challenge(&positions, &user_peer_id, peer_id),
+            })
+            .collect();
+        Some(entries)
+    }
+
+    /// Loads running games from correspondence directory, builder took identity from there
+    pub(super) fn restore_correspondence(&mut self) -> std::io::Result<()> {
+        let store = match &self.settings.correspondence_dir {
+            Some(dir) => correspondence::CorrespondenceStore::open(dir)?,
+            None => return Ok(()),
+        };
+
+        let user_peer_id = self.user_peer_id.to_string();
+        let restored: Vec<GameSession> = store
+            .load_games()
+            .iter()
+            .map(|saved| {
+                let mut session = GameSession::new(self.internal_sender.clone());
+                session.restore(saved, &user_peer_id);
+                session
+            })
+            .collect();
+        if !restored.is_empty() {
+            self.sessions.replace(restored);
+            self.active = 0;
+        }
+        self.known_peers = store.load_known_peers();
+        self.correspondence = Some(store);
+        Ok(())
+    }
+
+    /// Writes running games into correspondence directory
+    pub(super) fn save_games(&self) {
+        if let Some(store) = &self.correspondence {
+            let games: Vec<correspondence::SavedGame> = self.sessions
+                .iter()
+                .filter(|session| session.is_initiated() && !session.is_bot_game())
+                .filter(|session| !session.game().moves().is_empty() || session.is_scheduled())
+                .map(|session| correspondence::SavedGame {
+                    nonce: session.nonce.clone(),
+                    start_at: session.start_at,
+                    ..correspondence::SavedGame::new(&session.opponent_id, session.game())
+                })
+                .collect();
+            if let Err(error) = store.save_games(&games) {
+                self.report(format!("Cannot save games: {}", error));
+            }
+        }
+    }
+
+    #[cfg(feature = "webhook")]
+    pub(super) fn is_away(&self) -> bool {
+        self.last_input.elapsed() >= std::time::Duration::from_secs(self.settings.away_minutes * 60)
+    }
+
+    /// Posts opponent's move in correspondence game to webhook when I am away
+    #[cfg(feature = "webhook")]
+    pub(super) fn notify_move(&self, index: usize) {
+        let url = match &self.settings.webhook_url {
+            Some(url) if self.correspondence.is_some() && self.is_away() => url,
+            _ => return,
+        };
+        let webhook = match webhook::Webhook::parse(url) {
+            Ok(webhook) => webhook,
+            Err(error) => return self.report(error.to_string()),
+        };
+        let game_session = &self.sessions[index];
+        let notification = webhook::MoveNotification::new(game_session.topic.id(), &game_session.opponent_id, &game_session.game().grid());
+        // notification outlives session when the move ends the game
+        let errors = self.errors.clone();
+        tokio::spawn(async move {
+            if let Err(error) = webhook.send(&notification).await {
+                let _ = errors.send(error.to_string());
+            }
+        });
+    }
+
+    #[cfg(not(feature = "webhook"))]
+    pub(super) fn notify_move(&self, _index: usize) {}
+
+    /// Correspondence games wait for opponent however long they are away
+    pub(super) fn disconnect_policy(&self) -> DisconnectPolicy {
+        if self.correspondence.is_some() {
+            DisconnectPolicy::Adjourn
+        } else {
+            self.settings.disconnect_policy
+        }
+    }
+
+    /// Returns audit log for new swarm when it is enabled
+    pub(super) fn audit_log(&self) -> Option<audit::AuditLog> {
+        match &self.settings.audit_dir {
+            Some(dir) if self.swarm_config.audit => Some(audit::AuditLog::new(dir, self.user_key.clone())),
+            _ => None,
+        }
+    }
+
+    pub(super) fn replay_store(&self) -> Result<replay::ReplayStore, replay::ReplayError> {
+        match (&self.settings.replay_file, &self.settings.data_dir) {
+            (Some(path), _) => Ok(replay::ReplayStore::new(path)),
+            (None, Some(dir)) => replay::ReplayStore::in_dir(dir),
+            (None, None) => Err(replay::ReplayError::Disabled),
+        }
+    }
+
+    /// Writes stats to configured file
+    pub(super) fn save_stats(&self) {
+        if let Some(path) = &self.settings.stats_file {
+            if let Err(error) = self.stats.save(path) {
+                self.report(format!("Cannot save stats: {}", error));
+            }
+        }
+    }
+
+    /// Returns true when invitation from peer carries proof of my password, if I have one
+    pub(super) fn admits(&self, peer_id: &str, credentials: Option<&protocol::Credentials>) -> bool {
+        match &self.settings.password {
+            Some(password) => auth::verify(password, peer_id, credentials),
+            None => true,
+        }
+    }
+
+    /// Returns true when invitations from peer are declined because of their reputation
+    pub(super) fn is_disreputable(&self, peer_id: &str) -> bool {
+        matches!(self.settings.min_reputation, Some(minimum) if self.stats.reputation(peer_id) < minimum)
+    }
+
+    /// Returns my unanswered invitations, messages queued for offline peers and open questions
+    pub(super) fn pending(&self) -> Vec<pending::Pending> {
+        let invitations = self.sessions.iter().enumerate().filter_map(|(index, session)| {
+            let since = session.invited_at?;
+            Some(pending::Pending::Invitation(index, session.opponent_id.clone(), since.elapsed().as_secs()))
+        });
+        let queued = self.correspondence.iter().flat_map(|store| {
+            store.outbox().unwrap_or_else(|error| {
+                self.report(format!("Cannot read outbox: {}", error));
+                Vec::new()
+            })
+        });
+        let queued = queued.enumerate().map(|(position, entry)| {
+            let kind = protocol::decode(entry.payload.as_bytes()).map_or("message", |(message, _)| message.kind());
+            pending::Pending::Queued(position + 1, entry.opponent_id, kind)
+        });
+        let prompts = self.prompts.iter().cloned().map(pending::Pending::Prompt);
+        invitations.chain(queued).chain(prompts).collect()
+    }
+
+    /// Returns games waiting for my turn
+    pub(super) fn pending_summaries(&self) -> Vec<GameSummary> {
+        self.summaries().into_iter().filter(|game| game.your_turn).collect()
+    }
+
+    /// Remembers opponent with addresses to dial after restart, only with correspondence directory
+    pub(super) fn remember_opponent(&mut self, peer_id: &str, addresses: &[libp2p::Multiaddr]) {
+        let store = match &self.correspondence {
+            Some(store) if !addresses.is_empty() => store,
+            _ => return,
+        };
+        let peer = correspondence::KnownPeer {
+            peer_id: peer_id.to_string(),
+            addresses: addresses.iter().map(ToString::to_string).collect(),
+            last_played: clock::now_millis(),
+        };
+        correspondence::remember(&mut self.known_peers, peer);
+        if let Err(error) = store.save_known_peers(&self.known_peers) {
+            self.report(format!("Cannot save known peers: {}", error));
+        }
+    }
+
+    /// Returns games with start time, the earliest first
+    pub(super) fn schedule(&self) -> Vec<ScheduledGame> {
+        let mut games: Vec<ScheduledGame> = self.sessions
+            .iter()
+            .filter(|session| session.is_initiated())
+            .filter_map(|session| session.start_at.map(|start_at| ScheduledGame {
+                opponent_id: session.opponent_id.clone(),
+                start_at,
+                agreed: session.is_scheduled(),
+            }))
+            .collect();
+        games.sort_by_key(|game| game.start_at);
+        games
+    }
+
+    pub(super) fn summaries(&self) -> Vec<GameSummary> {
+        self.sessions
+            .iter()
+            .enumerate()
+            .filter(|(_, session)| session.is_initiated())
+            .map(|(index, session)| GameSummary {
+                index,
+                opponent_id: session.opponent_id.clone(),
+                game_id: session.topic.id().to_string(),
+                your_turn: session.is_your_turn(),
+                active: index == self.active,
+                latency_millis: session.latency.average(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[tokio::test]
+    async fn correspondence_turns_are_not_timed() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut user_session = builder::SessionBuilder::new(Settings::default()).build(sender.downgrade());
+        let game_session = user_session.game_session();
+        game_session.initiate("peer".to_string(), true, "me", tictactoe::Rules::default(), None);
+        game_session.invited_at = None;
+        assert!(user_session.next_turn_deadline().is_some());
+
+        let dir = std::env::temp_dir().join(format!("tictactoe-untimed-{}", std::process::id()));
+        user_session.correspondence = Some(correspondence::CorrespondenceStore::open(&dir).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(user_session.turn_timeout(), None);
+        assert_eq!(user_session.next_turn_deadline(), None);
+    }
+
+    #[tokio::test]
+    async fn failure_affects_session_of_sender() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let settings = Settings { simul_limit: Some(2), ..Settings::default() };
+        let mut user_session = builder::SessionBuilder::new(settings).build(sender.downgrade());
+        assert_eq!(user_session.session_of(""), None, "no game is played");
+
+        let index = user_session.free_session("bob").unwrap();
+        user_session.sessions[index].initiate("bob".to_string(), true, "me", tictactoe::Rules::default(), None);
+        let index = user_session.free_session("carol").unwrap();
+        user_session.sessions[index].initiate("carol".to_string(), false, "me", tictactoe::Rules::default(), None);
+
+        assert_eq!(user_session.session_of("carol"), Some(1));
+        assert_eq!(user_session.session_of(""), None, "internal messages concern no single game");
+        assert_eq!(user_session.session_of("dave"), None);
+    }
+}
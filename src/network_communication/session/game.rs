@@ -0,0 +1,418 @@
+//! # Game session
+//!
+//! One game with its opponent: turns, topic, clocks, tasks spawned for it and
+//! action waiting to be undone. It does not outlive the game, user session
+//! keeps results.
+
+#[cfg(feature = "ai")]
+use crate::ai;
+use crate::coords::Coordinates;
+use crate::network_communication::behaviour::{GameStatus, PeerMessage};
+use crate::network_communication::{channel, clock, correspondence, protocol, tasks, undo, OutputEvents, BOT_ID, LOBBY_TOPIC};
+use crate::tictactoe;
+
+/// How often turn reminders are checked
+const REMINDER_CHECK_PERIOD: std::time::Duration = std::time::Duration::from_secs(10);
+
+pub(in crate::network_communication) struct GameSession {
+    pub(in crate::network_communication) opponent_id: String,
+    /// Game with player on turn, not started until game is initiated
+    pub(in crate::network_communication) turns: crate::turns::Turns,
+    pub(in crate::network_communication) topic: libp2p::floodsub::Topic,
+    pub(in crate::network_communication) reminded: bool,
+    /// Losing turn which was already warned about, repeating it plays it
+    pub(in crate::network_communication) warned_turn: Option<Coordinates>,
+    /// When opponent disconnected, cleared once they return
+    pub(in crate::network_communication) disconnected_at: Option<std::time::Instant>,
+    /// When I invited opponent, cleared once they answer
+    pub(in crate::network_communication) invited_at: Option<std::time::Instant>,
+    /// Opponent invited me and I have not answered yet
+    pub(in crate::network_communication) awaiting_answer: bool,
+    /// Estimate of opponent's clock
+    pub(in crate::network_communication) clock: clock::ClockSync,
+    pub(in crate::network_communication) latency: clock::Latency,
+    /// Language for chat hooks, default one from settings when none
+    pub(in crate::network_communication) language: Option<String>,
+    /// Network was rebuilt, game is compared with opponent once they join its topic
+    pub(in crate::network_communication) resuming: bool,
+    /// Nonce chosen by inviting peer, none when it is older client which does not seal messages
+    pub(in crate::network_communication) nonce: Option<String>,
+    /// Proposed or agreed start time in UTC milliseconds
+    pub(in crate::network_communication) start_at: Option<u64>,
+    /// Start time has come and I was told about it
+    pub(in crate::network_communication) start_reminded: bool,
+    /// Opponent played by this client instead of peer
+    #[cfg(feature = "ai")]
+    pub(in crate::network_communication) bot: Option<ai::BotPlayer>,
+    /// Action waiting in outgoing queue which ends the session unless undone
+    pub(in crate::network_communication) closing: Option<undo::Action>,
+    pub(in crate::network_communication) tasks: tasks::TaskSupervisor,
+    pub(in crate::network_communication) internal_sender: channel::WeakSender<PeerMessage>,
+}
+
+impl GameSession {
+    pub(in crate::network_communication) fn new(internal_sender: channel::WeakSender<PeerMessage>) -> GameSession {
+        GameSession {
+            opponent_id: String::new(),
+            turns: crate::turns::Turns::new(),
+            topic: libp2p::floodsub::Topic::new(LOBBY_TOPIC),
+            reminded: false,
+            warned_turn: None,
+            disconnected_at: None,
+            invited_at: None,
+            awaiting_answer: false,
+            clock: clock::ClockSync::default(),
+            latency: clock::Latency::default(),
+            language: None,
+            resuming: false,
+            nonce: None,
+            start_at: None,
+            start_reminded: false,
+            #[cfg(feature = "ai")]
+            bot: None,
+            closing: None,
+            tasks: tasks::TaskSupervisor::new(),
+            internal_sender,
+        }
+    }
+
+    pub(in crate::network_communication) fn initiate(&mut self, opp_id: String, your_turn: bool, user_id: &str, rules: tictactoe::Rules, nonce: Option<String>) {
+        self.topic = if your_turn {
+            game_topic(user_id, &opp_id)
+        } else {
+            game_topic(&opp_id, user_id)
+        };
+        self.opponent_id = opp_id;
+        self.nonce = nonce;
+        self.invited_at = if your_turn { Some(std::time::Instant::now()) } else { None };
+        self.awaiting_answer = false;
+        self.start_at = None;
+        self.start_reminded = false;
+        #[cfg(feature = "ai")]
+        {
+            self.bot = None;
+        }
+        self.turns.start(your_turn, rules, std::time::Instant::now());
+        self.reminded = false;
+
+        self.tasks.cancel_all();
+        self.tasks.spawn(reminder_ticker(self.internal_sender.clone()));
+    }
+
+    /// Continues game saved in correspondence directory, opponent counts as away until found
+    pub(in crate::network_communication) fn restore(&mut self, saved: &correspondence::SavedGame, user_id: &str) {
+        let (game, your_turn) = saved.restore();
+        self.initiate(saved.opponent_id.clone(), saved.initiator, user_id, saved.rules, saved.nonce.clone());
+        self.start_at = saved.start_at;
+        self.turns.restore(game, your_turn);
+        self.invited_at = None;
+        self.disconnected_at = Some(std::time::Instant::now());
+    }
+
+    /// Respawns session tasks with new internal channel
+    pub(in crate::network_communication) fn restart_tasks(&mut self, internal_sender: channel::WeakSender<PeerMessage>) {
+        self.internal_sender = internal_sender;
+        self.tasks.cancel_all();
+        if self.is_initiated() {
+            self.tasks.spawn(reminder_ticker(self.internal_sender.clone()));
+        }
+    }
+
+    /// Returns true when opponent is played by this client
+    pub(in crate::network_communication) fn is_bot_game(&self) -> bool {
+        self.opponent_id == BOT_ID
+    }
+
+    pub(in crate::network_communication) fn is_initiated(&self) -> bool {
+        self.turns.is_started()
+    }
+
+    pub(in crate::network_communication) fn game(&self) -> &tictactoe::TicTacToe {
+        self.turns.game()
+    }
+
+    pub(in crate::network_communication) fn reset(&mut self) {
+        self.turns.reset();
+        self.opponent_id = String::new();
+        self.warned_turn = None;
+        self.disconnected_at = None;
+        self.invited_at = None;
+        self.awaiting_answer = false;
+        self.clock = clock::ClockSync::default();
+        self.latency = clock::Latency::default();
+        self.language = None;
+        self.nonce = None;
+        #[cfg(feature = "ai")]
+        {
+            self.bot = None;
+        }
+        self.start_at = None;
+        self.start_reminded = false;
+        self.closing = None;
+        self.tasks.cancel_all();
+    }
+
+    /// Stops clocks of the session while action waits to be sent, returns what undo puts back
+    pub(in crate::network_communication) fn hold(&mut self, action: undo::Action) -> undo::Snapshot {
+        self.closing = Some(action);
+        undo::Snapshot { turn_started: self.turns.stop_clock(), invited_at: self.invited_at.take() }
+    }
+
+    /// Takes held action back, game which went on meanwhile, e.g. opponent moved
+    /// or accepted the invitation, keeps its clock
+    pub(in crate::network_communication) fn restore_snapshot(&mut self, snapshot: undo::Snapshot) {
+        self.closing = None;
+        if self.turns.turn_started().is_none() {
+            if let Some(started) = snapshot.turn_started {
+                self.turns.start_clock(started);
+            }
+            self.invited_at = snapshot.invited_at;
+        }
+    }
+
+    pub(in crate::network_communication) fn start_turn_clock(&mut self) {
+        self.turns.start_clock(std::time::Instant::now());
+        self.reminded = false;
+    }
+
+    /// Returns instant of given local time in milliseconds
+    fn local_instant(millis: u64) -> std::time::Instant {
+        let elapsed = std::time::Duration::from_millis(clock::now_millis().saturating_sub(millis));
+        let now = std::time::Instant::now();
+        now.checked_sub(elapsed).unwrap_or(now)
+    }
+
+    /// Returns how long current player is on turn
+    pub(in crate::network_communication) fn turn_duration(&self) -> Option<std::time::Duration> {
+        self.turns.turn_started().map(|started| started.elapsed())
+    }
+
+    pub(in crate::network_communication) fn is_your_turn(&self) -> bool {
+        self.turns.is_your_turn()
+    }
+
+    /// Returns when player on turn runs out of time, none while game is not played,
+    /// e.g. before it is accepted or while opponent is away
+    pub(in crate::network_communication) fn turn_deadline(&self, timeout: std::time::Duration) -> Option<std::time::Instant> {
+        let playing = self.is_initiated()
+            && self.invited_at.is_none()
+            && !self.awaiting_answer
+            && !self.is_bot_game()
+            && self.disconnected_at.is_none()
+            && (self.start_at.is_none() || self.start_reminded);
+        self.turns.turn_started().filter(|_| playing).map(|started| started + timeout)
+    }
+
+    /// Returns true when both players agreed on start time of the game
+    pub(in crate::network_communication) fn is_scheduled(&self) -> bool {
+        self.is_initiated() && self.start_at.is_some() && self.invited_at.is_none() && !self.awaiting_answer
+    }
+
+    pub(in crate::network_communication) fn is_initiator(&self) -> bool {
+        self.turns.is_initiator()
+    }
+
+    pub(in crate::network_communication) fn is_race(&self, number: usize) -> bool {
+        self.turns.is_race(number)
+    }
+
+    /// Returns reason why I cannot play turn in initiated game, none when I can
+    pub(in crate::network_communication) fn turn_refusal(&self) -> Option<OutputEvents> {
+        let opponent = self.opponent_id.clone();
+        if self.closing.is_some() {
+            Some(OutputEvents::GameClosing(opponent))
+        } else if self.awaiting_answer {
+            Some(OutputEvents::GameNotStarted(opponent, true))
+        } else if self.invited_at.is_some() {
+            Some(OutputEvents::GameNotStarted(opponent, false))
+        } else if crate::game::Game::is_finished(self.game()) {
+            Some(OutputEvents::GameFinished(opponent))
+        } else if !self.is_your_turn() {
+            Some(OutputEvents::NotYourTurn(opponent))
+        } else {
+            None
+        }
+    }
+
+    /// Applies opponent's turn, my clock starts when they sent it by their clock
+    pub(in crate::network_communication) fn make_opponent_turn(&mut self, x: usize, y: usize, sent_at: Option<u64>, mark: Option<tictactoe::Tile>) -> Result<(), protocol::MoveRejection> {
+        let sent = match sent_at {
+            Some(sent_at) => GameSession::local_instant(self.clock.to_local(sent_at)),
+            None => std::time::Instant::now(),
+        };
+        self.turns.make_opponent_turn(x, y, mark, sent)?;
+        self.reminded = false;
+        Ok(())
+    }
+
+    /// Passes my turn to bot, its answer arrives like turn of peer
+    #[cfg(feature = "ai")]
+    pub(in crate::network_communication) fn pass_to_bot(&mut self, x: usize, y: usize, mark: tictactoe::Tile) {
+        let bot = match &mut self.bot {
+            Some(bot) => bot,
+            None => return,
+        };
+        if bot.opponent_turn((x, y), mark).is_err() {
+            return;
+        }
+        if let Some(((x, y), mark, number)) = bot.reply() {
+            let turn = GameStatus::Turn(x, y, None, Some(mark), Some(number), false);
+            let _ = self.internal_sender.send(PeerMessage::about(self.opponent_id.clone(), turn));
+        }
+    }
+
+    pub(in crate::network_communication) fn make_my_turn(&mut self, x: usize, y: usize, mark: Option<tictactoe::Tile>) -> Result<(), tictactoe::GameError> {
+        self.turns.make_my_turn(x, y, mark, std::time::Instant::now())?;
+        self.reminded = false;
+        Ok(())
+    }
+
+    /// Takes back my last turn on given field, it is my turn again
+    pub(in crate::network_communication) fn take_back_my_turn(&mut self, field: Coordinates) -> bool {
+        let taken = self.turns.take_back_my_turn(field, std::time::Instant::now());
+        if taken {
+            self.reminded = false;
+        }
+        taken
+    }
+}
+
+/// Topic of one game, both players derive the same name
+pub(in crate::network_communication) fn game_topic(initiator_id: &str, invitee_id: &str) -> libp2p::floodsub::Topic {
+    libp2p::floodsub::Topic::new(format!("{}/{}/{}", LOBBY_TOPIC, initiator_id, invitee_id))
+}
+
+/// Topic where two players review their last game, it does not depend on who started
+pub(in crate::network_communication) fn review_topic(peer_id: &str, other_id: &str) -> libp2p::floodsub::Topic {
+    let (first, second) = if peer_id < other_id { (peer_id, other_id) } else { (other_id, peer_id) };
+    libp2p::floodsub::Topic::new(format!("{}/review/{}/{}", LOBBY_TOPIC, first, second))
+}
+
+/// Periodically asks session to check turn reminders
+async fn reminder_ticker(internal_sender: channel::WeakSender<PeerMessage>) {
+    let mut timer = tokio::time::interval(REMINDER_CHECK_PERIOD);
+    loop {
+        timer.tick().await;
+        if internal_sender.send(PeerMessage::internal(GameStatus::ReminderTick)).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gomoku::BoardSize;
+
+    #[tokio::test]
+    async fn explains_why_turn_cannot_be_played() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut game_session = GameSession::new(sender.downgrade());
+        game_session.initiate("peer".to_string(), true, "me", tictactoe::Rules::default(), None);
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::GameNotStarted(peer, false)) if peer == "peer"));
+
+        game_session.invited_at = None;
+        assert!(game_session.turn_refusal().is_none());
+        assert!(game_session.make_my_turn(1, 1, None).is_ok());
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::NotYourTurn(_))));
+
+        game_session.initiate("peer".to_string(), false, "me", tictactoe::Rules::default(), None);
+        game_session.awaiting_answer = true;
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::GameNotStarted(_, true))));
+    }
+
+    #[tokio::test]
+    async fn initiator_turn_wins_race() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut initiator = GameSession::new(sender.downgrade());
+        let mut invitee = GameSession::new(sender.downgrade());
+        initiator.initiate("invitee".to_string(), true, "initiator", tictactoe::Rules::default(), None);
+        invitee.initiate("initiator".to_string(), false, "invitee", tictactoe::Rules::default(), None);
+        // duplicated answer made invitee believe it moves first
+        invitee.turns.restore(invitee.game().clone(), true);
+        assert!(initiator.make_my_turn(0, 0, None).is_ok());
+        assert!(invitee.make_my_turn(1, 1, None).is_ok());
+
+        assert!(initiator.is_race(1) && initiator.is_initiator());
+        assert!(invitee.is_race(1) && !invitee.is_initiator());
+        assert_eq!(invitee.turns.yield_race(), Some((1, 1)));
+        assert!(!invitee.is_race(1));
+        assert!(invitee.make_opponent_turn(0, 0, None, None).is_ok());
+        assert_eq!(invitee.game().moves(), initiator.game().moves());
+    }
+
+    #[tokio::test]
+    async fn invalid_opponent_turn_keeps_their_turn() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut game_session = GameSession::new(sender.downgrade());
+        game_session.initiate("peer".to_string(), false, "me", tictactoe::Rules::default(), None);
+        assert!(game_session.make_opponent_turn(1, 1, None, None).is_ok());
+        assert_eq!(game_session.make_opponent_turn(0, 0, None, None), Err(protocol::MoveRejection::NotYourTurn));
+        assert!(game_session.make_my_turn(0, 0, None).is_ok());
+        assert_eq!(game_session.make_opponent_turn(1, 1, None, None), Err(protocol::MoveRejection::OccupiedField));
+        assert!(!game_session.is_your_turn());
+        assert_eq!(game_session.game().moves(), &[(1, 1), (0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn larger_playmat_is_played_by_session() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut game_session = GameSession::new(sender.downgrade());
+        let rules = tictactoe::Rules { board: Some(BoardSize::GOMOKU), ..tictactoe::Rules::default() };
+        game_session.initiate("peer".to_string(), false, "me", rules, None);
+        assert_eq!(game_session.make_opponent_turn(9, 9, None, None), Ok(()));
+        assert!(matches!(game_session.make_my_turn(10, 0, None), Err(tictactoe::GameError::InvalidValue)));
+        assert!(game_session.make_my_turn(5, 7, None).is_ok());
+        assert_eq!(game_session.make_opponent_turn(3, 10, None, None), Err(protocol::MoveRejection::OutOfPlaymat));
+        assert_eq!(game_session.game().grid().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn turn_timer_runs_only_while_game_is_played() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let timeout = std::time::Duration::from_secs(60);
+        let mut game_session = GameSession::new(sender.downgrade());
+        game_session.initiate("peer".to_string(), true, "me", tictactoe::Rules::default(), None);
+        assert_eq!(game_session.turn_deadline(timeout), None, "invitation is not answered yet");
+
+        game_session.invited_at = None;
+        let deadline = game_session.turn_deadline(timeout).expect("game is played");
+        assert!(deadline > std::time::Instant::now() + timeout / 2);
+        game_session.disconnected_at = Some(std::time::Instant::now());
+        assert_eq!(game_session.turn_deadline(timeout), None, "opponent is away");
+    }
+
+    #[tokio::test]
+    async fn held_withdrawal_stops_invitation_until_undone() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let mut game_session = GameSession::new(sender.downgrade());
+        game_session.initiate("peer".to_string(), true, "me", tictactoe::Rules::default(), None);
+        let invited_at = game_session.invited_at;
+
+        let snapshot = game_session.hold(undo::Action::Withdraw);
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::GameClosing(peer)) if peer == "peer"));
+        assert_eq!(game_session.invited_at, None, "invitation does not expire while withdrawal waits");
+
+        game_session.restore_snapshot(snapshot);
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::GameNotStarted(_, false))));
+        assert_eq!(game_session.invited_at, invited_at);
+    }
+
+    #[tokio::test]
+    async fn held_resignation_stops_game_until_undone() {
+        let (sender, _receiver) = channel::bounded::<PeerMessage>(16);
+        let timeout = std::time::Duration::from_secs(60);
+        let mut game_session = GameSession::new(sender.downgrade());
+        game_session.initiate("peer".to_string(), true, "me", tictactoe::Rules::default(), None);
+        game_session.invited_at = None;
+        let deadline = game_session.turn_deadline(timeout);
+
+        let snapshot = game_session.hold(undo::Action::Resign);
+        assert!(matches!(game_session.turn_refusal(), Some(OutputEvents::GameClosing(peer)) if peer == "peer"));
+        assert_eq!(game_session.turn_deadline(timeout), None, "clock stops while resignation waits");
+
+        game_session.restore_snapshot(snapshot);
+        assert!(game_session.turn_refusal().is_none());
+        assert_eq!(game_session.turn_deadline(timeout), deadline);
+    }
+}
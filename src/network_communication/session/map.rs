@@ -0,0 +1,91 @@
+//! # Session map
+//!
+//! Game sessions keyed by their opponents, bot included. Each opponent has at most
+//! one running game, games are numbered in order they started and user picks them
+//! by these numbers. While nothing is played one idle session is kept, commands
+//! outside of games go to it.
+
+use super::GameSession;
+use crate::network_communication::behaviour::PeerMessage;
+use crate::network_communication::channel;
+
+pub(in crate::network_communication) struct SessionMap {
+    sessions: Vec<GameSession>,
+}
+
+impl SessionMap {
+    pub(in crate::network_communication) fn new(idle: GameSession) -> SessionMap {
+        SessionMap { sessions: vec![idle] }
+    }
+
+    /// Returns number of game played against given opponent
+    pub(in crate::network_communication) fn position(&self, opponent_id: &str) -> Option<usize> {
+        self.sessions
+            .iter()
+            .position(|session| session.is_initiated() && session.opponent_id == opponent_id)
+    }
+
+    /// Returns session with given number
+    pub(in crate::network_communication) fn get_index(&self, index: usize) -> Option<&GameSession> {
+        self.sessions.get(index)
+    }
+
+    /// Returns number of idle session for game with opponent, a new one when all
+    /// sessions play, none when I already play the opponent
+    pub(in crate::network_communication) fn open(&mut self, opponent_id: &str, internal_sender: &channel::WeakSender<PeerMessage>) -> Option<usize> {
+        if self.position(opponent_id).is_some() {
+            return None;
+        }
+        if let Some(index) = self.sessions.iter().position(|session| !session.is_initiated()) {
+            return Some(index);
+        }
+        self.sessions.push(GameSession::new(internal_sender.clone()));
+        Some(self.sessions.len() - 1)
+    }
+
+    /// Drops session of finished game, the last one stays idle. Returns true when
+    /// the session was removed, so numbers of later games went down.
+    pub(in crate::network_communication) fn close(&mut self, index: usize) -> bool {
+        if self.sessions.len() > 1 {
+            self.sessions.remove(index);
+            true
+        } else {
+            self.sessions[index].reset();
+            false
+        }
+    }
+
+    /// Replaces sessions with given ones, e.g. games restored from disk, none are kept idle
+    pub(in crate::network_communication) fn replace(&mut self, sessions: Vec<GameSession>) {
+        if !sessions.is_empty() {
+            self.sessions = sessions;
+        }
+    }
+
+    /// Returns number of running games and invitations
+    pub(in crate::network_communication) fn playing(&self) -> usize {
+        self.sessions.iter().filter(|session| session.is_initiated()).count()
+    }
+
+    pub(in crate::network_communication) fn iter(&self) -> std::slice::Iter<'_, GameSession> {
+        self.sessions.iter()
+    }
+
+    pub(in crate::network_communication) fn iter_mut(&mut self) -> std::slice::IterMut<'_, GameSession> {
+        self.sessions.iter_mut()
+    }
+}
+
+impl std::ops::Index<usize> for SessionMap {
+    type Output = GameSession;
+
+    fn index(&self, index: usize) -> &GameSession {
+        &self.sessions[index]
+    }
+}
+
+impl std::ops::IndexMut<usize> for SessionMap {
+    fn index_mut(&mut self, index: usize) -> &mut GameSession {
+        &mut self.sessions[index]
+    }
+}
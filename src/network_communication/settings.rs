@@ -0,0 +1,171 @@
+//! # Settings
+//!
+//! Behaviour switches of session loaded from config, with defaults used when
+//! config leaves them out.
+
+use crate::gomoku::BoardSize;
+use crate::network_communication::{chat, discovery};
+use crate::tictactoe;
+
+/// Session behaviour switches, loaded from config
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Show warning when peer other than opponent sends game message
+    pub security_warnings: bool,
+    /// Remind me after given minutes on my turn
+    pub reminder_minutes: Option<u64>,
+    /// Tell me after given minutes that opponent is taking a while
+    pub opponent_reminder_minutes: Option<u64>,
+    /// Simultaneous exhibition, auto accept up to given number of games
+    pub simul_limit: Option<usize>,
+    /// External engine executable choosing my moves
+    pub engine: Option<std::path::PathBuf>,
+    /// How long engine may think about one move
+    pub engine_timeout_secs: u64,
+    /// Show evaluation of position after each move, meant for sparring with engine
+    pub eval_bar: bool,
+    /// Explain rejected turns and warn before turns losing immediately
+    pub teach: bool,
+    /// What happens with game when opponent disconnects
+    pub disconnect_policy: DisconnectPolicy,
+    /// How long disconnected opponent may return before forfeiting
+    pub forfeit_grace_secs: u64,
+    /// Player who does not move within given seconds forfeits, none for unlimited turns,
+    /// turns of correspondence games are never timed
+    pub turn_timeout_secs: Option<u64>,
+    /// Play AI move for me when given seconds of turn timeout are left instead of
+    /// forfeiting, only in games outside of ladder
+    pub auto_move_secs: Option<u64>,
+    /// Publish my moves so other peers can watch my games
+    pub spectators: bool,
+    /// Peers whose verdicts on my and watched games are shown, verdicts of others are ignored
+    pub referees: Vec<String>,
+    /// File where finished games are recorded
+    pub stats_file: Option<std::path::PathBuf>,
+    /// Withdraw my unanswered invitation after given seconds
+    pub invitation_timeout_secs: Option<u64>,
+    /// Seconds resignation, declined or withdrawn invitation can be taken back with undo, 0 sends them right away
+    pub undo_secs: u64,
+    /// Decline invitations from peers with reputation below given value
+    pub min_reputation: Option<i64>,
+    /// Number of recent output events kept for 'log' command
+    pub history_size: usize,
+    /// Words masked in chat and nicknames
+    pub chat_filter: Vec<String>,
+    /// Lengths of chat and nicknames and profanity filter, applied on send and receive
+    pub text_limits: chat::TextLimits,
+    /// Language passed to chat hooks when game does not set its own
+    pub chat_language: Option<String>,
+    /// Name shown with my peer id in banner
+    pub nickname: Option<String>,
+    /// Isolated group of players, only peers in the same room are listed and invited
+    pub room: Option<String>,
+    /// File keeping challenge ladder of my room, ladder is played only in a room
+    pub ladder_file: Option<std::path::PathBuf>,
+    /// Invitations to me must prove knowledge of this password
+    pub password: Option<String>,
+    /// JSON lines file where finished games are kept for replay
+    pub replay_file: Option<std::path::PathBuf>,
+    /// Directory where finished games are kept when no replay file is set
+    pub data_dir: Option<std::path::PathBuf>,
+    /// Rule variant of games I propose
+    pub variant: tictactoe::Variant,
+    /// Playmat of games I propose, none for classic 3x3, invalid one is ignored
+    pub board: Option<BoardSize>,
+    /// Directory keeping identity and running games across restarts, turns for
+    /// offline opponents wait there too
+    pub correspondence_dir: Option<std::path::PathBuf>,
+    /// Endpoint told about opponent's moves in correspondence games, needs `webhook` feature
+    pub webhook_url: Option<String>,
+    /// I count as away after given minutes without any command
+    pub away_minutes: u64,
+    /// Give tips on what to do next when I seem stuck
+    pub hints: bool,
+    /// Directory with signed logs of messages exchanged in each game
+    pub audit_dir: Option<std::path::PathBuf>,
+    /// Ways of finding peers, they run together
+    pub discovery: Vec<discovery::DiscoveryMethod>,
+    /// Addresses I listen on at once, e.g. /ip4/0.0.0.0/tcp/0 for peers on LAN and
+    /// /ip4/0.0.0.0/tcp/8080/ws for browsers, default TCP one when empty
+    pub listen_addrs: Vec<String>,
+    /// Peers dialed by static discovery, multiaddrs ending with /p2p/<peer id>
+    pub static_peers: Vec<String>,
+    /// Peers through which Kademlia joins the DHT
+    pub bootstrap_peers: Vec<String>,
+    /// Rendezvous point where I register, none when I am the one
+    pub rendezvous_point: Option<String>,
+    /// Players register and discover each other within this namespace
+    pub rendezvous_namespace: String,
+    /// Relays I listen through, multiaddrs ending with /p2p/<peer id>, so peers
+    /// on other networks reach me. Relayed connection becomes direct one by hole
+    /// punching (DCUtR) when NATs of both peers let it.
+    pub relays: Vec<String>,
+    /// Relay connections between other peers, e.g. on public bootstrap node
+    pub relay_server: bool,
+    /// Dynamic libraries adding commands, needs `plugins` feature
+    pub plugins: Vec<std::path::PathBuf>,
+}
+
+/// Handling of game whose opponent disconnected
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisconnectPolicy {
+    /// Opponent loses unless they return within grace period
+    Forfeit,
+    /// Game waits until opponent returns
+    Adjourn,
+    /// Game is cancelled without result
+    Void,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            security_warnings: true,
+            reminder_minutes: None,
+            opponent_reminder_minutes: None,
+            simul_limit: None,
+            engine: None,
+            engine_timeout_secs: 5,
+            eval_bar: false,
+            teach: false,
+            disconnect_policy: DisconnectPolicy::Adjourn,
+            forfeit_grace_secs: 60,
+            turn_timeout_secs: Some(60),
+            auto_move_secs: None,
+            spectators: true,
+            referees: Vec::new(),
+            stats_file: None,
+            invitation_timeout_secs: Some(120),
+            undo_secs: 5,
+            min_reputation: None,
+            history_size: 50,
+            chat_filter: Vec::new(),
+            text_limits: chat::TextLimits::default(),
+            chat_language: None,
+            nickname: None,
+            room: None,
+            ladder_file: None,
+            password: None,
+            replay_file: None,
+            data_dir: None,
+            variant: tictactoe::Variant::Standard,
+            board: None,
+            correspondence_dir: None,
+            webhook_url: None,
+            away_minutes: 10,
+            hints: true,
+            audit_dir: None,
+            discovery: vec![discovery::DiscoveryMethod::Mdns],
+            listen_addrs: Vec::new(),
+            static_peers: Vec::new(),
+            bootstrap_peers: Vec::new(),
+            rendezvous_point: None,
+            rendezvous_namespace: "tictactoe/lobby".to_string(),
+            relays: Vec::new(),
+            relay_server: false,
+            plugins: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! # Spectate
+//!
+//! Ongoing games watched by peer which does not play them. Game id is the name
+//! of game topic, listed by players in their games. Each player publishes every
+//! own move to watch topic of the game, together with all moves before it, so
+//! spectator may start watching in the middle of game or miss a message. It
+//! keeps local replica of the game and plays there only moves it did not see.
+
+use crate::coords::Coordinates;
+use crate::tictactoe::{GameError, GameResult, Marks, Rules, TicTacToe, Tile};
+
+/// Returns topic where players of game with given id publish their moves
+pub fn watch_topic(game_id: &str) -> libp2p::floodsub::Topic {
+    libp2p::floodsub::Topic::new(format!("{}/watch", game_id))
+}
+
+/// Returns true when topic is watch topic of some game
+pub fn is_watch_topic(topic: &libp2p::floodsub::Topic) -> bool {
+    topic.id().strip_suffix("/watch").is_some_and(|game_id| players(game_id).is_some())
+}
+
+/// Returns initiator and invitee of game with given id, none when id is not game topic
+pub fn players(game_id: &str) -> Option<(&str, &str)> {
+    let players = game_id.strip_prefix(super::LOBBY_TOPIC)?.strip_prefix('/')?;
+    match players.split('/').collect::<Vec<_>>()[..] {
+        [initiator, invitee] if !initiator.is_empty() && !invitee.is_empty() => Some((initiator, invitee)),
+        _ => None,
+    }
+}
+
+/// Local copy of watched game, seen by its initiator who plays crosses
+#[derive(Debug, Clone)]
+pub struct Replica {
+    game: TicTacToe,
+    moves: Vec<(Coordinates, Tile)>,
+}
+
+impl Default for Replica {
+    fn default() -> Self {
+        Replica::new(Rules::default())
+    }
+}
+
+impl Replica {
+    fn new(rules: Rules) -> Replica {
+        Replica { game: TicTacToe::with_rules(Marks::default().swapped(), rules), moves: Vec::new() }
+    }
+
+    pub fn game(&self) -> &TicTacToe {
+        &self.game
+    }
+
+    /// Plays moves of game which replica does not know yet and returns them.
+    /// Replica starts over when moves do not follow its own, e.g. after take back.
+    pub fn sync(&mut self, rules: Rules, moves: &[(Coordinates, Tile)]) -> Result<Vec<(Coordinates, Tile)>, GameError> {
+        if self.game.rules() != rules || !moves.starts_with(&self.moves) {
+            *self = Replica::new(rules);
+        }
+        let new_moves = moves[self.moves.len()..].to_vec();
+        for &((x, y), tile) in &new_moves {
+            if self.moves.len() % 2 == 0 {
+                self.game.make_my_mark(x, y, tile)?;
+            } else {
+                self.game.make_opponent_mark(x, y, tile)?;
+            }
+            self.moves.push(((x, y), tile));
+        }
+        Ok(new_moves)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.game.result() != GameResult::InProgress
+    }
+
+    /// Returns player who won, none while the game goes on or ended in draw
+    pub fn winner<'a>(&self, game_id: &'a str) -> Option<&'a str> {
+        let (initiator, invitee) = players(game_id)?;
+        if self.game.am_i_winner() {
+            Some(initiator)
+        } else if self.game.is_opponent_winner() {
+            Some(invitee)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replica_catches_up_from_any_move() {
+        let game_id = format!("{}/alice/bob", super::super::LOBBY_TOPIC);
+        assert_eq!(players(&game_id), Some(("alice", "bob")));
+        assert!(is_watch_topic(&watch_topic(&game_id)));
+        assert!(players("tictactoe-other/alice/bob").is_none());
+
+        let (x, o) = (Tile::Cross, Tile::Circle);
+        let moves = [((0, 0), x), ((1, 0), o), ((0, 1), x), ((1, 1), o), ((0, 2), x)];
+        let mut replica = Replica::default();
+        assert_eq!(replica.sync(Rules::default(), &moves[..3]).ok(), Some(moves[..3].to_vec()), "joined in the middle");
+        assert_eq!(replica.sync(Rules::default(), &moves[..3]).ok(), Some(Vec::new()));
+        assert_eq!(replica.sync(Rules::default(), &moves).ok(), Some(moves[3..].to_vec()));
+        assert!(replica.is_finished());
+        assert_eq!(replica.winner(&game_id), Some("alice"));
+
+        let taken_back = [((0, 0), x), ((2, 2), o)];
+        assert_eq!(replica.sync(Rules::default(), &taken_back).ok(), Some(taken_back.to_vec()));
+        assert_eq!(replica.game().moves(), &[(0, 0), (2, 2)]);
+        assert!(replica.sync(Rules::default(), &[((0, 0), o)]).is_err(), "crosses move first");
+    }
+}
@@ -0,0 +1,192 @@
+//! # Stats
+//!
+//! Local record of finished games, optionally kept in a JSON file
+
+use super::drills::Drill;
+
+/// How game ended from my point of view
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Outcome {
+    Won,
+    Lost,
+    /// Opponent disconnected and did not return in time, did not move in time or resigned
+    WonByForfeit,
+    /// Game was cancelled after opponent disconnected
+    Voided,
+    /// Playmat filled up without line
+    Drawn,
+}
+
+/// One finished game
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GameRecord {
+    pub opponent_id: String,
+    pub outcome: Outcome,
+    /// UTC milliseconds since unix epoch, missing in games recorded by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+/// Numbers of won and lost games
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Tally {
+    pub won: u64,
+    pub lost: u64,
+}
+
+/// Misbehaviour of one peer
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PeerRecord {
+    /// Invalid messages and game messages sent outside of own game
+    pub violations: u64,
+    /// Invitations sent while I was already playing
+    pub spam: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub games: Vec<GameRecord>,
+    pub peers: std::collections::HashMap<String, PeerRecord>,
+    /// Positions I blundered in, repeated until I learn them
+    pub drills: Vec<Drill>,
+}
+
+impl Stats {
+    /// Loads stats from file, missing or broken file means empty stats
+    pub fn load(path: &std::path::Path) -> Stats {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes stats to file
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("cannot jsonify stats");
+        std::fs::write(path, json)
+    }
+
+    /// Adds game finished at given UTC milliseconds
+    pub fn record(&mut self, opponent_id: &str, outcome: Outcome, finished_at: u64) {
+        self.games.push(GameRecord { opponent_id: opponent_id.to_string(), outcome, finished_at: Some(finished_at) });
+    }
+
+    /// Returns my won and lost games, forfeits count as won
+    pub fn tally(&self) -> Tally {
+        self.games.iter().fold(Tally::default(), |tally, game| match game.outcome {
+            Outcome::Won | Outcome::WonByForfeit => Tally { won: tally.won + 1, ..tally },
+            Outcome::Lost => Tally { lost: tally.lost + 1, ..tally },
+            Outcome::Voided | Outcome::Drawn => tally,
+        })
+    }
+
+    /// Counts protocol violation of peer
+    pub fn record_violation(&mut self, peer_id: &str) {
+        self.peers.entry(peer_id.to_string()).or_default().violations += 1;
+    }
+
+    /// Counts unwanted invitation from peer
+    pub fn record_spam(&mut self, peer_id: &str) {
+        self.peers.entry(peer_id.to_string()).or_default().spam += 1;
+    }
+
+    /// Returns number of games peer left by disconnecting
+    pub fn abandoned_by(&self, peer_id: &str) -> usize {
+        self.games_against(peer_id)
+            .filter(|game| matches!(game.outcome, Outcome::WonByForfeit | Outcome::Voided))
+            .count()
+    }
+
+    /// Returns reputation of peer, finished games raise it and misbehaviour lowers it
+    pub fn reputation(&self, peer_id: &str) -> i64 {
+        let finished = self.games_against(peer_id)
+            .filter(|game| matches!(game.outcome, Outcome::Won | Outcome::Lost | Outcome::Drawn))
+            .count() as i64;
+        let record = self.peers.get(peer_id).cloned().unwrap_or_default();
+        finished - 2 * self.abandoned_by(peer_id) as i64 - record.violations as i64 - record.spam as i64
+    }
+
+    /// Adds drills of positions which are not drilled yet
+    pub fn add_drills(&mut self, drills: Vec<Drill>) {
+        for drill in drills {
+            let game = drill.position.game_after(drill.position.moves.len());
+            let known = self.drills.iter().any(|known| {
+                let known_game = known.position.game_after(known.position.moves.len());
+                known_game.grid() == game.grid() && known_game.marks() == game.marks() && known_game.rules() == game.rules()
+            });
+            if !known {
+                self.drills.push(drill);
+            }
+        }
+    }
+
+    /// Returns index of drill due the longest time
+    pub fn due_drill(&self, now_secs: u64) -> Option<usize> {
+        self.drills
+            .iter()
+            .enumerate()
+            .filter(|(_, drill)| drill.due_at <= now_secs)
+            .min_by_key(|(_, drill)| drill.due_at)
+            .map(|(index, _)| index)
+    }
+
+    /// Returns score against each opponent, voided games do not count
+    pub fn score_board(&self) -> crate::tictactoe::ScoreBoard {
+        let mut board = crate::tictactoe::ScoreBoard::default();
+        for game in &self.games {
+            match game.outcome {
+                Outcome::Won | Outcome::WonByForfeit => board.record_win(&game.opponent_id),
+                Outcome::Lost => board.record_loss(&game.opponent_id),
+                Outcome::Drawn => board.record_draw(&game.opponent_id),
+                Outcome::Voided => {}
+            }
+        }
+        board
+    }
+
+    /// Returns finished games against given peer
+    pub fn games_against<'a>(&'a self, peer_id: &'a str) -> impl Iterator<Item = &'a GameRecord> {
+        self.games.iter().filter(move |game| game.opponent_id == peer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survives_file_roundtrip() {
+        let path = std::env::temp_dir().join(format!("tictactoe-stats-{}.json", std::process::id()));
+        let mut stats = Stats::default();
+        stats.record("peer", Outcome::WonByForfeit, 0);
+        stats.record("other", Outcome::Lost, 0);
+        stats.save(&path).unwrap();
+
+        let loaded = Stats::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, stats);
+        assert_eq!(loaded.games_against("peer").count(), 1);
+        assert_eq!(loaded.tally(), Tally { won: 1, lost: 1 });
+    }
+
+    #[test]
+    fn misbehaviour_lowers_reputation() {
+        let mut stats = Stats::default();
+        stats.record("peer", Outcome::Won, 0);
+        assert_eq!(stats.reputation("peer"), 1);
+
+        stats.record("peer", Outcome::Voided, 0);
+        stats.record_violation("peer");
+        stats.record_spam("peer");
+        assert_eq!(stats.abandoned_by("peer"), 1);
+        assert_eq!(stats.reputation("peer"), -3);
+        assert_eq!(stats.reputation("stranger"), 0);
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        assert_eq!(Stats::load(std::path::Path::new("/nonexistent/stats.json")), Stats::default());
+    }
+}
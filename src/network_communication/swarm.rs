@@ -0,0 +1,309 @@
+//! # Swarm
+//!
+//! Transports, listening and dialing of peers, and helpers which send messages
+//! through the swarm.
+
+use super::behaviour::{GameStatus, PeerMessage, Protocols, TicTacToeBehaviour};
+use super::session::UserSession;
+use super::{builder, channel, compression, direct, discovery, loadtest, lobby, nat, protocol, seal, spectate, transfer, validation, LOBBY_TOPIC};
+use itertools::Itertools;
+use libp2p::futures::StreamExt;
+
+/// Network runs at least this long after quit command, so last messages leave
+const QUIT_FLUSH_MIN: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Longest wait for peers to acknowledge last messages before client quits
+const QUIT_FLUSH_MAX: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Protocol version exchanged by identify, peers report address they see me at
+pub(super) const IDENTIFY_PROTOCOL: &str = "/tictactoe/1.0.0";
+
+/// Address swarm listens on, all interfaces and a random, OS-assigned port
+pub(super) const LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/0";
+
+pub(super) type Transport = libp2p::core::transport::Boxed<(libp2p::PeerId, libp2p::core::muxing::StreamMuxerBox)>;
+
+/// Connections stay open between turns, floodsub sends only over open ones
+const IDLE_CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(u64::MAX);
+
+/// Creates encrypted and multiplexed TCP and websocket transport
+pub(super) fn create_transport(key: &libp2p::identity::Keypair) -> std::io::Result<Transport> {
+    authenticate(libp2p::core::transport::OptionalTransport::none(), key)
+}
+
+/// Creates the same transport which also dials and listens through relays,
+/// with relay behaviour it belongs to
+pub(super) fn create_relay_transport(key: &libp2p::identity::Keypair) -> std::io::Result<(Transport, libp2p::relay::client::Behaviour)> {
+    let (relayed, relay) = libp2p::relay::client::new(key.public().to_peer_id());
+    let transport = authenticate(libp2p::core::transport::OptionalTransport::some(relayed), key)?;
+    Ok((transport, relay))
+}
+
+/// Upgrades TCP and websocket transport, and relay one when given, with noise and yamux
+fn authenticate(
+    relayed: libp2p::core::transport::OptionalTransport<libp2p::relay::client::Transport>,
+    key: &libp2p::identity::Keypair,
+) -> std::io::Result<Transport> {
+    use libp2p::core::upgrade;
+    use libp2p::Transport as _;
+
+    let tcp = || libp2p::dns::tokio::Transport::system(libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default().nodelay(true)));
+    let base = tcp()?.or_transport(libp2p::websocket::WsConfig::new(tcp()?));
+    let noise = libp2p::noise::Config::new(key).map_err(std::io::Error::other)?;
+    Ok(relayed
+        .or_transport(base)
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise)
+        .multiplex(libp2p::yamux::Config::default())
+        .timeout(std::time::Duration::from_secs(20))
+        .boxed())
+}
+
+/// Starts local peer discovery, fails when multicast is not available
+pub(super) fn create_mdns(peer: libp2p::PeerId) -> std::io::Result<libp2p::mdns::tokio::Behaviour> {
+    libp2p::mdns::tokio::Behaviour::new(libp2p::mdns::Config::default(), peer)
+}
+
+pub(super) fn listen_address() -> libp2p::Multiaddr {
+    LISTEN_ADDRESS.parse().expect("can get a local socket")
+}
+
+pub(super) async fn init_swarm(user_sess: &UserSession, response_sender: channel::Sender<PeerMessage>) -> libp2p::swarm::Swarm<TicTacToeBehaviour> {
+    let config = &user_sess.swarm_config;
+    let (transport, relay) = match config.transport {
+        builder::TransportKind::Memory => (loadtest::memory_transport(&user_sess.user_key), None),
+        builder::TransportKind::Tcp if config.relay => {
+            let (transport, relay) = create_relay_transport(&user_sess.user_key).expect("transport create failed");
+            (transport, Some(relay))
+        }
+        builder::TransportKind::Tcp => (create_transport(&user_sess.user_key).expect("transport create failed"), None),
+    };
+    let relayed = relay.is_some();
+    let dcutr = relayed.then(|| libp2p::dcutr::Behaviour::new(user_sess.user_peer_id));
+    let mdns = if config.mdns {
+        Some(create_mdns(user_sess.user_peer_id).expect("can create mdns"))
+    } else {
+        None
+    };
+    let kademlia = if config.kademlia {
+        let store = libp2p::kad::store::MemoryStore::new(user_sess.user_peer_id);
+        let mut kademlia = libp2p::kad::Behaviour::new(user_sess.user_peer_id, store);
+        // answers queries of others also before it knows its public address
+        kademlia.set_mode(Some(libp2p::kad::Mode::Server));
+        Some(kademlia)
+    } else {
+        None
+    };
+
+    let identify = libp2p::identify::Config::new(IDENTIFY_PROTOCOL.to_string(), user_sess.user_key.public());
+    let relay_server = config.relay_server.then(|| libp2p::relay::Behaviour::new(user_sess.user_peer_id, Default::default()));
+    let rendezvous = config.rendezvous.then(|| libp2p::rendezvous::client::Behaviour::new(user_sess.user_key.clone()));
+    let rendezvous_point = config.rendezvous_point.then(|| libp2p::rendezvous::server::Behaviour::new(Default::default()));
+
+    let protocols = Protocols {
+        floodsub: libp2p::floodsub::Floodsub::new(user_sess.user_peer_id),
+        mdns: mdns.into(),
+        kademlia: kademlia.into(),
+        identify: libp2p::identify::Behaviour::new(identify),
+        autonat: libp2p::autonat::Behaviour::new(user_sess.user_peer_id, Default::default()),
+        relay: relay.into(),
+        dcutr: dcutr.into(),
+        relay_server: relay_server.into(),
+        direct: direct::behaviour(),
+        replays: transfer::behaviour(),
+        rendezvous: rendezvous.into(),
+        rendezvous_point: rendezvous_point.into(),
+    };
+    let mut behaviour = TicTacToeBehaviour {
+        protocols,
+        unacknowledged: std::collections::HashMap::new(),
+        floodsub_only: std::collections::HashSet::new(),
+        outgoing_replays: std::collections::HashMap::new(),
+        incoming_replays: transfer::Incoming::default(),
+        nat: nat::NatObserver::default(),
+        response_sender,
+        diagnostics: validation::Diagnostics::default(),
+        last_seen: std::collections::HashMap::new(),
+        lobby: user_sess.lobby.clone(),
+        lobby_members: std::collections::HashSet::new(),
+        user_peer_id: user_sess.user_peer_id.to_string(),
+        audit: user_sess.audit_log(),
+        netstats: user_sess.netstats.clone(),
+        found_by: std::collections::HashMap::new(),
+        addresses: std::collections::HashMap::new(),
+        ladder_topic: user_sess.ladder_topic(),
+        seeking_topic: lobby::seeking_topic(&user_sess.lobby),
+        seals: seal::Seals::new(user_sess.user_key.clone()),
+        compression: compression::Negotiated::default(),
+        errors: user_sess.errors.clone(),
+    };
+
+    behaviour.protocols.floodsub.subscribe(user_sess.lobby.clone());
+    for topic in &config.topics {
+        behaviour.protocols.floodsub.subscribe(topic.clone());
+    }
+    if let Some(topic) = behaviour.ladder_topic.clone() {
+        behaviour.protocols.floodsub.subscribe(topic);
+    }
+    let seeking_topic = behaviour.seeking_topic.clone();
+    behaviour.protocols.floodsub.subscribe(seeking_topic);
+    for session in user_sess.sessions.iter().filter(|session| session.is_initiated()) {
+        behaviour.join_game(session);
+    }
+    for game_id in user_sess.watched.keys().chain(user_sess.refereed.keys()) {
+        behaviour.protocols.floodsub.subscribe(spectate::watch_topic(game_id));
+    }
+    let swarm_config = libp2p::swarm::Config::with_tokio_executor().with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT);
+    let mut swarm = libp2p::Swarm::new(transport, behaviour, user_sess.user_peer_id, swarm_config);
+
+    // one listener which cannot start does not take the other transports down
+    for address in &config.listen_addrs {
+        if let Err(error) = swarm.listen_on(address.clone()) {
+            user_sess.report(format!("Cannot listen on {}: {}", address, error));
+        }
+    }
+    // peers on other networks reach me through relays
+    for (relay, address) in config.relays.iter().filter(|_| relayed) {
+        let circuit = address
+            .clone()
+            .with(libp2p::multiaddr::Protocol::P2p(*relay))
+            .with(libp2p::multiaddr::Protocol::P2pCircuit);
+        if let Err(error) = swarm.listen_on(circuit.clone()) {
+            user_sess.report(format!("Cannot listen through relay {}: {}", circuit, error));
+        }
+    }
+    if let Some(network) = &user_sess.virtual_network {
+        loadtest::reconnect(&mut swarm, network);
+    }
+    swarm
+}
+
+/// Returns discovered peers, in a room only those which joined its lobby
+pub(super) async fn get_peers(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) -> Vec<&libp2p::PeerId> {
+    let behaviour = swarm.behaviour();
+    let in_room = behaviour.lobby.id() != LOBBY_TOPIC;
+    let nodes: Vec<&libp2p::PeerId> = match behaviour.protocols.mdns.as_ref() {
+        Some(mdns) => mdns.discovered_nodes().chain(behaviour.found_by.keys()).collect(),
+        None if behaviour.found_by.is_empty() => behaviour.last_seen.keys().collect(),
+        None => behaviour.found_by.keys().collect(),
+    };
+    nodes
+        .into_iter()
+        .unique()
+        .filter(|peer| !in_room || behaviour.lobby_members.contains(*peer))
+        .collect()
+}
+
+/// Removes peers which are neither discovered nor connected from floodsub view
+pub(super) fn prune_partial_view(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) {
+    let stale = swarm
+        .behaviour()
+        .last_seen
+        .keys()
+        .filter(|peer| !swarm.behaviour().is_discovered(peer) && !swarm.is_connected(peer))
+        .copied()
+        .collect_vec();
+
+    let behaviour = swarm.behaviour_mut();
+    for peer in stale {
+        behaviour.protocols.floodsub.remove_node_from_partial_view(&peer);
+        behaviour.last_seen.remove(&peer);
+        behaviour.lobby_members.remove(&peer);
+        behaviour.found_by.remove(&peer);
+        let _ = behaviour.response_sender.send(PeerMessage::about(peer.to_string(), GameStatus::PeerLost));
+    }
+}
+
+/// Lets discovery strategies look for peers and dials those they found
+pub(super) fn refresh_discovery(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &mut UserSession) {
+    for strategy in user_session.discovery.iter_mut() {
+        let method = strategy.method();
+        for found in strategy.refresh(swarm) {
+            connect_found(swarm, found.peer, vec![found.address], method);
+        }
+    }
+}
+
+/// Dials recent opponents at remembered addresses, returns number of those not connected
+pub(super) fn reconnect_known(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>, user_session: &UserSession) -> usize {
+    let mut dialed = 0;
+    for known in &user_session.known_peers {
+        let peer = match known.peer_id.parse::<libp2p::PeerId>() {
+            Ok(peer) if !swarm.is_connected(&peer) => peer,
+            _ => continue,
+        };
+        let addresses = known.addresses.iter().filter_map(|address| address.parse().ok()).collect();
+        connect_found(swarm, peer, addresses, discovery::DiscoveryMethod::Known);
+        dialed += 1;
+    }
+    dialed
+}
+
+/// Dials peer found by discovery and adds it to floodsub view
+pub(super) fn connect_found(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    peer: libp2p::PeerId,
+    addresses: Vec<libp2p::Multiaddr>,
+    method: discovery::DiscoveryMethod,
+) {
+    if peer == *swarm.local_peer_id() {
+        return;
+    }
+    // the first address, dialed first, becomes the most recent one
+    for address in addresses.iter().rev() {
+        swarm.behaviour_mut().note_address(peer, address.clone());
+        swarm.add_peer_address(peer, address.clone());
+    }
+    if !swarm.is_connected(&peer) && !addresses.into_iter().any(|address| swarm.dial(address).is_ok()) {
+        return;
+    }
+
+    let behaviour = swarm.behaviour_mut();
+    behaviour.protocols.floodsub.add_node_to_partial_view(peer);
+    behaviour.last_seen.insert(peer, std::time::Instant::now());
+    let methods = behaviour.found_by.entry(peer).or_default();
+    let is_new = methods.is_empty();
+    methods.insert(method);
+    if is_new {
+        let _ = behaviour.response_sender.send(PeerMessage::about(peer.to_string(), GameStatus::PeerFound));
+    }
+}
+
+/// Drives network until direct messages are acknowledged, or for given longest time
+pub(super) async fn flush_swarm(swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>) {
+    let started = tokio::time::Instant::now();
+    // floodsub has no acknowledgements, it gets at least the shortest time
+    while started.elapsed() < QUIT_FLUSH_MIN || !swarm.behaviour().unacknowledged.is_empty() {
+        tokio::select! {
+            _ = swarm.select_next_some() => {}
+            _ = tokio::time::sleep(QUIT_FLUSH_MIN) => {}
+            _ = tokio::time::sleep_until(started + QUIT_FLUSH_MAX) => break,
+        }
+    }
+}
+
+/// Publishes message to topic in given format
+pub(super) fn publish(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    topic: libp2p::floodsub::Topic,
+    message: protocol::WireMessage,
+    format: protocol::WireFormat,
+) {
+    let payload = swarm.behaviour_mut().encode(&topic, &message, format);
+    swarm.behaviour_mut().publish(topic, payload);
+}
+
+/// Sends message of topic only to peer, legacy clients get it over floodsub
+pub(super) fn send_direct(
+    swarm: &mut libp2p::swarm::Swarm<TicTacToeBehaviour>,
+    peer_id: &str,
+    topic: libp2p::floodsub::Topic,
+    message: protocol::WireMessage,
+    format: protocol::WireFormat,
+) {
+    let payload = swarm.behaviour_mut().encode(&topic, &message, format);
+    match peer_id.parse() {
+        Ok(peer) if format != protocol::WireFormat::Legacy => swarm.behaviour_mut().deliver(peer, topic, payload),
+        _ => swarm.behaviour_mut().publish(topic, payload),
+    }
+}
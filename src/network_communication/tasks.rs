@@ -0,0 +1,71 @@
+//! # Tasks
+//!
+//! Background tasks whose lifetime is bound to a game session
+
+/// Owns tasks spawned for a session, dropping or resetting it aborts them
+#[derive(Default)]
+pub struct TaskSupervisor {
+    tasks: tokio::task::JoinSet<()>,
+}
+
+impl TaskSupervisor {
+    pub fn new() -> TaskSupervisor {
+        TaskSupervisor::default()
+    }
+
+    /// Spawns task owned by the session
+    pub fn spawn<F>(&mut self, task: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    /// Aborts all running tasks
+    pub fn cancel_all(&mut self) {
+        self.tasks.abort_all();
+    }
+
+    /// Returns number of tasks not yet reaped
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Waits for next finished task, never resolves when there is none
+    pub async fn reap(&mut self) -> Result<(), tokio::task::JoinError> {
+        match self.tasks.join_next().await {
+            Some(result) => result,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancels_tasks() {
+        let mut supervisor = TaskSupervisor::new();
+        supervisor.spawn(std::future::pending());
+        supervisor.spawn(std::future::pending());
+        assert_eq!(supervisor.len(), 2);
+
+        supervisor.cancel_all();
+        assert!(supervisor.reap().await.unwrap_err().is_cancelled());
+        assert!(supervisor.reap().await.unwrap_err().is_cancelled());
+        assert!(supervisor.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reaps_finished_task() {
+        let mut supervisor = TaskSupervisor::new();
+        supervisor.spawn(async {});
+        assert!(supervisor.reap().await.is_ok());
+        assert!(supervisor.is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! # Transfer
+//!
+//! Stored replays sent to other peer over request-response protocol of their
+//! own. Replay is split into chunks sent one at a time, each next chunk goes
+//! after the previous one is acknowledged, so both sides can report progress.
+//! Receiver assembles chunks in order and accepts only whole replay which is
+//! not larger than a limit.
+
+use async_trait::async_trait;
+use libp2p::futures::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{self, ProtocolSupport};
+
+use super::direct::{read_length_prefixed, write_length_prefixed, Ack};
+
+pub const PROTOCOL: libp2p::StreamProtocol = libp2p::StreamProtocol::new("/tictactoe/replay/1.0.0");
+/// Largest part of replay sent in one request
+pub const CHUNK_BYTES: usize = 1024;
+/// Largest replay accepted, finished games are far smaller
+pub const MAX_REPLAY_BYTES: usize = 64 * 1024;
+/// Largest encoded chunk, its part of replay escaped as JSON string
+const MAX_MESSAGE_BYTES: usize = 8 * CHUNK_BYTES;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferError {
+    /// Replay is longer than accepted
+    TooLarge,
+    /// Chunk with other index than expected
+    OutOfOrder(usize),
+}
+
+impl std::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransferError::TooLarge => write!(f, "replay is larger than {} bytes", MAX_REPLAY_BYTES),
+            TransferError::OutOfOrder(index) => write!(f, "chunk {} came out of order", index),
+        }
+    }
+}
+
+/// Part of replay, transfer tells apart replays sent by the same peer
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Chunk {
+    pub transfer: u64,
+    pub index: usize,
+    pub total: usize,
+    pub data: String,
+}
+
+/// Splits content into chunks of at most [`CHUNK_BYTES`], always at least one
+pub fn split(content: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for c in content.chars() {
+        if parts.last().is_some_and(|part| part.len() + c.len_utf8() > CHUNK_BYTES) {
+            parts.push(String::new());
+        }
+        parts.last_mut().expect("at least one part").push(c);
+    }
+    parts
+}
+
+/// Replay being sent to one peer
+#[derive(Debug)]
+pub struct Outgoing {
+    pub peer_id: String,
+    transfer: u64,
+    parts: Vec<String>,
+    sent: usize,
+}
+
+impl Outgoing {
+    pub fn new(peer_id: String, transfer: u64, content: &str) -> Outgoing {
+        Outgoing { peer_id, transfer, parts: split(content), sent: 0 }
+    }
+
+    /// Returns chunk to send next, none when all were sent
+    pub fn next_chunk(&mut self) -> Option<Chunk> {
+        let data = self.parts.get(self.sent)?.clone();
+        let chunk = Chunk { transfer: self.transfer, index: self.sent, total: self.parts.len(), data };
+        self.sent += 1;
+        Some(chunk)
+    }
+
+    /// Returns number of chunks sent and all chunks
+    pub fn progress(&self) -> (usize, usize) {
+        (self.sent, self.parts.len())
+    }
+}
+
+/// Replays being received, by peer and transfer
+#[derive(Debug, Default)]
+pub struct Incoming {
+    parts: std::collections::HashMap<(String, u64), String>,
+    next: std::collections::HashMap<(String, u64), usize>,
+}
+
+impl Incoming {
+    /// Adds chunk from peer, returns whole content after its last chunk. Broken
+    /// transfer is dropped.
+    pub fn accept(&mut self, peer_id: &str, chunk: Chunk) -> Result<Option<String>, TransferError> {
+        let key = (peer_id.to_string(), chunk.transfer);
+        let expected = self.next.get(&key).copied().unwrap_or(0);
+        if chunk.index != expected || chunk.index >= chunk.total {
+            self.drop_transfer(&key);
+            return Err(TransferError::OutOfOrder(chunk.index));
+        }
+        let content = self.parts.entry(key.clone()).or_default();
+        if chunk.data.len() > CHUNK_BYTES || content.len() + chunk.data.len() > MAX_REPLAY_BYTES {
+            self.drop_transfer(&key);
+            return Err(TransferError::TooLarge);
+        }
+        content.push_str(&chunk.data);
+        if chunk.index + 1 < chunk.total {
+            self.next.insert(key, expected + 1);
+            return Ok(None);
+        }
+        self.next.remove(&key);
+        Ok(self.parts.remove(&key))
+    }
+
+    fn drop_transfer(&mut self, key: &(String, u64)) {
+        self.parts.remove(key);
+        self.next.remove(key);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayCodec;
+
+fn invalid_data(error: serde_json::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+}
+
+#[async_trait]
+impl request_response::Codec for ReplayCodec {
+    type Protocol = libp2p::StreamProtocol;
+    type Request = Chunk;
+    type Response = Ack;
+
+    async fn read_request<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T) -> std::io::Result<Chunk>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let bytes = read_length_prefixed(io, MAX_MESSAGE_BYTES).await?;
+        serde_json::from_slice(&bytes).map_err(invalid_data)
+    }
+
+    async fn read_response<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T) -> std::io::Result<Ack>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_length_prefixed(io, 0).await?;
+        Ok(Ack)
+    }
+
+    async fn write_request<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T, chunk: Chunk) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = serde_json::to_vec(&chunk).map_err(invalid_data)?;
+        write_length_prefixed(io, bytes).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(&mut self, _: &libp2p::StreamProtocol, io: &mut T, _: Ack) -> std::io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        write_length_prefixed(io, []).await?;
+        io.close().await
+    }
+}
+
+pub fn behaviour() -> request_response::Behaviour<ReplayCodec> {
+    let protocols = std::iter::once((PROTOCOL, ProtocolSupport::Full));
+    request_response::Behaviour::new(protocols, request_response::Config::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_is_assembled_from_ordered_chunks() {
+        let content = "é".repeat(CHUNK_BYTES);
+        let mut outgoing = Outgoing::new("sender".to_string(), 7, &content);
+        let mut incoming = Incoming::default();
+        let mut received = None;
+        while let Some(chunk) = outgoing.next_chunk() {
+            assert!(chunk.data.len() <= CHUNK_BYTES);
+            received = incoming.accept("sender", chunk).unwrap();
+        }
+        assert_eq!(outgoing.progress(), (2, 2));
+        assert_eq!(received, Some(content));
+
+        let chunk = |index| Chunk { transfer: 8, index, total: 3, data: "x".to_string() };
+        assert_eq!(incoming.accept("sender", chunk(0)), Ok(None));
+        assert_eq!(incoming.accept("sender", chunk(2)), Err(TransferError::OutOfOrder(2)));
+        assert_eq!(incoming.accept("sender", chunk(1)), Err(TransferError::OutOfOrder(1)), "broken transfer is dropped");
+    }
+}
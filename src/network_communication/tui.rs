@@ -0,0 +1,453 @@
+//! # Tui
+//!
+//! Terminal UI with board of active game, peers, log of the session and
+//! command line. Commands are the same as typed to stdio client, they are
+//! parsed and events are rendered into log by [`Stdio`] which captures its
+//! lines instead of printing them. Status line and error banner stay on screen,
+//! Esc dismisses the banner, PageUp and PageDown scroll the log.
+//!
+//! Unless mouse is left to the terminal, click on field of game playmat plays
+//! turn there and click on peer puts command inviting it on the command line,
+//! both are the same inputs as typed commands.
+//!
+//! Failures, e.g. when file cannot be saved, are shown in the error banner.
+//! Panic leaves raw mode and alternate screen so its message can be read, when
+//! the client contains it and goes on, they are entered again on next redraw.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use super::input::{Input, Stdio};
+use super::observer::SwarmObserver;
+use super::{OutputEvents, StatusLine};
+
+/// Lines of log kept for scrolling back
+const LOG_LINES: usize = 1000;
+/// Lines scrolled by one PageUp or PageDown
+const SCROLL_STEP: usize = 10;
+
+/// Panic hook restored the terminal, screen is entered again on next redraw
+static LEFT_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Panels which can be clicked, as drawn the last time
+#[derive(Debug, Clone, Copy, Default)]
+struct Areas {
+    board: Rect,
+    peers: Rect,
+}
+
+/// How terminal was switched when UI started
+#[derive(Debug, Clone, Copy)]
+struct Screen {
+    mouse: bool,
+}
+
+pub struct Tui<B: Backend = CrosstermBackend<std::io::Stdout>> {
+    terminal: RefCell<ratatui::Terminal<B>>,
+    /// None when UI draws elsewhere than on the terminal, e.g. in tests
+    screen: Option<Screen>,
+    /// Parses commands and renders events into lines of log
+    console: Stdio,
+    /// Key presses and resizes read by blocking thread
+    events: tokio::sync::mpsc::UnboundedReceiver<Event>,
+    /// Command being typed
+    line: String,
+    log: RefCell<VecDeque<String>>,
+    /// Lines log is scrolled back by, 0 shows the newest
+    scroll: usize,
+    /// Board of active game in theme of console
+    board: RefCell<Vec<String>>,
+    /// Rows of shown playmat when it is game and clicks on it are turns
+    game_board: std::cell::Cell<Option<usize>>,
+    areas: std::cell::Cell<Areas>,
+    peers: RefCell<Vec<String>>,
+    status: RefCell<StatusLine>,
+    error: RefCell<Option<String>>,
+}
+
+impl Tui {
+    /// Switches terminal to raw mode and alternate screen, both are restored on drop or panic.
+    /// Mouse is captured when enabled, otherwise terminal keeps it e.g. for selecting text.
+    pub fn new(theme: crate::theme::Theme, labels: crate::coords::Labels, dates: crate::dates::DateFormat, mouse: bool) -> std::io::Result<Tui> {
+        let screen = Screen { mouse };
+        enter_screen(screen)?;
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            LEFT_SCREEN.store(true, Ordering::Relaxed);
+            hook(info);
+        }));
+        let terminal = ratatui::Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+        let (sender, events) = tokio::sync::mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            while let Ok(event) = crossterm::event::read() {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let tui = Tui::with_terminal(terminal, Some(screen), Stdio::captured(theme, labels, dates), events);
+        tui.draw();
+        Ok(tui)
+    }
+}
+
+impl<B: Backend> Tui<B> {
+    fn with_terminal(terminal: ratatui::Terminal<B>, screen: Option<Screen>, console: Stdio, events: tokio::sync::mpsc::UnboundedReceiver<Event>) -> Tui<B> {
+        Tui {
+            terminal: RefCell::new(terminal),
+            screen,
+            console,
+            events,
+            line: String::new(),
+            log: Default::default(),
+            scroll: 0,
+            board: Default::default(),
+            game_board: Default::default(),
+            areas: Default::default(),
+            peers: Default::default(),
+            status: Default::default(),
+            error: Default::default(),
+        }
+    }
+
+    /// Moves lines rendered by console into log
+    fn take_log(&self) {
+        let mut log = self.log.borrow_mut();
+        log.extend(self.console.take_lines());
+        while log.len() > LOG_LINES {
+            log.pop_front();
+        }
+    }
+
+    /// Keeps panels other than log in sync with event
+    fn observe(&self, event: &OutputEvents) {
+        let board = match event {
+            OutputEvents::StartTrue(grid, _)
+            | OutputEvents::TurnResolved(grid, _)
+            | OutputEvents::SwitchedGame(_, grid)
+            | OutputEvents::EnginePlayed(_, grid, _)
+            | OutputEvents::AutoMoved(_, _, grid) => {
+                self.game_board.set(Some(grid.len()));
+                self.console.table_lines(grid)
+            }
+            OutputEvents::SetupPosition(state) | OutputEvents::Drill(state, _) => {
+                self.game_board.set(None);
+                self.console.table_lines(state)
+            }
+            OutputEvents::ListPeers(peers) => {
+                *self.peers.borrow_mut() = peers.iter().map(|peer| peer.peer_id.clone()).collect();
+                return;
+            }
+            OutputEvents::Status(status) => {
+                *self.status.borrow_mut() = status.clone();
+                return;
+            }
+            OutputEvents::Error(error) => {
+                *self.error.borrow_mut() = Some(error.clone());
+                return;
+            }
+            _ => return,
+        };
+        *self.board.borrow_mut() = board;
+    }
+
+    /// Handles key, returns command once line is submitted
+    fn on_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Option<super::Input> {
+        match code {
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Some(super::Input::Quit),
+            KeyCode::Char(c) => self.line.push(c),
+            KeyCode::Backspace => {
+                self.line.pop();
+            }
+            KeyCode::Esc => *self.error.get_mut() = None,
+            KeyCode::PageUp => self.scroll = (self.scroll + SCROLL_STEP).min(self.log.get_mut().len()),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(SCROLL_STEP),
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.line);
+                if line.trim().is_empty() {
+                    return None;
+                }
+                self.log.get_mut().push_back(format!("> {}", line));
+                *self.error.get_mut() = None;
+                self.scroll = 0;
+                let input = self.console.process_input(line.trim());
+                self.take_log();
+                return input;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Handles click, returns turn on clicked field of game playmat
+    fn on_click(&mut self, column: u16, row: u16) -> Option<super::Input> {
+        let areas = self.areas.get();
+        if let Some((line, offset)) = inner_position(areas.board, column, row) {
+            let (x, y) = self.console.field_at(self.game_board.get()?, line, offset)?;
+            return Some(super::Input::Turn(x, y, None));
+        }
+        let (line, _) = inner_position(areas.peers, column, row)?;
+        if let Some(peer) = self.peers.get_mut().get(line) {
+            // invitation waits on command line for password or message
+            self.line = format!("start {}", peer);
+        }
+        None
+    }
+
+    fn draw(&self) {
+        if let Some(screen) = self.screen {
+            // panic the client contained left the screen
+            if LEFT_SCREEN.swap(false, Ordering::Relaxed) && enter_screen(screen).is_ok() {
+                let _ = self.terminal.borrow_mut().clear();
+            }
+        }
+        let log = self.log.borrow();
+        let board = self.board.borrow();
+        let peers = self.peers.borrow();
+        let status = self.status.borrow();
+        let error = self.error.borrow();
+        let _ = self.terminal.borrow_mut().draw(|frame| {
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(1),
+                    Constraint::Min(0),
+                    Constraint::Length(if error.is_some() { 1 } else { 0 }),
+                    Constraint::Length(3),
+                ])
+                .split(frame.size());
+            let board_width = board.iter().map(|line| line.chars().count()).max().unwrap_or(0).max(20) + 2;
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(board_width as u16), Constraint::Min(0)])
+                .split(rows[1]);
+            let side = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(board.len() as u16 + 2), Constraint::Min(0)])
+                .split(columns[0]);
+
+            frame.render_widget(Paragraph::new(status_text(&status)).style(Style::default().fg(Color::Black).bg(Color::Gray)), rows[0]);
+
+            self.areas.set(Areas { board: side[0], peers: side[1] });
+
+            let board_lines: Vec<Line> = board.iter().map(|line| Line::from(line.clone())).collect();
+            frame.render_widget(Paragraph::new(board_lines).block(Block::default().borders(Borders::ALL).title("Board")), side[0]);
+
+            let peer_lines: Vec<Line> = peers.iter().map(|peer| Line::from(peer.clone())).collect();
+            frame.render_widget(Paragraph::new(peer_lines).block(Block::default().borders(Borders::ALL).title("Peers")), side[1]);
+
+            // log shows its end, or the lines scrolled back to
+            let height = columns[1].height.saturating_sub(2) as usize;
+            let end = log.len() - self.scroll.min(log.len());
+            let log_lines: Vec<Line> = log.range(end.saturating_sub(height)..end).map(|line| Line::from(line.clone())).collect();
+            let title = if self.scroll > 0 { format!("Log (-{})", self.scroll) } else { "Log".to_string() };
+            frame.render_widget(Paragraph::new(log_lines).block(Block::default().borders(Borders::ALL).title(title)), columns[1]);
+
+            if let Some(error) = error.as_ref() {
+                let banner = format!("{} (Esc to dismiss)", error);
+                frame.render_widget(Paragraph::new(banner).style(Style::default().fg(Color::White).bg(Color::Red)), rows[2]);
+            }
+
+            frame.render_widget(Paragraph::new(self.line.as_str()).block(Block::default().borders(Borders::ALL).title("Command")), rows[3]);
+            frame.set_cursor(rows[3].x + 1 + self.line.chars().count() as u16, rows[3].y + 1);
+        });
+    }
+}
+
+/// Returns text of status line
+fn status_text(status: &StatusLine) -> String {
+    let mut parts = vec![format!("{} peers", status.peers), format!("{} games", status.games)];
+    if let Some(opponent) = &status.opponent {
+        let turn = if status.your_turn { "your turn" } else { "waiting" };
+        parts.push(format!("vs <{}>, {}", opponent, turn));
+    }
+    if status.seeking {
+        parts.push("seeking game".to_string());
+    }
+    parts.join(" | ")
+}
+
+/// Returns line and character inside borders of panel at given cell, none outside of it
+fn inner_position(area: Rect, column: u16, row: u16) -> Option<(usize, usize)> {
+    let inside = |position: u16, start: u16, length: u16| position > start && position + 1 < start + length;
+    (inside(column, area.x, area.width) && inside(row, area.y, area.height))
+        .then(|| ((row - area.y - 1) as usize, (column - area.x - 1) as usize))
+}
+
+/// Switches terminal to raw mode and alternate screen
+fn enter_screen(screen: Screen) -> std::io::Result<()> {
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    if screen.mouse {
+        crossterm::execute!(std::io::stdout(), crossterm::event::EnableMouseCapture)?;
+    }
+    Ok(())
+}
+
+fn restore_terminal() {
+    let _ = crossterm::terminal::disable_raw_mode();
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        crossterm::event::DisableMouseCapture,
+        crossterm::terminal::LeaveAlternateScreen,
+        crossterm::cursor::Show
+    );
+}
+
+#[async_trait]
+impl<B: Backend + Send> Input<super::Input, OutputEvents> for Tui<B> {
+    async fn get_input(&mut self) -> Option<super::Input> {
+        loop {
+            // delayed events of console wait for their time like in stdio
+            let event = match self.console.next_due() {
+                Some(at) => tokio::select! {
+                    event = self.events.recv() => Some(event),
+                    _ = tokio::time::sleep_until(at) => None,
+                },
+                None => Some(self.events.recv().await),
+            };
+            match event {
+                None => {
+                    self.console.render_due();
+                    self.take_log();
+                }
+                Some(None) => return Some(super::Input::Quit),
+                Some(Some(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    if let Some(input) = self.on_key(key.code, key.modifiers) {
+                        self.draw();
+                        return Some(input);
+                    }
+                }
+                Some(Some(Event::Mouse(mouse))) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                    if let Some(input) = self.on_click(mouse.column, mouse.row) {
+                        self.draw();
+                        return Some(input);
+                    }
+                }
+                Some(Some(_)) => {}
+            }
+            self.draw();
+        }
+    }
+
+    fn print_to_output(&self, event: OutputEvents) {
+        self.observe(&event);
+        self.console.print_to_output(event);
+        self.take_log();
+        self.draw();
+    }
+
+    fn ask(&mut self, prompt: super::prompt::Prompt) -> Option<super::prompt::Answer> {
+        // question goes to log, it is answered by typed y or n
+        let answer = self.console.ask(prompt);
+        self.take_log();
+        self.draw();
+        answer
+    }
+}
+
+impl<B: Backend> SwarmObserver for Tui<B> {
+    fn on_peer_discovered(&mut self, peer_id: &str) {
+        let peers = self.peers.get_mut();
+        if !peers.iter().any(|peer| peer == peer_id) {
+            peers.push(peer_id.to_string());
+        }
+        self.draw();
+    }
+
+    fn on_peer_lost(&mut self, peer_id: &str) {
+        self.peers.get_mut().retain(|peer| peer != peer_id);
+        self.draw();
+    }
+
+    fn on_config_reloaded(&mut self, config: &crate::config::Config) {
+        self.console.on_config_reloaded(config);
+    }
+}
+
+impl<B: Backend> Drop for Tui<B> {
+    fn drop(&mut self) {
+        if self.screen.is_some() {
+            restore_terminal();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_status() {
+        assert_eq!(status_text(&StatusLine::default()), "0 peers | 0 games");
+        let status = StatusLine { peers: 3, games: 1, opponent: Some("alice".to_string()), your_turn: true, seeking: true };
+        assert_eq!(status_text(&status), "3 peers | 1 games | vs <alice>, your turn | seeking game");
+    }
+
+    fn test_tui() -> Tui<ratatui::backend::TestBackend> {
+        let terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+        let console = Stdio::captured(crate::theme::Theme::classic(), crate::coords::Labels::default(), Default::default());
+        let (_, events) = tokio::sync::mpsc::unbounded_channel();
+        Tui::with_terminal(terminal, None, console, events)
+    }
+
+    #[tokio::test]
+    async fn submits_command_scrolls_log_and_dismisses_error() {
+        let mut tui = test_tui();
+        for c in "quitt".chars() {
+            assert!(tui.on_key(KeyCode::Char(c), KeyModifiers::NONE).is_none());
+        }
+        assert!(tui.on_key(KeyCode::Backspace, KeyModifiers::NONE).is_none());
+        assert!(matches!(tui.on_key(KeyCode::Enter, KeyModifiers::NONE), Some(crate::network_communication::Input::Quit)));
+        assert!(tui.line.is_empty());
+        assert!(tui.log.get_mut().contains(&"> quit".to_string()));
+        assert!(tui.on_key(KeyCode::Enter, KeyModifiers::NONE).is_none(), "empty line");
+
+        tui.log.get_mut().extend((0..25).map(|line| line.to_string()));
+        let lines = tui.log.get_mut().len();
+        tui.on_key(KeyCode::PageUp, KeyModifiers::NONE);
+        assert_eq!(tui.scroll, SCROLL_STEP);
+        for _ in 0..5 {
+            tui.on_key(KeyCode::PageUp, KeyModifiers::NONE);
+        }
+        assert_eq!(tui.scroll, lines);
+        tui.on_key(KeyCode::PageDown, KeyModifiers::NONE);
+        assert_eq!(tui.scroll, lines - SCROLL_STEP);
+
+        tui.print_to_output(OutputEvents::Error("Cannot save games".to_string()));
+        assert_eq!(tui.error.get_mut().as_deref(), Some("Cannot save games"));
+        tui.on_key(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(*tui.error.get_mut(), None);
+        assert!(matches!(tui.on_key(KeyCode::Char('c'), KeyModifiers::CONTROL), Some(crate::network_communication::Input::Quit)));
+    }
+
+    #[tokio::test]
+    async fn finds_clicked_field() {
+        let area = Rect { x: 0, y: 1, width: 22, height: 8 };
+        assert_eq!(inner_position(area, 0, 2), None, "border");
+        assert_eq!(inner_position(area, 21, 2), None, "border");
+        assert_eq!(inner_position(area, 5, 3), Some((1, 4)));
+
+        // header, then rows with separator lines between them
+        let console = Stdio::captured(crate::theme::Theme::classic(), crate::coords::Labels::default(), Default::default());
+        let lines = console.table_lines(&[[crate::tictactoe::Tile::Empty; 3]; 3]);
+        let column = lines[3].find('|').unwrap() + 2;
+        assert_eq!(console.field_at(3, 3, column), Some((1, 1)));
+        assert_eq!(console.field_at(3, 3, column - 2), None, "grid");
+        assert_eq!(console.field_at(3, 2, column), None, "row separator");
+        assert_eq!(console.field_at(3, 0, column), None, "header");
+        assert_eq!(console.field_at(3, 5, 2), Some((2, 0)));
+    }
+}
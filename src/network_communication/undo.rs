@@ -0,0 +1,102 @@
+//! # Undo
+//!
+//! Resignation, declined or withdrawn invitation can be taken back for a few seconds.
+//! Session state the action changes is snapshotted and its message waits in
+//! outgoing queue instead of being sent. Undo puts the snapshot back, asks the
+//! answered question again and drops the message before it left, once grace
+//! period passes the message is sent and the session ends.
+
+use std::time::Instant;
+
+use super::prompt::Question;
+use super::protocol::{WireFormat, WireMessage};
+
+/// Action which can be taken back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Resign,
+    /// Invitation withdrawn or declined
+    Withdraw,
+}
+
+/// Session state the action changes, put back by undo
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub turn_started: Option<Instant>,
+    pub invited_at: Option<Instant>,
+}
+
+/// Action held back with message it sends, none when opponent is not told
+#[derive(Debug, Clone, PartialEq)]
+pub struct Held {
+    pub action: Action,
+    pub opponent_id: String,
+    pub snapshot: Snapshot,
+    pub message: Option<(libp2p::floodsub::Topic, WireMessage, WireFormat)>,
+    /// Question the action answered, asked again on undo
+    pub question: Option<Question>,
+    pub send_at: Instant,
+}
+
+/// Actions waiting for their grace period to pass, in order they were taken
+#[derive(Debug, Default)]
+pub struct OutgoingQueue {
+    held: Vec<Held>,
+}
+
+impl OutgoingQueue {
+    pub fn hold(&mut self, held: Held) {
+        self.held.push(held);
+    }
+
+    /// Removes the latest action, undo takes it back
+    pub fn take_last(&mut self) -> Option<Held> {
+        self.held.pop()
+    }
+
+    /// Returns when the next action is due
+    pub fn next_due(&self) -> Option<Instant> {
+        self.held.iter().map(|held| held.send_at).min()
+    }
+
+    /// Removes actions whose grace period passed at given time
+    pub fn take_due(&mut self, now: Instant) -> Vec<Held> {
+        let (due, held) = std::mem::take(&mut self.held).into_iter().partition(|held| held.send_at <= now);
+        self.held = held;
+        due
+    }
+
+    /// Removes all actions, e.g. when client quits and cannot wait
+    pub fn take_all(&mut self) -> Vec<Held> {
+        std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(opponent_id: &str, send_at: Instant) -> Held {
+        let snapshot = Snapshot { turn_started: Some(send_at), invited_at: None };
+        let topic = libp2p::floodsub::Topic::new(format!("TicTacToe/me/{}", opponent_id));
+        let message = Some((topic, WireMessage::Withdrawn, WireFormat::Tagged));
+        Held { action: Action::Withdraw, opponent_id: opponent_id.to_string(), snapshot, message, question: None, send_at }
+    }
+
+    #[test]
+    fn undo_takes_back_the_latest_action_before_it_is_due() {
+        let now = Instant::now();
+        let grace = std::time::Duration::from_secs(5);
+        let mut queue = OutgoingQueue::default();
+        queue.hold(held("alice", now + grace));
+        queue.hold(held("bob", now + grace * 2));
+        assert_eq!(queue.next_due(), Some(now + grace));
+
+        assert_eq!(queue.take_last().map(|held| held.opponent_id), Some("bob".to_string()));
+        assert!(queue.take_due(now).is_empty());
+        let due: Vec<String> = queue.take_due(now + grace).into_iter().map(|held| held.opponent_id).collect();
+        assert_eq!(due, vec!["alice"]);
+        assert_eq!(queue.next_due(), None);
+        assert_eq!(queue.take_last(), None);
+    }
+}
@@ -0,0 +1,385 @@
+//! # Validation
+//!
+//! Converts raw messages from peers into typed, range checked game events.
+//! Fields are checked against the largest playmat, the game checks them against
+//! its own one.
+
+use super::protocol::{self, WireFormat, WireMessage};
+use super::referee::Verdict;
+use super::review::ReviewMessage;
+use super::seal::SealError;
+use super::behaviour::GameStatus;
+use crate::gomoku::MAX_SIZE;
+use crate::tictactoe::{Rules, Tile};
+
+/// Longest nickname in invitation, in characters
+pub const MAX_NICKNAME_CHARS: usize = 32;
+
+/// Longest message attached to invitation, in characters
+pub const MAX_INVITATION_MESSAGE_CHARS: usize = 280;
+
+/// Reason why message from peer was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvalidMessage {
+    /// Payload is not any known message
+    Malformed,
+    /// Turn coordinates are outside of playmat
+    OutOfRange(usize, usize),
+    /// Game proposal does not name any peer
+    EmptyPeerId,
+    /// Turn places no tile
+    EmptyMark,
+    /// Turn number is not any move of game
+    MoveNumber(usize),
+    /// Nickname or message of invitation is longer than allowed
+    IntroductionTooLong,
+    /// Game proposed from position which play cannot reach
+    IllegalPosition,
+    /// Game proposed on playmat which cannot be played
+    InvalidBoard(String),
+    /// Handling of the message panicked, it was skipped
+    Panicked(String),
+    /// Message is replayed or does not belong to game
+    Seal(SealError),
+    /// Verdict is not signed by referee it names
+    ForgedVerdict,
+    /// Envelope of protocol version this client does not know
+    UnsupportedVersion(u32),
+}
+
+impl InvalidMessage {
+    /// Returns true when payload could not be parsed at all
+    pub fn is_malformed(&self) -> bool {
+        *self == InvalidMessage::Malformed
+    }
+}
+
+impl std::fmt::Display for InvalidMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidMessage::Malformed => write!(f, "malformed message"),
+            InvalidMessage::OutOfRange(x, y) => write!(f, "turn ({}, {}) is out of playmat", x, y),
+            InvalidMessage::EmptyPeerId => write!(f, "game proposal without peer id"),
+            InvalidMessage::EmptyMark => write!(f, "turn without tile"),
+            InvalidMessage::MoveNumber(number) => write!(f, "turn claims move {} of game", number),
+            InvalidMessage::IntroductionTooLong => write!(f, "invitation with too long nickname or message"),
+            InvalidMessage::IllegalPosition => write!(f, "game proposed from illegal position"),
+            InvalidMessage::InvalidBoard(reason) => write!(f, "game proposed on invalid playmat: {}", reason),
+            InvalidMessage::Panicked(reason) => write!(f, "message could not be handled: {}", reason),
+            InvalidMessage::Seal(error) => write!(f, "rejected game message: {}", error),
+            InvalidMessage::ForgedVerdict => write!(f, "verdict not signed by its referee"),
+            InvalidMessage::UnsupportedVersion(version) => {
+                write!(f, "message of protocol version {}, this client speaks version {}", version, protocol::PROTOCOL_VERSION)
+            }
+        }
+    }
+}
+
+/// Counters of rejected messages
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Diagnostics {
+    pub malformed: u64,
+    pub invalid: u64,
+}
+
+impl Diagnostics {
+    /// Counts rejected message
+    pub fn record(&mut self, error: &InvalidMessage) {
+        if error.is_malformed() {
+            self.malformed += 1;
+        } else {
+            self.invalid += 1;
+        }
+    }
+}
+
+/// Parses and validates message received from peer, returns also format peer used
+pub(super) fn validate(data: &[u8]) -> Result<(GameStatus, WireFormat), InvalidMessage> {
+    let (message, format) = protocol::decode(data).ok_or_else(|| match protocol::version(data) {
+        Some(version) if !protocol::is_supported(version) => InvalidMessage::UnsupportedVersion(version),
+        _ => InvalidMessage::Malformed,
+    })?;
+    let status = match message {
+        WireMessage::Propose { sender, credentials, rules, nonce, start_at, introduction, .. } => {
+            validate_request(sender, credentials, rules, nonce, start_at, introduction)?
+        }
+        WireMessage::Answer { accept, .. } => GameStatus::Start(accept),
+        WireMessage::Turn { x, y, sent_at, mark, number, auto } => validate_turn(x, y, sent_at, mark, number, auto)?,
+        WireMessage::Nudge => GameStatus::Nudge,
+        WireMessage::Withdrawn => GameStatus::Withdrawn,
+        WireMessage::Ping { sent_at } => GameStatus::Ping(sent_at),
+        WireMessage::Pong { ping_sent_at, received_at, sent_at } => GameStatus::Pong(ping_sent_at, received_at, sent_at),
+        WireMessage::Chat { text } => GameStatus::Chat(text),
+        WireMessage::ReviewPropose => GameStatus::Review(ReviewMessage::Propose),
+        WireMessage::ReviewAnswer { accept } => GameStatus::Review(ReviewMessage::Answer(accept)),
+        WireMessage::ReviewGoto { position } => GameStatus::Review(ReviewMessage::Goto(position)),
+        WireMessage::ReviewEnd => GameStatus::Review(ReviewMessage::End),
+        WireMessage::Resume { moves } => GameStatus::Resume(moves),
+        WireMessage::Reschedule { start_at } => GameStatus::Reschedule(start_at),
+        WireMessage::Forfeit => GameStatus::Forfeit,
+        WireMessage::Resign => GameStatus::Resign,
+        WireMessage::Spectated { game, rules, moves } => validate_spectated(game, rules, moves)?,
+        WireMessage::Verdict(verdict) => validate_verdict(verdict)?,
+        WireMessage::InvalidMove { x, y, reason } => {
+            GameStatus::InvalidMove(protocol::from_wire(x, y, MAX_SIZE).ok_or(InvalidMessage::OutOfRange(x, y))?, reason)
+        }
+    };
+    Ok((status, format))
+}
+
+/// Runs handler of one message, panic is caught and turned into rejection of the message
+pub(super) fn contain<R>(handler: impl FnOnce() -> R) -> Result<R, InvalidMessage> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(handler)).map_err(|panic| {
+        let reason = match panic.downcast::<String>() {
+            Ok(reason) => *reason,
+            Err(panic) => panic.downcast_ref::<&str>().map_or("unknown panic", |reason| reason).to_string(),
+        };
+        InvalidMessage::Panicked(reason)
+    })
+}
+
+fn validate_request(
+    sender: String,
+    credentials: Option<protocol::Credentials>,
+    rules: Rules,
+    nonce: Option<String>,
+    start_at: Option<u64>,
+    introduction: protocol::Introduction,
+) -> Result<GameStatus, InvalidMessage> {
+    if sender.trim().is_empty() {
+        return Err(InvalidMessage::EmptyPeerId);
+    }
+    let too_long = |text: &Option<String>, limit: usize| text.as_ref().is_some_and(|text| text.chars().count() > limit);
+    if too_long(&introduction.nickname, MAX_NICKNAME_CHARS) || too_long(&introduction.message, MAX_INVITATION_MESSAGE_CHARS) {
+        return Err(InvalidMessage::IntroductionTooLong);
+    }
+    if let Some(board) = rules.board {
+        board.validated().map_err(InvalidMessage::InvalidBoard)?;
+        // set up positions are 3x3
+        if rules.from_position.is_some() {
+            return Err(InvalidMessage::InvalidBoard("set up position on other playmat".to_string()));
+        }
+    }
+    if let Some(position) = &rules.from_position {
+        crate::setup::validate(position, rules).map_err(|_| InvalidMessage::IllegalPosition)?;
+    }
+    Ok(GameStatus::Init(sender, credentials, rules, nonce, start_at, introduction))
+}
+
+fn validate_turn(
+    x: usize,
+    y: usize,
+    sent_at: Option<u64>,
+    mark: Option<Tile>,
+    number: Option<usize>,
+    auto: bool,
+) -> Result<GameStatus, InvalidMessage> {
+    let (row, col) = protocol::from_wire(x, y, MAX_SIZE).ok_or(InvalidMessage::OutOfRange(x, y))?;
+    if mark == Some(Tile::Empty) {
+        return Err(InvalidMessage::EmptyMark);
+    }
+    if let Some(number) = number.filter(|number| !(1..=MAX_SIZE * MAX_SIZE).contains(number)) {
+        return Err(InvalidMessage::MoveNumber(number));
+    }
+    Ok(GameStatus::Turn(row, col, sent_at, mark, number, auto))
+}
+
+fn validate_spectated(game: String, rules: Rules, moves: Vec<protocol::SpectatedMove>) -> Result<GameStatus, InvalidMessage> {
+    // spectators get set up tiles as the first moves
+    if rules.from_position.is_some() {
+        return Err(InvalidMessage::InvalidBoard("spectated game is not played from empty playmat".to_string()));
+    }
+    let size = rules.board_size().validated().map_err(InvalidMessage::InvalidBoard)?.size;
+    if moves.len() > size * size {
+        return Err(InvalidMessage::MoveNumber(moves.len()));
+    }
+    let mut fields = Vec::with_capacity(moves.len());
+    for protocol::SpectatedMove { x, y, mark } in moves {
+        let field = protocol::from_wire(x, y, size).ok_or(InvalidMessage::OutOfRange(x, y))?;
+        if mark == Tile::Empty {
+            return Err(InvalidMessage::EmptyMark);
+        }
+        fields.push((field, mark));
+    }
+    Ok(GameStatus::Spectated(game, rules, fields))
+}
+
+fn validate_verdict(verdict: Verdict) -> Result<GameStatus, InvalidMessage> {
+    if verdict.moves.len() > MAX_SIZE * MAX_SIZE {
+        return Err(InvalidMessage::MoveNumber(verdict.moves.len()));
+    }
+    for timed in &verdict.moves {
+        protocol::from_wire(timed.x, timed.y, MAX_SIZE).ok_or(InvalidMessage::OutOfRange(timed.x, timed.y))?;
+        if timed.mark == Tile::Empty {
+            return Err(InvalidMessage::EmptyMark);
+        }
+    }
+    if !verdict.verify() {
+        return Err(InvalidMessage::ForgedVerdict);
+    }
+    Ok(GameStatus::Verdict(verdict))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_turn() {
+        let status = validate(br#"{"x":2,"y":0}"#);
+        assert!(matches!(status, Ok((GameStatus::Turn(2, 0, None, None, None, false), WireFormat::Legacy))));
+    }
+
+    #[test]
+    fn accepts_tagged_turn() {
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"sent_at":5}}"#);
+        assert!(matches!(status, Ok((GameStatus::Turn(0, 1, Some(5), None, None, false), WireFormat::Tagged))));
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"auto":true}}"#);
+        assert!(matches!(status, Ok((GameStatus::Turn(0, 1, None, None, None, true), WireFormat::Tagged))));
+    }
+
+    #[test]
+    fn rejects_unknown_version() {
+        let status = validate(br#"{"version":7,"message":{"type":"turn","x":0,"y":1}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::UnsupportedVersion(7)));
+        let status = validate(br#"{"version":2,"message":{"type":"teleport"}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::Malformed));
+    }
+
+    #[test]
+    fn accepts_rejection_of_my_turn() {
+        let status = validate(br#"{"version":2,"message":{"type":"invalid_move","x":2,"y":1,"reason":"occupied_field"}}"#);
+        assert!(matches!(status, Ok((GameStatus::InvalidMove((2, 1), protocol::MoveRejection::OccupiedField), _))));
+        let status = validate(br#"{"version":2,"message":{"type":"invalid_move","x":19,"y":1,"reason":"not_your_turn"}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::OutOfRange(19, 1)));
+    }
+
+    #[test]
+    fn accepts_wild_turn() {
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"mark":"cross"}}"#);
+        assert!(matches!(status, Ok((GameStatus::Turn(0, 1, None, Some(Tile::Cross), None, false), WireFormat::Tagged))));
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"mark":"empty"}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::EmptyMark));
+    }
+
+    #[test]
+    fn checks_move_number() {
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"number":3}}"#);
+        assert!(matches!(status, Ok((GameStatus::Turn(0, 1, None, None, Some(3), false), WireFormat::Tagged))));
+        let status = validate(br#"{"version":2,"message":{"type":"turn","x":0,"y":1,"number":0}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::MoveNumber(0)));
+    }
+
+    #[test]
+    fn rejects_turn_out_of_range() {
+        let status = validate(br#"{"x":5,"y":19}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::OutOfRange(5, 19)));
+        // field of larger playmat is checked by the game itself
+        assert!(matches!(validate(br#"{"x":5,"y":9}"#), Ok((GameStatus::Turn(5, 9, ..), _))));
+    }
+
+    #[test]
+    fn accepts_nudge() {
+        assert!(matches!(validate(br#"{"nudge":true}"#), Ok((GameStatus::Nudge, _))));
+    }
+
+    #[test]
+    fn accepts_resume() {
+        let status = validate(br#"{"version":2,"message":{"type":"resume","moves":4}}"#);
+        assert!(matches!(status, Ok((GameStatus::Resume(4), WireFormat::Tagged))));
+    }
+
+    #[test]
+    fn accepts_scheduled_invitation() {
+        let status = validate(br#"{"version":2,"message":{"type":"propose","sender":"peer","start_at":1709993100000}}"#);
+        assert!(matches!(status, Ok((GameStatus::Init(_, None, _, None, Some(1_709_993_100_000), _), WireFormat::Tagged))));
+        let status = validate(br#"{"version":2,"message":{"type":"reschedule","start_at":5}}"#);
+        assert!(matches!(status, Ok((GameStatus::Reschedule(5), WireFormat::Tagged))));
+    }
+
+    #[test]
+    fn limits_introduction() {
+        let status = validate(br#"{"version":2,"message":{"type":"propose","sender":"peer","introduction":{"nickname":"alice","message":"hi"}}}"#);
+        assert!(matches!(status, Ok((GameStatus::Init(.., introduction), _)) if introduction.nickname.as_deref() == Some("alice")));
+        let message = format!(
+            r#"{{"version":2,"message":{{"type":"propose","sender":"peer","introduction":{{"message":"{}"}}}}}}"#,
+            "a".repeat(MAX_INVITATION_MESSAGE_CHARS + 1)
+        );
+        assert_eq!(validate(message.as_bytes()).err(), Some(InvalidMessage::IntroductionTooLong));
+    }
+
+    #[test]
+    fn rejects_unreachable_start_position() {
+        let propose = |row: &str| {
+            format!(r#"{{"version":2,"message":{{"type":"propose","sender":"peer","rules":{{"from_position":[{},["empty","empty","empty"],["empty","empty","empty"]]}}}}}}"#, row)
+        };
+        let status = validate(propose(r#"["cross","empty","empty"]"#).as_bytes());
+        assert!(matches!(status, Ok((GameStatus::Init(_, _, rules, ..), _)) if rules.from_position.is_some()));
+        assert_eq!(validate(propose(r#"["circle","circle","empty"]"#).as_bytes()).err(), Some(InvalidMessage::IllegalPosition));
+    }
+
+    #[test]
+    fn carries_board_size_in_rules() {
+        let propose = |size: usize| {
+            format!(r#"{{"version":2,"message":{{"type":"propose","sender":"peer","rules":{{"board":{{"size":{},"line":5}}}}}}}}"#, size)
+        };
+        let status = validate(propose(10).as_bytes());
+        assert!(matches!(status, Ok((GameStatus::Init(_, _, rules, ..), _)) if rules.board == Some(crate::gomoku::BoardSize::GOMOKU)));
+        assert!(matches!(validate(propose(40).as_bytes()).err(), Some(InvalidMessage::InvalidBoard(_))));
+    }
+
+    #[test]
+    fn checks_spectated_moves() {
+        let status = validate(br#"{"version":2,"message":{"type":"spectated","game":"g","moves":[{"x":1,"y":2,"mark":"cross"}]}}"#);
+        assert!(matches!(status, Ok((GameStatus::Spectated(game, _, moves), _)) if game == "g" && moves == [((1, 2), Tile::Cross)]));
+        let status = validate(br#"{"version":2,"message":{"type":"spectated","game":"g","moves":[{"x":3,"y":0,"mark":"cross"}]}}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::OutOfRange(3, 0)));
+        let status = validate(br#"{"version":2,"message":{"type":"spectated","game":"g","rules":{"board":{"size":10,"line":5}},"moves":[{"x":3,"y":0,"mark":"cross"}]}}"#);
+        assert!(matches!(status, Ok((GameStatus::Spectated(_, rules, moves), _)) if rules.board.is_some() && moves == [((3, 0), Tile::Cross)]));
+    }
+
+    #[test]
+    fn checks_verdict_signature() {
+        use super::super::referee::{Ruling, Verdict};
+        let key = libp2p::identity::Keypair::generate_ed25519();
+        let mut verdict = Verdict::sign(&key, "g", Ruling::Drawn, Vec::new()).unwrap();
+        let encoded = protocol::encode(&WireMessage::Verdict(verdict.clone()), WireFormat::Tagged);
+        assert!(matches!(validate(encoded.as_bytes()), Ok((GameStatus::Verdict(received), _)) if received == verdict));
+        verdict.ruling = Ruling::Won { winner: "referee's friend".to_string() };
+        let encoded = protocol::encode(&WireMessage::Verdict(verdict), WireFormat::Tagged);
+        assert_eq!(validate(encoded.as_bytes()).err(), Some(InvalidMessage::ForgedVerdict));
+    }
+
+    #[test]
+    fn accepts_withdrawn_invitation() {
+        assert!(matches!(validate(br#"{"withdrawn":true}"#), Ok((GameStatus::Withdrawn, _))));
+    }
+
+    #[test]
+    fn contains_panic() {
+        assert_eq!(contain(|| 1), Ok(1));
+        assert_eq!(contain(|| -> u8 { panic!("turn {} is broken", 3) }), Err(InvalidMessage::Panicked("turn 3 is broken".to_string())));
+        assert_eq!(contain(|| -> u8 { panic!("broken") }), Err(InvalidMessage::Panicked("broken".to_string())));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(validate(b"not a json").err(), Some(InvalidMessage::Malformed));
+        assert_eq!(validate(br#"{"x":"A"}"#).err(), Some(InvalidMessage::Malformed));
+    }
+
+    #[test]
+    fn rejects_proposal_without_peer() {
+        let status = validate(br#"{"sender":" "}"#);
+        assert_eq!(status.err(), Some(InvalidMessage::EmptyPeerId));
+    }
+
+    #[test]
+    fn counts_rejected_messages() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.record(&InvalidMessage::Malformed);
+        diagnostics.record(&InvalidMessage::OutOfRange(3, 3));
+        diagnostics.record(&InvalidMessage::EmptyPeerId);
+        assert_eq!(diagnostics, Diagnostics { malformed: 1, invalid: 2 });
+    }
+}
@@ -0,0 +1,139 @@
+//! # Webhook
+//!
+//! Tells outside service that opponent moved in correspondence game while I was
+//! away. Notification is posted as JSON to plain HTTP endpoint, built only with
+//! `webhook` feature.
+
+use crate::tictactoe::Tile;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[derive(Debug)]
+pub enum WebhookError {
+    /// Only http://host[:port][/path] endpoints are supported
+    InvalidUrl(String),
+    Io(std::io::Error),
+    /// Endpoint answered with other status than 2xx
+    Rejected(String),
+}
+
+impl std::fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookError::InvalidUrl(url) => write!(f, "unsupported webhook url '{}', use http://host[:port]/path", url),
+            WebhookError::Io(err) => write!(f, "cannot reach webhook: {}", err),
+            WebhookError::Rejected(status) => write!(f, "webhook rejected notification: {}", status),
+        }
+    }
+}
+
+/// Opponent's move in one of my games
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct MoveNotification {
+    /// Topic of the game, it stays the same across restarts
+    pub game_id: String,
+    pub opponent_id: String,
+    /// Playmat after the move, one line per row
+    pub board: String,
+}
+
+impl MoveNotification {
+    pub fn new<Row: AsRef<[Tile]>>(game_id: &str, opponent_id: &str, grid: &[Row]) -> MoveNotification {
+        MoveNotification {
+            game_id: game_id.to_string(),
+            opponent_id: opponent_id.to_string(),
+            board: render(grid),
+        }
+    }
+}
+
+/// Renders playmat as plain text, mail and chat clients show it in monospace
+pub fn render<Row: AsRef<[Tile]>>(grid: &[Row]) -> String {
+    grid
+        .iter()
+        .map(|row| {
+            row.as_ref()
+                .iter()
+                .map(|tile| match tile {
+                    Tile::Cross => 'X',
+                    Tile::Circle => 'O',
+                    Tile::Empty => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Endpoint receiving notifications
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Webhook {
+    pub fn parse(url: &str) -> Result<Webhook, WebhookError> {
+        let invalid = || WebhookError::InvalidUrl(url.to_string());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Webhook { host: host.to_string(), port, path: path.to_string() })
+    }
+
+    /// Returns HTTP request posting the notification
+    fn request(&self, notification: &MoveNotification) -> String {
+        let body = serde_json::to_string(notification).expect("cannot jsonify notification");
+        format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            body.len(),
+            body
+        )
+    }
+
+    pub async fn send(&self, notification: &MoveNotification) -> Result<(), WebhookError> {
+        let mut stream = tokio::net::TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(WebhookError::Io)?;
+        stream.write_all(self.request(notification).as_bytes()).await.map_err(WebhookError::Io)?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.map_err(WebhookError::Io)?;
+        let status = response.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(WebhookError::Rejected(status.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_board_to_endpoint() {
+        let webhook = Webhook::parse("http://localhost:8080/hooks/tictactoe").unwrap();
+        assert_eq!(webhook, Webhook { host: "localhost".to_string(), port: 8080, path: "/hooks/tictactoe".to_string() });
+        assert!(Webhook::parse("https://example.com").is_err());
+
+        let mut state = [[Tile::Empty; 3]; 3];
+        state[1][1] = Tile::Cross;
+        state[0][2] = Tile::Circle;
+        let request = webhook.request(&MoveNotification::new("game", "peer", &state));
+        assert!(request.starts_with("POST /hooks/tictactoe HTTP/1.1\r\nHost: localhost\r\n"));
+        assert!(request.ends_with(r#""board":"..O\n.X.\n..."}"#));
+    }
+}
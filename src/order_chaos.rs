@@ -0,0 +1,218 @@
+//! # Order and Chaos
+//!
+//! Asymmetric variant on 6x6 playmat. Both players place either symbol, Order
+//! wants five in a row of one symbol and Chaos wants to fill the playmat without
+//! such a line. Order moves first.
+
+use crate::game::Game;
+use crate::tictactoe::Tile;
+
+/// Number of rows and columns on playmat
+pub const SIZE: usize = 6;
+
+/// Length of line Order needs
+const LINE: usize = 5;
+
+/// Represents 6x6 playmat
+pub type State = [[Tile; SIZE]; SIZE];
+
+/// Side of player, assigned when game is proposed
+#[derive(Copy, Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Order,
+    Chaos,
+}
+
+impl Role {
+    /// Returns role of the other player
+    pub fn other(&self) -> Role {
+        match self {
+            Role::Order => Role::Chaos,
+            Role::Chaos => Role::Order,
+        }
+    }
+}
+
+/// Turn together with chosen symbol
+#[derive(Copy, Clone, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct OrderChaosMove {
+    pub x: usize,
+    pub y: usize,
+    pub mark: Tile,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum OrderChaosError {
+    InvalidValue,
+    OccupiedField,
+    /// Only cross or circle can be placed
+    EmptyMark,
+    NotYourTurn,
+    Finished,
+}
+
+impl std::fmt::Display for OrderChaosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderChaosError::InvalidValue => write!(f, "field is out of playmat"),
+            OrderChaosError::OccupiedField => write!(f, "field is already occupied"),
+            OrderChaosError::EmptyMark => write!(f, "turn has to place cross or circle"),
+            OrderChaosError::NotYourTurn => write!(f, "it is not your turn"),
+            OrderChaosError::Finished => write!(f, "game is over"),
+        }
+    }
+}
+
+/// Order and Chaos game from point of view of one player
+#[derive(Clone, Debug)]
+pub struct OrderChaos {
+    state: State,
+    role: Role,
+    turns: usize,
+    /// Set once Order completes a line
+    line_completed: bool,
+}
+
+impl OrderChaos {
+    /// Creates new game where I play given role
+    pub fn new(role: Role) -> OrderChaos {
+        OrderChaos {
+            state: [[Tile::Empty; SIZE]; SIZE],
+            role,
+            turns: 0,
+            line_completed: false,
+        }
+    }
+
+    /// Returns my role
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Returns current state of playmat
+    pub fn get_state(&self) -> State {
+        self.state
+    }
+
+    /// Returns role which plays next turn
+    pub fn role_on_turn(&self) -> Role {
+        if self.turns % 2 == 0 {
+            Role::Order
+        } else {
+            Role::Chaos
+        }
+    }
+
+    fn winner(&self) -> Option<Role> {
+        if self.line_completed {
+            Some(Role::Order)
+        } else if self.turns == SIZE * SIZE {
+            Some(Role::Chaos)
+        } else {
+            None
+        }
+    }
+
+    fn play(&mut self, role: Role, turn: OrderChaosMove) -> Result<(), OrderChaosError> {
+        let OrderChaosMove { x, y, mark } = turn;
+        if self.winner().is_some() {
+            return Err(OrderChaosError::Finished);
+        }
+        if role != self.role_on_turn() {
+            return Err(OrderChaosError::NotYourTurn);
+        }
+        if x >= SIZE || y >= SIZE {
+            return Err(OrderChaosError::InvalidValue);
+        }
+        if mark == Tile::Empty {
+            return Err(OrderChaosError::EmptyMark);
+        }
+        if self.state[x][y] != Tile::Empty {
+            return Err(OrderChaosError::OccupiedField);
+        }
+
+        self.state[x][y] = mark;
+        self.turns += 1;
+        self.line_completed = self.line_completed || self.completes_line(x, y);
+        Ok(())
+    }
+
+    /// Returns true when symbol in given field is part of five in a row
+    fn completes_line(&self, x: usize, y: usize) -> bool {
+        let tile = self.state[x][y];
+        let count = |dx: isize, dy: isize| {
+            (1..LINE as isize)
+                .map(|step| (x as isize + step * dx, y as isize + step * dy))
+                .take_while(|&(x, y)| {
+                    (0..SIZE as isize).contains(&x) && (0..SIZE as isize).contains(&y) && self.state[x as usize][y as usize] == tile
+                })
+                .count()
+        };
+        [(0, 1), (1, 0), (1, 1), (1, -1)]
+            .iter()
+            .any(|&(dx, dy)| 1 + count(dx, dy) + count(-dx, -dy) >= LINE)
+    }
+}
+
+impl Game for OrderChaos {
+    type Move = OrderChaosMove;
+    type Error = OrderChaosError;
+
+    fn make_my_move(&mut self, turn: OrderChaosMove) -> Result<(), OrderChaosError> {
+        self.play(self.role, turn)
+    }
+
+    fn make_opponent_move(&mut self, turn: OrderChaosMove) -> Result<(), OrderChaosError> {
+        self.play(self.role.other(), turn)
+    }
+
+    fn am_i_winner(&self) -> bool {
+        self.winner() == Some(self.role)
+    }
+
+    fn is_opponent_winner(&self) -> bool {
+        self.winner() == Some(self.role.other())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.winner().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_wins_with_five_of_either_symbol() {
+        let mut game = OrderChaos::new(Role::Order);
+        let opponent = OrderChaosMove { x: 5, y: 0, mark: Tile::Cross };
+        assert_eq!(game.make_opponent_move(opponent), Err(OrderChaosError::NotYourTurn));
+        for y in 0..LINE {
+            assert_eq!(game.make_my_move(OrderChaosMove { x: 1, y, mark: Tile::Circle }), Ok(()));
+            if !game.is_finished() {
+                let _ = game.make_opponent_move(OrderChaosMove { x: 4, y, mark: Tile::Cross });
+            }
+        }
+        assert!(game.am_i_winner());
+    }
+
+    #[test]
+    fn chaos_wins_on_full_playmat() {
+        let mut game = OrderChaos::new(Role::Chaos);
+        for x in 0..SIZE {
+            for y in 0..SIZE {
+                let mark = if (x + y / 2) % 2 == 0 { Tile::Cross } else { Tile::Circle };
+                let turn = OrderChaosMove { x, y, mark };
+                let played = match game.role_on_turn() {
+                    Role::Chaos => game.make_my_move(turn),
+                    Role::Order => game.make_opponent_move(turn),
+                };
+                assert_eq!(played, Ok(()));
+            }
+        }
+        assert!(game.am_i_winner());
+        assert!(!game.is_opponent_winner());
+    }
+}
@@ -0,0 +1,231 @@
+//! # Personality
+//!
+//! Named AI opponents described by data. Strength decides how often the AI
+//! searches the whole game, blunder rate how often it plays a random move, style
+//! and openings break ties among equally good moves. Built-in personalities
+//! ship with the crate, config file can add own ones or replace them.
+
+use crate::ai::{self, Evaluation};
+use crate::coords::Coordinates;
+use crate::tictactoe::{TicTacToe, Tile};
+
+/// Personalities shipped with the crate
+const BUILTIN: &str = include_str!("personalities.json");
+
+/// Personality used when none is chosen
+pub const DEFAULT_PERSONALITY: &str = "perfect";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PersonalityError {
+    Unknown(String),
+    /// Chance is not between 0 and 1
+    InvalidChance(String, f64),
+}
+
+impl std::fmt::Display for PersonalityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PersonalityError::Unknown(name) => write!(f, "unknown personality '{}'", name),
+            PersonalityError::InvalidChance(name, chance) => {
+                write!(f, "personality '{}' has chance {} outside of 0 to 1", name, chance)
+            }
+        }
+    }
+}
+
+/// Preference among equally good moves
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    #[default]
+    Balanced,
+    /// Creates as many own threats as possible
+    Aggressive,
+    /// Leaves opponent as few threats as possible
+    Defensive,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Personality {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Chance that move comes from full search, otherwise only immediate wins and blocks are seen
+    pub strength: f64,
+    /// Chance of playing random move instead
+    #[serde(default)]
+    pub blunder_rate: f64,
+    #[serde(default)]
+    pub style: Style,
+    /// Fields preferred among equally good moves, the first free one is played
+    #[serde(default)]
+    pub openings: Vec<Coordinates>,
+}
+
+impl Personality {
+    pub fn validated(self) -> Result<Personality, PersonalityError> {
+        for chance in [self.strength, self.blunder_rate] {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(PersonalityError::InvalidChance(self.name, chance));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns my move with tile to place, none when game is over or playmat is full
+    pub fn choose_move(&self, game: &TicTacToe, rng: &mut Rng) -> Option<(Coordinates, Tile)> {
+        let scored = ai::scored_moves(game);
+        if scored.is_empty() {
+            return None;
+        }
+        if rng.chance(self.blunder_rate) {
+            let (field, mark, _) = scored[rng.below(scored.len())];
+            return Some((field, mark));
+        }
+
+        let candidates: Vec<(Coordinates, Tile)> = if rng.chance(self.strength) {
+            let best = scored.iter().map(|(_, _, evaluation)| *evaluation).max().unwrap_or(Evaluation::Draw);
+            scored.iter().filter(|(_, _, evaluation)| *evaluation == best).map(|(field, mark, _)| (*field, *mark)).collect()
+        } else {
+            shallow_moves(game, scored.iter().map(|(field, mark, _)| (*field, *mark)).collect())
+        };
+
+        let preferred = self.by_style(game, candidates);
+        let opening = self.openings.iter().find_map(|opening| preferred.iter().find(|(field, _)| field == opening));
+        opening.copied().or_else(|| preferred.get(rng.below(preferred.len())).copied())
+    }
+
+    /// Keeps moves which suit style the most
+    fn by_style(&self, game: &TicTacToe, candidates: Vec<(Coordinates, Tile)>) -> Vec<(Coordinates, Tile)> {
+        let score = |&((x, y), mark): &(Coordinates, Tile)| -> i64 {
+            let mut next = game.clone();
+            let _ = next.make_my_mark(x, y, mark);
+            match self.style {
+                Style::Balanced => 0,
+                Style::Aggressive => next.my_marks().into_iter().map(|tile| next.winning_moves(tile).len() as i64).sum(),
+                Style::Defensive => -(next.opponent_winning_moves().len() as i64),
+            }
+        };
+        let best = candidates.iter().map(score).max().unwrap_or_default();
+        candidates.into_iter().filter(|candidate| score(candidate) == best).collect()
+    }
+}
+
+/// Moves seen without search: win now, otherwise block opponent's win, otherwise anything
+fn shallow_moves(game: &TicTacToe, moves: Vec<(Coordinates, Tile)>) -> Vec<(Coordinates, Tile)> {
+    let winning: Vec<_> = moves
+        .iter()
+        .filter(|(field, mark)| game.winning_moves(*mark).contains(field))
+        .copied()
+        .collect();
+    if !winning.is_empty() {
+        return winning;
+    }
+    let threats = game.opponent_winning_moves();
+    let blocking: Vec<_> = moves.iter().filter(|(field, _)| threats.contains(field)).copied().collect();
+    if blocking.is_empty() {
+        moves
+    } else {
+        blocking
+    }
+}
+
+/// Returns built-in personalities with custom ones added, custom one replaces built-in of the same name
+pub fn all(custom: &[Personality]) -> Result<Vec<Personality>, PersonalityError> {
+    let builtin: Vec<Personality> = serde_json::from_str(BUILTIN).expect("built-in personalities are valid");
+    let mut personalities: Vec<Personality> = builtin
+        .into_iter()
+        .filter(|personality| custom.iter().all(|own| own.name != personality.name))
+        .collect();
+    for personality in custom {
+        personalities.push(personality.clone().validated()?);
+    }
+    Ok(personalities)
+}
+
+/// Finds personality by name among built-in and custom ones
+pub fn find(name: &str, custom: &[Personality]) -> Result<Personality, PersonalityError> {
+    all(custom)?
+        .into_iter()
+        .find(|personality| personality.name == name)
+        .ok_or_else(|| PersonalityError::Unknown(name.to_string()))
+}
+
+/// Small xorshift generator, AI needs no cryptographic randomness
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Rng {
+        Rng(seed.max(1))
+    }
+
+    /// Returns generator seeded by current time
+    pub fn from_time() -> Rng {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(1, |since| since.as_nanos() as u64);
+        Rng::seeded(nanos)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns number lower than given bound, which must not be zero
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    /// Returns true with given probability
+    pub fn chance(&mut self, probability: f64) -> bool {
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_personalities_differ() {
+        let personalities = all(&[]).unwrap();
+        let names: Vec<&str> = personalities.iter().map(|personality| personality.name.as_str()).collect();
+        assert_eq!(names, vec!["perfect", "aggressive", "defensive", "random-ish"]);
+
+        let mut rng = Rng::seeded(7);
+        let perfect = find(DEFAULT_PERSONALITY, &[]).unwrap();
+        assert_eq!(perfect.choose_move(&TicTacToe::new(), &mut rng), Some(((1, 1), Tile::Circle)));
+
+        // opponent threatens the top row, perfect personality always blocks
+        let mut game = TicTacToe::new();
+        let _ = game.make_opponent_turn(0, 0);
+        let _ = game.make_my_turn(2, 2);
+        let _ = game.make_opponent_turn(0, 1);
+        for _ in 0..20 {
+            assert_eq!(perfect.choose_move(&game, &mut rng), Some(((0, 2), Tile::Circle)));
+        }
+    }
+
+    #[test]
+    fn custom_personality_replaces_builtin() {
+        let custom = Personality {
+            name: "perfect".to_string(),
+            description: String::new(),
+            strength: 1.0,
+            blunder_rate: 0.0,
+            style: Style::Balanced,
+            openings: vec![(0, 0)],
+        };
+        let mut rng = Rng::seeded(7);
+        let found = find("perfect", std::slice::from_ref(&custom)).unwrap();
+        assert_eq!(found.choose_move(&TicTacToe::new(), &mut rng), Some(((0, 0), Tile::Circle)));
+        assert_eq!(find("nobody", &[]), Err(PersonalityError::Unknown("nobody".to_string())));
+        let invalid = Personality { strength: 2.0, ..custom };
+        assert!(matches!(find("perfect", &[invalid]), Err(PersonalityError::InvalidChance(_, _))));
+    }
+}
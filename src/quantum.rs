@@ -0,0 +1,399 @@
+//! # Quantum
+//!
+//! Quantum tic tac toe. Every turn puts one spooky mark into two fields at once,
+//! marks sharing a field are entangled. When a turn closes a cycle of entangled
+//! marks, the other player chooses which of its two fields the closing mark takes
+//! and the cycle, together with marks hanging on it, collapses into classical marks.
+//!
+//! Turns are written as two fields, e.g. `turn A1 B2`, collapse as `collapse A1`.
+
+use crate::coords::{Coordinates, CoordinatesError, Labels, SIZE};
+use crate::game::Game;
+use crate::theme::Theme;
+use crate::tictactoe::{Marks, Tile};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuantumError {
+    InvalidValue,
+    /// Both halves of spooky mark are in one field
+    SameField,
+    /// Field already holds classical mark
+    ClassicalField,
+    /// Cycle has to be collapsed before next turn
+    CollapsePending,
+    /// There is no cycle to collapse or the other player chooses
+    NotYourCollapse,
+    /// Closing mark cannot collapse into given field
+    NotInCycle,
+    Finished,
+}
+
+impl std::fmt::Display for QuantumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuantumError::InvalidValue => write!(f, "field is out of playmat"),
+            QuantumError::SameField => write!(f, "spooky mark needs two different fields"),
+            QuantumError::ClassicalField => write!(f, "field already holds classical mark"),
+            QuantumError::CollapsePending => write!(f, "cycle has to be collapsed first"),
+            QuantumError::NotYourCollapse => write!(f, "there is no cycle for you to collapse"),
+            QuantumError::NotInCycle => write!(f, "closing mark is not in that field"),
+            QuantumError::Finished => write!(f, "game is over"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Owner {
+    You,
+    Opponent,
+}
+
+impl Owner {
+    fn other(&self) -> Owner {
+        match self {
+            Owner::You => Owner::Opponent,
+            Owner::Opponent => Owner::You,
+        }
+    }
+}
+
+/// Turn of quantum game
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuantumMove {
+    /// Spooky mark in two fields
+    Spooky(Coordinates, Coordinates),
+    /// Field taken by mark which closed cycle
+    Collapse(Coordinates),
+}
+
+/// Mark of one turn, it stays in both fields until it collapses into one
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpookyMark {
+    pub owner: Owner,
+    pub tile: Tile,
+    /// Number of turn which placed the mark, counted from 1
+    pub turn: usize,
+    pub fields: [Coordinates; 2],
+    pub collapsed: Option<Coordinates>,
+}
+
+impl SpookyMark {
+    fn other_field(&self, field: Coordinates) -> Coordinates {
+        if self.fields[0] == field {
+            self.fields[1]
+        } else {
+            self.fields[0]
+        }
+    }
+}
+
+/// Content of one field
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell<'a> {
+    Classical(&'a SpookyMark),
+    /// Uncollapsed marks in the field, empty field has none
+    Superposed(Vec<&'a SpookyMark>),
+}
+
+const LINES: [[Coordinates; 3]; 8] = [
+    [(0, 0), (0, 1), (0, 2)],
+    [(1, 0), (1, 1), (1, 2)],
+    [(2, 0), (2, 1), (2, 2)],
+    [(0, 0), (1, 0), (2, 0)],
+    [(0, 1), (1, 1), (2, 1)],
+    [(0, 2), (1, 2), (2, 2)],
+    [(0, 0), (1, 1), (2, 2)],
+    [(2, 0), (1, 1), (0, 2)],
+];
+
+/// Quantum game state
+#[derive(Debug, Clone)]
+pub struct QuantumTicTacToe {
+    marks: Marks,
+    spooky: Vec<SpookyMark>,
+    /// Mark which closed cycle and waits for collapse
+    pending: Option<usize>,
+}
+
+impl QuantumTicTacToe {
+    /// Creates new game with default marks
+    pub fn new() -> QuantumTicTacToe {
+        QuantumTicTacToe::with_marks(Marks::default())
+    }
+
+    /// Creates new game with given mark assignment
+    pub fn with_marks(marks: Marks) -> QuantumTicTacToe {
+        QuantumTicTacToe { marks, spooky: Vec::new(), pending: None }
+    }
+
+    /// Returns all marks in order of turns
+    pub fn spooky_marks(&self) -> &[SpookyMark] {
+        &self.spooky
+    }
+
+    /// Returns content of given field
+    pub fn cell(&self, field: Coordinates) -> Cell<'_> {
+        match self.classical(field) {
+            Some(mark) => Cell::Classical(mark),
+            None => Cell::Superposed(
+                self.spooky
+                    .iter()
+                    .filter(|mark| mark.collapsed.is_none() && mark.fields.contains(&field))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns player who chooses how pending cycle collapses
+    pub fn collapse_chooser(&self) -> Option<Owner> {
+        self.pending.map(|index| self.spooky[index].owner.other())
+    }
+
+    /// Returns fields which closing mark of pending cycle may take
+    pub fn collapse_options(&self) -> Option<[Coordinates; 2]> {
+        self.pending.map(|index| self.spooky[index].fields)
+    }
+
+    fn play(&mut self, owner: Owner, turn: QuantumMove) -> Result<(), QuantumError> {
+        if self.is_finished() {
+            return Err(QuantumError::Finished);
+        }
+        match turn {
+            QuantumMove::Spooky(first, second) => self.place(owner, first, second),
+            QuantumMove::Collapse(field) => self.collapse(owner, field),
+        }
+    }
+
+    fn place(&mut self, owner: Owner, first: Coordinates, second: Coordinates) -> Result<(), QuantumError> {
+        if self.pending.is_some() {
+            return Err(QuantumError::CollapsePending);
+        }
+        if [first, second].iter().any(|(x, y)| *x >= SIZE || *y >= SIZE) {
+            return Err(QuantumError::InvalidValue);
+        }
+        if first == second {
+            return Err(QuantumError::SameField);
+        }
+        if self.classical(first).is_some() || self.classical(second).is_some() {
+            return Err(QuantumError::ClassicalField);
+        }
+
+        let closes_cycle = self.entangled(first, second);
+        let tile = match owner {
+            Owner::You => self.marks.you,
+            Owner::Opponent => self.marks.opponent,
+        };
+        self.spooky.push(SpookyMark {
+            owner,
+            tile,
+            turn: self.spooky.len() + 1,
+            fields: [first, second],
+            collapsed: None,
+        });
+        if closes_cycle {
+            self.pending = Some(self.spooky.len() - 1);
+        }
+        Ok(())
+    }
+
+    fn collapse(&mut self, owner: Owner, field: Coordinates) -> Result<(), QuantumError> {
+        let index = match self.pending {
+            Some(index) if self.collapse_chooser() == Some(owner) => index,
+            _ => return Err(QuantumError::NotYourCollapse),
+        };
+        if !self.spooky[index].fields.contains(&field) {
+            return Err(QuantumError::NotInCycle);
+        }
+
+        // every other mark in a taken field has to take its second field
+        let mut queue = vec![(index, field)];
+        while let Some((taken, field)) = queue.pop() {
+            if self.spooky[taken].collapsed.is_some() {
+                continue;
+            }
+            self.spooky[taken].collapsed = Some(field);
+            for (other, mark) in self.spooky.iter().enumerate() {
+                if mark.collapsed.is_none() && mark.fields.contains(&field) {
+                    queue.push((other, mark.other_field(field)));
+                }
+            }
+        }
+        self.pending = None;
+        Ok(())
+    }
+
+    /// Returns true when fields are linked through uncollapsed marks
+    fn entangled(&self, from: Coordinates, to: Coordinates) -> bool {
+        let mut seen = vec![from];
+        let mut queue = vec![from];
+        while let Some(field) = queue.pop() {
+            if field == to {
+                return true;
+            }
+            for mark in self.spooky.iter().filter(|mark| mark.collapsed.is_none() && mark.fields.contains(&field)) {
+                let next = mark.other_field(field);
+                if !seen.contains(&next) {
+                    seen.push(next);
+                    queue.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    fn classical(&self, field: Coordinates) -> Option<&SpookyMark> {
+        self.spooky.iter().find(|mark| mark.collapsed == Some(field))
+    }
+
+    /// Returns owner of completed line, when collapse completes more lines the one
+    /// whose last mark is the oldest wins
+    fn winner(&self) -> Option<Owner> {
+        LINES
+            .iter()
+            .filter_map(|line| {
+                let marks: Vec<&SpookyMark> = line.iter().filter_map(|field| self.classical(*field)).collect();
+                let owner = marks.first()?.owner;
+                if marks.len() == SIZE && marks.iter().all(|mark| mark.owner == owner) {
+                    marks.iter().map(|mark| mark.turn).max().map(|age| (age, owner))
+                } else {
+                    None
+                }
+            })
+            .min_by_key(|(age, _)| *age)
+            .map(|(_, owner)| owner)
+    }
+}
+
+impl Default for QuantumTicTacToe {
+    fn default() -> Self {
+        QuantumTicTacToe::new()
+    }
+}
+
+impl Game for QuantumTicTacToe {
+    type Move = QuantumMove;
+    type Error = QuantumError;
+
+    fn make_my_move(&mut self, turn: QuantumMove) -> Result<(), QuantumError> {
+        self.play(Owner::You, turn)
+    }
+
+    fn make_opponent_move(&mut self, turn: QuantumMove) -> Result<(), QuantumError> {
+        self.play(Owner::Opponent, turn)
+    }
+
+    fn am_i_winner(&self) -> bool {
+        self.winner() == Some(Owner::You)
+    }
+
+    fn is_opponent_winner(&self) -> bool {
+        self.winner() == Some(Owner::Opponent)
+    }
+
+    /// Game also ends when spooky mark no longer fits, the last free field stays empty
+    fn is_finished(&self) -> bool {
+        let free = (0..SIZE)
+            .flat_map(|x| (0..SIZE).map(move |y| (x, y)))
+            .filter(|field| self.classical(*field).is_none())
+            .count();
+        self.winner().is_some() || (self.pending.is_none() && free < 2)
+    }
+}
+
+/// Parses `turn <field> <field>` or `collapse <field>`, fields are written like "B2"
+pub fn parse_move(labels: &Labels, line: &str) -> Result<QuantumMove, CoordinatesError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match tokens.as_slice() {
+        ["turn", first, second] => Ok(QuantumMove::Spooky(labels.parse_field(first)?, labels.parse_field(second)?)),
+        ["collapse", field] => Ok(QuantumMove::Collapse(labels.parse_field(field)?)),
+        _ => Err(CoordinatesError::InvalidFormat),
+    }
+}
+
+/// Renders playmat, classical marks are in brackets, superposed ones are listed
+/// with numbers of turns which placed them
+pub fn render(game: &QuantumTicTacToe, theme: &Theme, labels: &Labels) -> String {
+    let cells: Vec<Vec<String>> = (0..SIZE)
+        .map(|x| {
+            (0..SIZE)
+                .map(|y| match game.cell((x, y)) {
+                    Cell::Classical(mark) => format!("[{}{}]", theme.symbol(mark.tile), mark.turn),
+                    Cell::Superposed(marks) if marks.is_empty() => theme.symbol(Tile::Empty).to_string(),
+                    Cell::Superposed(marks) => marks
+                        .iter()
+                        .map(|mark| format!("{}{}", theme.symbol(mark.tile), mark.turn))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                })
+                .collect()
+        })
+        .collect();
+
+    let width = cells.iter().flatten().map(|cell| cell.chars().count()).max().unwrap_or(1);
+    let separator = theme.grid.column_separator();
+    let header: Vec<String> = (0..SIZE).map(|y| format!("{:^width$}", labels.col(y), width = width)).collect();
+    let mut lines = vec![format!("  {}", header.join(separator))];
+    for (x, row) in cells.iter().enumerate() {
+        let fields: Vec<String> = row.iter().map(|cell| format!("{:<width$}", cell, width = width)).collect();
+        lines.push(format!("{} {}", labels.row(x), fields.join(separator)));
+
+        // row separator of theme is drawn for classic fields, it is stretched to wider ones
+        let line = theme.grid.row_separator().and_then(|line| line.trim().chars().next());
+        if let Some(symbol) = line.filter(|_| x + 1 < SIZE) {
+            let length = SIZE * width + (SIZE - 1) * separator.chars().count();
+            lines.push(format!("  {}", symbol.to_string().repeat(length)));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_collapses_into_classical_marks() {
+        let mut game = QuantumTicTacToe::new();
+        let _ = game.make_my_move(QuantumMove::Spooky((0, 0), (1, 1)));
+        let _ = game.make_opponent_move(QuantumMove::Spooky((1, 1), (2, 2)));
+        assert_eq!(game.collapse_chooser(), None);
+        let _ = game.make_my_move(QuantumMove::Spooky((2, 2), (0, 0)));
+
+        assert_eq!(game.collapse_chooser(), Some(Owner::Opponent));
+        assert_eq!(game.make_my_move(QuantumMove::Spooky((0, 1), (0, 2))), Err(QuantumError::CollapsePending));
+        assert_eq!(game.make_my_move(QuantumMove::Collapse((0, 0))), Err(QuantumError::NotYourCollapse));
+        assert_eq!(game.make_opponent_move(QuantumMove::Collapse((0, 0))), Ok(()));
+
+        // third mark takes A1, first one moves to B2 and second one to C3
+        let turns: Vec<(usize, Option<Coordinates>)> = game.spooky_marks().iter().map(|mark| (mark.turn, mark.collapsed)).collect();
+        assert_eq!(turns, vec![(1, Some((1, 1))), (2, Some((2, 2))), (3, Some((0, 0)))]);
+        assert_eq!(game.make_opponent_move(QuantumMove::Spooky((0, 0), (0, 1))), Err(QuantumError::ClassicalField));
+    }
+
+    #[test]
+    fn older_line_wins() {
+        let mut game = QuantumTicTacToe::new();
+        for (first, second) in [((0, 0), (0, 1)), ((2, 0), (2, 1)), ((0, 1), (0, 2)), ((2, 1), (2, 2)), ((0, 2), (0, 0))] {
+            let turn = QuantumMove::Spooky(first, second);
+            let _ = if game.spooky_marks().len() % 2 == 0 { game.make_my_move(turn) } else { game.make_opponent_move(turn) };
+        }
+        let _ = game.make_opponent_move(QuantumMove::Collapse((0, 0)));
+
+        assert!(game.am_i_winner());
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn parses_and_renders_turns() {
+        let labels = Labels::default();
+        assert!(matches!(parse_move(&labels, "turn A1 b2"), Ok(QuantumMove::Spooky((0, 0), (1, 1)))));
+        assert!(matches!(parse_move(&labels, "collapse C3"), Ok(QuantumMove::Collapse((2, 2)))));
+        assert!(matches!(parse_move(&labels, "turn A1"), Err(CoordinatesError::InvalidFormat)));
+
+        let mut game = QuantumTicTacToe::new();
+        let _ = game.make_my_move(QuantumMove::Spooky((0, 0), (1, 1)));
+        let rendered = render(&game, &Theme::classic(), &labels);
+        assert!(rendered.lines().nth(1).unwrap().starts_with("A O1"));
+    }
+}
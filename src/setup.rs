@@ -0,0 +1,119 @@
+//! # Setup
+//!
+//! Positions put together by hand instead of played, e.g. to analyze them or to
+//! start game from them. Position is legal when play could reach it: crosses
+//! move first and players alternate, so in standard variant there are as many
+//! circles as crosses or one cross more. Position with line or full playmat has
+//! nothing left to play and is refused too.
+
+use crate::coords::{Coordinates, SIZE};
+use crate::game::Game;
+use crate::tictactoe::{GameError, Marks, Rules, State, TicTacToe, Tile, Variant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SetupError {
+    /// Numbers of crosses and circles which alternating turns cannot leave
+    Counts(usize, usize),
+    /// Position has line or no free field
+    Finished,
+}
+
+impl std::fmt::Display for SetupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetupError::Counts(crosses, circles) => {
+                write!(f, "{} crosses and {} circles, crosses move first and players alternate", crosses, circles)
+            }
+            SetupError::Finished => write!(f, "game is already over in this position"),
+        }
+    }
+}
+
+/// Returns placed tiles, row by row
+fn placed(position: &State) -> Vec<(Coordinates, Tile)> {
+    (0..SIZE)
+        .flat_map(|x| (0..SIZE).map(move |y| ((x, y), position[x][y])))
+        .filter(|(_, tile)| *tile != Tile::Empty)
+        .collect()
+}
+
+/// Returns true when player who moved first in the game moves next
+pub fn first_to_move(position: &State) -> bool {
+    placed(position).len() % 2 == 0
+}
+
+/// Plays tiles of position into new game as alternating turns, crosses first.
+/// Any order gives the same game, as position has no line in any of them.
+pub fn play_out(game: &mut TicTacToe, position: &State) -> Result<(), GameError> {
+    let tiles = placed(position);
+    let order = if game.rules().variant == Variant::Standard {
+        let (crosses, circles): (Vec<_>, Vec<_>) = tiles.into_iter().partition(|(_, tile)| *tile == Tile::Cross);
+        let mut circles = circles.into_iter();
+        let mut order = Vec::new();
+        for cross in crosses {
+            order.push(cross);
+            order.extend(circles.next());
+        }
+        order.extend(circles);
+        order
+    } else {
+        tiles
+    };
+
+    let i_move_first = game.marks().you == Tile::Cross;
+    for (index, ((x, y), tile)) in order.into_iter().enumerate() {
+        if (index % 2 == 0) == i_move_first {
+            game.make_my_mark(x, y, tile)?;
+        } else {
+            game.make_opponent_mark(x, y, tile)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that position can be reached and played on under given rules
+pub fn validate(position: &State, rules: Rules) -> Result<(), SetupError> {
+    game_to_move(position, rules).map(|_| ())
+}
+
+/// Returns game in legal position seen by player who moves next
+pub fn game_to_move(position: &State, rules: Rules) -> Result<TicTacToe, SetupError> {
+    let tiles = placed(position);
+    let crosses = tiles.iter().filter(|(_, tile)| *tile == Tile::Cross).count();
+    let circles = tiles.len() - crosses;
+    if rules.variant == Variant::Standard && crosses != circles && crosses != circles + 1 {
+        return Err(SetupError::Counts(crosses, circles));
+    }
+
+    let marks = if first_to_move(position) { Marks::default().swapped() } else { Marks::default() };
+    let mut game = TicTacToe::with_rules(marks, Rules { from_position: None, ..rules });
+    if play_out(&mut game, position).is_err() || game.is_finished() {
+        return Err(SetupError::Finished);
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_unreachable_positions() {
+        let (x, o, e) = (Tile::Cross, Tile::Circle, Tile::Empty);
+        let rules = Rules::default();
+        assert_eq!(validate(&[[o, e, e], [e, e, e], [e, e, e]], rules), Err(SetupError::Counts(0, 1)));
+        assert_eq!(validate(&[[x, x, e], [e, e, e], [e, e, e]], rules), Err(SetupError::Counts(2, 0)));
+        assert_eq!(validate(&[[x, x, x], [o, o, e], [e, e, e]], rules), Err(SetupError::Finished));
+        let wild = Rules { variant: Variant::Wild, ..rules };
+        assert_eq!(validate(&[[x, x, e], [e, e, e], [e, e, e]], wild), Ok(()));
+
+        // circles to move, only center holds draw
+        let position = [[x, e, e], [e, e, e], [e, e, e]];
+        let game = game_to_move(&position, rules).unwrap();
+        assert!(!first_to_move(&position));
+        assert_eq!(game.get_state(), position);
+        assert_eq!(game.marks().you, Tile::Circle);
+        #[cfg(feature = "ai")]
+        assert_eq!(crate::ai::best_move(&game).map(|(field, _)| field), Some((1, 1)));
+    }
+}
@@ -0,0 +1,203 @@
+//! # Simulate
+//!
+//! Headless games between two players, e.g. to check that a change of AI or new
+//! variant keeps the balance. Games run in parallel threads, each with own random
+//! generator, and only totals are reported.
+
+use crate::ai;
+use crate::coords::Coordinates;
+use crate::personality::{self, Personality, PersonalityError, Rng};
+use crate::tictactoe::{Marks, TicTacToe, Tile};
+
+/// Side choosing moves in game without user
+pub trait Player: Send {
+    fn name(&self) -> &str;
+    /// Returns move for game seen from the player's side, none when no move is left
+    fn choose_move(&mut self, game: &TicTacToe, rng: &mut Rng) -> Option<(Coordinates, Tile)>;
+}
+
+/// Plays any free field with any allowed tile
+#[derive(Debug, Clone, Default)]
+pub struct RandomPlayer;
+
+impl Player for RandomPlayer {
+    fn name(&self) -> &str {
+        "random"
+    }
+
+    fn choose_move(&mut self, game: &TicTacToe, rng: &mut Rng) -> Option<(Coordinates, Tile)> {
+        let fields = ai::free_fields(game);
+        let marks = game.my_marks();
+        if fields.is_empty() || marks.is_empty() {
+            return None;
+        }
+        Some((fields[rng.below(fields.len())], marks[rng.below(marks.len())]))
+    }
+}
+
+/// Plays the first of best moves found by full search
+#[derive(Debug, Clone, Default)]
+pub struct MinimaxPlayer;
+
+impl Player for MinimaxPlayer {
+    fn name(&self) -> &str {
+        "minimax"
+    }
+
+    fn choose_move(&mut self, game: &TicTacToe, _rng: &mut Rng) -> Option<(Coordinates, Tile)> {
+        ai::best_marked_move(game).map(|(field, mark, _)| (field, mark))
+    }
+}
+
+impl Player for Personality {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn choose_move(&mut self, game: &TicTacToe, rng: &mut Rng) -> Option<(Coordinates, Tile)> {
+        Personality::choose_move(self, game, rng)
+    }
+}
+
+/// Returns player by name, "random", "minimax" or name of personality
+pub fn player(name: &str, custom: &[Personality]) -> Result<Box<dyn Player>, PersonalityError> {
+    match name {
+        "random" => Ok(Box::new(RandomPlayer)),
+        "minimax" => Ok(Box::new(MinimaxPlayer)),
+        name => Ok(Box::new(personality::find(name, custom)?)),
+    }
+}
+
+/// Totals of simulated games, first player places crosses and moves first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub first: String,
+    pub second: String,
+    pub games: u64,
+    pub first_wins: u64,
+    pub second_wins: u64,
+    pub draws: u64,
+    /// Sum of moves of all games
+    pub moves: u64,
+}
+
+impl Report {
+    fn add(&mut self, other: &Report) {
+        self.games += other.games;
+        self.first_wins += other.first_wins;
+        self.second_wins += other.second_wins;
+        self.draws += other.draws;
+        self.moves += other.moves;
+    }
+
+    /// Returns average number of moves in game
+    pub fn average_length(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.moves as f64 / self.games as f64
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rate = |count: u64| if self.games == 0 { 0.0 } else { 100.0 * count as f64 / self.games as f64 };
+        writeln!(f, "{} games, {} (first) against {}", self.games, self.first, self.second)?;
+        writeln!(f, "{} wins: {} ({:.1} %)", self.first, self.first_wins, rate(self.first_wins))?;
+        writeln!(f, "{} wins: {} ({:.1} %)", self.second, self.second_wins, rate(self.second_wins))?;
+        writeln!(f, "draws: {} ({:.1} %)", self.draws, rate(self.draws))?;
+        write!(f, "average length: {:.2} moves", self.average_length())
+    }
+}
+
+/// Plays one game, returns true when first player won, false when second one won
+/// and number of moves
+fn play_game(first: &mut dyn Player, second: &mut dyn Player, rng: &mut Rng) -> (Option<bool>, u64) {
+    // each player sees the game from own side, first one plays crosses
+    let mut first_view = TicTacToe::with_marks(Marks::default().swapped());
+    let mut second_view = TicTacToe::with_marks(Marks::default());
+    let mut moves = 0;
+    let mut first_on_turn = true;
+    loop {
+        let turn = if first_on_turn {
+            first.choose_move(&first_view, rng)
+        } else {
+            second.choose_move(&second_view, rng)
+        };
+        let ((x, y), mark) = match turn {
+            Some(turn) => turn,
+            None => return (None, moves),
+        };
+        let (view, other_view) = if first_on_turn {
+            (&mut first_view, &mut second_view)
+        } else {
+            (&mut second_view, &mut first_view)
+        };
+        if view.make_my_mark(x, y, mark).is_err() || other_view.make_opponent_mark(x, y, mark).is_err() {
+            // player broke rules, the other one wins
+            return (Some(!first_on_turn), moves);
+        }
+        moves += 1;
+        if view.am_i_winner() {
+            return (Some(first_on_turn), moves);
+        }
+        first_on_turn = !first_on_turn;
+    }
+}
+
+/// Plays given number of games between players created by factory, spread over threads
+pub fn run<F>(games: u64, threads: usize, seed: u64, players: F) -> Report
+where
+    F: Fn() -> (Box<dyn Player>, Box<dyn Player>) + Sync,
+{
+    let threads = threads.max(1) as u64;
+    let reports: Vec<Report> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let players = &players;
+                let share = games / threads + u64::from(thread < games % threads);
+                scope.spawn(move || {
+                    let (mut first, mut second) = players();
+                    let mut rng = Rng::seeded(seed.wrapping_add(thread.wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+                    let mut report = Report::default();
+                    for _ in 0..share {
+                        let (winner, moves) = play_game(first.as_mut(), second.as_mut(), &mut rng);
+                        report.games += 1;
+                        report.moves += moves;
+                        match winner {
+                            Some(true) => report.first_wins += 1,
+                            Some(false) => report.second_wins += 1,
+                            None => report.draws += 1,
+                        }
+                    }
+                    report
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().expect("simulation thread panicked")).collect()
+    });
+
+    let (first, second) = players();
+    let mut total = Report { first: first.name().to_string(), second: second.name().to_string(), ..Report::default() };
+    reports.iter().for_each(|report| total.add(report));
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimax_never_loses_to_random() {
+        let report = run(200, 3, 11, || (Box::new(RandomPlayer), Box::new(MinimaxPlayer)));
+        assert_eq!((report.first.as_str(), report.second.as_str()), ("random", "minimax"));
+        assert_eq!(report.games, 200);
+        assert_eq!(report.first_wins, 0);
+        assert_eq!(report.first_wins + report.second_wins + report.draws, 200);
+        assert!((5.0..=9.0).contains(&report.average_length()));
+
+        let perfect = run(4, 2, 1, || (Box::new(MinimaxPlayer), Box::new(MinimaxPlayer)));
+        assert_eq!(perfect.draws, 4);
+    }
+}
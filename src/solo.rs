@@ -0,0 +1,107 @@
+//! # Solo
+//!
+//! Game against AI personality in terminal, no network is needed. Player places
+//! crosses and moves first.
+
+use crate::coords::Labels;
+use crate::game::Game;
+use crate::personality::{Personality, Rng};
+use crate::tictactoe::{TicTacToe, Tile};
+
+/// Plays one game reading player's fields from input, e.g. "B2", until it ends or player quits
+pub fn play<R: std::io::BufRead, W: std::io::Write>(
+    personality: &Personality,
+    labels: &Labels,
+    move_delay: std::time::Duration,
+    rng: &mut Rng,
+    input: R,
+    output: &mut W,
+) -> std::io::Result<()> {
+    // game is seen from AI side, player is its opponent
+    let mut game = TicTacToe::new();
+    writeln!(output, "Playing against {} ({}).", personality.name, personality.description)?;
+    render(&game, labels, output)?;
+    writeln!(output, "Your move, e.g. {}{}:", labels.row(1), labels.col(1))?;
+
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line == "quit" {
+            return Ok(());
+        }
+        let (x, y) = match labels.parse_field(line) {
+            Ok(field) => field,
+            Err(_) => {
+                writeln!(output, "Write field as row and column, e.g. {}{}, or quit.", labels.row(1), labels.col(1))?;
+                continue;
+            }
+        };
+        if game.make_opponent_turn(x, y).is_err() {
+            writeln!(output, "Field {}{} is taken.", labels.row(x), labels.col(y))?;
+            continue;
+        }
+
+        if !game.is_finished() {
+            if let Some(((x, y), mark)) = personality.choose_move(&game, rng) {
+                if !move_delay.is_zero() {
+                    writeln!(output, "{} is thinking...", personality.name)?;
+                    output.flush()?;
+                    std::thread::sleep(move_delay);
+                }
+                let _ = game.make_my_mark(x, y, mark);
+                writeln!(output, "{} plays {}{}.", personality.name, labels.row(x), labels.col(y))?;
+            }
+        }
+        render(&game, labels, output)?;
+
+        if game.is_opponent_winner() {
+            writeln!(output, "You win!")?;
+        } else if game.am_i_winner() {
+            writeln!(output, "{} wins.", personality.name)?;
+        } else if game.is_finished() {
+            writeln!(output, "Draw.")?;
+        } else {
+            writeln!(output, "Your move:")?;
+            continue;
+        }
+        return Ok(());
+    }
+    Ok(())
+}
+
+fn render<W: std::io::Write>(game: &TicTacToe, labels: &Labels, output: &mut W) -> std::io::Result<()> {
+    let cols: String = labels.cols.chars().map(|col| format!(" {}", col)).collect();
+    writeln!(output, " {}", cols)?;
+    for (x, row) in game.rows().enumerate() {
+        let tiles: String = row
+            .iter()
+            .map(|(_, tile)| match tile {
+                Tile::Cross => " X",
+                Tile::Circle => " O",
+                Tile::Empty => " .",
+            })
+            .collect();
+        writeln!(output, "{}{}", labels.row(x), tiles)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn perfect_personality_draws() {
+        let perfect = crate::personality::find("perfect", &[]).unwrap();
+        let mut output = Vec::new();
+        // player tries to take corners, AI blocks every line
+        let input = "A1\nZ9\nA1\nC3\nA3\nC1\nB3\nB1\nA2\nC2\nB2\n".as_bytes();
+        play(&perfect, &Labels::default(), std::time::Duration::ZERO, &mut Rng::seeded(3), input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Write field as row and column"));
+        assert!(output.contains("Field A1 is taken."));
+        assert!(!output.contains("You win!"));
+        assert!(output.ends_with("wins.\n") || output.ends_with("Draw.\n"));
+    }
+}
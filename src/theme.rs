@@ -0,0 +1,177 @@
+//! # Theme
+//!
+//! Maps game symbols to the strings printed by the render layer and paces how
+//! fast moves of the other side appear
+
+use crate::tictactoe::Tile;
+
+/// Style of lines drawn between playmat fields
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GridStyle {
+    Ascii,
+    Unicode,
+    Plain,
+}
+
+impl GridStyle {
+    /// Returns separator printed between two fields in a row
+    pub fn column_separator(&self) -> &'static str {
+        match self {
+            GridStyle::Ascii => " | ",
+            GridStyle::Unicode => " │ ",
+            GridStyle::Plain => "   ",
+        }
+    }
+
+    /// Returns line printed between two rows, if any
+    pub fn row_separator(&self) -> Option<&'static str> {
+        match self {
+            GridStyle::Ascii => Some("  ---------"),
+            GridStyle::Unicode => Some("  ──┼───┼──"),
+            GridStyle::Plain => None,
+        }
+    }
+
+    /// Returns line printed between two rows of playmat with given number of
+    /// columns, each field given number of characters wide
+    pub fn row_line(&self, columns: usize, width: usize) -> Option<String> {
+        match self {
+            GridStyle::Ascii => Some("-".repeat(columns * width + (columns - 1) * 3)),
+            GridStyle::Unicode => {
+                let inner = std::iter::repeat("─".repeat(width + 2)).take(columns.saturating_sub(2));
+                let fields: Vec<String> = std::iter::once("─".repeat(width + 1)).chain(inner).chain(std::iter::once("─".repeat(width + 1))).collect();
+                Some(fields.join("┼"))
+            }
+            GridStyle::Plain => None,
+        }
+    }
+}
+
+/// Theme settings as written in config, every field is optional
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: Option<String>,
+    pub cross: Option<String>,
+    pub circle: Option<String>,
+    pub empty: Option<String>,
+    pub grid: Option<GridStyle>,
+    /// Pause in milliseconds before move of opponent or AI is shown
+    pub move_delay_ms: Option<u64>,
+}
+
+/// Symbols used for rendering the playmat
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub cross: String,
+    pub circle: String,
+    pub empty: String,
+    pub grid: GridStyle,
+    /// Pause before move of opponent or AI is shown, cue is printed meanwhile
+    pub move_delay: std::time::Duration,
+}
+
+impl Theme {
+    /// Default theme with plain letters
+    pub fn classic() -> Theme {
+        Theme {
+            cross: "X".to_string(),
+            circle: "O".to_string(),
+            empty: " ".to_string(),
+            grid: GridStyle::Ascii,
+            move_delay: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Theme using emoji marks
+    pub fn emoji() -> Theme {
+        Theme {
+            cross: "❌".to_string(),
+            circle: "⭕".to_string(),
+            empty: "⬜".to_string(),
+            grid: GridStyle::Plain,
+            move_delay: std::time::Duration::ZERO,
+        }
+    }
+
+    /// Returns preset with given name
+    pub fn by_name(name: &str) -> Option<Theme> {
+        match name {
+            "classic" => Some(Theme::classic()),
+            "emoji" => Some(Theme::emoji()),
+            _ => None,
+        }
+    }
+
+    /// Builds theme from config, explicit symbols override the preset
+    pub fn from_config(config: &ThemeConfig) -> Theme {
+        let mut theme = config
+            .preset
+            .as_deref()
+            .and_then(Theme::by_name)
+            .unwrap_or_else(Theme::classic);
+
+        if let Some(cross) = &config.cross {
+            theme.cross = cross.clone();
+        }
+        if let Some(circle) = &config.circle {
+            theme.circle = circle.clone();
+        }
+        if let Some(empty) = &config.empty {
+            theme.empty = empty.clone();
+        }
+        if let Some(grid) = config.grid {
+            theme.grid = grid;
+        }
+        if let Some(millis) = config.move_delay_ms {
+            theme.move_delay = std::time::Duration::from_millis(millis);
+        }
+        theme
+    }
+
+    /// Returns string representing given tile
+    pub fn symbol(&self, tile: Tile) -> &str {
+        match tile {
+            Tile::Cross => &self.cross,
+            Tile::Circle => &self.circle,
+            Tile::Empty => &self.empty,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::classic()
+    }
+}
+
+/// Returns playmat as line of emoji per row without any grid, e.g. for chat
+/// bridges whose fonts do not keep letters and lines aligned
+pub fn emoji_board<Row: AsRef<[Tile]>>(grid: &[Row]) -> String {
+    let theme = Theme::emoji();
+    grid
+        .iter()
+        .map(|row| row.as_ref().iter().map(|tile| theme.symbol(*tile)).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_board_has_row_per_line() {
+        let (x, o, e) = (Tile::Cross, Tile::Circle, Tile::Empty);
+        assert_eq!(emoji_board(&[[x, e, e], [e, o, e], [e, e, x]]), "❌⬜⬜\n⬜⭕⬜\n⬜⬜❌");
+    }
+
+    #[test]
+    fn row_line_stretches_with_playmat() {
+        for grid in [GridStyle::Ascii, GridStyle::Unicode, GridStyle::Plain] {
+            assert_eq!(grid.row_line(3, 1).as_deref(), grid.row_separator().map(str::trim_start));
+        }
+        assert_eq!(GridStyle::Unicode.row_line(4, 2).as_deref(), Some("───┼────┼────┼───"));
+    }
+}
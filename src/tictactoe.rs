@@ -0,0 +1,653 @@
+//! # TicTacToe
+//!
+//! Library for simple tic tac toe game 
+
+
+use crate::coords::Coordinates;
+use crate::gomoku::BoardSize;
+
+/// Represents symbols on game playmat
+#[derive(Copy,Clone,PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tile {
+    Cross,
+    Circle,
+    Empty,
+}
+
+/// Represents 3x3 playmat
+pub type State = [[Tile; 3]; 3];
+
+/// Playmat of any size, rows from the top, each with its fields from the left
+pub type Grid = Vec<Vec<Tile>>;
+
+/// Playmat as frontends embedding the engine see it, the same as [`State`]
+pub type Board = State;
+
+/// Turn of classic game, field as row and column counted from 0
+pub type Move = Coordinates;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Player {
+    You,
+    Opponent,
+    Noone,
+}
+
+impl Player {
+    /// Returns player tile according to mark assignment
+    fn tile(&self, marks: &Marks) -> Tile {
+        match self {
+            Player::You => marks.you,
+            Player::Opponent => marks.opponent,
+            Player::Noone => Tile::Empty,
+        }
+    }
+}
+
+/// State of game after a turn
+#[derive(PartialEq, Debug, Clone)]
+pub enum GameResult {
+    Win(Player),
+    /// Playmat is full and nobody has a line
+    Draw,
+    InProgress,
+}
+
+/// Result of position with perfect play, from point of view of one player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Evaluation {
+    Loss,
+    Draw,
+    Win,
+}
+
+impl Evaluation {
+    /// Returns same result seen by the other player
+    pub fn flipped(&self) -> Evaluation {
+        match self {
+            Evaluation::Loss => Evaluation::Win,
+            Evaluation::Draw => Evaluation::Draw,
+            Evaluation::Win => Evaluation::Loss,
+        }
+    }
+}
+
+/// Assignment of tiles to players, set when game is created
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Marks {
+    pub you: Tile,
+    pub opponent: Tile,
+}
+
+impl Marks {
+    /// Returns assignment with players' tiles exchanged
+    pub fn swapped(&self) -> Marks {
+        Marks {
+            you: self.opponent,
+            opponent: self.you,
+        }
+    }
+}
+
+impl Default for Marks {
+    fn default() -> Self {
+        Marks {
+            you: Tile::Circle,
+            opponent: Tile::Cross,
+        }
+    }
+}
+
+/// Rule variant, both players must play the same one
+#[derive(Copy, Clone, PartialEq, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Either player places either symbol, first line of any symbol wins
+    Wild,
+}
+
+/// Rules of one game, agreed on when game is proposed
+#[derive(Copy, Clone, PartialEq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub variant: Variant,
+    /// Set up position the game starts from, its tiles count as first moves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_position: Option<State>,
+    /// Playmat other than classic 3x3, see [`crate::gomoku`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub board: Option<BoardSize>,
+}
+
+impl Variant {
+    /// Returns name used in rules handshake
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "standard",
+            Variant::Wild => "wild",
+        }
+    }
+}
+
+impl Rules {
+    pub fn is_standard(&self) -> bool {
+        self.variant == Variant::Standard && self.from_position.is_none() && self.board.is_none()
+    }
+
+    /// Returns size of playmat the game is played on
+    pub fn board_size(&self) -> BoardSize {
+        self.board.unwrap_or(BoardSize::CLASSIC)
+    }
+}
+
+pub enum CoordinateError {
+    InvalidX,
+    InvalidY,
+}
+
+pub enum GameError {
+    InvalidValue,
+    OccupiedField,
+    /// Rules do not allow player to place the tile
+    WrongMark,
+    /// Game already has a winner
+    Finished,
+}
+
+impl std::fmt::Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameError::InvalidValue => write!(f, "field is out of playmat"),
+            GameError::OccupiedField => write!(f, "field is already occupied"),
+            GameError::WrongMark => write!(f, "rules do not allow this symbol"),
+            GameError::Finished => write!(f, "game is over"),
+        }
+    }
+}
+
+/// Main structure handling game logic
+#[derive(Clone, Debug)]
+pub struct TicTacToe {
+    cells: Grid,
+    winner: Player,
+    marks: Marks,
+    rules: Rules,
+    /// Played fields in order of turns
+    moves: Vec<(usize, usize)>,
+}
+
+impl TicTacToe {
+    /// Creates new game with default marks
+    pub fn new() -> TicTacToe {
+        TicTacToe::with_marks(Marks::default())
+    }
+
+    /// Creates new game with given mark assignment
+    pub fn with_marks(marks: Marks) -> TicTacToe {
+        TicTacToe::with_rules(marks, Rules::default())
+    }
+
+    /// Creates new game with given mark assignment and rules
+    pub fn with_rules(marks: Marks, rules: Rules) -> TicTacToe {
+        TicTacToe { 
+            cells: empty_grid(rules.board_size()),
+            winner: Player::Noone,
+            marks,
+            rules,
+            moves: Vec::new(),
+         }
+    }
+
+    /// Returns mark assignment of this game
+    pub fn marks(&self) -> Marks {
+        self.marks
+    }
+
+    /// Returns rules of this game
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Returns size of playmat and length of winning line
+    pub fn board_size(&self) -> BoardSize {
+        self.rules.board_size()
+    }
+
+    /// Returns tiles I may place
+    pub fn my_marks(&self) -> Vec<Tile> {
+        self.allowed_marks(Player::You)
+    }
+
+    /// Returns tiles opponent may place
+    pub fn opponent_marks(&self) -> Vec<Tile> {
+        self.allowed_marks(Player::Opponent)
+    }
+
+    fn allowed_marks(&self, player: Player) -> Vec<Tile> {
+        match self.rules.variant {
+            Variant::Standard => vec![player.tile(&self.marks)],
+            Variant::Wild => vec![Tile::Cross, Tile::Circle],
+        }
+    }
+
+    /// Evaluates my turn
+    pub fn make_my_turn(&mut self, x: usize, y: usize) -> Result<GameResult, GameError> {
+        self.make_turn_universal(Player::You, self.marks.you, x, y)?;
+        Ok(self.result())
+    }
+
+    /// Evaluates opponent's turn
+    pub fn make_opponent_turn(&mut self, x: usize, y: usize) -> Result<GameResult, GameError> {
+        self.make_turn_universal(Player::Opponent, self.marks.opponent, x, y)?;
+        Ok(self.result())
+    }
+
+    /// Evaluates my turn placing given tile
+    pub fn make_my_mark(&mut self, x: usize, y: usize, tile: Tile) -> Result<(), GameError> {
+        self.make_turn_universal(Player::You, tile, x, y)
+    }
+
+    /// Evaluates opponent's turn placing given tile
+    pub fn make_opponent_mark(&mut self, x: usize, y: usize, tile: Tile) -> Result<(), GameError> {
+        self.make_turn_universal(Player::Opponent, tile, x, y)
+    }
+
+    fn make_turn_universal(&mut self, player : Player, tile: Tile, x: usize, y: usize) -> Result<(), GameError> {
+        if self.winner != Player::Noone {
+            return Err(GameError::Finished);
+        }
+
+        if x >= self.cells.len() || y >= self.cells.len() {
+            return Err(GameError::InvalidValue);
+        }
+
+        if self.cells[x][y] != Tile::Empty {
+            return Err(GameError::OccupiedField);
+        }
+
+        if !self.allowed_marks(player.clone()).contains(&tile) {
+            return Err(GameError::WrongMark);
+        }
+
+        let is_winning_turn = self.make_turn(tile, x, y);
+        self.moves.push((x, y));
+        if is_winning_turn {
+            self.winner = player;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true when I won
+    pub fn am_i_winner(&self) -> bool {
+        self.winner == Player::You
+    }
+
+    /// Returns true when opponent won
+    pub fn is_opponent_winner(&self) -> bool {
+        self.winner == Player::Opponent
+    }
+
+    /// Returns true when playmat is full and nobody won
+    pub fn is_draw(&self) -> bool {
+        self.result() == GameResult::Draw
+    }
+
+    pub fn result(&self) -> GameResult {
+        if self.winner != Player::Noone {
+            GameResult::Win(self.winner.clone())
+        } else if self.cells.iter().flatten().all(|tile| *tile != Tile::Empty) {
+            GameResult::Draw
+        } else {
+            GameResult::InProgress
+        }
+    }
+
+    /// Returns current state of classic playmat, larger playmat is cut to its
+    /// top-left corner, [`TicTacToe::grid`] returns all of it
+    pub fn get_state(&self) -> State {
+        std::array::from_fn(|x| std::array::from_fn(|y| self.cells[x][y]))
+    }
+
+    /// Returns current state of playmat of any size
+    pub fn grid(&self) -> Grid {
+        self.cells.clone()
+    }
+
+    /// Returns tile in given field
+    pub fn tile(&self, x: usize, y: usize) -> Tile {
+        self.cells[x][y]
+    }
+
+    /// Returns every field with its tile, row by row from the top-left one
+    pub fn iter_cells(&self) -> impl Iterator<Item = (Coordinates, Tile)> + '_ {
+        self.rows().flatten()
+    }
+
+    /// Returns rows from the top, each with its fields from the left
+    pub fn rows(&self) -> impl Iterator<Item = Vec<(Coordinates, Tile)>> + '_ {
+        let size = self.cells.len();
+        (0..size).map(move |x| (0..size).map(|y| ((x, y), self.cells[x][y])).collect())
+    }
+
+    /// Returns columns from the left, each with its fields from the top
+    pub fn cols(&self) -> impl Iterator<Item = Vec<(Coordinates, Tile)>> + '_ {
+        let size = self.cells.len();
+        (0..size).map(move |y| (0..size).map(|x| ((x, y), self.cells[x][y])).collect())
+    }
+
+    /// Returns played fields in order of turns
+    pub fn moves(&self) -> &[(usize, usize)] {
+        &self.moves
+    }
+
+    /// Returns number of turn which occupied given field, counted from 1
+    pub fn move_number(&self, x: usize, y: usize) -> Option<usize> {
+        self.moves.iter().position(|&field| field == (x, y)).map(|index| index + 1)
+    }
+
+    /// Returns empty fields where given tile would complete a line, none for
+    /// [`Tile::Empty`] which is not a player's symbol
+    pub fn winning_moves(&self, tile: Tile) -> Vec<(usize, usize)> {
+        let mut moves = Vec::new();
+        if tile == Tile::Empty {
+            return moves;
+        }
+        for x in 0..self.cells.len() {
+            for y in 0..self.cells.len() {
+                if self.cells[x][y] == Tile::Empty && self.clone().make_turn(tile, x, y) {
+                    moves.push((x, y));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Returns empty fields where opponent completes a line with any tile they may place
+    pub fn opponent_winning_moves(&self) -> Vec<(usize, usize)> {
+        let mut moves: Vec<(usize, usize)> = self.opponent_marks().into_iter().flat_map(|tile| self.winning_moves(tile)).collect();
+        moves.sort_unstable();
+        moves.dedup();
+        moves
+    }
+
+    /// Returns empty fields which complete a line for either player
+    pub fn threatened_cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = self.winning_moves(Tile::Cross);
+        cells.extend(self.winning_moves(Tile::Circle));
+        cells.sort_unstable();
+        cells.dedup();
+        cells
+    }
+
+    /// Removes last turn from playmat, returns its field. Game is not won before
+    /// its last turn, otherwise it would have ended.
+    pub fn take_back(&mut self) -> Option<(usize, usize)> {
+        let (x, y) = self.moves.pop()?;
+        self.cells[x][y] = Tile::Empty;
+        self.winner = Player::Noone;
+        Some((x, y))
+    }
+
+    /// Allows starting new game with same players
+    /// TODO - Game should be separated from players.
+    pub fn reset(&mut self) {
+        self.cells = empty_grid(self.board_size());
+        self.winner = Player::Noone;
+        self.moves.clear();
+    }
+
+    fn make_turn(&mut self, tile: Tile, x: usize, y: usize) -> bool {
+        self.cells[x][y] = tile;
+        self.check_win(tile, x, y)
+    }
+
+    /// Returns true when given tile fills line of winning length through given field
+    fn check_win(&self, tile: Tile, x: usize, y: usize) -> bool {
+        self.cells[x][y] == tile && crate::gomoku::completes_line(&self.cells, self.board_size().line, (x, y))
+    }
+}
+
+fn empty_grid(board: BoardSize) -> Grid {
+    vec![vec![Tile::Empty; board.size]; board.size]
+}
+
+/// Numbers of games won, lost and drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Score {
+    pub wins: u64,
+    pub losses: u64,
+    pub draws: u64,
+}
+
+/// Scores of games against each opponent, rematches add up
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScoreBoard {
+    scores: std::collections::BTreeMap<String, Score>,
+}
+
+impl ScoreBoard {
+    pub fn record_win(&mut self, opponent: &str) {
+        self.scores.entry(opponent.to_string()).or_default().wins += 1;
+    }
+
+    pub fn record_loss(&mut self, opponent: &str) {
+        self.scores.entry(opponent.to_string()).or_default().losses += 1;
+    }
+
+    pub fn record_draw(&mut self, opponent: &str) {
+        self.scores.entry(opponent.to_string()).or_default().draws += 1;
+    }
+
+    /// Returns score against opponent, zero when we never played
+    pub fn against(&self, opponent: &str) -> Score {
+        self.scores.get(opponent).copied().unwrap_or_default()
+    }
+
+    /// Returns score against all opponents together
+    pub fn overall(&self) -> Score {
+        self.scores.values().fold(Score::default(), |total, score| Score {
+            wins: total.wins + score.wins,
+            losses: total.losses + score.losses,
+            draws: total.draws + score.draws,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck::Arbitrary;
+
+    use super::*;
+
+    fn check_win_brute_force(state : [[Tile; 3]; 3], tile : Tile, x : usize, y : usize) -> bool{
+        // col
+        if state[0][y] == tile
+        && state[1][y] == tile
+        && state[2][y] == tile {
+            return true;
+        }
+
+        // row
+        if state[x][0] == tile
+        && state[x][1] == tile
+        && state[x][2] == tile {
+            return true;
+        }
+
+        // direct diagonal
+        if [(0,0), (1,1), (2,2)].contains(&(x,y)) 
+            && state[0][0] == tile
+            && state[1][1] == tile
+            && state[2][2] == tile {
+            return true;
+        }
+
+        
+        // undirect diagonal
+        if [(2,0), (1,1), (0,2)].contains(&(x,y))
+            && state[2][0] == tile
+            && state[1][1] == tile
+            && state[0][2] == tile {
+                return true;
+        }
+        false
+    }
+
+    impl Arbitrary for Tile {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            g.choose(&[Tile::Empty, Tile::Circle, Tile::Cross]).unwrap().to_owned()
+        }
+    }
+
+    impl Arbitrary for TicTacToe {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let mut game = TicTacToe::new();
+            for i in 0..3 {
+                for j in 0..3 {
+                    game.cells[i][j] = Tile::arbitrary(g);
+                }
+            }
+            game
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    enum Indices {
+        Zero = 0,
+        One,
+        Two
+    }
+    impl Indices {
+        pub fn get_int(&self) -> usize {
+            match self {
+                Indices::Zero => 0,
+                Indices::One => 1,
+                Indices::Two => 2,
+            }
+        }
+    }
+
+    impl Arbitrary for Indices {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            g.choose(&[Indices::Zero, Indices::One, Indices::Two]).unwrap().to_owned()
+        }
+    }
+
+    const LINES: [[(usize, usize); 3]; 8] = [
+        [(0, 0), (0, 1), (0, 2)],
+        [(1, 0), (1, 1), (1, 2)],
+        [(2, 0), (2, 1), (2, 2)],
+        [(0, 0), (1, 0), (2, 0)],
+        [(0, 1), (1, 1), (2, 1)],
+        [(0, 2), (1, 2), (2, 2)],
+        [(0, 0), (1, 1), (2, 2)],
+        [(2, 0), (1, 1), (0, 2)],
+    ];
+
+    fn winning_moves_brute_force(state : State, tile : Tile) -> Vec<(usize, usize)> {
+        let mut moves : Vec<(usize, usize)> = LINES.iter()
+            .filter(|line| line.iter().filter(|&&(x, y)| state[x][y] == tile).count() == 2)
+            .filter_map(|line| line.iter().find(|&&(x, y)| state[x][y] == Tile::Empty).copied())
+            .collect();
+        moves.sort_unstable();
+        moves.dedup();
+        moves
+    }
+
+    #[test]
+    fn wild_line_of_any_tile_wins() {
+        let mut game = TicTacToe::with_rules(Marks::default(), Rules { variant: Variant::Wild, ..Rules::default() });
+        let _ = game.make_my_mark(0, 0, Tile::Cross);
+        let _ = game.make_opponent_mark(1, 1, Tile::Circle);
+        let _ = game.make_my_mark(0, 1, Tile::Cross);
+        assert_eq!(game.opponent_winning_moves(), vec![(0, 2)]);
+        let _ = game.make_opponent_mark(0, 2, Tile::Cross);
+        assert!(game.is_opponent_winner());
+
+        let mut standard = TicTacToe::new();
+        assert!(matches!(standard.make_my_mark(0, 0, Tile::Cross), Err(GameError::WrongMark)));
+    }
+
+    #[test]
+    fn score_board_adds_up_rematches() {
+        let mut board = ScoreBoard::default();
+        board.record_win("alice");
+        board.record_win("alice");
+        board.record_draw("alice");
+        board.record_loss("bob");
+        assert_eq!(board.against("alice"), Score { wins: 2, losses: 0, draws: 1 });
+        assert_eq!(board.against("carol"), Score::default());
+        assert_eq!(board.overall(), Score { wins: 2, losses: 1, draws: 1 });
+    }
+
+    #[test]
+    fn full_playmat_without_line_is_draw() {
+        let mut game = TicTacToe::new();
+        // rows end up mine, theirs, mine / mine, theirs, theirs / theirs, mine, mine
+        let mine = [(0, 0), (0, 2), (1, 0), (2, 1), (2, 2)];
+        let theirs = [(0, 1), (1, 1), (1, 2), (2, 0)];
+        for (turn, (x, y)) in mine.iter().enumerate() {
+            assert_eq!(game.result(), GameResult::InProgress);
+            let result = game.make_my_turn(*x, *y);
+            if let Some((x, y)) = theirs.get(turn) {
+                assert!(matches!(result, Ok(GameResult::InProgress)));
+                let _ = game.make_opponent_turn(*x, *y);
+            } else {
+                assert!(matches!(result, Ok(GameResult::Draw)));
+            }
+        }
+        assert!(game.is_draw());
+    }
+
+    #[test]
+    fn remembers_move_numbers() {
+        let mut game = TicTacToe::new();
+        let _ = game.make_my_turn(1, 1);
+        let _ = game.make_opponent_turn(0, 2);
+        assert_eq!(game.move_number(0, 2), Some(2));
+        assert_eq!(game.move_number(2, 2), None);
+    }
+
+    #[test]
+    fn iterates_cells() {
+        let mut game = TicTacToe::new();
+        let _ = game.make_my_turn(0, 2);
+        let _ = game.make_opponent_turn(1, 0);
+        let cells: Vec<_> = game.iter_cells().collect();
+        assert_eq!(cells.len(), 9);
+        assert_eq!(cells[2], ((0, 2), game.marks().you));
+        assert_eq!(cells[3], ((1, 0), game.marks().opponent));
+        assert!(cells.iter().all(|&((x, y), tile)| game.get_state()[x][y] == tile));
+
+        let first_row = game.rows().next().unwrap();
+        assert_eq!(first_row.iter().map(|(field, _)| *field).collect::<Vec<_>>(), [(0, 0), (0, 1), (0, 2)]);
+        let first_col = game.cols().next().unwrap();
+        assert_eq!(first_col.iter().map(|(_, tile)| *tile).collect::<Vec<_>>(), [Tile::Empty, game.marks().opponent, Tile::Empty]);
+    }
+
+    quickcheck! {
+          fn check_win(game : TicTacToe, x : Indices, y : Indices) -> bool {
+            assert_eq!(check_win_brute_force(game.get_state(), Tile::Circle, x.get_int(), y.get_int()) ,game.clone().check_win(Tile::Circle, x.get_int(), y.get_int()));
+            true
+        }
+
+        fn check_winning_moves(game : TicTacToe) -> bool {
+            winning_moves_brute_force(game.get_state(), Tile::Cross) == game.winning_moves(Tile::Cross)
+                && winning_moves_brute_force(game.get_state(), Tile::Circle) == game.winning_moves(Tile::Circle)
+                && game.winning_moves(Tile::Empty).is_empty()
+        }
+
+        fn check_threatened_cells(game : TicTacToe) -> bool {
+            let mut expected = winning_moves_brute_force(game.get_state(), Tile::Cross);
+            expected.extend(winning_moves_brute_force(game.get_state(), Tile::Circle));
+            expected.sort_unstable();
+            expected.dedup();
+            expected == game.threatened_cells()
+        }
+    }
+
+}
\ No newline at end of file
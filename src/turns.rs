@@ -0,0 +1,190 @@
+//! # Turns
+//!
+//! Turn order of one game against one opponent, independent of how turns get
+//! to the opponent. Frontend starts the game, feeds it turns it plays and turns
+//! opponent sent and asks whose turn it is and since when. Peer to peer client
+//! keeps one per game session, other frontends embed it the same way.
+
+use std::time::Instant;
+
+use crate::coords::Coordinates;
+use crate::tictactoe::{GameError, Marks, Rules, TicTacToe, Tile};
+
+pub enum TurnError {
+    /// Opponent played while it was my turn
+    NotYourTurn,
+    Game(GameError),
+}
+
+impl From<GameError> for TurnError {
+    fn from(error: GameError) -> Self {
+        TurnError::Game(error)
+    }
+}
+
+impl std::fmt::Display for TurnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TurnError::NotYourTurn => write!(f, "it was not their turn"),
+            TurnError::Game(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Game with player on turn and when their turn started, none of it before game starts
+#[derive(Clone, Debug)]
+pub struct Turns {
+    game: TicTacToe,
+    your_turn: Option<bool>,
+    started: Option<Instant>,
+}
+
+impl Default for Turns {
+    fn default() -> Self {
+        Turns::new()
+    }
+}
+
+impl Turns {
+    pub fn new() -> Turns {
+        Turns { game: TicTacToe::new(), your_turn: None, started: None }
+    }
+
+    /// Starts game, initiator plays crosses and moves first unless rules
+    /// start from set up position
+    pub fn start(&mut self, initiator: bool, rules: Rules, now: Instant) {
+        // initiator plays crosses, so both peers render the same playmat
+        let marks = if initiator { Marks::default().swapped() } else { Marks::default() };
+        self.game = TicTacToe::with_rules(marks, rules);
+        self.your_turn = Some(initiator);
+        // tiles of set up position are the first moves, so invitee may move next
+        if let Some(position) = rules.from_position {
+            let _ = crate::setup::play_out(&mut self.game, &position);
+            self.your_turn = Some(initiator == crate::setup::first_to_move(&position));
+        }
+        self.started = Some(now);
+    }
+
+    /// Continues game played before, e.g. loaded from disk
+    pub fn restore(&mut self, game: TicTacToe, your_turn: bool) {
+        self.game = game;
+        self.your_turn = Some(your_turn);
+    }
+
+    pub fn reset(&mut self) {
+        self.game.reset();
+        self.your_turn = None;
+        self.started = None;
+    }
+
+    pub fn game(&self) -> &TicTacToe {
+        &self.game
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.your_turn.is_some()
+    }
+
+    pub fn is_your_turn(&self) -> bool {
+        self.your_turn.unwrap_or(false)
+    }
+
+    /// Initiator plays crosses
+    pub fn is_initiator(&self) -> bool {
+        self.game.marks().you == Tile::Cross
+    }
+
+    /// Returns true when opponent's turn has number of my last turn, both of us
+    /// believed it was our turn
+    pub fn is_race(&self, number: usize) -> bool {
+        self.is_started() && !self.is_your_turn() && self.game.moves().len() == number
+    }
+
+    /// Returns when player on turn started thinking, none while clock is stopped
+    pub fn turn_started(&self) -> Option<Instant> {
+        self.started
+    }
+
+    pub fn start_clock(&mut self, started: Instant) {
+        self.started = Some(started);
+    }
+
+    /// Stops clock, returns when the turn started
+    pub fn stop_clock(&mut self) -> Option<Instant> {
+        self.started.take()
+    }
+
+    /// Plays my turn, my mark when none, clock starts for opponent
+    pub fn make_my_turn(&mut self, x: usize, y: usize, mark: Option<Tile>, now: Instant) -> Result<(), GameError> {
+        let mark = mark.unwrap_or(self.game.marks().you);
+        self.game.make_my_mark(x, y, mark)?;
+        self.your_turn = Some(false);
+        self.started = Some(now);
+        Ok(())
+    }
+
+    /// Plays opponent's turn, their mark when none, my clock starts when they
+    /// sent it. Turn out of order or breaking rules is rejected and it stays
+    /// opponent's turn.
+    pub fn make_opponent_turn(&mut self, x: usize, y: usize, mark: Option<Tile>, sent: Instant) -> Result<(), TurnError> {
+        // duplicated answer may make invitee believe it moves first, initiator's first turn stands
+        let first_turn = self.game.moves().is_empty() && !self.is_initiator();
+        if self.is_your_turn() && !first_turn {
+            return Err(TurnError::NotYourTurn);
+        }
+        let mark = mark.unwrap_or(self.game.marks().opponent);
+        self.game.make_opponent_mark(x, y, mark)?;
+        self.your_turn = Some(true);
+        self.started = Some(sent);
+        Ok(())
+    }
+
+    /// Takes back my last turn on given field, it is my turn again
+    pub fn take_back_my_turn(&mut self, field: Coordinates, now: Instant) -> bool {
+        if self.is_your_turn() || self.game.moves().last() != Some(&field) {
+            return false;
+        }
+        self.game.take_back();
+        self.your_turn = Some(true);
+        self.started = Some(now);
+        true
+    }
+
+    /// Takes back my turn which raced with opponent's turn of the same number,
+    /// it stays their turn and their turn is played next
+    pub fn yield_race(&mut self) -> Option<Coordinates> {
+        if !self.is_race(self.game.moves().len()) || self.is_initiator() {
+            return None;
+        }
+        self.game.take_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turns_alternate_and_race_is_won_by_initiator() {
+        let now = Instant::now();
+        let mut initiator = Turns::new();
+        let mut invitee = Turns::new();
+        assert!(!initiator.is_started());
+        initiator.start(true, Rules::default(), now);
+        invitee.start(false, Rules::default(), now);
+        assert!(initiator.is_your_turn() && initiator.is_initiator());
+        assert!(matches!(invitee.make_opponent_turn(1, 1, None, now), Ok(())));
+        assert!(matches!(invitee.make_opponent_turn(0, 0, None, now), Err(TurnError::NotYourTurn)));
+
+        // duplicated answer made invitee believe it moves first
+        invitee.restore(TicTacToe::with_marks(Marks::default()), true);
+        assert!(initiator.make_my_turn(0, 0, None, now).is_ok());
+        assert!(invitee.make_my_turn(1, 1, None, now).is_ok());
+        assert!(initiator.is_race(1) && invitee.is_race(1));
+        assert_eq!(initiator.yield_race(), None);
+        assert_eq!(invitee.yield_race(), Some((1, 1)));
+        assert!(invitee.make_opponent_turn(0, 0, None, now).is_ok());
+        assert_eq!(invitee.game().moves(), initiator.game().moves());
+        assert!(invitee.is_your_turn());
+    }
+}